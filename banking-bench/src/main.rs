@@ -471,6 +471,7 @@ fn main() {
         non_vote_receiver,
         tpu_vote_receiver,
         gossip_vote_receiver,
+        banking_tracer.clone(),
         num_banking_threads,
         None,
         replay_vote_sender,
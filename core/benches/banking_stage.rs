@@ -252,6 +252,7 @@ fn bench_banking(
         non_vote_receiver,
         tpu_vote_receiver,
         gossip_vote_receiver,
+        banking_tracer,
         None,
         s,
         None,
@@ -0,0 +1,142 @@
+use {
+    agave_banking_stage_ingress_types::BankingPacketBatch,
+    criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput},
+    solana_core::banking_trace::{
+        for_test::{drop_and_clean_temp_dir_unless_suppressed, sample_packet_batch, write_events},
+        reader::TraceReader,
+        receiving_loop_with_minimized_sender_overhead,
+        replayer::ReplayTarget,
+        BankingTracer, ChannelLabel, TimedTracedEvent, TraceError, TracedEvent,
+        BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT,
+    },
+    std::{
+        env,
+        path::PathBuf,
+        sync::{atomic::AtomicBool, Arc},
+        thread,
+        time::{Duration, Instant, SystemTime},
+    },
+    tempfile::TempDir,
+};
+
+const SYNTHETIC_TRACE_EVENT_COUNT: usize = 1000;
+
+// Loads the trace at BANKING_TRACE_BENCH_DIR if specified. Otherwise, a small synthetic trace
+// with the typical label mix is written and loaded, so that the bench runs out of the box.
+fn load_trace() -> Vec<(ChannelLabel, BankingPacketBatch)> {
+    let temp_dir = TempDir::new().unwrap();
+    let path = match env::var("BANKING_TRACE_BENCH_DIR") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => {
+            let path = temp_dir.path().join("banking-trace");
+            let labels = [
+                ChannelLabel::NonVote,
+                ChannelLabel::NonVote,
+                ChannelLabel::NonVote,
+                ChannelLabel::TpuVote,
+                ChannelLabel::GossipVote,
+            ];
+            write_events(
+                &path,
+                u64::MAX,
+                labels
+                    .into_iter()
+                    .cycle()
+                    .take(SYNTHETIC_TRACE_EVENT_COUNT)
+                    .map(|label| {
+                        TimedTracedEvent(
+                            SystemTime::now(),
+                            TracedEvent::PacketBatch(label, sample_packet_batch()),
+                        )
+                    }),
+            );
+            path
+        }
+    };
+
+    let events = TraceReader::open_dir(&path)
+        .unwrap()
+        .iter()
+        .filter_map(|event| match event.unwrap() {
+            TimedTracedEvent(_, TracedEvent::PacketBatch(label, batch))
+                if label != ChannelLabel::Dummy =>
+            {
+                Some((label, batch))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    events
+}
+
+// Sends all batches through unified channels and returns the elapsed time until they're
+// received and, if enabled, written to the trace at `trace_path`.
+fn send_trace(
+    events: &[(ChannelLabel, BankingPacketBatch)],
+    trace_path: Option<&PathBuf>,
+) -> Duration {
+    let exit = Arc::<AtomicBool>::default();
+    let (tracer, tracer_thread) = match trace_path {
+        Some(path) => {
+            // make sure fresh setup; otherwise banking tracer appends and rotates trace files
+            // created by prior bench iterations.
+            BankingTracer::ensure_cleanup_path(path).unwrap();
+            BankingTracer::new(Some((
+                path,
+                exit.clone(),
+                BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT,
+            )))
+            .unwrap()
+        }
+        None => (BankingTracer::new_disabled(), None),
+    };
+    let channels = tracer.create_channels(true);
+    let receiver = channels.non_vote_receiver.clone();
+    let main_thread = thread::spawn(move || {
+        receiving_loop_with_minimized_sender_overhead::<_, TraceError, 0>(
+            exit,
+            receiver,
+            |packet_batch| {
+                black_box(packet_batch);
+                Ok(())
+            },
+        )
+    });
+
+    let start = Instant::now();
+    for (label, batch) in events {
+        channels.send(*label, batch.clone()).unwrap();
+    }
+    drop((channels, tracer));
+    main_thread.join().unwrap().unwrap();
+    if let Some(tracer_thread) = tracer_thread {
+        tracer_thread.join().unwrap().unwrap();
+    }
+    start.elapsed()
+}
+
+fn bench_banking_trace_replay(c: &mut Criterion) {
+    let events = load_trace();
+    let temp_dir = TempDir::new().unwrap();
+    let trace_path = temp_dir.path().join("banking-trace");
+
+    let mut group = c.benchmark_group("banking_trace_replay");
+    group.throughput(Throughput::Elements(events.len() as u64));
+    group.bench_function("channel_throughput", |bencher| {
+        bencher.iter_custom(|iters| (0..iters).map(|_| send_trace(&events, None)).sum())
+    });
+    group.bench_function("channel_throughput_with_tracer", |bencher| {
+        bencher.iter_custom(|iters| {
+            (0..iters)
+                .map(|_| send_trace(&events, Some(&trace_path)))
+                .sum()
+        })
+    });
+    group.finish();
+
+    drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+}
+
+criterion_group!(benches, bench_banking_trace_replay);
+criterion_main!(benches);
@@ -0,0 +1,18 @@
+fn main() -> Result<(), std::io::Error> {
+    #[cfg(feature = "banking-trace-grpc")]
+    {
+        const PROTOC_ENVAR: &str = "PROTOC";
+        if std::env::var(PROTOC_ENVAR).is_err() {
+            #[cfg(not(windows))]
+            std::env::set_var(PROTOC_ENVAR, protobuf_src::protoc());
+        }
+
+        let proto = std::path::PathBuf::from("proto/banking_trace.proto");
+        println!("cargo:rerun-if-changed={}", proto.display());
+        tonic_build::configure()
+            .build_client(true)
+            .build_server(true)
+            .compile(&[&proto], &[proto.parent().unwrap()])?;
+    }
+    Ok(())
+}
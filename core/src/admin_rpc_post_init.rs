@@ -1,5 +1,6 @@
 use {
     crate::{
+        banking_trace::BankingTracer,
         cluster_slots_service::cluster_slots::ClusterSlots,
         repair::{outstanding_requests::OutstandingRequests, serve_repair::ShredRepairType},
     },
@@ -11,7 +12,8 @@ use {
     std::{
         collections::{HashMap, HashSet},
         net::UdpSocket,
-        sync::{Arc, RwLock},
+        path::PathBuf,
+        sync::{atomic::AtomicBool, Arc, RwLock},
     },
 };
 
@@ -80,4 +82,8 @@ pub struct AdminRpcRequestMetadataPostInit {
     pub outstanding_repair_requests: Arc<RwLock<OutstandingRequests<ShredRepairType>>>,
     pub cluster_slots: Arc<ClusterSlots>,
     pub gossip_socket: Option<AtomicUdpSocket>,
+    pub banking_tracer: Arc<BankingTracer>,
+    /// Where the banking trace files are written to when enabled through the admin RPC.
+    pub banking_trace_path: PathBuf,
+    pub banking_trace_exit: Arc<AtomicBool>,
 }
@@ -5,14 +5,14 @@ use {
             update_bank_forks_and_poh_recorder_for_new_tpu_bank, BankingStage, LikeClusterInfo,
         },
         banking_trace::{
-            BankingTracer, ChannelLabel, Channels, TimedTracedEvent, TracedEvent, TracedSender,
-            TracerThread, BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT, BASENAME,
+            reader::{self, TraceFileReader},
+            BankingTracer, ChannelLabel, Channels, TimedTracedEvent, TraceError, TracedEvent,
+            TracedSender, TracerThread, BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT,
         },
         validator::{BlockProductionMethod, TransactionStructure},
     },
     agave_banking_stage_ingress_types::BankingPacketBatch,
     assert_matches::assert_matches,
-    bincode::deserialize_from,
     crossbeam_channel::{unbounded, Sender},
     itertools::Itertools,
     log::*,
@@ -47,8 +47,7 @@ use {
     std::{
         collections::BTreeMap,
         fmt::Display,
-        fs::File,
-        io::{self, BufRead, BufReader},
+        io,
         net::{IpAddr, Ipv4Addr},
         path::PathBuf,
         sync::{
@@ -127,6 +126,9 @@ pub enum SimulateError {
 
     #[error("Deserialization Error: {0}")]
     DeserializeError(#[from] bincode::Error),
+
+    #[error("Trace Error: {0}")]
+    TraceError(#[from] TraceError),
 }
 
 // Defined to be enough to cover the holding phase prior to leader slots with some idling (+5 secs)
@@ -157,12 +159,10 @@ impl BankingTraceEvents {
         event_file_path: &PathBuf,
         mut callback: impl FnMut(TimedTracedEvent),
     ) -> Result<(), SimulateError> {
-        let mut reader = BufReader::new(File::open(event_file_path)?);
-
-        // EOF is reached at a correct deserialization boundary or just the file is just empty.
-        // We want to look-ahead the buf, so NOT calling reader.consume(..) is correct.
-        while !reader.fill_buf()?.is_empty() {
-            callback(deserialize_from(&mut reader)?);
+        // Torn trailing frames due to file corruption or unclean validator shutdown are silently
+        // skipped by the reader with warnings, as this can happen under normal operation...
+        for event in TraceFileReader::open(event_file_path)? {
+            callback(event?);
         }
 
         Ok(())
@@ -182,24 +182,7 @@ impl BankingTraceEvents {
                 event_count - old_event_count,
                 event_file_path,
             );
-
-            if matches!(
-                read_result,
-                Err(SimulateError::DeserializeError(ref deser_err))
-                    if matches!(
-                        &**deser_err,
-                        bincode::ErrorKind::Io(io_err)
-                            if io_err.kind() == std::io::ErrorKind::UnexpectedEof
-                    )
-            ) {
-                // Silence errors here as this can happen under normal operation...
-                warn!(
-                    "Reading {event_file_path:?} failed {read_result:?} due to file corruption or \
-                     unclean validator shutdown",
-                );
-            } else {
-                read_result?
-            }
+            read_result?
         }
 
         Ok(events)
@@ -228,6 +211,8 @@ impl BankingTraceEvents {
                 self.hash_overrides.add_override(slot, blockhash, bank_hash);
                 assert!(is_new);
             }
+            // Other events aren't needed to simulate.
+            _ => {}
         }
     }
 
@@ -496,6 +481,11 @@ impl SimulatorLoop {
                     .hash_event(bank.slot(), &bank.last_blockhash(), &bank.hash());
                 if *bank.collector_id() == self.simulated_leader {
                     logger.log_frozen_bank_cost(&bank, bank_created.elapsed());
+                    self.retracer.bank_frozen_event(
+                        bank.slot(),
+                        bank.tick_height(),
+                        bank.executed_transaction_count(),
+                    );
                 }
                 self.retransmit_slots_sender.send(bank.slot()).unwrap();
                 update_bank_forks_and_poh_recorder_for_new_tpu_bank(
@@ -773,6 +763,12 @@ impl BankingSimulator {
         .unwrap();
         assert!(retracer.is_enabled());
         info!("Enabled banking retracer (dir_byte_limit: {BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT})",);
+        poh_recorder.write().unwrap().set_tick_observer(Box::new({
+            let retracer = retracer.clone();
+            move |slot, tick_height, last_tick_in_slot| {
+                retracer.poh_tick_event(slot, tick_height, last_tick_in_slot)
+            }
+        }));
 
         // Create a partially-dummy ClusterInfo for the banking stage.
         let cluster_info_for_banking = Arc::new(DummyClusterInfo {
@@ -835,6 +831,7 @@ impl BankingSimulator {
             non_vote_receiver,
             tpu_vote_receiver,
             gossip_vote_receiver,
+            retracer.clone(),
             BankingStage::num_threads(),
             None,
             replay_vote_sender,
@@ -938,10 +935,6 @@ impl BankingSimulator {
     }
 
     pub fn event_file_name(index: usize) -> String {
-        if index == 0 {
-            BASENAME.to_string()
-        } else {
-            format!("{BASENAME}.{index}")
-        }
+        reader::event_file_name(index)
     }
 }
@@ -18,6 +18,7 @@ use {
                 scheduler_controller::SchedulerController, scheduler_error::SchedulerError,
             },
         },
+        banking_trace::BankingTracer,
         validator::{BlockProductionMethod, TransactionStructure},
     },
     agave_banking_stage_ingress_types::BankingPacketReceiver,
@@ -372,6 +373,7 @@ impl BankingStage {
         non_vote_receiver: BankingPacketReceiver,
         tpu_vote_receiver: BankingPacketReceiver,
         gossip_vote_receiver: BankingPacketReceiver,
+        banking_tracer: Arc<BankingTracer>,
         transaction_status_sender: Option<TransactionStatusSender>,
         replay_vote_sender: ReplayVoteSender,
         log_messages_bytes_limit: Option<usize>,
@@ -387,6 +389,7 @@ impl BankingStage {
             non_vote_receiver,
             tpu_vote_receiver,
             gossip_vote_receiver,
+            banking_tracer,
             Self::num_threads(),
             transaction_status_sender,
             replay_vote_sender,
@@ -406,6 +409,7 @@ impl BankingStage {
         non_vote_receiver: BankingPacketReceiver,
         tpu_vote_receiver: BankingPacketReceiver,
         gossip_vote_receiver: BankingPacketReceiver,
+        banking_tracer: Arc<BankingTracer>,
         num_threads: u32,
         transaction_status_sender: Option<TransactionStatusSender>,
         replay_vote_sender: ReplayVoteSender,
@@ -426,6 +430,7 @@ impl BankingStage {
             non_vote_receiver,
             tpu_vote_receiver,
             gossip_vote_receiver,
+            banking_tracer,
             num_threads,
             transaction_status_sender,
             replay_vote_sender,
@@ -445,6 +450,7 @@ impl BankingStage {
         non_vote_receiver: BankingPacketReceiver,
         tpu_vote_receiver: BankingPacketReceiver,
         gossip_vote_receiver: BankingPacketReceiver,
+        banking_tracer: Arc<BankingTracer>,
         num_threads: u32,
         transaction_status_sender: Option<TransactionStatusSender>,
         replay_vote_sender: ReplayVoteSender,
@@ -497,6 +503,7 @@ impl BankingStage {
                     num_threads,
                     log_messages_bytes_limit,
                     bank_forks,
+                    banking_tracer,
                 );
             }
             TransactionStructure::View => {
@@ -515,6 +522,7 @@ impl BankingStage {
                     num_threads,
                     log_messages_bytes_limit,
                     bank_forks,
+                    banking_tracer,
                 );
             }
         }
@@ -534,6 +542,7 @@ impl BankingStage {
         num_threads: u32,
         log_messages_bytes_limit: Option<usize>,
         bank_forks: Arc<RwLock<BankForks>>,
+        banking_tracer: Arc<BankingTracer>,
     ) {
         // Create channels for communication between scheduler and workers
         let num_workers = (num_threads).saturating_sub(NUM_VOTE_PROCESSING_THREADS);
@@ -556,6 +565,7 @@ impl BankingStage {
                 ),
                 finished_work_sender.clone(),
                 poh_recorder.read().unwrap().new_leader_bank_notifier(),
+                banking_tracer.clone(),
             );
 
             worker_metrics.push(consume_worker.metrics_handle());
@@ -584,6 +594,7 @@ impl BankingStage {
                                 bank_forks,
                                 $scheduler,
                                 worker_metrics,
+                                banking_tracer,
                             );
 
                             match scheduler_controller.run() {
@@ -773,6 +784,7 @@ mod tests {
             non_vote_receiver,
             tpu_vote_receiver,
             gossip_vote_receiver,
+            banking_tracer.clone(),
             None,
             replay_vote_sender,
             None,
@@ -831,6 +843,7 @@ mod tests {
             non_vote_receiver,
             tpu_vote_receiver,
             gossip_vote_receiver,
+            banking_tracer.clone(),
             None,
             replay_vote_sender,
             None,
@@ -898,6 +911,7 @@ mod tests {
             non_vote_receiver,
             tpu_vote_receiver,
             gossip_vote_receiver,
+            banking_tracer.clone(),
             None,
             replay_vote_sender,
             None,
@@ -1053,6 +1067,7 @@ mod tests {
                 non_vote_receiver,
                 tpu_vote_receiver,
                 gossip_vote_receiver,
+                banking_tracer.clone(),
                 None,
                 replay_vote_sender,
                 None,
@@ -1242,6 +1257,7 @@ mod tests {
             non_vote_receiver,
             tpu_vote_receiver,
             gossip_vote_receiver,
+            banking_tracer.clone(),
             None,
             replay_vote_sender,
             None,
@@ -1,16 +1,20 @@
 use {
     super::{
+        committer::CommitTransactionDetails,
         consumer::{Consumer, ExecuteAndCommitTransactionsOutput, ProcessTransactionBatchOutput},
         leader_slot_timing_metrics::LeaderExecuteAndCommitTimings,
         scheduler_messages::{ConsumeWork, FinishedConsumeWork},
     },
+    crate::banking_trace::{BankingTracer, BatchErrorClass, CostLimit, TransactionOutcome},
     crossbeam_channel::{Receiver, RecvError, SendError, Sender},
+    solana_clock::Slot,
     solana_measure::measure_us,
-    solana_poh::leader_bank_notifier::LeaderBankNotifier,
+    solana_poh::{leader_bank_notifier::LeaderBankNotifier, poh_recorder::PohRecorderError},
     solana_runtime::bank::Bank,
     solana_runtime_transaction::transaction_with_meta::TransactionWithMeta,
     solana_svm::transaction_error_metrics::TransactionErrorMetrics,
     solana_time_utils::AtomicInterval,
+    solana_transaction_error::TransactionError,
     std::{
         sync::{
             atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
@@ -30,12 +34,14 @@ pub enum ConsumeWorkerError<Tx> {
 }
 
 pub(crate) struct ConsumeWorker<Tx> {
+    id: u32,
     consume_receiver: Receiver<ConsumeWork<Tx>>,
     consumer: Consumer,
     consumed_sender: Sender<FinishedConsumeWork<Tx>>,
 
     leader_bank_notifier: Arc<LeaderBankNotifier>,
     metrics: Arc<ConsumeWorkerMetrics>,
+    banking_tracer: Arc<BankingTracer>,
 }
 
 impl<Tx: TransactionWithMeta> ConsumeWorker<Tx> {
@@ -45,13 +51,16 @@ impl<Tx: TransactionWithMeta> ConsumeWorker<Tx> {
         consumer: Consumer,
         consumed_sender: Sender<FinishedConsumeWork<Tx>>,
         leader_bank_notifier: Arc<LeaderBankNotifier>,
+        banking_tracer: Arc<BankingTracer>,
     ) -> Self {
         Self {
+            id,
             consume_receiver,
             consumer,
             consumed_sender,
             leader_bank_notifier,
             metrics: Arc::new(ConsumeWorkerMetrics::new(id)),
+            banking_tracer,
         }
     }
 
@@ -112,6 +121,7 @@ impl<Tx: TransactionWithMeta> ConsumeWorker<Tx> {
         bank: &Arc<Bank>,
         work: ConsumeWork<Tx>,
     ) -> Result<(), ConsumeWorkerError<Tx>> {
+        self.trace_scheduled_batch(&work);
         let output = self.consumer.process_and_record_aged_transactions(
             bank,
             &work.transactions,
@@ -120,6 +130,8 @@ impl<Tx: TransactionWithMeta> ConsumeWorker<Tx> {
 
         self.metrics.update_for_consume(&output);
         self.metrics.has_data.store(true, Ordering::Relaxed);
+        self.trace_cost_model_rejections(bank.slot(), &work, &output.cost_model_rejections);
+        self.trace_batch_outcome(&work, &output.execute_and_commit_transactions_output);
 
         self.consumed_sender.send(FinishedConsumeWork {
             work,
@@ -152,6 +164,7 @@ impl<Tx: TransactionWithMeta> ConsumeWorker<Tx> {
 
     /// Send transactions back to scheduler as retryable.
     fn retry(&self, work: ConsumeWork<Tx>) -> Result<(), ConsumeWorkerError<Tx>> {
+        self.trace_scheduled_batch(&work);
         let retryable_indexes: Vec<_> = (0..work.transactions.len()).collect();
         let num_retryable = retryable_indexes.len();
         self.metrics
@@ -163,12 +176,107 @@ impl<Tx: TransactionWithMeta> ConsumeWorker<Tx> {
             .retryable_expired_bank_count
             .fetch_add(num_retryable, Ordering::Relaxed);
         self.metrics.has_data.store(true, Ordering::Relaxed);
+        self.banking_tracer.transaction_batch_outcome_event(
+            self.id,
+            work.batch_id.index(),
+            vec![TransactionOutcome::Retryable; num_retryable],
+            Some(BatchErrorClass::NoBank),
+        );
         self.consumed_sender.send(FinishedConsumeWork {
             work,
             retryable_indexes,
         })?;
         Ok(())
     }
+
+    /// Trace the batch assigned to this worker, so that the scheduling decisions can be
+    /// reconstructed from the trace.
+    fn trace_scheduled_batch(&self, work: &ConsumeWork<Tx>) {
+        self.banking_tracer.transaction_batch_scheduled_event(
+            self.id,
+            work.batch_id.index(),
+            work.transactions.iter().map(|tx| *tx.signature()),
+        );
+    }
+
+    fn trace_cost_model_rejections(
+        &self,
+        slot: Slot,
+        work: &ConsumeWork<Tx>,
+        cost_model_rejections: &[(usize, TransactionError)],
+    ) {
+        if cost_model_rejections.is_empty() {
+            return;
+        }
+
+        self.banking_tracer.cost_limit_exceeded_event(
+            self.id,
+            work.batch_id.index(),
+            slot,
+            cost_model_rejections.iter().filter_map(|(index, err)| {
+                let limit = match err {
+                    TransactionError::WouldExceedMaxBlockCostLimit => CostLimit::Block,
+                    TransactionError::WouldExceedMaxVoteCostLimit => CostLimit::Vote,
+                    TransactionError::WouldExceedMaxAccountCostLimit => CostLimit::Account,
+                    TransactionError::WouldExceedAccountDataBlockLimit => {
+                        CostLimit::AccountDataBlock
+                    }
+                    TransactionError::WouldExceedAccountDataTotalLimit => {
+                        CostLimit::AccountDataTotal
+                    }
+                    _ => return None,
+                };
+                Some((*index as u32, limit))
+            }),
+        );
+    }
+
+    fn trace_batch_outcome(
+        &self,
+        work: &ConsumeWork<Tx>,
+        output: &ExecuteAndCommitTransactionsOutput,
+    ) {
+        if !self.banking_tracer.is_enabled() {
+            return;
+        }
+
+        let mut outcomes = vec![TransactionOutcome::Dropped; work.transactions.len()];
+        let error = match &output.commit_transactions_result {
+            Ok(commit_transaction_details) => {
+                for (outcome, details) in outcomes.iter_mut().zip(commit_transaction_details) {
+                    if let CommitTransactionDetails::Committed { .. } = details {
+                        *outcome = TransactionOutcome::Committed;
+                    }
+                }
+                None
+            }
+            Err(PohRecorderError::MaxHeightReached) => Some(BatchErrorClass::MaxHeightReached),
+            Err(PohRecorderError::MinHeightNotReached) => {
+                Some(BatchErrorClass::MinHeightNotReached)
+            }
+            Err(PohRecorderError::SendError(_)) => Some(BatchErrorClass::RecordFailed),
+        };
+        for index in &output.retryable_transaction_indexes {
+            if let Some(outcome) = outcomes.get_mut(*index) {
+                *outcome = TransactionOutcome::Retryable;
+            }
+        }
+
+        self.banking_tracer.transaction_batch_outcome_event(
+            self.id,
+            work.batch_id.index(),
+            outcomes,
+            error,
+        );
+        self.banking_tracer.transaction_results_event(
+            self.id,
+            work.batch_id.index(),
+            work.transactions
+                .iter()
+                .map(|tx| *tx.signature())
+                .zip(output.transaction_results.iter().cloned()),
+        );
+    }
 }
 
 /// Helper function to create an non-blocking iterator over work in the receiver,
@@ -220,6 +328,7 @@ impl ConsumeWorkerMetrics {
         ProcessTransactionBatchOutput {
             cost_model_throttled_transactions_count,
             cost_model_us,
+            cost_model_rejections: _,
             execute_and_commit_transactions_output,
         }: &ProcessTransactionBatchOutput,
     ) {
@@ -846,6 +955,7 @@ mod tests {
             consumer,
             consumed_sender,
             poh_recorder.read().unwrap().new_leader_bank_notifier(),
+            BankingTracer::new_disabled(),
         );
 
         (
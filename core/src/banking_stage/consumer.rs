@@ -27,7 +27,7 @@ use {
         transaction_processing_result::TransactionProcessingResultExtensions,
         transaction_processor::{ExecutionRecordingConfig, TransactionProcessingConfig},
     },
-    solana_transaction_error::TransactionError,
+    solana_transaction_error::{TransactionError, TransactionResult},
     std::{num::Saturating, sync::Arc},
 };
 
@@ -39,6 +39,9 @@ pub struct ProcessTransactionBatchOutput {
     pub(crate) cost_model_throttled_transactions_count: u64,
     // Amount of time spent running the cost model
     pub(crate) cost_model_us: u64,
+    // Indexes of the transactions which didn't fit into the block per the cost tracker, along
+    // with the exceeded limit
+    pub(crate) cost_model_rejections: Vec<(usize, TransactionError)>,
     pub execute_and_commit_transactions_output: ExecuteAndCommitTransactionsOutput,
 }
 
@@ -52,6 +55,9 @@ pub struct ExecuteAndCommitTransactionsOutput {
     // A result that indicates whether transactions were successfully
     // committed into the Poh stream.
     pub commit_transactions_result: Result<Vec<CommitTransactionDetails>, PohRecorderError>,
+    // Outcome of each transaction in the batch, in batch order: success, or the error which
+    // prevented it from being loaded or executed successfully.
+    pub(crate) transaction_results: Vec<TransactionResult<()>>,
     pub(crate) execute_and_commit_timings: LeaderExecuteAndCommitTimings,
     pub(crate) error_counters: TransactionErrorMetrics,
     pub(crate) min_prioritization_fees: u64,
@@ -171,6 +177,20 @@ impl Consumer {
             txs,
             pre_results
         ));
+        let cost_model_rejections = transaction_qos_cost_results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| match result {
+                Err(
+                    err @ (TransactionError::WouldExceedMaxBlockCostLimit
+                    | TransactionError::WouldExceedMaxVoteCostLimit
+                    | TransactionError::WouldExceedMaxAccountCostLimit
+                    | TransactionError::WouldExceedAccountDataBlockLimit
+                    | TransactionError::WouldExceedAccountDataTotalLimit),
+                ) => Some((index, err.clone())),
+                _ => None,
+            })
+            .collect();
 
         // Only lock accounts for those transactions are selected for the block;
         // Once accounts are locked, other threads cannot encode transactions that will modify the
@@ -221,6 +241,7 @@ impl Consumer {
         ProcessTransactionBatchOutput {
             cost_model_throttled_transactions_count,
             cost_model_us,
+            cost_model_rejections,
             execute_and_commit_transactions_output,
         }
     }
@@ -334,6 +355,11 @@ impl Consumer {
             attempted_processing_count: processing_results.len() as u64,
         };
 
+        let transaction_results = processing_results
+            .iter()
+            .map(|processing_result| processing_result.flattened_result())
+            .collect_vec();
+
         let (processed_transactions, processing_results_to_transactions_us) =
             measure_us!(processing_results
                 .iter()
@@ -380,6 +406,7 @@ impl Consumer {
                 transaction_counts,
                 retryable_transaction_indexes,
                 commit_transactions_result: Err(recorder_err),
+                transaction_results,
                 execute_and_commit_timings,
                 error_counters,
                 min_prioritization_fees,
@@ -430,6 +457,7 @@ impl Consumer {
             transaction_counts,
             retryable_transaction_indexes,
             commit_transactions_result: Ok(commit_transaction_statuses),
+            transaction_results,
             execute_and_commit_timings,
             error_counters,
             min_prioritization_fees,
@@ -11,6 +11,10 @@ impl TransactionBatchId {
     pub fn new(index: u64) -> Self {
         Self(index)
     }
+
+    pub fn index(&self) -> u64 {
+        self.0
+    }
 }
 
 impl Display for TransactionBatchId {
@@ -11,23 +11,31 @@ use {
             SchedulingDetails,
         },
     },
-    crate::banking_stage::{
-        consume_worker::ConsumeWorkerMetrics,
-        consumer::Consumer,
-        decision_maker::{BufferedPacketsDecision, DecisionMaker},
-        transaction_scheduler::transaction_state_container::StateContainer,
-        TOTAL_BUFFERED_PACKETS,
+    crate::{
+        banking_stage::{
+            consume_worker::ConsumeWorkerMetrics,
+            consumer::Consumer,
+            decision_maker::{BufferedPacketsDecision, DecisionMaker},
+            transaction_scheduler::transaction_state_container::StateContainer,
+            TOTAL_BUFFERED_PACKETS,
+        },
+        banking_trace::{BankingTracer, ChannelLabel, PacketDropReason},
     },
-    solana_clock::MAX_PROCESSING_AGE,
+    solana_clock::{Slot, MAX_PROCESSING_AGE},
     solana_measure::measure_us,
     solana_runtime::{bank::Bank, bank_forks::BankForks},
+    solana_runtime_transaction::transaction_meta::StaticMeta,
     solana_svm::transaction_error_metrics::TransactionErrorMetrics,
     std::{
         num::Saturating,
         sync::{Arc, RwLock},
+        time::{Duration, Instant},
     },
 };
 
+/// How often the compute unit prices of the buffered transactions are traced.
+const PRIORITY_FEE_SNAPSHOT_INTERVAL: Duration = Duration::from_millis(400);
+
 /// Controls packet and transaction flow into scheduler, and scheduling execution.
 pub(crate) struct SchedulerController<R, S>
 where
@@ -55,6 +63,12 @@ where
     worker_metrics: Vec<Arc<ConsumeWorkerMetrics>>,
     /// Detailed scheduling metrics.
     scheduling_details: SchedulingDetails,
+    /// Tracer for leader slot boundaries and dropped packets.
+    banking_tracer: Arc<BankingTracer>,
+    /// The leader slot which is being consumed, if any.
+    leader_slot: Option<Slot>,
+    /// When the compute unit prices of the buffered transactions were last traced.
+    last_priority_fee_snapshot: Instant,
 }
 
 impl<R, S> SchedulerController<R, S>
@@ -68,6 +82,7 @@ where
         bank_forks: Arc<RwLock<BankForks>>,
         scheduler: S,
         worker_metrics: Vec<Arc<ConsumeWorkerMetrics>>,
+        banking_tracer: Arc<BankingTracer>,
     ) -> Self {
         Self {
             decision_maker,
@@ -80,6 +95,9 @@ where
             timing_metrics: SchedulerTimingMetrics::default(),
             worker_metrics,
             scheduling_details: SchedulingDetails::default(),
+            banking_tracer,
+            leader_slot: None,
+            last_priority_fee_snapshot: Instant::now(),
         }
     }
 
@@ -107,6 +125,7 @@ where
                 .maybe_report_and_reset_slot(new_leader_slot);
             self.timing_metrics
                 .maybe_report_and_reset_slot(new_leader_slot);
+            self.trace_leader_slot_boundary(new_leader_slot);
 
             self.receive_completed()?;
             self.process_transactions(&decision)?;
@@ -128,11 +147,59 @@ where
                 .iter()
                 .for_each(|metrics| metrics.maybe_report_and_reset());
             self.scheduling_details.maybe_report();
+            self.maybe_trace_priority_fee_snapshot();
         }
 
         Ok(())
     }
 
+    /// Traces the compute unit prices of the buffered transactions, at most once per
+    /// `PRIORITY_FEE_SNAPSHOT_INTERVAL`.
+    fn maybe_trace_priority_fee_snapshot(&mut self) {
+        if !self.banking_tracer.is_enabled()
+            || self.last_priority_fee_snapshot.elapsed() < PRIORITY_FEE_SNAPSHOT_INTERVAL
+        {
+            return;
+        }
+        self.last_priority_fee_snapshot = Instant::now();
+
+        let bank = self.bank_forks.read().unwrap().working_bank();
+        self.banking_tracer.priority_fee_snapshot_event(
+            self.leader_slot,
+            self.container
+                .queued_transactions()
+                .filter_map(|transaction| {
+                    transaction
+                        .compute_budget_instruction_details()
+                        .sanitize_and_convert_to_compute_budget_limits(&bank.feature_set)
+                        .ok()
+                        .map(|limits| limits.compute_unit_price)
+                }),
+        );
+    }
+
+    /// Traces the end of the previous leader slot along with what's left buffered, and the start
+    /// of the new one, if changed.
+    fn trace_leader_slot_boundary(&mut self, new_leader_slot: Option<Slot>) {
+        if self.leader_slot == new_leader_slot {
+            return;
+        }
+        if let Some(slot) = self.leader_slot {
+            let now = Instant::now();
+            self.banking_tracer.buffered_transactions_event(
+                slot,
+                self.container
+                    .queued_transaction_states()
+                    .map(|state| (state.priority(), now.duration_since(state.buffered_time()))),
+            );
+            self.banking_tracer.leader_slot_boundary_event(slot, false);
+        }
+        if let Some(slot) = new_leader_slot {
+            self.banking_tracer.leader_slot_boundary_event(slot, true);
+        }
+        self.leader_slot = new_leader_slot;
+    }
+
     /// Process packets based on decision.
     fn process_transactions(
         &mut self,
@@ -224,6 +291,11 @@ where
         self.count_metrics.update(|count_metrics| {
             count_metrics.num_dropped_on_clear += num_dropped_on_clear;
         });
+        self.banking_tracer.packets_dropped_event(
+            ChannelLabel::NonVote,
+            num_dropped_on_clear.0,
+            PacketDropReason::Forwarded,
+        );
     }
 
     /// Clean unprocessable transactions from the queue. These will be transactions that are
@@ -286,6 +358,11 @@ where
         self.count_metrics.update(|count_metrics| {
             count_metrics.num_dropped_on_age_and_status += num_dropped_on_age_and_status;
         });
+        self.banking_tracer.packets_dropped_event(
+            ChannelLabel::NonVote,
+            num_dropped_on_age_and_status.0,
+            PacketDropReason::TooOldBlockhash,
+        );
     }
 
     /// Receives completed transactions from the workers and updates metrics.
@@ -309,12 +386,35 @@ where
         &mut self,
         decision: &BufferedPacketsDecision,
     ) -> Result<usize, DisconnectedError> {
-        self.receive_and_buffer.receive_and_buffer_packets(
+        let dropped_counts = |count_metrics: &SchedulerCountMetrics| {
+            let metrics = count_metrics.interval_metrics();
+            (
+                metrics.num_dropped_on_capacity,
+                metrics.num_dropped_on_age_and_status,
+            )
+        };
+        let (num_dropped_on_capacity, num_dropped_on_age_and_status) =
+            dropped_counts(&self.count_metrics);
+        let result = self.receive_and_buffer.receive_and_buffer_packets(
             &mut self.container,
             &mut self.timing_metrics,
             &mut self.count_metrics,
             decision,
-        )
+        );
+
+        let (new_num_dropped_on_capacity, new_num_dropped_on_age_and_status) =
+            dropped_counts(&self.count_metrics);
+        self.banking_tracer.packets_dropped_event(
+            ChannelLabel::NonVote,
+            (new_num_dropped_on_capacity - num_dropped_on_capacity).0,
+            PacketDropReason::BufferOverflow,
+        );
+        self.banking_tracer.packets_dropped_event(
+            ChannelLabel::NonVote,
+            (new_num_dropped_on_age_and_status - num_dropped_on_age_and_status).0,
+            PacketDropReason::TooOldBlockhash,
+        );
+        result
     }
 }
 
@@ -454,6 +554,7 @@ mod tests {
             bank_forks,
             scheduler,
             vec![], // no actual workers with metrics to report, this can be empty
+            BankingTracer::new_disabled(),
         );
 
         (test_frame, scheduler_controller)
@@ -33,6 +33,11 @@ impl SchedulerCountMetrics {
     pub fn interval_has_data(&self) -> bool {
         self.interval.metrics.has_data()
     }
+
+    /// Returns the counts of the current interval, which are reset when reported.
+    pub fn interval_metrics(&self) -> &SchedulerCountMetricsInner {
+        &self.interval.metrics
+    }
 }
 
 #[derive(Default)]
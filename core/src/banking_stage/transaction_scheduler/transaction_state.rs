@@ -1,6 +1,6 @@
-use crate::banking_stage::scheduler_messages::MaxAge;
 #[cfg(feature = "dev-context-only-utils")]
 use qualifier_attr::qualifiers;
+use {crate::banking_stage::scheduler_messages::MaxAge, std::time::Instant};
 
 /// TransactionState is used to track the state of a transaction in the transaction scheduler
 /// and banking stage as a whole.
@@ -23,6 +23,8 @@ pub(crate) struct TransactionState<Tx> {
     priority: u64,
     /// Estimated cost of the transaction.
     cost: u64,
+    /// When the transaction was buffered.
+    buffered_time: Instant,
 }
 
 impl<Tx> TransactionState<Tx> {
@@ -33,6 +35,7 @@ impl<Tx> TransactionState<Tx> {
             max_age,
             priority,
             cost,
+            buffered_time: Instant::now(),
         }
     }
 
@@ -48,6 +51,11 @@ impl<Tx> TransactionState<Tx> {
         self.cost
     }
 
+    /// Return when the transaction was buffered, which isn't reset by retries.
+    pub(crate) fn buffered_time(&self) -> Instant {
+        self.buffered_time
+    }
+
     /// Intended to be called when a transaction is scheduled. This method
     /// takes ownership of the transaction from the state.
     ///
@@ -96,6 +96,16 @@ pub(crate) trait StateContainer<Tx: TransactionWithMeta> {
 
     fn get_min_max_priority(&self) -> MinMaxResult<u64>;
 
+    /// Iterates over the transactions in the priority queue, in no particular order.
+    fn queued_transactions<'a>(&'a self) -> impl Iterator<Item = &'a Tx>
+    where
+        Tx: 'a;
+
+    /// Same as `queued_transactions()`, except that the states of the transactions are returned.
+    fn queued_transaction_states<'a>(&'a self) -> impl Iterator<Item = &'a TransactionState<Tx>>
+    where
+        Tx: 'a;
+
     #[cfg(feature = "dev-context-only-utils")]
     fn clear(&mut self);
 }
@@ -181,6 +191,24 @@ impl<Tx: TransactionWithMeta> StateContainer<Tx> for TransactionStateContainer<T
         }
     }
 
+    fn queued_transactions<'a>(&'a self) -> impl Iterator<Item = &'a Tx>
+    where
+        Tx: 'a,
+    {
+        self.priority_queue
+            .iter()
+            .filter_map(|priority_id| self.get_transaction(priority_id.id))
+    }
+
+    fn queued_transaction_states<'a>(&'a self) -> impl Iterator<Item = &'a TransactionState<Tx>>
+    where
+        Tx: 'a,
+    {
+        self.priority_queue
+            .iter()
+            .filter_map(|priority_id| self.id_to_transaction_state.get(priority_id.id))
+    }
+
     #[cfg(feature = "dev-context-only-utils")]
     fn clear(&mut self) {
         self.priority_queue.clear();
@@ -333,6 +361,24 @@ impl StateContainer<RuntimeTransactionView> for TransactionViewStateContainer {
         self.inner.get_min_max_priority()
     }
 
+    #[inline]
+    fn queued_transactions<'a>(&'a self) -> impl Iterator<Item = &'a RuntimeTransactionView>
+    where
+        RuntimeTransactionView: 'a,
+    {
+        self.inner.queued_transactions()
+    }
+
+    #[inline]
+    fn queued_transaction_states<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = &'a TransactionState<RuntimeTransactionView>>
+    where
+        RuntimeTransactionView: 'a,
+    {
+        self.inner.queued_transaction_states()
+    }
+
     #[cfg(feature = "dev-context-only-utils")]
     #[inline]
     fn clear(&mut self) {
@@ -431,6 +477,23 @@ mod tests {
             .is_none());
     }
 
+    #[test]
+    fn test_queued_transactions() {
+        let mut container = TransactionStateContainer::with_capacity(5);
+        push_to_container(&mut container, 5);
+        assert_eq!(container.queued_transactions().count(), 5);
+        assert_eq!(container.queued_transaction_states().count(), 5);
+
+        // scheduled transactions are no longer queued
+        let priority_id = container.pop().unwrap();
+        container
+            .get_mut_transaction_state(priority_id.id)
+            .unwrap()
+            .take_transaction_for_scheduling();
+        assert_eq!(container.queued_transactions().count(), 4);
+        assert_eq!(container.queued_transaction_states().count(), 4);
+    }
+
     #[test]
     fn test_view_push_ids_to_queue() {
         let mut container = TransactionViewStateContainer::with_capacity(2);
@@ -34,11 +34,14 @@ use {
         packet_deserializer::PacketDeserializer,
         LikeClusterInfo,
     },
-    crate::banking_trace::Channels,
+    crate::banking_trace::{BankingTracer, Channels, SchedulerTaskState},
     agave_banking_stage_ingress_types::BankingPacketBatch,
     solana_poh::{poh_recorder::PohRecorder, transaction_recorder::TransactionRecorder},
     solana_runtime::{bank_forks::BankForks, root_bank_cache::RootBankCache},
-    solana_unified_scheduler_pool::{BankingStageHelper, DefaultSchedulerPool},
+    solana_unified_scheduler_logic::Task,
+    solana_unified_scheduler_pool::{
+        BankingStageHelper, BankingTaskObserver, DefaultSchedulerPool, TaskLifecycle,
+    },
     std::sync::{Arc, RwLock},
 };
 
@@ -52,6 +55,7 @@ pub(crate) fn ensure_banking_stage_setup(
     poh_recorder: &Arc<RwLock<PohRecorder>>,
     transaction_recorder: TransactionRecorder,
     num_threads: u32,
+    banking_tracer: &Arc<BankingTracer>,
 ) {
     let mut root_bank_cache = RootBankCache::new(bank_forks.clone());
     let unified_receiver = channels.unified_receiver().clone();
@@ -99,5 +103,21 @@ pub(crate) fn ensure_banking_stage_setup(
         banking_packet_handler,
         transaction_recorder,
         banking_stage_monitor,
+        banking_tracer
+            .is_enabled()
+            .then(|| banking_task_observer(banking_tracer.clone())),
     );
 }
+
+fn banking_task_observer(banking_tracer: Arc<BankingTracer>) -> Arc<dyn BankingTaskObserver> {
+    Arc::new(move |task: &Task, lifecycle| {
+        let state = match lifecycle {
+            TaskLifecycle::Created => SchedulerTaskState::Created {
+                signature: *task.transaction().signature(),
+            },
+            TaskLifecycle::Blocked => SchedulerTaskState::Blocked,
+            TaskLifecycle::Completed { committed } => SchedulerTaskState::Completed { committed },
+        };
+        banking_tracer.scheduler_task_event(task.task_index(), state);
+    })
+}
@@ -391,6 +391,7 @@ impl VoteWorker {
         let ProcessTransactionBatchOutput {
             cost_model_throttled_transactions_count,
             cost_model_us,
+            cost_model_rejections: _,
             execute_and_commit_transactions_output,
         } = process_transaction_batch_output;
 
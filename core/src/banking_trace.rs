@@ -1,29 +1,121 @@
+#[cfg(all(target_os = "linux", feature = "banking-trace-fs"))]
+use nix::fcntl::FallocateFlags;
+#[cfg(feature = "banking-trace-noop")]
+pub use noop_sender::TracedSender;
+#[cfg(feature = "banking-trace-tcp")]
+use tcp_sink::{TcpSink, TCP_SINK_DEFAULT_BUFFER_BYTE_LIMIT};
 use {
     agave_banking_stage_ingress_types::{BankingPacketBatch, BankingPacketReceiver},
+    arc_swap::{ArcSwap, ArcSwapOption},
     bincode::serialize_into,
-    chrono::{DateTime, Local},
-    crossbeam_channel::{unbounded, Receiver, SendError, Sender, TryRecvError},
+    channel_stats::{ChannelStats, ChannelStatsSnapshot},
+    chrono::{
+        format::{Item, StrftimeItems},
+        DateTime, Local,
+    },
+    compressor::{CompressedFile, RotatedFileCompressor},
+    crossbeam_channel::{
+        bounded, select_biased, unbounded, Receiver, RecvError, RecvTimeoutError, Sender,
+        TryRecvError,
+    },
+    double_buffered_writer::DoubleBufferedWriter,
+    reader::TraceCompression,
     rolling_file::{RollingCondition, RollingConditionBasic, RollingFileAppender},
+    rotating_appender::RotatingAppender,
     solana_clock::Slot,
     solana_hash::Hash,
+    solana_signature::Signature,
+    solana_transaction_error::TransactionResult,
     std::{
-        fs::{create_dir_all, remove_dir_all},
+        cell::{Cell, LazyCell, RefCell},
+        collections::{BTreeMap, VecDeque},
+        ffi::OsString,
+        fs::{self, create_dir_all, remove_dir_all, File, OpenOptions},
         io::{self, Write},
-        path::PathBuf,
+        iter,
+        mem::{self, size_of},
+        net::SocketAddr,
+        panic,
+        path::{Path, PathBuf},
+        str::FromStr,
         sync::{
-            atomic::{AtomicBool, Ordering},
-            Arc,
+            atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+            Arc, Mutex, Weak,
         },
         thread::{self, sleep, JoinHandle},
-        time::{Duration, SystemTime},
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     },
+    strum::VariantNames,
+    strum_macros::{Display, EnumString, EnumVariantNames, IntoStaticStr},
     thiserror::Error,
 };
+#[cfg(not(feature = "banking-trace-noop"))]
+use {
+    channel_gate::ChannelGate,
+    crossbeam_channel::SendError,
+    rate_limit::{RateLimit, RateLimiter},
+    reader::TraceCounts,
+    send_filter::SendFilter,
+};
+
+pub mod ab_harness;
+#[cfg(feature = "banking-trace-async")]
+pub mod async_channel;
+pub mod broadcast_channel;
+#[cfg(feature = "banking-trace-bulk-insert")]
+pub mod bulk_insert_sink;
+pub mod channel_gate;
+pub mod channel_stats;
+pub mod chrome_trace;
+pub mod compressor;
+pub mod debug_sink;
+mod double_buffered_writer;
+pub mod filter_sink;
+pub mod geyser_sink;
+#[cfg(feature = "banking-trace-grpc")]
+pub mod grpc_sink;
+#[cfg(feature = "banking-trace-kafka")]
+pub mod kafka_sink;
+#[cfg(feature = "banking-trace-noop")]
+mod noop_sender;
+#[cfg(feature = "banking-trace-otel")]
+pub mod otel_sink;
+pub mod priority_channel;
+#[cfg(feature = "banking-trace-prometheus")]
+pub mod prometheus_sink;
+pub mod rate_limit;
+pub mod reader;
+#[cfg(feature = "banking-trace-receiver-group")]
+pub mod receiver_group;
+pub mod replayer;
+pub mod ring_buffer_sink;
+mod rotating_appender;
+pub mod sampling_sink;
+pub mod send_filter;
+#[cfg(all(unix, feature = "banking-trace-shm"))]
+pub mod shm_channel;
+#[cfg(all(unix, feature = "banking-trace-shm"))]
+pub mod shm_sink;
+#[cfg(feature = "banking-trace-sqlite")]
+pub mod sqlite_sink;
+#[cfg(feature = "banking-trace-tcp")]
+pub mod tcp_sink;
+pub mod traced_receiver;
+#[cfg(unix)]
+pub mod uds_sink;
+#[cfg(feature = "banking-trace-upload")]
+pub mod uploader;
+#[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+mod uring_writer;
+#[cfg(feature = "banking-trace-websocket")]
+pub mod websocket_sink;
 
 pub type BankingPacketSender = TracedSender;
 pub type TracerThreadResult = Result<(), TraceError>;
 pub type TracerThread = Option<JoinHandle<TracerThreadResult>>;
 pub type DirByteLimit = u64;
+/// Counts keyed by the lower bound of each bucket, omitting empty buckets.
+pub type BucketCounts = Vec<(u64, u64)>;
 
 #[derive(Error, Debug)]
 pub enum TraceError {
@@ -38,84 +130,1058 @@ pub enum TraceError {
 
     #[error("Trace directory's byte limit is too small (must be larger than {1}): {0}")]
     TooSmallDirByteLimit(DirByteLimit, DirByteLimit),
+
+    #[error("Trace file rotate count must be positive")]
+    ZeroRotateCount,
+
+    #[error("Invalid trace file name or template: {0}")]
+    InvalidFileName(String),
+
+    #[error("Invalid fsync policy (must be never, on-rotation, every-flush or every-<N>s): {0}")]
+    InvalidFsyncPolicy(String),
+
+    #[error("Invalid value of environment variable {0}: {1}")]
+    InvalidEnvOverride(&'static str, String),
+
+    #[error("Trace sink wasn't flushed within {0:?}")]
+    FlushTimeout(Duration),
+
+    #[error("Corrupted trace frame in {0:?} at offset {1}")]
+    CorruptedFrame(PathBuf, u64),
+
+    #[error("Random access isn't supported for compressed trace file: {0:?}")]
+    CompressedFile(PathBuf),
+
+    #[error("Trace file isn't part of the trace: {0:?}")]
+    UnknownEventFile(PathBuf),
+
+    #[error("Trace sink's buffer is full (byte limit: {0})")]
+    SinkBufferFull(usize),
+
+    #[error("Unsupported trace sink: {0}")]
+    UnsupportedSink(String),
+
+    #[error("Unsupported trace file compression: {0:?}")]
+    UnsupportedCompression(TraceCompression),
+
+    #[cfg(feature = "banking-trace-kafka")]
+    #[error("Kafka Error: {0}")]
+    KafkaError(#[from] rdkafka::error::KafkaError),
+
+    #[cfg(feature = "banking-trace-sqlite")]
+    #[error("SQLite Error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+
+    #[cfg(feature = "banking-trace-upload")]
+    #[error("Object Store Error: {0}")]
+    ObjectStoreError(#[from] object_store::Error),
 }
 
 pub(crate) const BASENAME: &str = "events";
-const TRACE_FILE_ROTATE_COUNT: u64 = 14; // target 2 weeks retention under normal load
-const TRACE_FILE_WRITE_INTERVAL_MS: u64 = 100;
-const BUF_WRITER_CAPACITY: usize = 10 * 1024 * 1024;
+// Each trace file starts with this magic and is followed by frames of bincode-serialized
+// TimedTracedEvents. A frame is laid out as [len: u32 LE][payload][len: u32 LE], so that
+// readers can walk a file backwards from its end as well as forwards from its start.
+pub(crate) const TRACE_FILE_MAGIC: &[u8; 8] = b"BNKTRC01";
+pub(crate) const FRAME_LEN_SIZE: usize = size_of::<u32>();
+// Completed files are followed by an index footer after their last frame:
+// [magic][the event boundaries: u64 LE each][their count: u64 LE][magic]. So readers can locate
+// events without scanning the whole file, and stop at the leading magic when reading forwards.
+pub(crate) const TRACE_INDEX_MAGIC: &[u8; 8] = b"BNKTIDX1";
+pub const TRACE_FILE_DEFAULT_ROTATE_COUNT: u64 = 14; // target 2 weeks retention under normal load
+pub const TRACE_FILE_DEFAULT_WRITE_INTERVAL: Duration = Duration::from_millis(100);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const DISK_SPACE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const STATS_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+// Events drained in a single iteration are written at once up to this count, so that their
+// coalesced frames stay reasonably sized under floods.
+const MAX_COALESCED_EVENT_COUNT: usize = 1024;
+const TRACER_THREAD_NAME: &str = "solBanknTracer";
+pub const PANIC_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long `BankingTracer` waits for the tracer thread to rotate, resize or report on the sink,
+/// after writing the events traced before.
+pub const SINK_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+pub const BUFFERED_AGE_BUCKETS_MS: [u64; 6] = [0, 100, 400, 1_000, 5_000, 30_000];
+pub const TRACE_FILE_DEFAULT_BUF_WRITER_CAPACITY: usize = 10 * 1024 * 1024;
+pub const TRACE_FILE_DEFAULT_COMPRESSION_THREAD_COUNT: usize = 2;
 pub const TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD: u64 = 1024 * 1024 * 1024;
 pub const DISABLED_BAKING_TRACE_DIR: DirByteLimit = 0;
 pub const BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT: DirByteLimit =
-    TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD * TRACE_FILE_ROTATE_COUNT;
+    TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD * TRACE_FILE_DEFAULT_ROTATE_COUNT;
+
+type ChannelStatsRegistry = Arc<Mutex<Vec<(ChannelLabel, Arc<ChannelStats>)>>>;
+
+/// Overrides of the configuration given to `BankingTracer::new()`, read from the environment,
+/// so that tracing can be tweaked without plumbing CLI flags:
+///
+/// - `BANKING_TRACE_DIR`: the trace directory
+/// - `BANKING_TRACE_DIR_BYTE_LIMIT`: the byte limit of the trace directory, disabling tracing
+///   if zero
+/// - `BANKING_TRACE_COMPRESSION`: the compression of the completed trace files (`zstd` or
+///   `lz4`)
+/// - `BANKING_TRACE_SKIP_LABELS`: comma-separated labels (e.g. `tpu-vote,gossip-vote`) whose
+///   packet batches aren't traced
+///
+/// They're read and applied only if tracing is enabled in the first place.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct EnvOverrides {
+    dir: Option<PathBuf>,
+    dir_byte_limit: Option<DirByteLimit>,
+    compression: Option<TraceCompression>,
+    untraced_labels: Vec<ChannelLabel>,
+}
+
+impl EnvOverrides {
+    const DIR: &'static str = "BANKING_TRACE_DIR";
+    const DIR_BYTE_LIMIT: &'static str = "BANKING_TRACE_DIR_BYTE_LIMIT";
+    const COMPRESSION: &'static str = "BANKING_TRACE_COMPRESSION";
+    const SKIP_LABELS: &'static str = "BANKING_TRACE_SKIP_LABELS";
+
+    pub(crate) fn from_env() -> Result<Self, TraceError> {
+        Self::from_var_os(std::env::var_os)
+    }
+
+    /// Reads the overrides with `var_os`, e.g. `std::env::var_os`, so that unrelated variables
+    /// which aren't valid unicode don't matter.
+    fn from_var_os(var_os: impl Fn(&'static str) -> Option<OsString>) -> Result<Self, TraceError> {
+        let var = |name| {
+            var_os(name)
+                .map(|value| {
+                    value.into_string().map_err(|value| {
+                        TraceError::InvalidEnvOverride(name, value.to_string_lossy().into_owned())
+                    })
+                })
+                .transpose()
+        };
+        let invalid = |name, value: &str| TraceError::InvalidEnvOverride(name, value.to_owned());
+        let mut overrides = Self {
+            dir: var_os(Self::DIR).map(PathBuf::from),
+            ..Self::default()
+        };
+        if let Some(value) = var(Self::DIR_BYTE_LIMIT)? {
+            overrides.dir_byte_limit = Some(
+                value
+                    .parse()
+                    .map_err(|_| invalid(Self::DIR_BYTE_LIMIT, &value))?,
+            );
+        }
+        if let Some(value) = var(Self::COMPRESSION)? {
+            overrides.compression = Some(
+                value
+                    .parse()
+                    .map_err(|_| invalid(Self::COMPRESSION, &value))?,
+            );
+        }
+        if let Some(value) = var(Self::SKIP_LABELS)? {
+            overrides.untraced_labels = value
+                .split(',')
+                .map(|label| ChannelLabel::from_str(label.trim()))
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid(Self::SKIP_LABELS, &value))?;
+        }
+        Ok(overrides)
+    }
+
+    /// Overrides the trace directory, its byte limit and the file sink `config` with the ones
+    /// set in the environment, leaving the rest as given.
+    pub(crate) fn apply(
+        &self,
+        path: &mut PathBuf,
+        dir_byte_limit: &mut DirByteLimit,
+        config: &mut RollingFileSinkConfig,
+    ) {
+        if *self != Self::default() {
+            info!("overriding banking trace config from the environment: {self:?}");
+        }
+        if let Some(dir) = &self.dir {
+            path.clone_from(dir);
+        }
+        if let Some(overridden_dir_byte_limit) = self.dir_byte_limit {
+            *dir_byte_limit = overridden_dir_byte_limit;
+        }
+        if let Some(compression) = self.compression {
+            config.compression = Some(compression);
+        }
+    }
+
+    /// The labels to be untraced once the tracer is created, on top of the configured ones.
+    pub(crate) fn untraced_labels(&self) -> &[ChannelLabel] {
+        &self.untraced_labels
+    }
+}
+
+/// The tracer thread being traced into, until swapped out by `BankingTracer::enable()` or
+/// `BankingTracer::disable()`.
+#[derive(Debug)]
+struct TraceSession {
+    trace_sender: Sender<TraceMessage>,
+    /// Receives the events not yet written by the tracer thread, to abandon them on shutdown.
+    /// Owned by the tracer thread, so that it's dropped along with the events once it exits.
+    pending_events: Weak<Receiver<TraceMessage>>,
+    command_sender: Sender<SinkCommand>,
+    /// Disconnected once the tracer thread exits.
+    exited: Receiver<()>,
+    exit: Arc<AtomicBool>,
+}
+
+impl TraceSession {
+    /// Sends `command` built with a result sender, and waits up to `timeout` for its result.
+    /// Returns `None` if the tracer thread has exited.
+    fn execute_command(
+        &self,
+        command: impl FnOnce(Sender<Result<(), TraceError>>) -> SinkCommand,
+        timeout: Duration,
+    ) -> Option<Result<(), TraceError>> {
+        let (result_sender, result_receiver) = bounded(1);
+        self.command_sender.send(command(result_sender)).ok()?;
+        match result_receiver.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            // the events traced before are still being written
+            Err(RecvTimeoutError::Timeout) => Some(Err(TraceError::FlushTimeout(timeout))),
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+/// An event sent to the tracer thread, along with its frame if already serialized by the
+/// sender in the serialize-in-sender mode. The event itself is still needed for the stats, the
+/// taps and the sinks not writing frames, while sharing its packet batch.
+#[derive(Debug)]
+struct TraceMessage {
+    event: TimedTracedEvent,
+    frame: Option<Vec<u8>>,
+}
+
+impl From<TimedTracedEvent> for TraceMessage {
+    fn from(event: TimedTracedEvent) -> Self {
+        Self { event, frame: None }
+    }
+}
+
+/// Requests from `BankingTracer` to its sink, handled by the tracer thread in between events.
+/// The events traced before a command is sent are written before it's executed.
+#[derive(Debug)]
+enum SinkCommand {
+    SetDirByteLimit(DirByteLimit, Sender<Result<(), TraceError>>),
+    Flush(Sender<Result<(), TraceError>>),
+    Rotate(Sender<Result<(), TraceError>>),
+    /// Copies the events written from now on into the sender, until it's disconnected or full.
+    Tap(Sender<TimedTracedEvent>),
+    EffectiveConfig(Sender<SinkEffectiveConfig>),
+}
+
+impl SinkCommand {
+    fn execute(self, state: &mut TracerThreadState<impl TraceSink>) {
+        match self {
+            Self::SetDirByteLimit(dir_byte_limit, result_sender) => {
+                let _ = result_sender.send(state.sink.set_dir_byte_limit(dir_byte_limit));
+            }
+            Self::Flush(result_sender) => {
+                let _ = result_sender.send(state.sink.flush());
+            }
+            Self::Rotate(result_sender) => {
+                let _ = result_sender.send(state.sink.rotate_now());
+            }
+            Self::Tap(tap) => state.taps.push(tap),
+            Self::EffectiveConfig(config_sender) => {
+                let _ = config_sender.send(state.sink.effective_config());
+            }
+        }
+    }
+}
 
+/// Flushes the sink of a `BankingTracer`, as obtained by `BankingTracer::flusher()`.
 #[derive(Clone, Debug)]
+pub struct TraceFlusher {
+    session: Weak<ArcSwapOption<TraceSession>>,
+}
+
+impl TraceFlusher {
+    /// Writes the events traced so far and flushes the sink, waiting up to `timeout` for it.
+    /// Does nothing unless enabled.
+    pub fn flush_now(&self, timeout: Duration) -> Result<(), TraceError> {
+        // the tracer thread flushes anyway once exited
+        self.session
+            .upgrade()
+            .and_then(|session| session.load_full())
+            .and_then(|session| session.execute_command(SinkCommand::Flush, timeout))
+            .unwrap_or(Ok(()))
+    }
+}
+
+/// Shared by a `BankingTracer` and its channels, so that tracing can be enabled and disabled at
+/// runtime.
+#[derive(Clone, Debug, Default)]
 struct ActiveTracer {
-    trace_sender: Sender<TimedTracedEvent>,
-    exit: Arc<AtomicBool>,
+    session: Arc<ArcSwapOption<TraceSession>>,
+    /// The bits of the labels whose packet batches aren't traced.
+    untraced_labels: Arc<AtomicU8>,
+    disk_space_watchdog: Arc<DiskSpaceWatchdog>,
+    write_interval: Arc<WriteInterval>,
+    /// Other tracers into which everything traced by this one is traced as well, as added by
+    /// `BankingTracer::mirror_into()`. Their own mirrors aren't followed, so that mirroring
+    /// can't loop.
+    mirrors: Arc<ArcSwap<Vec<ActiveTracer>>>,
+    /// Whether the packet batches are serialized by the senders themselves.
+    serialize_in_sender: Arc<AtomicBool>,
+    clock: Arc<TraceClock>,
+}
+
+/// How long the tracer thread sleeps once it has written all the events received so far,
+/// adjustable at runtime. Shorter intervals reduce the latency of the trace at the cost of
+/// more wakeups.
+#[derive(Debug)]
+struct WriteInterval {
+    micros: AtomicU64,
+}
+
+impl Default for WriteInterval {
+    fn default() -> Self {
+        Self {
+            micros: AtomicU64::new(TRACE_FILE_DEFAULT_WRITE_INTERVAL.as_micros() as u64),
+        }
+    }
+}
+
+impl WriteInterval {
+    fn load(&self) -> Duration {
+        Duration::from_micros(self.micros.load(Ordering::Relaxed))
+    }
+
+    fn store(&self, write_interval: Duration) {
+        self.micros.store(
+            u64::try_from(write_interval.as_micros()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/// Timestamps the traced events. In the coarse mode, the time is cached by the tracer thread
+/// for each event it receives and once every write interval while idle, instead of being read
+/// from the system clock for each event, trading precision for the cost of the clock calls on
+/// the hot paths. It may lag further while the tracer thread is busy writing.
+#[derive(Debug, Default)]
+struct TraceClock {
+    is_coarse: AtomicBool,
+    /// Microseconds since the epoch as of the last update, or zero unless being updated.
+    coarse_micros: AtomicU64,
+}
+
+impl TraceClock {
+    fn now(&self) -> SystemTime {
+        if self.is_coarse.load(Ordering::Relaxed) {
+            let micros = self.coarse_micros.load(Ordering::Relaxed);
+            // otherwise, no tracer thread is updating it
+            if micros > 0 {
+                return UNIX_EPOCH + Duration::from_micros(micros);
+            }
+        }
+        SystemTime::now()
+    }
+
+    /// Caches the current time, if coarse. Called by the tracer thread.
+    fn update(&self) {
+        let micros = if self.is_coarse.load(Ordering::Relaxed) {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_micros() as u64)
+        } else {
+            0
+        };
+        self.coarse_micros.store(micros, Ordering::Relaxed);
+    }
+
+    /// Stops using the cached time, once the tracer thread exits.
+    fn reset(&self) {
+        self.coarse_micros.store(0, Ordering::Relaxed);
+    }
+
+    /// Sleeps for `interval`, then caches the current time, if coarse. So, the cached time
+    /// advances along with the write interval while idle, without any wakeups of its own.
+    fn sleep(&self, interval: Duration) {
+        sleep(interval);
+        self.update();
+    }
+}
+
+/// Suspends tracing packet batches while the free space of the trace filesystem is low, as
+/// checked by the tracer thread every `DISK_SPACE_CHECK_INTERVAL`.
+#[derive(Debug, Default)]
+struct DiskSpaceWatchdog {
+    /// Disabled if zero.
+    min_available_bytes: AtomicU64,
+    is_suspended: AtomicBool,
+}
+
+impl DiskSpaceWatchdog {
+    /// Checks the free space of `sink` if due, returning the event to trace if tracing packet
+    /// batches has just been suspended or resumed.
+    fn check(
+        &self,
+        sink: &impl TraceSink,
+        last_check_time: &Cell<Option<Instant>>,
+    ) -> Option<TimedTracedEvent> {
+        let now = Instant::now();
+        if last_check_time.get().is_some_and(|last_check_time| {
+            now.duration_since(last_check_time) < DISK_SPACE_CHECK_INTERVAL
+        }) {
+            return None;
+        }
+        last_check_time.set(Some(now));
+
+        let min_available_bytes = self.min_available_bytes.load(Ordering::Relaxed);
+        let available_bytes = (min_available_bytes > 0)
+            .then(|| sink.available_bytes())
+            .flatten();
+        let is_suspended =
+            available_bytes.is_some_and(|available_bytes| available_bytes < min_available_bytes);
+        if self.is_suspended.swap(is_suspended, Ordering::Relaxed) == is_suspended {
+            return None;
+        }
+        if is_suspended {
+            warn!(
+                "suspended tracing packet batches due to low disk space (available: \
+                 {available_bytes:?}, min: {min_available_bytes})"
+            );
+        } else {
+            info!("resumed tracing packet batches (available: {available_bytes:?})");
+        }
+        Some(TimedTracedEvent(
+            SystemTime::now(),
+            TracedEvent::PacketTracingSuspended {
+                is_suspended,
+                available_bytes,
+            },
+        ))
+    }
+}
+
+impl ActiveTracer {
+    fn is_enabled(&self) -> bool {
+        self.session.load().is_some()
+    }
+
+    /// Whether any event would be traced into this tracer or its mirrors.
+    fn is_tracing(&self) -> bool {
+        self.is_enabled() || self.mirrors.load().iter().any(ActiveTracer::is_enabled)
+    }
+
+    fn is_label_traced(&self, label: ChannelLabel) -> bool {
+        self.untraced_labels.load(Ordering::Relaxed) & label.bit() == 0
+    }
+
+    fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    fn trace_event(&self, on_trace: impl FnOnce() -> TimedTracedEvent) {
+        let mirrors = self.mirrors.load();
+        if mirrors.is_empty() {
+            self.trace_own_event(on_trace);
+            return;
+        }
+        // created only if traced by any of the tracers
+        let event = LazyCell::new(on_trace);
+        for tracer in iter::once(self).chain(mirrors.iter()) {
+            tracer.trace_own_event(|| TimedTracedEvent::clone(&event));
+        }
+    }
+
+    fn trace_own_event(&self, on_trace: impl FnOnce() -> TimedTracedEvent) {
+        if let Some(session) = &*self.session.load() {
+            if !session.exit.load(Ordering::Relaxed) {
+                // dropped like the packet batches of the mirrors if the tracer thread has gone
+                // without setting `exit` (e.g. a mirror being torn down concurrently)
+                let _ = session.trace_sender.send(on_trace().into());
+            }
+        }
+    }
+
+    /// Traces `batch` about to be sent to the channel of `label`. Fails only if the tracer
+    /// thread has unexpectedly gone; the failures of the mirrors are just logged.
+    fn trace_packet_batch(
+        &self,
+        label: ChannelLabel,
+        batch: &BankingPacketBatch,
+    ) -> Result<(), ()> {
+        for mirror in self.mirrors.load().iter() {
+            let _ = mirror.trace_own_packet_batch(label, batch);
+        }
+        self.trace_own_packet_batch(label, batch)
+    }
+
+    fn trace_own_packet_batch(
+        &self,
+        label: ChannelLabel,
+        batch: &BankingPacketBatch,
+    ) -> Result<(), ()> {
+        if !self.is_label_traced(label)
+            || self
+                .disk_space_watchdog
+                .is_suspended
+                .load(Ordering::Relaxed)
+        {
+            return Ok(());
+        }
+        let session = self.session.load();
+        let Some(session) = session
+            .as_ref()
+            .filter(|session| !session.exit.load(Ordering::Relaxed))
+        else {
+            return Ok(());
+        };
+        let event = TimedTracedEvent(
+            self.now(),
+            TracedEvent::PacketBatch(label, BankingPacketBatch::clone(batch)),
+        );
+        let mut frame = None;
+        if self.serialize_in_sender.load(Ordering::Relaxed) {
+            let mut frame_buf = vec![];
+            // left to the tracer thread on failures, which records the event as lost then
+            if serialize_frame(&mut frame_buf, &event).is_ok() {
+                frame = Some(frame_buf);
+            }
+        }
+        session
+            .trace_sender
+            .send(TraceMessage { event, frame })
+            .map_err(|err| {
+                // not {err:?}, which would format the whole batch
+                error!("unexpected error when tracing a banking event...: {err}");
+            })
+    }
 }
 
 #[derive(Debug)]
 pub struct BankingTracer {
-    active_tracer: Option<ActiveTracer>,
+    active_tracer: ActiveTracer,
+    /// The tracer thread spawned by `enable()`, if it's still traced into.
+    tracer_thread: Mutex<TracerThread>,
+    /// Also sampled by the tracer thread for `TracedEvent::ChannelDepths`.
+    channel_stats: ChannelStatsRegistry,
+}
+
+/// Point-in-time state of a `BankingTracer`, as returned by `BankingTracer::status()`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BankingTracerStatus {
+    pub is_enabled: bool,
+    pub is_packet_tracing_suspended: bool,
+    pub untraced_labels: Vec<ChannelLabel>,
+    /// Zero if the disk space watchdog is disabled.
+    pub min_available_bytes: u64,
 }
 
 #[cfg_attr(
     feature = "frozen-abi",
     derive(AbiExample),
-    frozen_abi(digest = "91baCBT3aY2nXSAuzY3S5dnMhWabVsHowgWqYPLjfyg7")
+    frozen_abi(digest = "DJa86FePVzBSJHJGciV2V9wou4eKXJ9aKVicVvT6PPA3")
 )]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TimedTracedEvent(pub std::time::SystemTime, pub TracedEvent);
 
 #[cfg_attr(feature = "frozen-abi", derive(AbiExample, AbiEnumVisitor))]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TracedEvent {
     PacketBatch(ChannelLabel, BankingPacketBatch),
     BlockAndBankHash(Slot, Hash, Hash),
+    /// Traced when our leader slot starts (`start: true`) or ends (`start: false`) being
+    /// processed by the banking stage.
+    LeaderSlotBoundary {
+        slot: Slot,
+        start: bool,
+    },
+    /// Traced when packets are dropped before making it into a block.
+    PacketsDropped {
+        label: ChannelLabel,
+        count: u64,
+        reason: PacketDropReason,
+    },
+    /// Traced periodically by the sigverify stage with the counts of the last interval.
+    SigverifyStats {
+        label: ChannelLabel,
+        verified: u64,
+        failed: u64,
+        dedup: u64,
+        interval_us: u64,
+    },
+    /// Traced by a banking worker when it takes a batch of transactions assigned to it by the
+    /// scheduler. Batch ids increase in the order the batches were scheduled, and the signatures
+    /// are listed in the order the transactions are processed.
+    TransactionBatchScheduled {
+        worker_id: u32,
+        batch_id: u64,
+        signatures: Vec<Signature>,
+    },
+    /// Traced by a banking worker when it's done with a batch traced as
+    /// `TransactionBatchScheduled` with the same `worker_id` and `batch_id`.
+    TransactionBatchOutcome {
+        worker_id: u32,
+        batch_id: u64,
+        /// In the same order as the signatures of the scheduled batch.
+        outcomes: Vec<TransactionOutcome>,
+        /// Why none of the transactions could be committed, if so.
+        error: Option<BatchErrorClass>,
+    },
+    /// Traced when the bank of our leader slot is frozen after the block is complete.
+    BankFrozen {
+        slot: Slot,
+        tick_height: u64,
+        /// The number of transactions executed in the bank, excluding its ancestors.
+        transaction_count: u64,
+    },
+    /// Traced when the forwarding stage ships buffered packets to the next leader instead of
+    /// processing them.
+    Forwarded {
+        label: ChannelLabel,
+        count: u64,
+        /// The address of the next leader, if known by the forwarding client.
+        target: Option<SocketAddr>,
+    },
+    /// Traced by a banking worker when transactions of a batch traced as
+    /// `TransactionBatchScheduled` don't fit into the block of `slot` per the cost tracker.
+    CostLimitExceeded {
+        worker_id: u32,
+        batch_id: u64,
+        slot: Slot,
+        /// Indexes of the transactions in the batch, along with the limit they would exceed.
+        rejections: Vec<(u32, CostLimit)>,
+    },
+    /// Traced periodically by the scheduler with the compute unit prices of the transactions
+    /// buffered for scheduling.
+    PriorityFeeSnapshot {
+        leader_slot: Option<Slot>,
+        compute_unit_prices: ComputeUnitPriceDistribution,
+    },
+    /// Traced periodically by the tracer thread with the number of batches waiting in each of
+    /// the labeled channels, as of the last send to it. Unified channels are listed once, under
+    /// the first of their labels.
+    ChannelDepths {
+        depths: Vec<(ChannelLabel, u64)>,
+    },
+    /// Traced by the tracer thread every `HEARTBEAT_INTERVAL` while no packets arrive, so that
+    /// a gap in the trace means lost events rather than a quiet period.
+    Heartbeat,
+    /// Traced on each tick of PoH, so that other events can be positioned within the slot.
+    PohTick {
+        slot: Slot,
+        tick_height: u64,
+        /// Whether the tick completes `slot`, i.e. the next tick starts the next slot.
+        last_tick_in_slot: bool,
+    },
+    /// Free-form marker injected with `BankingTracer::annotate()`.
+    Annotation(String),
+    /// Traced by the unified scheduler along the lifecycle of each task of block production.
+    SchedulerTask {
+        task_index: u64,
+        state: SchedulerTaskState,
+    },
+    /// Traced by the tracer thread once it manages to write again after failing to write some
+    /// events. Timestamped with the first lost event, which is followed by the last one after
+    /// `span`.
+    TraceGap {
+        dropped_count: u64,
+        span: Duration,
+    },
+    /// Traced by the scheduler at the end of our leader slot with the transactions left
+    /// unprocessed in its buffer.
+    BufferedTransactions {
+        slot: Slot,
+        /// Counts by priority; the buckets are bounded by 0 and powers of ten.
+        by_priority: BucketCounts,
+        /// Counts by time since buffered; the buckets are bounded by `BUFFERED_AGE_BUCKETS_MS`.
+        by_age_ms: BucketCounts,
+    },
+    /// Traced by a banking worker along with `TransactionBatchOutcome`, with the execution
+    /// result of each transaction of the batch, so the fate of a given transaction can be
+    /// looked up by its signature alone.
+    TransactionResults {
+        worker_id: u32,
+        batch_id: u64,
+        results: Vec<(Signature, TransactionResult<()>)>,
+    },
+    /// Traced along with each `PacketBatch` sent to a priority channel with the lane it was
+    /// sent to.
+    PacketBatchLane {
+        label: ChannelLabel,
+        lane: PriorityLane,
+    },
+    /// Traced by a `TracedReceiver` when it dequeues a batch, if asked to.
+    PacketBatchDequeued {
+        label: ChannelLabel,
+    },
+    /// Traced by the tracer thread when it suspends or resumes tracing packet batches as the
+    /// free space of the trace filesystem crosses `BankingTracer::set_min_available_bytes()`,
+    /// with the free space if known.
+    PacketTracingSuspended {
+        is_suspended: bool,
+        available_bytes: Option<u64>,
+    },
+}
+
+impl TracedEvent {
+    /// Name of the variant, e.g. `"PacketBatch"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::PacketBatch(..) => "PacketBatch",
+            Self::BlockAndBankHash(..) => "BlockAndBankHash",
+            Self::LeaderSlotBoundary { .. } => "LeaderSlotBoundary",
+            Self::PacketsDropped { .. } => "PacketsDropped",
+            Self::SigverifyStats { .. } => "SigverifyStats",
+            Self::TransactionBatchScheduled { .. } => "TransactionBatchScheduled",
+            Self::TransactionBatchOutcome { .. } => "TransactionBatchOutcome",
+            Self::BankFrozen { .. } => "BankFrozen",
+            Self::Forwarded { .. } => "Forwarded",
+            Self::CostLimitExceeded { .. } => "CostLimitExceeded",
+            Self::PriorityFeeSnapshot { .. } => "PriorityFeeSnapshot",
+            Self::ChannelDepths { .. } => "ChannelDepths",
+            Self::Heartbeat => "Heartbeat",
+            Self::PohTick { .. } => "PohTick",
+            Self::Annotation(..) => "Annotation",
+            Self::SchedulerTask { .. } => "SchedulerTask",
+            Self::TraceGap { .. } => "TraceGap",
+            Self::BufferedTransactions { .. } => "BufferedTransactions",
+            Self::TransactionResults { .. } => "TransactionResults",
+            Self::PacketBatchLane { .. } => "PacketBatchLane",
+            Self::PacketBatchDequeued { .. } => "PacketBatchDequeued",
+            Self::PacketTracingSuspended { .. } => "PacketTracingSuspended",
+        }
+    }
+
+    /// The channel which this event is about, if any.
+    pub fn label(&self) -> Option<ChannelLabel> {
+        match self {
+            Self::PacketBatch(label, _)
+            | Self::PacketsDropped { label, .. }
+            | Self::SigverifyStats { label, .. }
+            | Self::Forwarded { label, .. }
+            | Self::PacketBatchLane { label, .. }
+            | Self::PacketBatchDequeued { label } => Some(*label),
+            _ => None,
+        }
+    }
+
+    /// The slot which this event is about, if any.
+    pub fn slot(&self) -> Option<Slot> {
+        match self {
+            Self::BlockAndBankHash(slot, ..)
+            | Self::LeaderSlotBoundary { slot, .. }
+            | Self::BankFrozen { slot, .. }
+            | Self::CostLimitExceeded { slot, .. }
+            | Self::PohTick { slot, .. }
+            | Self::BufferedTransactions { slot, .. } => Some(*slot),
+            Self::PriorityFeeSnapshot { leader_slot, .. } => *leader_slot,
+            _ => None,
+        }
+    }
+}
+
+#[cfg_attr(feature = "frozen-abi", derive(AbiExample, AbiEnumVisitor))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PacketDropReason {
+    /// Failed signature verification.
+    SigverifyFailure,
+    /// Duplicate of a recently received packet.
+    Dedup,
+    /// Shed due to excess load or because the buffer is full.
+    BufferOverflow,
+    /// Dropped from the buffer while not being the leader, leaving them to be forwarded.
+    Forwarded,
+    /// Too old blockhash or already processed.
+    TooOldBlockhash,
+    /// Evicted from a full bounded channel as its oldest batch to make room for a new one.
+    ChannelOverflow,
+    /// Over the rate limit of the sender of the channel.
+    RateLimited,
+    /// Discarded by the `SendFilter` of the sender of the channel.
+    Filtered,
+}
+
+/// How sends to a full bounded channel are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Blocks until the receiver makes room.
+    Block,
+    /// Evicts the oldest batch, tracing its packets as dropped, so that the sender (e.g. the
+    /// QUIC ingest path) is never blocked by stale transactions.
+    DropOldest,
+}
+
+#[cfg_attr(feature = "frozen-abi", derive(AbiExample, AbiEnumVisitor))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionOutcome {
+    Committed,
+    /// Sent back to the scheduler to be scheduled again.
+    Retryable,
+    /// Neither committed nor retried.
+    Dropped,
+}
+
+#[cfg_attr(feature = "frozen-abi", derive(AbiExample, AbiEnumVisitor))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SchedulerTaskState {
+    /// Created from the transaction with `signature`, which is traced as part of a `PacketBatch`.
+    /// A retried task is created again under a new index.
+    Created { signature: Signature },
+    /// Buffered because some of its accounts are locked by other tasks.
+    Blocked,
+    /// Executed, whether committed or not.
+    Completed { committed: bool },
+}
+
+#[cfg_attr(feature = "frozen-abi", derive(AbiExample, AbiEnumVisitor))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CostLimit {
+    /// The block cost limit; the transaction is retried.
+    Block,
+    /// The block cost limit of vote transactions; the transaction is retried.
+    Vote,
+    /// The cost limit of a writable account; the transaction is retried.
+    Account,
+    /// The block limit of account data size growth; the transaction is retried.
+    AccountDataBlock,
+    /// The limit of total account data size; the transaction is dropped.
+    AccountDataTotal,
+}
+
+/// Distribution of compute unit prices in micro-lamports.
+#[cfg_attr(feature = "frozen-abi", derive(AbiExample))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComputeUnitPriceDistribution {
+    pub count: u64,
+    pub min: u64,
+    pub p25: u64,
+    pub median: u64,
+    pub p75: u64,
+    pub p90: u64,
+    pub max: u64,
+}
+
+impl ComputeUnitPriceDistribution {
+    /// Returns `None` if there are no prices.
+    pub fn from_prices(mut prices: Vec<u64>) -> Option<Self> {
+        prices.sort_unstable();
+        let last_index = prices.len().checked_sub(1)?;
+        let percentile = |percent: usize| prices[last_index * percent / 100];
+        Some(Self {
+            count: prices.len() as u64,
+            min: prices[0],
+            p25: percentile(25),
+            median: percentile(50),
+            p75: percentile(75),
+            p90: percentile(90),
+            max: prices[last_index],
+        })
+    }
+}
+
+#[cfg_attr(feature = "frozen-abi", derive(AbiExample, AbiEnumVisitor))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BatchErrorClass {
+    /// No leader bank was available to process the batch with.
+    NoBank,
+    /// The leader bank had reached its max tick height.
+    MaxHeightReached,
+    /// The leader bank hadn't reached its min tick height yet.
+    MinHeightNotReached,
+    /// Recording the executed transactions into PoH failed.
+    RecordFailed,
+}
+
+/// Lane of a priority channel, whose receiver drains `High` before `Low`.
+#[cfg_attr(feature = "frozen-abi", derive(AbiExample, AbiEnumVisitor))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PriorityLane {
+    High,
+    Low,
 }
 
 #[cfg_attr(feature = "frozen-abi", derive(AbiExample, AbiEnumVisitor))]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    EnumString,
+    IntoStaticStr,
+)]
+#[strum(serialize_all = "kebab-case")]
 pub enum ChannelLabel {
     NonVote,
     TpuVote,
     GossipVote,
+    #[strum(disabled)]
     Dummy,
 }
 
+impl ChannelLabel {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// When to start a new trace file, besides on `TraceSink::rotate_hint()` and at the start of
+/// tracing.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, EnumVariantNames, IntoStaticStr, Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RotationPolicy {
+    /// Every hour, or once the file reaches the rotation threshold, e.g. for short-lived test
+    /// validators.
+    Hourly,
+    /// Every day, or once the file reaches the rotation threshold.
+    #[default]
+    Daily,
+    /// Only once the file reaches the rotation threshold, e.g. for archival nodes.
+    Size,
+    /// Never, so that the byte limit of the trace directory isn't enforced.
+    Never,
+}
+
+impl RotationPolicy {
+    pub const fn cli_names() -> &'static [&'static str] {
+        Self::VARIANTS
+    }
+}
+
+/// How `RollingFileSink` writes into the trace files.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, EnumVariantNames, IntoStaticStr, Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum FileIoBackend {
+    /// Blocking writes through a `BufWriter`.
+    #[default]
+    Buffered,
+    /// Writes submitted to io_uring without waiting for them, except on flushes and rotations.
+    /// Only on Linux with the `banking-trace-io-uring` feature; buffered writes are used
+    /// otherwise.
+    IoUring,
+    /// Two buffers, one being filled while the other is written out by an I/O helper thread.
+    DoubleBuffered,
+}
+
+impl FileIoBackend {
+    pub const fn cli_names() -> &'static [&'static str] {
+        Self::VARIANTS
+    }
+}
+
+/// When to fsync the trace files, trading throughput for not losing the last buffered events
+/// on crashes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Leave it to the OS.
+    #[default]
+    Never,
+    /// Once each file is completed.
+    OnRotation,
+    /// Each time the sink is flushed.
+    EveryFlush,
+    /// Flush and fsync the file being written into at this interval, while events are traced.
+    Interval(Duration),
+}
+
+impl FromStr for FsyncPolicy {
+    type Err = TraceError;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "never" => Ok(Self::Never),
+            "on-rotation" => Ok(Self::OnRotation),
+            "every-flush" => Ok(Self::EveryFlush),
+            _ => policy
+                .strip_prefix("every-")
+                .and_then(|secs| secs.strip_suffix('s'))
+                .and_then(|secs| secs.parse().ok())
+                .filter(|&secs| secs > 0)
+                .map(|secs| Self::Interval(Duration::from_secs(secs)))
+                .ok_or_else(|| TraceError::InvalidFsyncPolicy(policy.into())),
+        }
+    }
+}
+
 struct RollingConditionGrouped {
     basic: RollingConditionBasic,
+    rotation_policy: RotationPolicy,
+    /// Checked separately from `basic`, so that it can be changed on a live appender.
+    rotate_threshold_size: u64,
     tried_rollover_after_opened: bool,
     is_checked: bool,
+    is_new_file: bool,
+    is_rollover_requested: bool,
+    rollover_count: u64,
 }
 
 impl RollingConditionGrouped {
-    fn new(basic: RollingConditionBasic) -> Self {
+    fn new(rotation_policy: RotationPolicy, rotate_threshold_size: u64) -> Self {
         Self {
-            basic,
+            basic: Self::basic(rotation_policy),
+            rotation_policy,
+            rotate_threshold_size,
             tried_rollover_after_opened: bool::default(),
             is_checked: bool::default(),
+            is_new_file: bool::default(),
+            is_rollover_requested: bool::default(),
+            rollover_count: u64::default(),
         }
     }
 
+    fn basic(rotation_policy: RotationPolicy) -> RollingConditionBasic {
+        match rotation_policy {
+            RotationPolicy::Hourly => RollingConditionBasic::new().hourly(),
+            RotationPolicy::Daily => RollingConditionBasic::new().daily(),
+            RotationPolicy::Size | RotationPolicy::Never => RollingConditionBasic::new(),
+        }
+    }
+
+    fn set_rotation_policy(&mut self, rotation_policy: RotationPolicy) {
+        self.basic = Self::basic(rotation_policy);
+        self.rotation_policy = rotation_policy;
+    }
+
+    fn is_beyond_rotate_threshold(&self, current_filesize: u64) -> bool {
+        self.rotation_policy != RotationPolicy::Never
+            && current_filesize >= self.rotate_threshold_size
+    }
+
+    fn request_rollover(&mut self) {
+        self.is_rollover_requested = true;
+    }
+
     fn reset(&mut self) {
         self.is_checked = false;
     }
+
+    fn take_new_file(&mut self) -> bool {
+        std::mem::take(&mut self.is_new_file)
+    }
 }
 
 struct GroupedWriter<'a> {
     now: DateTime<Local>,
-    underlying: &'a mut RollingFileAppender<RollingConditionGrouped>,
+    underlying: &'a mut TraceFileAppender,
 }
 
 impl<'a> GroupedWriter<'a> {
-    fn new(underlying: &'a mut RollingFileAppender<RollingConditionGrouped>) -> Self {
+    fn new(underlying: &'a mut TraceFileAppender) -> Self {
         Self {
             now: Local::now(),
             underlying,
         }
     }
+
+    // Let the appender decide on rollover before any byte of the current group is written and
+    // report whether the group will be placed at the start of a (possibly new) blank file.
+    fn open_group(&mut self) -> Result<bool, io::Error> {
+        self.underlying.write_with_datetime(&[], &self.now)?;
+        Ok(self.underlying.condition_mut().take_new_file())
+    }
 }
 
 impl RollingCondition for RollingConditionGrouped {
@@ -128,518 +1194,4302 @@ impl RollingCondition for RollingConditionGrouped {
                 // forcibly rollover anew, so that we always avoid to append
                 // to a possibly-damaged tracing file even after unclean
                 // restarts
+                self.is_checked = true;
+                self.is_new_file = true;
+                self.rollover_count += 1;
                 return true;
             }
         }
 
         if !self.is_checked {
             self.is_checked = true;
-            self.basic.should_rollover(now, current_filesize)
+            // don't bother to rollover a blank file even if requested
+            let is_rollover_requested =
+                std::mem::take(&mut self.is_rollover_requested) && current_filesize > 0;
+            let should_rollover = is_rollover_requested
+                || self.basic.should_rollover(now, current_filesize)
+                || self.is_beyond_rotate_threshold(current_filesize);
+            self.is_new_file = should_rollover || current_filesize == 0;
+            self.rollover_count += u64::from(should_rollover);
+            should_rollover
         } else {
             false
         }
     }
 }
 
-impl Write for GroupedWriter<'_> {
-    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, io::Error> {
-        self.underlying.write_with_datetime(buf, &self.now)
+/// The appender of `RollingFileSink`, depending on its `FileIoBackend`.
+enum TraceFileAppender {
+    Buffered(RollingFileAppender<RollingConditionGrouped>),
+    #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+    IoUring(Box<RotatingAppender<uring_writer::RingFileWriter>>),
+    DoubleBuffered(RotatingAppender<DoubleBufferedWriter>),
+}
+
+impl TraceFileAppender {
+    fn io_backend(&self) -> FileIoBackend {
+        match self {
+            Self::Buffered(_) => FileIoBackend::Buffered,
+            #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+            Self::IoUring(_) => FileIoBackend::IoUring,
+            Self::DoubleBuffered(_) => FileIoBackend::DoubleBuffered,
+        }
     }
-    fn flush(&mut self) -> std::result::Result<(), io::Error> {
-        self.underlying.flush()
+
+    fn condition_ref(&self) -> &RollingConditionGrouped {
+        match self {
+            Self::Buffered(appender) => appender.condition_ref(),
+            #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+            Self::IoUring(appender) => appender.condition_ref(),
+            Self::DoubleBuffered(appender) => appender.condition_ref(),
+        }
     }
-}
 
-pub fn receiving_loop_with_minimized_sender_overhead<T, E, const SLEEP_MS: u64>(
-    exit: Arc<AtomicBool>,
-    receiver: Receiver<T>,
-    mut on_recv: impl FnMut(T) -> Result<(), E>,
-) -> Result<(), E> {
-    'outer: while !exit.load(Ordering::Relaxed) {
-        'inner: loop {
-            // avoid futex-based blocking here, otherwise a sender would have to
-            // wake me up at a syscall cost...
-            match receiver.try_recv() {
-                Ok(message) => on_recv(message)?,
-                Err(TryRecvError::Empty) => break 'inner,
-                Err(TryRecvError::Disconnected) => {
-                    break 'outer;
-                }
-            };
-            if exit.load(Ordering::Relaxed) {
-                break 'outer;
-            }
+    fn condition_mut(&mut self) -> &mut RollingConditionGrouped {
+        match self {
+            Self::Buffered(appender) => appender.condition_mut(),
+            #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+            Self::IoUring(appender) => appender.condition_mut(),
+            Self::DoubleBuffered(appender) => appender.condition_mut(),
         }
-        sleep(Duration::from_millis(SLEEP_MS));
     }
 
-    Ok(())
-}
+    fn rollover(&mut self) -> Result<(), io::Error> {
+        match self {
+            Self::Buffered(appender) => appender.rollover(),
+            #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+            Self::IoUring(appender) => appender.rollover(),
+            Self::DoubleBuffered(appender) => appender.rollover(),
+        }
+    }
 
-pub struct Channels {
-    pub non_vote_sender: BankingPacketSender,
-    pub non_vote_receiver: BankingPacketReceiver,
-    pub tpu_vote_sender: BankingPacketSender,
-    pub tpu_vote_receiver: BankingPacketReceiver,
-    pub gossip_vote_sender: BankingPacketSender,
-    pub gossip_vote_receiver: BankingPacketReceiver,
+    fn write_with_datetime(
+        &mut self,
+        buf: &[u8],
+        now: &DateTime<Local>,
+    ) -> Result<usize, io::Error> {
+        match self {
+            Self::Buffered(appender) => appender.write_with_datetime(buf, now),
+            #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+            Self::IoUring(appender) => appender.write_with_datetime(buf, now),
+            Self::DoubleBuffered(appender) => appender.write_with_datetime(buf, now),
+        }
+    }
 }
 
-#[allow(dead_code)]
-impl Channels {
-    #[cfg(feature = "dev-context-only-utils")]
-    pub fn unified_sender(&self) -> &BankingPacketSender {
-        let unified_sender = &self.non_vote_sender;
-        assert!(unified_sender
-            .sender
-            .same_channel(&self.tpu_vote_sender.sender));
-        assert!(unified_sender
-            .sender
-            .same_channel(&self.gossip_vote_sender.sender));
-        unified_sender
+impl Write for TraceFileAppender {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        match self {
+            Self::Buffered(appender) => appender.write(buf),
+            #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+            Self::IoUring(appender) => appender.write(buf),
+            Self::DoubleBuffered(appender) => appender.write(buf),
+        }
     }
 
-    pub(crate) fn unified_receiver(&self) -> &BankingPacketReceiver {
-        let unified_receiver = &self.non_vote_receiver;
-        assert!(unified_receiver.same_channel(&self.tpu_vote_receiver));
-        assert!(unified_receiver.same_channel(&self.gossip_vote_receiver));
-        unified_receiver
+    fn flush(&mut self) -> Result<(), io::Error> {
+        match self {
+            Self::Buffered(appender) => appender.flush(),
+            #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+            Self::IoUring(appender) => appender.flush(),
+            Self::DoubleBuffered(appender) => appender.flush(),
+        }
     }
 }
 
-impl BankingTracer {
-    pub fn new(
-        maybe_config: Option<(&PathBuf, Arc<AtomicBool>, DirByteLimit)>,
-    ) -> Result<(Arc<Self>, TracerThread), TraceError> {
-        match maybe_config {
-            None => Ok((Self::new_disabled(), None)),
-            Some((path, exit, dir_byte_limit)) => {
-                let rotate_threshold_size = dir_byte_limit / TRACE_FILE_ROTATE_COUNT;
-                if rotate_threshold_size == 0 {
-                    return Err(TraceError::TooSmallDirByteLimit(
-                        dir_byte_limit,
-                        TRACE_FILE_ROTATE_COUNT,
-                    ));
-                }
-
-                let (trace_sender, trace_receiver) = unbounded();
+impl Write for GroupedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, io::Error> {
+        self.underlying.write_with_datetime(buf, &self.now)
+    }
+    fn flush(&mut self) -> std::result::Result<(), io::Error> {
+        self.underlying.flush()
+    }
+}
 
-                let file_appender = Self::create_file_appender(path, rotate_threshold_size)?;
+/// Serializes `event` into `frame_buf` as a whole frame, including both of its lengths.
+pub(crate) fn serialize_frame(
+    frame_buf: &mut Vec<u8>,
+    event: &TimedTracedEvent,
+) -> Result<(), TraceError> {
+    frame_buf.clear();
+    frame_buf.extend_from_slice(&[0; FRAME_LEN_SIZE]);
+    serialize_into(&mut *frame_buf, event)?;
+    let frame_len = u32::try_from(frame_buf.len() - FRAME_LEN_SIZE)?.to_le_bytes();
+    frame_buf[..FRAME_LEN_SIZE].copy_from_slice(&frame_len);
+    frame_buf.extend_from_slice(&frame_len);
+    Ok(())
+}
 
-                let tracer_thread =
-                    Self::spawn_background_thread(trace_receiver, file_appender, exit.clone())?;
+/// Destination of the events traced by `BankingTracer`, driven by its background thread.
+pub trait TraceSink: Send + 'static {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError>;
 
-                Ok((
-                    Arc::new(Self {
-                        active_tracer: Some(ActiveTracer { trace_sender, exit }),
-                    }),
-                    Some(tracer_thread),
-                ))
-            }
+    /// Writes `events` in order, coalescing them into as few underlying writes as the sink can.
+    /// On failures, returns the number of the leading events written anyway along with the
+    /// error. Defaults to `write_event()` for each of them.
+    fn write_events(&mut self, events: &[TimedTracedEvent]) -> Result<(), (usize, TraceError)> {
+        for (index, event) in events.iter().enumerate() {
+            self.write_event(event).map_err(|err| (index, err))?;
         }
+        Ok(())
     }
 
-    pub fn new_disabled() -> Arc<Self> {
-        Arc::new(Self {
-            active_tracer: None,
-        })
+    /// Like `write_events()`, but with the frames of some of `events` already serialized by
+    /// `serialize_frame()` in the serialize-in-sender mode, as `frames[index]` for
+    /// `events[index]`. Sinks not writing frames can just ignore them, as by default.
+    fn write_serialized_events(
+        &mut self,
+        events: &[TimedTracedEvent],
+        _frames: &[Option<Vec<u8>>],
+    ) -> Result<(), (usize, TraceError)> {
+        self.write_events(events)
     }
 
-    pub fn is_enabled(&self) -> bool {
-        self.active_tracer.is_some()
-    }
+    fn flush(&mut self) -> Result<(), TraceError>;
 
-    pub fn create_channels(&self, unify_channels: bool) -> Channels {
-        if unify_channels {
-            // Returning the same channel is needed when unified scheduler supports block
-            // production because unified scheduler doesn't distinguish them and treats them as
-            // unified as the single source of incoming transactions. This is to reduce the number
-            // of recv operation per loop and load balance evenly as much as possible there.
-            let (non_vote_sender, non_vote_receiver) = self.create_channel_non_vote();
-            // Tap into some private helper fns so that banking trace labelling works as before.
-            let (tpu_vote_sender, tpu_vote_receiver) =
-                self.create_unified_channel_tpu_vote(&non_vote_sender, &non_vote_receiver);
-            let (gossip_vote_sender, gossip_vote_receiver) =
-                self.create_unified_channel_gossip_vote(&non_vote_sender, &non_vote_receiver);
+    /// Hints that the following events should go to a new segment (e.g. a new file). Sinks
+    /// without any notion of segments can just ignore it.
+    fn rotate_hint(&mut self) {}
 
-            Channels {
-                non_vote_sender,
-                non_vote_receiver,
-                tpu_vote_sender,
-                tpu_vote_receiver,
-                gossip_vote_sender,
-                gossip_vote_receiver,
-            }
-        } else {
-            let (non_vote_sender, non_vote_receiver) = self.create_channel_non_vote();
-            let (tpu_vote_sender, tpu_vote_receiver) = self.create_channel_tpu_vote();
-            let (gossip_vote_sender, gossip_vote_receiver) = self.create_channel_gossip_vote();
+    /// Starts a new segment right away, after flushing the events written so far, so that they
+    /// can be taken from a completed segment. Defaults to `rotate_hint()` followed by `flush()`,
+    /// for sinks which can't do better.
+    fn rotate_now(&mut self) -> Result<(), TraceError> {
+        self.rotate_hint();
+        self.flush()
+    }
 
-            Channels {
-                non_vote_sender,
-                non_vote_receiver,
-                tpu_vote_sender,
-                tpu_vote_receiver,
-                gossip_vote_sender,
-                gossip_vote_receiver,
-            }
-        }
+    /// The number of times a new segment has been started so far, for metrics.
+    fn rotation_count(&self) -> u64 {
+        0
     }
 
-    fn create_channel(&self, label: ChannelLabel) -> (BankingPacketSender, BankingPacketReceiver) {
-        Self::channel(label, self.active_tracer.as_ref().cloned())
+    /// Changes the byte limit of the trace directory written into. Sinks without any trace
+    /// directory can just ignore it.
+    fn set_dir_byte_limit(&mut self, _dir_byte_limit: DirByteLimit) -> Result<(), TraceError> {
+        Ok(())
     }
 
-    pub fn create_channel_non_vote(&self) -> (BankingPacketSender, BankingPacketReceiver) {
-        self.create_channel(ChannelLabel::NonVote)
+    /// The free space of the filesystem written into, watched by the disk space watchdog of
+    /// the tracer thread. Sinks without any filesystem can just return `None`.
+    fn available_bytes(&self) -> Option<u64> {
+        None
     }
 
-    fn create_channel_tpu_vote(&self) -> (BankingPacketSender, BankingPacketReceiver) {
-        self.create_channel(ChannelLabel::TpuVote)
+    /// The settings this sink is actually running with. Sinks without any settings worth
+    /// reporting can just go with the default, which reports their type name.
+    fn effective_config(&self) -> SinkEffectiveConfig {
+        SinkEffectiveConfig::Other(std::any::type_name::<Self>())
     }
+}
+
+/// Resolved settings of a `TraceSink`, as reported by `TraceSink::effective_config()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SinkEffectiveConfig {
+    RollingFile {
+        path: PathBuf,
+        dir_byte_limit: DirByteLimit,
+        rotate_threshold_size: u64,
+        config: RollingFileSinkConfig,
+    },
+    Sampling {
+        rate: u64,
+        inner: Box<SinkEffectiveConfig>,
+    },
+    Filter {
+        inner: Box<SinkEffectiveConfig>,
+    },
+    Tee(Vec<SinkEffectiveConfig>),
+    /// By the type name of the sink.
+    Other(&'static str),
+}
+
+/// Resolved settings of a `BankingTracer`, as returned by `BankingTracer::effective_config()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BankingTracerEffectiveConfig {
+    /// `None` unless enabled, or if the tracer thread didn't answer in time.
+    pub sink: Option<SinkEffectiveConfig>,
+    pub write_interval: Duration,
+    pub serialize_in_sender: bool,
+    pub coarse_clock: bool,
+    pub untraced_labels: Vec<ChannelLabel>,
+    /// Zero if the disk space watchdog is disabled.
+    pub min_available_bytes: u64,
+}
+
+/// Configuration of `RollingFileSink`, besides the trace directory and its byte limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RollingFileSinkConfig {
+    /// The number of retained files (including the one being written into), among which the
+    /// byte limit is divided.
+    pub rotate_count: u64,
+    pub rotation_policy: RotationPolicy,
+    /// The name of the file being written into. The rotated files are named after it with
+    /// their index appended, e.g. `events.1`.
+    pub basename: String,
+    /// If given, each completed file is renamed after this template instead, expanded with the
+    /// strftime-style specifiers (e.g. `events-%Y%m%d-%H`) at the time the file was started
+    /// and `{slot}` with the first slot traced into it. Of such files, only the ones completed
+    /// by the sink count towards `rotate_count`.
+    pub file_name_template: Option<String>,
+    pub fsync_policy: FsyncPolicy,
+    /// The capacity of the write buffer of the file being written into. Smaller buffers save
+    /// memory, while larger ones save syscalls.
+    pub buf_writer_capacity: usize,
+    pub io_backend: FileIoBackend,
+    /// If given, each completed file is compressed in the background and named with the
+    /// extension appended, e.g. `events.1.zst`. Not applied to the uploaded files. Only with
+    /// `banking-trace-compression`; the sink fails to be created otherwise.
+    pub compression: Option<TraceCompression>,
+    /// The number of threads compressing the completed files.
+    pub compression_thread_count: usize,
+    /// Reserve the blocks of each file up to the rotation threshold once it's created, so that
+    /// appending to it doesn't allocate them on the way. The blocks left unused are released
+    /// once it's completed. Only on Linux with `banking-trace-fs`; ignored otherwise.
+    pub preallocate: bool,
+}
 
-    fn create_channel_gossip_vote(&self) -> (BankingPacketSender, BankingPacketReceiver) {
-        self.create_channel(ChannelLabel::GossipVote)
+impl Default for RollingFileSinkConfig {
+    fn default() -> Self {
+        Self {
+            rotate_count: TRACE_FILE_DEFAULT_ROTATE_COUNT,
+            rotation_policy: RotationPolicy::default(),
+            basename: BASENAME.into(),
+            file_name_template: None,
+            fsync_policy: FsyncPolicy::default(),
+            buf_writer_capacity: TRACE_FILE_DEFAULT_BUF_WRITER_CAPACITY,
+            io_backend: FileIoBackend::default(),
+            compression: None,
+            compression_thread_count: TRACE_FILE_DEFAULT_COMPRESSION_THREAD_COUNT,
+            preallocate: false,
+        }
     }
+}
 
-    fn create_unified_channel_tpu_vote(
-        &self,
-        sender: &TracedSender,
-        receiver: &BankingPacketReceiver,
-    ) -> (BankingPacketSender, BankingPacketReceiver) {
-        Self::channel_inner(
-            ChannelLabel::TpuVote,
-            self.active_tracer.as_ref().cloned(),
-            sender.sender.clone(),
-            receiver.clone(),
-        )
+impl RollingFileSinkConfig {
+    fn validate_file_names(&self) -> Result<(), TraceError> {
+        let is_file_name = |name: &str| !name.is_empty() && !name.contains(['/', '\\']);
+        if !is_file_name(&self.basename) {
+            return Err(TraceError::InvalidFileName(self.basename.clone()));
+        }
+        if let Some(template) = &self.file_name_template {
+            if !is_file_name(template)
+                || StrftimeItems::new(template).any(|item| matches!(item, Item::Error))
+            {
+                return Err(TraceError::InvalidFileName(template.clone()));
+            }
+        }
+        Ok(())
     }
+}
 
-    fn create_unified_channel_gossip_vote(
-        &self,
-        sender: &TracedSender,
-        receiver: &BankingPacketReceiver,
-    ) -> (BankingPacketSender, BankingPacketReceiver) {
-        Self::channel_inner(
-            ChannelLabel::GossipVote,
-            self.active_tracer.as_ref().cloned(),
-            sender.sender.clone(),
-            receiver.clone(),
-        )
+/// The default `TraceSink`, which writes framed events into the rotated files of a trace
+/// directory, as read by `TraceReader`.
+pub struct RollingFileSink {
+    path: PathBuf,
+    file_appender: TraceFileAppender,
+    /// Enforced by `collect_garbage()` over all the trace files of the directory, besides the
+    /// rotation.
+    dir_byte_limit: DirByteLimit,
+    /// The number of retained files, among which the byte limit is divided.
+    rotate_count: u64,
+    basename: String,
+    file_name_template: Option<String>,
+    /// When the file being written into was started, unless it was by a previous sink.
+    file_start: Option<DateTime<Local>>,
+    file_first_slot: Option<Slot>,
+    /// The completed files renamed after `file_name_template`, from the oldest.
+    templated_files: VecDeque<PathBuf>,
+    fsync_policy: FsyncPolicy,
+    last_fsync: Instant,
+    buf_writer_capacity: usize,
+    frame_buf: Vec<u8>,
+    /// The event boundaries of the file being written into, recorded into its index footer once
+    /// it's completed. Empty if unknown, i.e. the file was started by a previous sink.
+    frame_boundaries: Vec<u64>,
+    /// The frames coalesced by `write_events()`.
+    frames_buf: Vec<u8>,
+    rotation_count: u64,
+    /// The number of files completed by this sink, with which the compressed files are matched
+    /// to their rotated originals.
+    completed_file_count: u64,
+    compression_thread_count: usize,
+    compressor: Option<RotatedFileCompressor<CompletedFile>>,
+    preallocate: bool,
+    #[cfg(feature = "banking-trace-upload")]
+    uploader: Option<uploader::RotatedFileUploader>,
+}
+
+impl RollingFileSink {
+    pub fn new(path: &PathBuf, dir_byte_limit: DirByteLimit) -> Result<Self, TraceError> {
+        Self::new_with_config(path, dir_byte_limit, RollingFileSinkConfig::default())
     }
 
-    pub fn hash_event(&self, slot: Slot, blockhash: &Hash, bank_hash: &Hash) {
-        self.trace_event(|| {
-            TimedTracedEvent(
-                SystemTime::now(),
-                TracedEvent::BlockAndBankHash(slot, *blockhash, *bank_hash),
-            )
-        })
+    /// Like `new()`, but retains `rotate_count` files (including the one being written into)
+    /// instead of `TRACE_FILE_DEFAULT_ROTATE_COUNT`, with the byte limit divided among them.
+    pub fn new_with_rotate_count(
+        path: &PathBuf,
+        dir_byte_limit: DirByteLimit,
+        rotate_count: u64,
+    ) -> Result<Self, TraceError> {
+        let config = RollingFileSinkConfig {
+            rotate_count,
+            ..RollingFileSinkConfig::default()
+        };
+        Self::new_with_config(path, dir_byte_limit, config)
     }
 
-    fn trace_event(&self, on_trace: impl Fn() -> TimedTracedEvent) {
-        if let Some(ActiveTracer { trace_sender, exit }) = &self.active_tracer {
-            if !exit.load(Ordering::Relaxed) {
-                trace_sender
-                    .send(on_trace())
-                    .expect("active tracer thread unless exited");
-            }
+    pub fn new_with_config(
+        path: &PathBuf,
+        dir_byte_limit: DirByteLimit,
+        config: RollingFileSinkConfig,
+    ) -> Result<Self, TraceError> {
+        let rotate_threshold_size =
+            Self::rotate_threshold_size(dir_byte_limit, config.rotate_count)?;
+        Self::with_rotate_threshold_size_and_config(
+            path,
+            dir_byte_limit,
+            rotate_threshold_size,
+            config,
+        )
+    }
+
+    fn rotate_threshold_size(
+        dir_byte_limit: DirByteLimit,
+        rotate_count: u64,
+    ) -> Result<u64, TraceError> {
+        if rotate_count == 0 {
+            return Err(TraceError::ZeroRotateCount);
         }
+        let rotate_threshold_size = dir_byte_limit / rotate_count;
+        if rotate_threshold_size == 0 {
+            return Err(TraceError::TooSmallDirByteLimit(
+                dir_byte_limit,
+                rotate_count,
+            ));
+        }
+        Ok(rotate_threshold_size)
     }
 
-    pub fn channel_for_test() -> (TracedSender, Receiver<BankingPacketBatch>) {
-        Self::channel(ChannelLabel::Dummy, None)
+    #[cfg(any(test, feature = "dev-context-only-utils"))]
+    fn with_rotate_threshold_size(
+        path: &PathBuf,
+        rotate_threshold_size: u64,
+    ) -> Result<Self, TraceError> {
+        // unlimited, so that the rotated files aren't garbage-collected however small they are
+        Self::with_rotate_threshold_size_and_config(
+            path,
+            DirByteLimit::MAX,
+            rotate_threshold_size,
+            RollingFileSinkConfig::default(),
+        )
     }
 
-    fn channel(
-        label: ChannelLabel,
-        active_tracer: Option<ActiveTracer>,
-    ) -> (TracedSender, Receiver<BankingPacketBatch>) {
-        let (sender, receiver) = unbounded();
-        Self::channel_inner(label, active_tracer, sender, receiver)
+    fn with_rotate_threshold_size_and_config(
+        path: &PathBuf,
+        dir_byte_limit: DirByteLimit,
+        rotate_threshold_size: u64,
+        config: RollingFileSinkConfig,
+    ) -> Result<Self, TraceError> {
+        config.validate_file_names()?;
+        let mut file_appender = Self::create_file_appender(path, rotate_threshold_size, &config)?;
+        file_appender
+            .condition_mut()
+            .set_rotation_policy(config.rotation_policy);
+        if config.preallocate && !cfg!(all(target_os = "linux", feature = "banking-trace-fs")) {
+            warn!(
+                "preallocating the banking trace files is only available on Linux with \
+                 banking-trace-fs"
+            );
+        }
+        let compressor = config
+            .compression
+            .map(|compression| {
+                RotatedFileCompressor::new(path, compression, config.compression_thread_count)
+            })
+            .transpose()?;
+        let mut sink = Self {
+            path: path.clone(),
+            file_appender,
+            dir_byte_limit,
+            rotate_count: config.rotate_count,
+            basename: config.basename,
+            file_name_template: config.file_name_template,
+            file_start: None,
+            file_first_slot: None,
+            templated_files: VecDeque::new(),
+            fsync_policy: config.fsync_policy,
+            last_fsync: Instant::now(),
+            buf_writer_capacity: config.buf_writer_capacity,
+            frame_buf: vec![],
+            frame_boundaries: vec![],
+            frames_buf: vec![],
+            rotation_count: 0,
+            completed_file_count: 0,
+            compression_thread_count: config.compression_thread_count,
+            compressor,
+            preallocate: config.preallocate
+                && cfg!(all(target_os = "linux", feature = "banking-trace-fs")),
+            #[cfg(feature = "banking-trace-upload")]
+            uploader: None,
+        };
+        sink.collect_garbage();
+        Ok(sink)
     }
 
-    fn channel_inner(
-        label: ChannelLabel,
-        active_tracer: Option<ActiveTracer>,
-        sender: Sender<BankingPacketBatch>,
-        receiver: BankingPacketReceiver,
-    ) -> (TracedSender, Receiver<BankingPacketBatch>) {
-        (TracedSender::new(label, sender, active_tracer), receiver)
+    pub fn with_rotation_policy(mut self, rotation_policy: RotationPolicy) -> Self {
+        self.file_appender
+            .condition_mut()
+            .set_rotation_policy(rotation_policy);
+        self
     }
 
-    pub fn ensure_cleanup_path(path: &PathBuf) -> Result<(), io::Error> {
-        remove_dir_all(path).or_else(|err| {
-            if err.kind() == io::ErrorKind::NotFound {
-                Ok(())
-            } else {
-                Err(err)
-            }
-        })
+    /// Ships each completed trace file to an object store with `uploader`, instead of keeping
+    /// it among the rotated files.
+    #[cfg(feature = "banking-trace-upload")]
+    pub fn with_uploader(mut self, uploader: uploader::RotatedFileUploader) -> Self {
+        self.uploader = Some(uploader);
+        self
     }
 
     fn create_file_appender(
         path: &PathBuf,
         rotate_threshold_size: u64,
-    ) -> Result<RollingFileAppender<RollingConditionGrouped>, TraceError> {
+        config: &RollingFileSinkConfig,
+    ) -> Result<TraceFileAppender, TraceError> {
         create_dir_all(path)?;
-        let grouped = RollingConditionGrouped::new(
-            RollingConditionBasic::new()
-                .daily()
-                .max_size(rotate_threshold_size),
-        );
+        let grouped =
+            RollingConditionGrouped::new(RotationPolicy::default(), rotate_threshold_size);
+        #[cfg(all(target_os = "linux", feature = "banking-trace-io-uring"))]
+        if config.io_backend == FileIoBackend::IoUring && agave_io_uring::io_uring_supported() {
+            let appender = RotatingAppender::new(
+                path.join(&config.basename),
+                grouped,
+                (config.rotate_count - 1).try_into()?,
+                config.buf_writer_capacity,
+            )?;
+            return Ok(TraceFileAppender::IoUring(Box::new(appender)));
+        }
+        if config.io_backend == FileIoBackend::DoubleBuffered {
+            let appender = RotatingAppender::new(
+                path.join(&config.basename),
+                grouped,
+                (config.rotate_count - 1).try_into()?,
+                config.buf_writer_capacity,
+            )?;
+            return Ok(TraceFileAppender::DoubleBuffered(appender));
+        }
+        if config.io_backend != FileIoBackend::Buffered {
+            warn!(
+                "{} isn't available for the banking trace, writing with buffered I/O instead",
+                config.io_backend
+            );
+        }
         let appender = RollingFileAppender::new_with_buffer_capacity(
-            path.join(BASENAME),
+            path.join(&config.basename),
             grouped,
-            (TRACE_FILE_ROTATE_COUNT - 1).try_into()?,
-            BUF_WRITER_CAPACITY,
+            (config.rotate_count - 1).try_into()?,
+            config.buf_writer_capacity,
         )?;
-        Ok(appender)
+        Ok(TraceFileAppender::Buffered(appender))
     }
 
-    fn spawn_background_thread(
-        trace_receiver: Receiver<TimedTracedEvent>,
-        mut file_appender: RollingFileAppender<RollingConditionGrouped>,
-        exit: Arc<AtomicBool>,
-    ) -> Result<JoinHandle<TracerThreadResult>, TraceError> {
-        let thread = thread::Builder::new().name("solBanknTracer".into()).spawn(
-            move || -> TracerThreadResult {
-                receiving_loop_with_minimized_sender_overhead::<_, _, TRACE_FILE_WRITE_INTERVAL_MS>(
-                    exit,
-                    trace_receiver,
-                    |event| -> Result<(), TraceError> {
-                        file_appender.condition_mut().reset();
-                        serialize_into(&mut GroupedWriter::new(&mut file_appender), &event)?;
-                        Ok(())
-                    },
-                )?;
-                file_appender.flush()?;
-                Ok(())
-            },
-        )?;
+    /// Hands over the just completed file, which has been rotated to be the first one, to the
+    /// uploader or renames it after the template, if any, and then to the compressor, if any.
+    /// It's kept as is on failures; the events themselves have been written anyway.
+    fn complete_file(&mut self, frame_boundaries: &[u64]) {
+        let rotated_path = self.rotated_path(1);
+        if !frame_boundaries.is_empty() {
+            if let Err(err) = Self::append_index_footer(&rotated_path, frame_boundaries) {
+                warn!("failed to append the index footer to {rotated_path:?}: {err:?}");
+            }
+        }
+        if self.preallocate {
+            if let Err(err) = Self::release_preallocated(&rotated_path) {
+                warn!("failed to release the blocks preallocated for {rotated_path:?}: {err:?}");
+            }
+        }
+        if self.fsync_policy == FsyncPolicy::OnRotation {
+            if let Err(err) = Self::fsync(&rotated_path) {
+                warn!("failed to fsync {rotated_path:?}: {err:?}");
+            }
+        }
+        #[cfg(feature = "banking-trace-upload")]
+        if let Some(uploader) = self.uploader.as_ref() {
+            if let Err(err) = uploader.upload_rotated(&rotated_path) {
+                warn!("failed to queue {rotated_path:?} for upload: {err:?}");
+            }
+            return;
+        }
+        self.completed_file_count += 1;
+        let completed_file = match self.rename_after_template(&rotated_path) {
+            Ok(Some(templated_path)) => CompletedFile::Templated(templated_path),
+            Ok(None) => CompletedFile::Rotated(self.completed_file_count),
+            Err(err) => {
+                warn!("failed to rename {rotated_path:?} after the template: {err:?}");
+                CompletedFile::Rotated(self.completed_file_count)
+            }
+        };
+        self.shift_compressed_files();
+        if let Some(compressor) = self.compressor.as_mut() {
+            let completed_path = match &completed_file {
+                CompletedFile::Rotated(_) => rotated_path,
+                CompletedFile::Templated(templated_path) => templated_path.clone(),
+            };
+            if let Err(err) = compressor.compress(&completed_path, completed_file) {
+                warn!("failed to queue {completed_path:?} for compression: {err:?}");
+            }
+        }
+    }
 
-        Ok(thread)
+    fn compressed_path(path: &Path, compression: TraceCompression) -> PathBuf {
+        let mut compressed_path = path.as_os_str().to_owned();
+        compressed_path.push(".");
+        compressed_path.push(compression.extension());
+        compressed_path.into()
     }
-}
 
-pub struct TracedSender {
-    label: ChannelLabel,
-    sender: Sender<BankingPacketBatch>,
-    active_tracer: Option<ActiveTracer>,
-}
+    fn rotated_path(&self, index: u64) -> PathBuf {
+        self.path.join(format!("{}.{index}", self.basename))
+    }
 
-impl TracedSender {
-    fn new(
-        label: ChannelLabel,
-        sender: Sender<BankingPacketBatch>,
-        active_tracer: Option<ActiveTracer>,
-    ) -> Self {
-        Self {
-            label,
-            sender,
-            active_tracer,
+    /// Shifts the compressed rotated files along with the rotated files themselves, which the
+    /// appender has just shifted, dropping the oldest one beyond the rotate count.
+    fn shift_compressed_files(&self) {
+        let Some(compressor) = &self.compressor else {
+            return;
+        };
+        let compressed_path =
+            |index| Self::compressed_path(&self.rotated_path(index), compressor.compression());
+        let _ = fs::remove_file(compressed_path(self.rotate_count.saturating_sub(1)));
+        for index in (1..self.rotate_count.saturating_sub(1)).rev() {
+            match fs::rename(compressed_path(index), compressed_path(index + 1)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                    warn!("failed to shift {:?}: {err:?}", compressed_path(index));
+                }
+                _ => {}
+            }
         }
     }
 
-    pub fn send(&self, batch: BankingPacketBatch) -> Result<(), SendError<BankingPacketBatch>> {
-        if let Some(ActiveTracer { trace_sender, exit }) = &self.active_tracer {
-            if !exit.load(Ordering::Relaxed) {
-                trace_sender
-                    .send(TimedTracedEvent(
-                        SystemTime::now(),
-                        TracedEvent::PacketBatch(self.label, BankingPacketBatch::clone(&batch)),
-                    ))
-                    .map_err(|err| {
-                        error!("unexpected error when tracing a banking event...: {err:?}");
-                        SendError(BankingPacketBatch::clone(&batch))
-                    })?;
+    /// Moves the files compressed by the workers so far into place.
+    fn place_compressed_files(&mut self) {
+        while let Some(compressed_file) = self
+            .compressor
+            .as_ref()
+            .and_then(|compressor| compressor.try_recv())
+        {
+            self.place_compressed_file(compressed_file);
+        }
+    }
+
+    /// Moves `compressed_file` to where its original is now, replacing it. It's dropped if the
+    /// original has been removed meanwhile, by the rotation or the template's retention.
+    fn place_compressed_file(&mut self, compressed_file: CompressedFile<CompletedFile>) {
+        let CompressedFile { path, tag } = compressed_file;
+        let compression = TraceCompression::from_path(&path).unwrap();
+        let original_path = match tag {
+            CompletedFile::Rotated(completed_file_count) => {
+                // shifted by each file completed since
+                let index = 1 + self.completed_file_count - completed_file_count;
+                (index < self.rotate_count).then(|| self.rotated_path(index))
             }
+            CompletedFile::Templated(templated_path) => self
+                .templated_files
+                .iter_mut()
+                .find(|retained_path| **retained_path == templated_path)
+                .map(|retained_path| {
+                    *retained_path = Self::compressed_path(&templated_path, compression);
+                    templated_path
+                }),
+        };
+        let result = match original_path {
+            Some(original_path) => {
+                fs::rename(&path, Self::compressed_path(&original_path, compression))
+                    .and_then(|()| fs::remove_file(&original_path))
+            }
+            None => fs::remove_file(&path),
+        };
+        if let Err(err) = result {
+            warn!("failed to place compressed {path:?}: {err:?}");
         }
-        self.sender.send(batch)
     }
 
-    pub fn len(&self) -> usize {
-        self.sender.len()
+    /// Removes the oldest completed trace files, until the trace files fit within the byte limit
+    /// along with the one being written into. Besides the generations retained by this sink,
+    /// these include the rotated files left by previous runs (e.g. with a larger rotate count),
+    /// which the rotation doesn't account for. The other files of the directory are left alone,
+    /// including the ones still being written by other tools.
+    fn collect_garbage(&mut self) {
+        match self.remove_oldest_files() {
+            Ok(0) => {}
+            Ok(removed_count) => {
+                info!("removed {removed_count} files from {:?}", self.path)
+            }
+            Err(err) => warn!("failed to collect garbage in {:?}: {err:?}", self.path),
+        }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    fn remove_oldest_files(&mut self) -> Result<usize, io::Error> {
+        let active_path = self.path.join(&self.basename);
+        let mut total_size = fs::metadata(&active_path).map_or(0, |metadata| metadata.len());
+        let mut files = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if !self.is_completed_file(&path) {
+                continue;
+            }
+            let (modified, size) = match fs::metadata(&path)
+                .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+            {
+                Ok(file) => file,
+                Err(err) => {
+                    warn!("failed to inspect {path:?}: {err:?}");
+                    continue;
+                }
+            };
+            total_size += size;
+            files.push((modified, size, path));
+        }
+
+        // from the oldest
+        files.sort_unstable();
+        let mut removed_count = 0;
+        for (_modified, size, path) in files {
+            if total_size <= self.dir_byte_limit {
+                break;
+            }
+            if let Err(err) = fs::remove_file(&path) {
+                warn!("failed to remove {path:?}: {err:?}");
+                continue;
+            }
+            self.templated_files.retain(|templated| *templated != path);
+            total_size -= size;
+            removed_count += 1;
+        }
+        Ok(removed_count)
     }
-}
 
-#[cfg(any(test, feature = "dev-context-only-utils"))]
-pub mod for_test {
-    use {
-        super::*,
-        solana_perf::{packet::to_packet_batches, test_tx::test_tx},
-        tempfile::TempDir,
-    };
+    /// Whether `path` is of a completed trace file, possibly compressed: a rotated one, e.g.
+    /// `events.1.zst`, or one renamed after the template by this sink.
+    fn is_completed_file(&self, path: &Path) -> bool {
+        let uncompressed_path = match TraceCompression::from_path(path) {
+            Some(_) => path.with_extension(""),
+            None => path.to_path_buf(),
+        };
+        let is_rotated = uncompressed_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(|file_name| file_name.strip_prefix(&self.basename))
+            .and_then(|suffix| suffix.strip_prefix('.'))
+            .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()));
+        is_rotated
+            || self
+                .templated_files
+                .iter()
+                .any(|templated| *templated == path || *templated == uncompressed_path)
+    }
 
-    pub fn sample_packet_batch() -> BankingPacketBatch {
-        BankingPacketBatch::new(to_packet_batches(&vec![test_tx(); 4], 10))
+    fn fsync(path: &Path) -> Result<(), io::Error> {
+        // syncs the file regardless of the descriptor it's written through
+        File::open(path)?.sync_data()
     }
 
-    pub fn drop_and_clean_temp_dir_unless_suppressed(temp_dir: TempDir) {
-        std::env::var("BANKING_TRACE_LEAVE_FILES").is_ok().then(|| {
-            warn!("prevented to remove {:?}", temp_dir.path());
-            drop(temp_dir.keep());
-        });
+    /// Reserves the blocks of the file up to `len` without changing its size, so that readers
+    /// and the appender see it as is.
+    #[cfg(all(target_os = "linux", feature = "banking-trace-fs"))]
+    fn preallocate(path: &Path, len: u64) -> Result<(), io::Error> {
+        let file = OpenOptions::new().write(true).open(path)?;
+        let len = len.try_into().map_err(|_| io::ErrorKind::InvalidInput)?;
+        nix::fcntl::fallocate(&file, FallocateFlags::FALLOC_FL_KEEP_SIZE, 0, len)?;
+        Ok(())
     }
 
-    pub fn terminate_tracer(
-        tracer: Arc<BankingTracer>,
-        tracer_thread: TracerThread,
-        main_thread: JoinHandle<TracerThreadResult>,
-        sender: TracedSender,
-        exit: Option<Arc<AtomicBool>>,
-    ) {
-        if let Some(exit) = exit {
-            exit.store(true, Ordering::Relaxed);
+    #[cfg(not(all(target_os = "linux", feature = "banking-trace-fs")))]
+    fn preallocate(_path: &Path, _len: u64) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    /// Appends the index footer of the file at `path`, which must end with the last one of
+    /// `frame_boundaries`.
+    fn append_index_footer(path: &Path, frame_boundaries: &[u64]) -> Result<(), io::Error> {
+        let mut file = OpenOptions::new().append(true).open(path)?;
+        // don't write a footer which doesn't match the file, e.g. after a partial write
+        if frame_boundaries.last() != Some(&file.metadata()?.len()) {
+            return Err(io::ErrorKind::InvalidData.into());
         }
-        drop((sender, tracer));
-        main_thread.join().unwrap().unwrap();
-        if let Some(tracer_thread) = tracer_thread {
-            tracer_thread.join().unwrap().unwrap();
+        let mut footer = Vec::with_capacity(
+            (frame_boundaries.len() + 1) * size_of::<u64>() + 2 * TRACE_INDEX_MAGIC.len(),
+        );
+        footer.extend_from_slice(TRACE_INDEX_MAGIC);
+        for boundary in frame_boundaries {
+            footer.extend_from_slice(&boundary.to_le_bytes());
+        }
+        footer.extend_from_slice(&(frame_boundaries.len() as u64).to_le_bytes());
+        footer.extend_from_slice(TRACE_INDEX_MAGIC);
+        file.write_all(&footer)
+    }
+
+    /// Appends the ends of the frames in `frames`, which follow the last one of `boundaries`.
+    fn push_frame_boundaries(boundaries: &mut Vec<u64>, frames: &[u8]) {
+        let Some(&start) = boundaries.last() else {
+            return;
+        };
+        let mut offset = 0;
+        while offset < frames.len() {
+            let frame_len = &frames[offset..offset + FRAME_LEN_SIZE];
+            offset +=
+                u32::from_le_bytes(frame_len.try_into().unwrap()) as usize + 2 * FRAME_LEN_SIZE;
+            boundaries.push(start + offset as u64);
+        }
+    }
+
+    fn release_preallocated(path: &Path) -> Result<(), io::Error> {
+        let file = OpenOptions::new().write(true).open(path)?;
+        // truncating even to the current size frees the blocks beyond it
+        file.set_len(file.metadata()?.len())
+    }
+
+    fn flush_and_fsync(&mut self) -> Result<(), io::Error> {
+        self.file_appender.flush()?;
+        Self::fsync(&self.path.join(&self.basename))?;
+        self.last_fsync = Instant::now();
+        Ok(())
+    }
+
+    /// Returns the path renamed to, unless there's no template.
+    fn rename_after_template(&mut self, rotated_path: &Path) -> Result<Option<PathBuf>, io::Error> {
+        let Some(template) = &self.file_name_template else {
+            return Ok(None);
+        };
+        let file_start = match self.file_start {
+            Some(file_start) => file_start,
+            None => DateTime::from(fs::metadata(rotated_path)?.modified()?),
+        };
+        let slot = self
+            .file_first_slot
+            .map_or_else(|| "unknown".to_string(), |slot| slot.to_string());
+        let file_name = file_start
+            .format(&template.replace("{slot}", &slot))
+            .to_string();
+        // avoid overwriting the files of other tools, or of the same hour
+        let templated_path = iter::once(file_name.clone())
+            .chain((1..).map(|index| format!("{file_name}-{index}")))
+            .map(|file_name| self.path.join(file_name))
+            .find(|path| !path.exists())
+            .unwrap();
+        fs::rename(rotated_path, &templated_path)?;
+
+        self.templated_files.push_back(templated_path.clone());
+        while self.templated_files.len() as u64 >= self.rotate_count {
+            let oldest_path = self.templated_files.pop_front().unwrap();
+            if let Err(err) = fs::remove_file(&oldest_path) {
+                warn!("failed to remove {oldest_path:?}: {err:?}");
+            }
+        }
+        Ok(Some(templated_path))
+    }
+
+    /// Writes `frames` as a single group, started from `first_slot` if any.
+    fn write_frames(&mut self, frames: &[u8], first_slot: Option<Slot>) -> Result<(), TraceError> {
+        self.file_appender.condition_mut().reset();
+        let mut writer = GroupedWriter::new(&mut self.file_appender);
+        let now = writer.now;
+        let is_new_file = writer.open_group()?;
+        // the boundaries of the current file are unknown until the frames are written as a whole
+        let (completed_frame_boundaries, mut frame_boundaries) = if is_new_file {
+            let header_size = TRACE_FILE_MAGIC.len() as u64;
+            (mem::take(&mut self.frame_boundaries), vec![header_size])
+        } else {
+            (vec![], mem::take(&mut self.frame_boundaries))
+        };
+        if is_new_file {
+            writer.write_all(TRACE_FILE_MAGIC)?;
+        }
+        writer.write_all(frames)?;
+        Self::push_frame_boundaries(&mut frame_boundaries, frames);
+        self.frame_boundaries = frame_boundaries;
+        let rotation_count = self.file_appender.condition_mut().rollover_count;
+        if rotation_count > self.rotation_count {
+            self.complete_file(&completed_frame_boundaries);
+            self.collect_garbage();
+        }
+        self.place_compressed_files();
+        if is_new_file {
+            self.file_start = Some(now);
+            self.file_first_slot = None;
+            if self.preallocate {
+                let path = self.path.join(&self.basename);
+                let len = self.file_appender.condition_ref().rotate_threshold_size;
+                // the file has just been created by the appender; this is best effort anyway
+                if let Err(err) = Self::preallocate(&path, len) {
+                    warn!("failed to preallocate {path:?}: {err:?}");
+                }
+            }
+        }
+        self.file_first_slot = self.file_first_slot.or(first_slot);
+        self.rotation_count = rotation_count;
+        if let FsyncPolicy::Interval(interval) = self.fsync_policy {
+            if self.last_fsync.elapsed() >= interval {
+                // the frames themselves have been written anyway
+                if let Err(err) = self.flush_and_fsync() {
+                    warn!("failed to fsync the current trace file: {err:?}");
+                }
+            }
         }
+        Ok(())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use {
-        super::*,
-        bincode::ErrorKind::Io as BincodeIoError,
-        std::{
-            fs::File,
-            io::{BufReader, ErrorKind::UnexpectedEof},
-            str::FromStr,
-        },
-        tempfile::TempDir,
-    };
+impl Drop for RollingFileSink {
+    /// Waits for the pending compressions, so that the completed files are left compressed.
+    /// Also appends the index footer to the file being written into, which is never appended to
+    /// afterwards as it's rotated by the next sink, and releases the blocks preallocated for it.
+    fn drop(&mut self) {
+        let path = self.path.join(&self.basename);
+        if let Err(err) = self.file_appender.flush() {
+            warn!("failed to flush {path:?}: {err:?}");
+        } else {
+            if !self.frame_boundaries.is_empty() {
+                if let Err(err) = Self::append_index_footer(&path, &self.frame_boundaries) {
+                    warn!("failed to append the index footer to {path:?}: {err:?}");
+                }
+            }
+            if self.preallocate {
+                if let Err(err) = Self::release_preallocated(&path) {
+                    warn!("failed to release the blocks preallocated for {path:?}: {err:?}");
+                }
+            }
+        }
+        if let Some(mut compressor) = self.compressor.take() {
+            for compressed_file in compressor.finish() {
+                self.place_compressed_file(compressed_file);
+            }
+        }
+    }
+}
+
+/// A file completed by `RollingFileSink`, as handed over to its compressor.
+#[derive(Debug)]
+enum CompletedFile {
+    /// Rotated to be the first one when the given number of files had been completed.
+    Rotated(u64),
+    Templated(PathBuf),
+}
+
+impl TraceSink for RollingFileSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        serialize_frame(&mut self.frame_buf, event)?;
+        let frame_buf = mem::take(&mut self.frame_buf);
+        let result = self.write_frames(&frame_buf, event.1.slot());
+        self.frame_buf = frame_buf;
+        result
+    }
+
+    /// Writes the frames of `events` at once, as a single group. So the file being written
+    /// into can go beyond the rotation threshold by the whole group.
+    fn write_events(&mut self, events: &[TimedTracedEvent]) -> Result<(), (usize, TraceError)> {
+        self.write_serialized_events(events, &[])
+    }
+
+    /// Same as `write_events()`, except that the given frames are written as is.
+    fn write_serialized_events(
+        &mut self,
+        events: &[TimedTracedEvent],
+        frames: &[Option<Vec<u8>>],
+    ) -> Result<(), (usize, TraceError)> {
+        let mut frames_buf = mem::take(&mut self.frames_buf);
+        frames_buf.clear();
+        let mut serialized_count = 0;
+        let mut serialize_result = Ok(());
+        for (index, event) in events.iter().enumerate() {
+            if let Some(frame) = frames.get(index).and_then(Option::as_ref) {
+                frames_buf.extend_from_slice(frame);
+                serialized_count += 1;
+                continue;
+            }
+            serialize_result = serialize_frame(&mut self.frame_buf, event);
+            if serialize_result.is_err() {
+                break;
+            }
+            frames_buf.extend_from_slice(&self.frame_buf);
+            serialized_count += 1;
+        }
+        let first_slot = events[..serialized_count]
+            .iter()
+            .find_map(|event| event.1.slot());
+        let write_result = if frames_buf.is_empty() {
+            Ok(())
+        } else {
+            self.write_frames(&frames_buf, first_slot)
+        };
+        self.frames_buf = frames_buf;
+        write_result.map_err(|err| (0, err))?;
+        serialize_result.map_err(|err| (serialized_count, err))
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.place_compressed_files();
+        if self.fsync_policy == FsyncPolicy::EveryFlush {
+            self.flush_and_fsync()?;
+        } else {
+            self.file_appender.flush()?;
+        }
+        Ok(())
+    }
+
+    fn rotate_hint(&mut self) {
+        self.file_appender.condition_mut().request_rollover();
+    }
+
+    /// Completes the current file as on any other rotation, unless it's still blank.
+    fn rotate_now(&mut self) -> Result<(), TraceError> {
+        self.file_appender.flush()?;
+        if fs::metadata(self.path.join(&self.basename))?.len() == 0 {
+            return Ok(());
+        }
+        self.file_appender.rollover()?;
+        let condition = self.file_appender.condition_mut();
+        condition.rollover_count += 1;
+        self.rotation_count = condition.rollover_count;
+        let frame_boundaries = mem::take(&mut self.frame_boundaries);
+        self.complete_file(&frame_boundaries);
+        self.collect_garbage();
+        Ok(())
+    }
+
+    fn rotation_count(&self) -> u64 {
+        self.rotation_count
+    }
+
+    /// Takes effect from the next event, which starts a new file if the current one is already
+    /// beyond the re-derived rotation threshold. The files rotated so far are kept as is.
+    fn set_dir_byte_limit(&mut self, dir_byte_limit: DirByteLimit) -> Result<(), TraceError> {
+        self.file_appender.condition_mut().rotate_threshold_size =
+            Self::rotate_threshold_size(dir_byte_limit, self.rotate_count)?;
+        self.dir_byte_limit = dir_byte_limit;
+        self.collect_garbage();
+        Ok(())
+    }
+
+    #[cfg(all(unix, feature = "banking-trace-fs"))]
+    fn available_bytes(&self) -> Option<u64> {
+        let stat = nix::sys::statvfs::statvfs(&self.path).ok()?;
+        Some(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+    }
+
+    fn effective_config(&self) -> SinkEffectiveConfig {
+        let condition = self.file_appender.condition_ref();
+        SinkEffectiveConfig::RollingFile {
+            path: self.path.clone(),
+            dir_byte_limit: self.dir_byte_limit,
+            rotate_threshold_size: condition.rotate_threshold_size,
+            config: RollingFileSinkConfig {
+                rotate_count: self.rotate_count,
+                rotation_policy: condition.rotation_policy,
+                basename: self.basename.clone(),
+                file_name_template: self.file_name_template.clone(),
+                fsync_policy: self.fsync_policy,
+                buf_writer_capacity: self.buf_writer_capacity,
+                io_backend: self.file_appender.io_backend(),
+                compression: self
+                    .compressor
+                    .as_ref()
+                    .map(|compressor| compressor.compression()),
+                compression_thread_count: self.compression_thread_count,
+                preallocate: self.preallocate,
+            },
+        }
+    }
+}
+
+impl TraceSink for Box<dyn TraceSink> {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        (**self).write_event(event)
+    }
+
+    fn write_events(&mut self, events: &[TimedTracedEvent]) -> Result<(), (usize, TraceError)> {
+        (**self).write_events(events)
+    }
+
+    fn write_serialized_events(
+        &mut self,
+        events: &[TimedTracedEvent],
+        frames: &[Option<Vec<u8>>],
+    ) -> Result<(), (usize, TraceError)> {
+        (**self).write_serialized_events(events, frames)
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        (**self).flush()
+    }
+
+    fn rotate_hint(&mut self) {
+        (**self).rotate_hint()
+    }
+
+    fn rotate_now(&mut self) -> Result<(), TraceError> {
+        (**self).rotate_now()
+    }
+
+    fn rotation_count(&self) -> u64 {
+        (**self).rotation_count()
+    }
+
+    fn set_dir_byte_limit(&mut self, dir_byte_limit: DirByteLimit) -> Result<(), TraceError> {
+        (**self).set_dir_byte_limit(dir_byte_limit)
+    }
+
+    fn available_bytes(&self) -> Option<u64> {
+        (**self).available_bytes()
+    }
+
+    fn effective_config(&self) -> SinkEffectiveConfig {
+        (**self).effective_config()
+    }
+}
+
+/// `TraceSink` which fans out each event to all of its sinks. Failures are isolated per sink:
+/// events failed to be written to a sink are recorded as a `TraceGap` there, without affecting
+/// the other sinks.
+pub struct TeeSink {
+    sinks: Vec<(Box<dyn TraceSink>, LostEvents)>,
+}
+
+impl TeeSink {
+    pub fn new(sinks: impl IntoIterator<Item = Box<dyn TraceSink>>) -> Self {
+        Self {
+            sinks: sinks
+                .into_iter()
+                .map(|sink| (sink, LostEvents::default()))
+                .collect(),
+        }
+    }
+}
+
+impl TraceSink for TeeSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        for (sink, lost_events) in &mut self.sinks {
+            BankingTracer::write_event_or_record_loss(sink, lost_events, event);
+        }
+        Ok(())
+    }
+
+    fn write_events(&mut self, events: &[TimedTracedEvent]) -> Result<(), (usize, TraceError)> {
+        self.write_serialized_events(events, &[])
+    }
+
+    fn write_serialized_events(
+        &mut self,
+        events: &[TimedTracedEvent],
+        frames: &[Option<Vec<u8>>],
+    ) -> Result<(), (usize, TraceError)> {
+        for (sink, lost_events) in &mut self.sinks {
+            BankingTracer::write_events_or_record_loss(sink, lost_events, events, frames);
+        }
+        Ok(())
+    }
+
+    /// Flushes all sinks after writing their pending `TraceGap`s, if any. Returns the first
+    /// error, if any.
+    fn flush(&mut self) -> Result<(), TraceError> {
+        let mut result = Ok(());
+        for (sink, lost_events) in &mut self.sinks {
+            if let Some(gap_event) = lost_events.gap_event() {
+                if sink.write_event(&gap_event).is_ok() {
+                    *lost_events = LostEvents::default();
+                }
+            }
+            let flush_result = sink.flush();
+            if result.is_ok() {
+                result = flush_result;
+            }
+        }
+        result
+    }
+
+    fn rotate_hint(&mut self) {
+        for (sink, _lost_events) in &mut self.sinks {
+            sink.rotate_hint();
+        }
+    }
+
+    /// Applies to all sinks, returning the first error, if any.
+    fn rotate_now(&mut self) -> Result<(), TraceError> {
+        let mut result = Ok(());
+        for (sink, _lost_events) in &mut self.sinks {
+            let rotate_result = sink.rotate_now();
+            if result.is_ok() {
+                result = rotate_result;
+            }
+        }
+        result
+    }
+
+    fn rotation_count(&self) -> u64 {
+        self.sinks
+            .iter()
+            .map(|(sink, _lost_events)| sink.rotation_count())
+            .sum()
+    }
+
+    /// Applies to all sinks, returning the first error, if any.
+    fn set_dir_byte_limit(&mut self, dir_byte_limit: DirByteLimit) -> Result<(), TraceError> {
+        let mut result = Ok(());
+        for (sink, _lost_events) in &mut self.sinks {
+            let set_result = sink.set_dir_byte_limit(dir_byte_limit);
+            if result.is_ok() {
+                result = set_result;
+            }
+        }
+        result
+    }
+
+    /// The least among the sinks.
+    fn available_bytes(&self) -> Option<u64> {
+        self.sinks
+            .iter()
+            .filter_map(|(sink, _lost_events)| sink.available_bytes())
+            .min()
+    }
+
+    fn effective_config(&self) -> SinkEffectiveConfig {
+        SinkEffectiveConfig::Tee(
+            self.sinks
+                .iter()
+                .map(|(sink, _lost_events)| sink.effective_config())
+                .collect(),
+        )
+    }
+}
+
+/// Destination of the banking trace in addition to the trace files, given as `SCHEME://...`:
+///
+/// * `tcp://HOST:PORT`: streams to a collector with `TcpSink` (`banking-trace-tcp` only).
+/// * `uds://PATH`: serves local subscribers with `UdsSink` (Unix only).
+/// * `shm://PATH`: writes into a ring in shared memory with `ShmSink` (`banking-trace-shm` on
+///   Unix only), e.g. `shm:///dev/shm/banking-trace`.
+/// * `grpc://ADDR`: serves `SubscribeBankingTrace` with `GrpcSink` (`banking-trace-grpc` only).
+/// * `ws://ADDR[?strip-payloads]`: broadcasts JSON to WebSocket clients with `WebSocketSink`
+///   (`banking-trace-websocket` only).
+/// * `prometheus://ADDR`: exports metrics derived from the events with `PrometheusSink`
+///   (`banking-trace-prometheus` only).
+/// * `kafka://BROKERS/TOPIC`: publishes to the topic with `KafkaSink` (`banking-trace-kafka`
+///   only). `BROKERS` is comma-separated.
+/// * `sqlite://PATH[?payloads]`: writes into the database with `SqliteSink`
+///   (`banking-trace-sqlite` only).
+/// * `otlp://HOST:PORT[/PATH]`: exports spans over OTLP/HTTP with `OtelSink`
+///   (`banking-trace-otel` only). `PATH` defaults to `/v1/traces`.
+/// * `postgres://...`, `clickhouse://HOST:PORT`: bulk-inserts event summaries with
+///   `BulkInsertSink` (`banking-trace-bulk-insert` only). The former is passed to the PostgreSQL
+///   client as-is, the latter is the HTTP interface of ClickHouse.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceSinkConfig {
+    #[cfg(feature = "banking-trace-tcp")]
+    Tcp(String),
+    #[cfg(unix)]
+    Uds(PathBuf),
+    #[cfg(all(unix, feature = "banking-trace-shm"))]
+    Shm(PathBuf),
+    #[cfg(feature = "banking-trace-grpc")]
+    Grpc(SocketAddr),
+    #[cfg(feature = "banking-trace-websocket")]
+    WebSocket {
+        addr: SocketAddr,
+        strip_payloads: bool,
+    },
+    #[cfg(feature = "banking-trace-prometheus")]
+    Prometheus(SocketAddr),
+    #[cfg(feature = "banking-trace-kafka")]
+    Kafka { brokers: String, topic: String },
+    #[cfg(feature = "banking-trace-sqlite")]
+    Sqlite { path: PathBuf, store_payloads: bool },
+    #[cfg(feature = "banking-trace-otel")]
+    Otlp(String),
+    #[cfg(feature = "banking-trace-bulk-insert")]
+    Postgres(String),
+    #[cfg(feature = "banking-trace-bulk-insert")]
+    ClickHouse(String),
+}
+
+impl FromStr for TraceSinkConfig {
+    type Err = TraceError;
+
+    fn from_str(config: &str) -> Result<Self, Self::Err> {
+        match config.split_once("://") {
+            #[cfg(feature = "banking-trace-tcp")]
+            Some(("tcp", endpoint)) if !endpoint.is_empty() => Ok(Self::Tcp(endpoint.into())),
+            #[cfg(unix)]
+            Some(("uds", path)) if !path.is_empty() => Ok(Self::Uds(path.into())),
+            #[cfg(all(unix, feature = "banking-trace-shm"))]
+            Some(("shm", path)) if !path.is_empty() => Ok(Self::Shm(path.into())),
+            #[cfg(feature = "banking-trace-grpc")]
+            Some(("grpc", addr)) => addr
+                .parse()
+                .map(Self::Grpc)
+                .map_err(|_| TraceError::UnsupportedSink(config.into())),
+            #[cfg(feature = "banking-trace-websocket")]
+            Some(("ws", addr)) => {
+                let (addr, strip_payloads) = match addr.split_once('?') {
+                    Some((addr, "strip-payloads")) => (addr, true),
+                    Some(_) => return Err(TraceError::UnsupportedSink(config.into())),
+                    None => (addr, false),
+                };
+                addr.parse()
+                    .map(|addr| Self::WebSocket {
+                        addr,
+                        strip_payloads,
+                    })
+                    .map_err(|_| TraceError::UnsupportedSink(config.into()))
+            }
+            #[cfg(feature = "banking-trace-prometheus")]
+            Some(("prometheus", addr)) => addr
+                .parse()
+                .map(Self::Prometheus)
+                .map_err(|_| TraceError::UnsupportedSink(config.into())),
+            #[cfg(feature = "banking-trace-kafka")]
+            Some(("kafka", brokers_and_topic)) => match brokers_and_topic.rsplit_once('/') {
+                Some((brokers, topic)) if !brokers.is_empty() && !topic.is_empty() => {
+                    Ok(Self::Kafka {
+                        brokers: brokers.into(),
+                        topic: topic.into(),
+                    })
+                }
+                _ => Err(TraceError::UnsupportedSink(config.into())),
+            },
+            #[cfg(feature = "banking-trace-sqlite")]
+            Some(("sqlite", path)) => {
+                let (path, store_payloads) = match path.split_once('?') {
+                    Some((path, "payloads")) => (path, true),
+                    Some(_) => return Err(TraceError::UnsupportedSink(config.into())),
+                    None => (path, false),
+                };
+                if path.is_empty() {
+                    return Err(TraceError::UnsupportedSink(config.into()));
+                }
+                Ok(Self::Sqlite {
+                    path: path.into(),
+                    store_payloads,
+                })
+            }
+            #[cfg(feature = "banking-trace-otel")]
+            Some(("otlp", endpoint)) if !endpoint.is_empty() => {
+                Ok(Self::Otlp(if endpoint.contains('/') {
+                    format!("http://{endpoint}")
+                } else {
+                    format!("http://{endpoint}/v1/traces")
+                }))
+            }
+            #[cfg(feature = "banking-trace-bulk-insert")]
+            Some(("postgres" | "postgresql", _)) => Ok(Self::Postgres(config.into())),
+            #[cfg(feature = "banking-trace-bulk-insert")]
+            Some(("clickhouse", addr)) if !addr.is_empty() => {
+                Ok(Self::ClickHouse(format!("http://{addr}")))
+            }
+            _ => Err(TraceError::UnsupportedSink(config.into())),
+        }
+    }
+}
+
+impl TraceSinkConfig {
+    pub fn build(&self) -> Result<Box<dyn TraceSink>, TraceError> {
+        Ok(match self {
+            #[cfg(feature = "banking-trace-tcp")]
+            Self::Tcp(endpoint) => Box::new(TcpSink::new(
+                endpoint.clone(),
+                TCP_SINK_DEFAULT_BUFFER_BYTE_LIMIT,
+            )),
+            #[cfg(unix)]
+            Self::Uds(path) => Box::new(uds_sink::UdsSink::new(path.clone())?),
+            #[cfg(all(unix, feature = "banking-trace-shm"))]
+            Self::Shm(path) => Box::new(shm_sink::ShmSink::new(
+                path,
+                shm_sink::SHM_SINK_DEFAULT_BYTE_CAPACITY,
+            )?),
+            #[cfg(feature = "banking-trace-grpc")]
+            Self::Grpc(addr) => Box::new(grpc_sink::GrpcSink::new(*addr)?),
+            #[cfg(feature = "banking-trace-websocket")]
+            Self::WebSocket {
+                addr,
+                strip_payloads,
+            } => Box::new(websocket_sink::WebSocketSink::new(*addr, *strip_payloads)?),
+            #[cfg(feature = "banking-trace-prometheus")]
+            Self::Prometheus(addr) => Box::new(prometheus_sink::PrometheusSink::new(Some(*addr))?),
+            #[cfg(feature = "banking-trace-kafka")]
+            Self::Kafka { brokers, topic } => {
+                Box::new(kafka_sink::KafkaSink::new(brokers, topic.clone())?)
+            }
+            #[cfg(feature = "banking-trace-sqlite")]
+            Self::Sqlite {
+                path,
+                store_payloads,
+            } => Box::new(sqlite_sink::SqliteSink::new(path, *store_payloads)?),
+            #[cfg(feature = "banking-trace-otel")]
+            Self::Otlp(endpoint) => Box::new(otel_sink::OtelSink::new(endpoint)?),
+            #[cfg(feature = "banking-trace-bulk-insert")]
+            Self::Postgres(config) => Box::new(bulk_insert_sink::BulkInsertSink::postgres(config)?),
+            #[cfg(feature = "banking-trace-bulk-insert")]
+            Self::ClickHouse(url) => Box::new(bulk_insert_sink::BulkInsertSink::clickhouse(url)?),
+        })
+    }
+}
+
+pub fn receiving_loop_with_minimized_sender_overhead<T, E, const SLEEP_MS: u64>(
+    exit: Arc<AtomicBool>,
+    receiver: Receiver<T>,
+    on_recv: impl FnMut(T) -> Result<(), E>,
+) -> Result<(), E> {
+    receiving_loop_with_idle_messages(
+        exit,
+        receiver,
+        on_recv,
+        || None,
+        || sleep(Duration::from_millis(SLEEP_MS)),
+    )
+}
+
+/// Event-driven alternative to `receiving_loop_with_minimized_sender_overhead()`, which blocks
+/// on `receiver` and `exit_receiver` at once instead of sleeping when idle. So, messages are
+/// handled without the latency of the sleep, at the cost of senders waking up the receiving
+/// thread. The loop exits once a message is sent to `exit_receiver` or its sender is dropped,
+/// as well as once `receiver` is disconnected.
+pub fn receiving_loop_with_exit_notification<T, E>(
+    exit_receiver: Receiver<()>,
+    receiver: Receiver<T>,
+    mut on_recv: impl FnMut(T) -> Result<(), E>,
+) -> Result<(), E> {
+    loop {
+        select_biased! {
+            recv(exit_receiver) -> _ => break,
+            recv(receiver) -> message => match message {
+                Ok(message) => on_recv(message)?,
+                Err(RecvError) => break,
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Same as `receiving_loop_with_minimized_sender_overhead()`, except that `on_idle` is called
+/// each time the receiver is drained. The messages returned by it are passed to `on_recv` as if
+/// they were received. Instead of sleeping by itself, `sleep_when_idle` is called afterwards, so
+/// that the sleep can be adjusted while looping.
+fn receiving_loop_with_idle_messages<T, E, I: IntoIterator<Item = T>>(
+    exit: Arc<AtomicBool>,
+    receiver: Receiver<T>,
+    mut on_recv: impl FnMut(T) -> Result<(), E>,
+    mut on_idle: impl FnMut() -> I,
+    mut sleep_when_idle: impl FnMut(),
+) -> Result<(), E> {
+    'outer: while !exit.load(Ordering::Relaxed) {
+        'inner: loop {
+            // avoid futex-based blocking here, otherwise a sender would have to
+            // wake me up at a syscall cost...
+            match receiver.try_recv() {
+                Ok(message) => on_recv(message)?,
+                Err(TryRecvError::Empty) => break 'inner,
+                Err(TryRecvError::Disconnected) => {
+                    break 'outer;
+                }
+            };
+            if exit.load(Ordering::Relaxed) {
+                break 'outer;
+            }
+        }
+        for message in on_idle() {
+            on_recv(message)?;
+        }
+        sleep_when_idle();
+    }
+
+    Ok(())
+}
+
+pub struct Channels {
+    pub non_vote_sender: BankingPacketSender,
+    pub non_vote_receiver: BankingPacketReceiver,
+    pub tpu_vote_sender: BankingPacketSender,
+    pub tpu_vote_receiver: BankingPacketReceiver,
+    pub gossip_vote_sender: BankingPacketSender,
+    pub gossip_vote_receiver: BankingPacketReceiver,
+}
+
+#[allow(dead_code)]
+impl Channels {
+    #[cfg(feature = "dev-context-only-utils")]
+    pub fn unified_sender(&self) -> &BankingPacketSender {
+        let unified_sender = &self.non_vote_sender;
+        assert!(unified_sender
+            .sender
+            .same_channel(&self.tpu_vote_sender.sender));
+        assert!(unified_sender
+            .sender
+            .same_channel(&self.gossip_vote_sender.sender));
+        unified_sender
+    }
+
+    /// Holds back the batches sent to any of the channels from now on, until resumed. See
+    /// `ChannelGate`.
+    #[cfg(not(feature = "banking-trace-noop"))]
+    pub fn pause(&self) {
+        self.non_vote_sender.channel_gate().pause()
+    }
+
+    /// Delivers the batches held back since paused, returning their count.
+    #[cfg(not(feature = "banking-trace-noop"))]
+    pub fn resume(&self) -> usize {
+        self.non_vote_sender.channel_gate().resume()
+    }
+
+    pub(crate) fn unified_receiver(&self) -> &BankingPacketReceiver {
+        let unified_receiver = &self.non_vote_receiver;
+        assert!(unified_receiver.same_channel(&self.tpu_vote_receiver));
+        assert!(unified_receiver.same_channel(&self.gossip_vote_receiver));
+        unified_receiver
+    }
+}
+
+impl BankingTracer {
+    /// Creates a tracer into the trace files under `path` if `maybe_config` is given, subject to
+    /// the `EnvOverrides`.
+    pub fn new(
+        maybe_config: Option<(&PathBuf, Arc<AtomicBool>, DirByteLimit)>,
+    ) -> Result<(Arc<Self>, TracerThread), TraceError> {
+        let overrides = match maybe_config {
+            Some(_) => EnvOverrides::from_env()?,
+            None => EnvOverrides::default(),
+        };
+        Self::new_with_env_overrides(maybe_config, overrides)
+    }
+
+    fn new_with_env_overrides(
+        maybe_config: Option<(&PathBuf, Arc<AtomicBool>, DirByteLimit)>,
+        overrides: EnvOverrides,
+    ) -> Result<(Arc<Self>, TracerThread), TraceError> {
+        let Some((path, exit, dir_byte_limit)) = maybe_config else {
+            return Ok((Self::new_disabled(), None));
+        };
+        let mut path = path.clone();
+        let mut dir_byte_limit = dir_byte_limit;
+        let mut config = RollingFileSinkConfig::default();
+        overrides.apply(&mut path, &mut dir_byte_limit, &mut config);
+        let (tracer, tracer_thread) =
+            Self::new_with_file_sink_config(Some((&path, exit, dir_byte_limit)), config)?;
+        for label in overrides.untraced_labels() {
+            tracer.set_label_traced(*label, false);
+        }
+        Ok((tracer, tracer_thread))
+    }
+
+    /// Like `new()`, but with the given `config` of the trace files and regardless of the
+    /// `EnvOverrides`, which are up to the caller to apply.
+    pub(crate) fn new_with_file_sink_config(
+        maybe_config: Option<(&PathBuf, Arc<AtomicBool>, DirByteLimit)>,
+        config: RollingFileSinkConfig,
+    ) -> Result<(Arc<Self>, TracerThread), TraceError> {
+        let Some((path, exit, dir_byte_limit)) = maybe_config else {
+            return Ok((Self::new_disabled(), None));
+        };
+        if dir_byte_limit == DISABLED_BAKING_TRACE_DIR {
+            return Ok((Self::new_disabled(), None));
+        }
+        if cfg!(feature = "banking-trace-noop") {
+            warn!(
+                "packet batches aren't traced with the banking-trace-noop feature; only the other \
+                 events are traced into {path:?}"
+            );
+        }
+        Self::new_with_sink(
+            RollingFileSink::new_with_config(path, dir_byte_limit, config)?,
+            exit,
+        )
+    }
+
+    /// Like `new()`, but traces into the given `sink` instead of the trace files.
+    pub fn new_with_sink(
+        sink: impl TraceSink,
+        exit: Arc<AtomicBool>,
+    ) -> Result<(Arc<Self>, TracerThread), TraceError> {
+        let tracer = Self::new_disabled();
+        let tracer_thread = tracer.start_session(sink, exit)?;
+        Ok((tracer, Some(tracer_thread)))
+    }
+
+    /// Like `new_with_sink()`, but fans out each event to all of `sinks` (e.g. a
+    /// `RollingFileSink` along with an exporter), isolating their failures as `TeeSink` does.
+    pub fn new_with_sinks(
+        sinks: impl IntoIterator<Item = Box<dyn TraceSink>>,
+        exit: Arc<AtomicBool>,
+    ) -> Result<(Arc<Self>, TracerThread), TraceError> {
+        Self::new_with_sink(TeeSink::new(sinks), exit)
+    }
+
+    /// Creates a tracer which can be enabled later with `enable()`.
+    pub fn new_disabled() -> Arc<Self> {
+        Arc::new(Self {
+            active_tracer: ActiveTracer::default(),
+            tracer_thread: Mutex::default(),
+            channel_stats: ChannelStatsRegistry::default(),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.active_tracer.is_enabled()
+    }
+
+    /// Starts tracing into the trace files under `path` without restarting, including the
+    /// channels created so far. If already enabled, the current tracing is atomically replaced
+    /// and then torn down as with `disable()`, whose result is returned.
+    ///
+    /// Note that the depths of the channels created before this aren't sampled into the trace.
+    pub fn enable(
+        &self,
+        (path, exit, dir_byte_limit): (&PathBuf, Arc<AtomicBool>, DirByteLimit),
+    ) -> TracerThreadResult {
+        self.enable_with_sink(RollingFileSink::new(path, dir_byte_limit)?, exit)
+    }
+
+    /// Like `enable()`, but traces into the given `sink` instead of the trace files.
+    pub fn enable_with_sink(
+        &self,
+        sink: impl TraceSink,
+        exit: Arc<AtomicBool>,
+    ) -> TracerThreadResult {
+        let mut tracer_thread = self.tracer_thread.lock().unwrap();
+        let previous_thread = tracer_thread.replace(self.start_session(sink, exit)?);
+        Self::join_tracer_thread(previous_thread)
+    }
+
+    /// Stops tracing, waiting for the tracer thread spawned by `enable()`, if any, to write the
+    /// events traced so far and returning its result. The tracer thread returned by `new()` is
+    /// left to its owner; it exits once the events traced so far are written.
+    pub fn disable(&self) -> TracerThreadResult {
+        let mut tracer_thread = self.tracer_thread.lock().unwrap();
+        self.active_tracer.session.store(None);
+        Self::join_tracer_thread(tracer_thread.take())
+    }
+
+    /// Stops tracing like `disable()`, but gives the tracer thread only until `timeout` to write
+    /// and flush the events traced so far, instead of coordinating `exit` and the join handle.
+    /// Returns the number of events abandoned unwritten at the deadline. The tracer thread
+    /// spawned by `enable()` is joined if it has exited by then, failing with its error if any;
+    /// otherwise it's left to exit on its own.
+    pub fn shutdown(&self, timeout: Duration) -> Result<u64, TraceError> {
+        let deadline = Instant::now() + timeout;
+        let mut tracer_thread = self.tracer_thread.lock().unwrap();
+        let Some(session) = self.active_tracer.session.swap(None) else {
+            return Ok(0);
+        };
+        let (pending_events, exited) = (session.pending_events.clone(), session.exited.clone());
+        // disconnects the trace channel unless being traced into right now, so that the tracer
+        // thread exits once it's drained
+        drop(session);
+
+        match exited.recv_deadline(deadline) {
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+                Self::join_tracer_thread(tracer_thread.take())?;
+                Ok(0)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                // the tracer thread may still be writing or flushing what it has received
+                let abandoned_count = pending_events
+                    .upgrade()
+                    .map_or(0, |pending_events| pending_events.try_iter().count())
+                    as u64;
+                warn!("abandoned {abandoned_count} banking trace events on shutdown");
+                drop(tracer_thread.take());
+                Ok(abandoned_count)
+            }
+        }
+    }
+
+    /// Spawns a tracer thread into `sink` and swaps it in, so that the previous one, if any,
+    /// exits once the in-flight events are traced.
+    fn start_session(
+        &self,
+        sink: impl TraceSink,
+        exit: Arc<AtomicBool>,
+    ) -> Result<JoinHandle<TracerThreadResult>, TraceError> {
+        let (trace_sender, trace_receiver) = unbounded();
+        let (command_sender, command_receiver) = unbounded();
+        let (exited_sender, exited) = bounded(0);
+        let pending_events = Arc::new(trace_receiver.clone());
+        let tracer_thread = Self::spawn_background_thread(
+            trace_receiver,
+            Arc::clone(&pending_events),
+            command_receiver,
+            exited_sender,
+            sink,
+            Arc::clone(&self.active_tracer.disk_space_watchdog),
+            Arc::clone(&self.active_tracer.write_interval),
+            Arc::clone(&self.active_tracer.clock),
+            exit.clone(),
+            Arc::clone(&self.channel_stats),
+        )?;
+        self.active_tracer
+            .session
+            .store(Some(Arc::new(TraceSession {
+                trace_sender,
+                pending_events: Arc::downgrade(&pending_events),
+                command_sender,
+                exited,
+                exit,
+            })));
+        Ok(tracer_thread)
+    }
+
+    /// Changes the byte limit of the trace directory on the live tracer, re-deriving the
+    /// rotation threshold of the trace files. Does nothing unless enabled. Fails with
+    /// `TraceError::FlushTimeout` if not done within `SINK_COMMAND_TIMEOUT`.
+    pub fn set_dir_byte_limit(&self, dir_byte_limit: DirByteLimit) -> Result<(), TraceError> {
+        // nothing to adjust if the tracer thread has exited
+        self.active_tracer
+            .session
+            .load_full()
+            .and_then(|session| {
+                session.execute_command(
+                    |result_sender| SinkCommand::SetDirByteLimit(dir_byte_limit, result_sender),
+                    SINK_COMMAND_TIMEOUT,
+                )
+            })
+            .unwrap_or(Ok(()))
+    }
+
+    /// Stops or resumes tracing the packet batches sent to the channels of `label`, including
+    /// the ones created so far, e.g. to keep high-volume lanes from taking up the trace
+    /// directory's byte limit. The other events of such channels are still traced. All labels
+    /// are traced by default.
+    pub fn set_label_traced(&self, label: ChannelLabel, is_traced: bool) {
+        let untraced_labels = &self.active_tracer.untraced_labels;
+        if is_traced {
+            untraced_labels.fetch_and(!label.bit(), Ordering::Relaxed);
+        } else {
+            untraced_labels.fetch_or(label.bit(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_label_traced(&self, label: ChannelLabel) -> bool {
+        self.active_tracer.is_label_traced(label)
+    }
+
+    /// Suspends tracing packet batches while the free space of the filesystem written into by
+    /// the sink is below `min_available_bytes`, keeping the other events traced so that the
+    /// trace doesn't fill up the volume. Zero disables the watchdog, which is the default. Trace
+    /// files only report the free space with `banking-trace-fs` on Unix.
+    pub fn set_min_available_bytes(&self, min_available_bytes: u64) {
+        self.active_tracer
+            .disk_space_watchdog
+            .min_available_bytes
+            .store(min_available_bytes, Ordering::Relaxed);
+    }
+
+    pub fn is_packet_tracing_suspended(&self) -> bool {
+        self.active_tracer
+            .disk_space_watchdog
+            .is_suspended
+            .load(Ordering::Relaxed)
+    }
+
+    /// Changes how long the tracer thread sleeps once it has written all the events received
+    /// so far, including the tracer thread running now. Shorter intervals get the events to
+    /// the sink sooner and sample the channel depths more often, at the cost of more wakeups.
+    /// Defaults to `TRACE_FILE_DEFAULT_WRITE_INTERVAL`.
+    pub fn set_write_interval(&self, write_interval: Duration) {
+        self.active_tracer.write_interval.store(write_interval);
+    }
+
+    pub fn write_interval(&self) -> Duration {
+        self.active_tracer.write_interval.load()
+    }
+
+    /// Makes the senders serialize the packet batches they trace themselves, shipping the
+    /// frames to the tracer thread, which then only has to write them. So, the serialization
+    /// is spread over the sending threads, instead of bottlenecking the single tracer thread
+    /// at high packet rates. Applies to the packet batches sent from now on. Disabled by
+    /// default.
+    pub fn set_serialize_in_sender(&self, serialize_in_sender: bool) {
+        self.active_tracer
+            .serialize_in_sender
+            .store(serialize_in_sender, Ordering::Relaxed);
+    }
+
+    pub fn serialize_in_sender(&self) -> bool {
+        self.active_tracer
+            .serialize_in_sender
+            .load(Ordering::Relaxed)
+    }
+
+    /// Makes the events timestamped by a clock cached by the tracer thread, updated for each
+    /// event it receives and once every write interval while idle, instead of calling the
+    /// system clock for each of them. So, the timestamps may lag by up to the write interval
+    /// after a quiet period, in exchange for the clock calls saved on the hot paths. Disabled by
+    /// default.
+    pub fn set_coarse_clock(&self, coarse_clock: bool) {
+        self.active_tracer
+            .clock
+            .is_coarse
+            .store(coarse_clock, Ordering::Relaxed);
+    }
+
+    pub fn coarse_clock(&self) -> bool {
+        self.active_tracer.clock.is_coarse.load(Ordering::Relaxed)
+    }
+
+    /// Traces everything traced by this tracer into `mirror` as well, including the packet
+    /// batches sent to the channels created so far. So, the same channels can be traced by
+    /// several tracers, each with its own sink, thread and limits (e.g. a full-fidelity tracer
+    /// with a short retention, along with a sampled one with a long retention). The untraced
+    /// labels and the disk space watchdog of `mirror` apply to it independently, while the
+    /// depths of the channels are sampled only into this tracer. The mirrors of `mirror`
+    /// aren't followed.
+    ///
+    /// Note that `mirror` keeps tracing until cleared by `clear_mirrors()`, even if dropped.
+    pub fn mirror_into(&self, mirror: &BankingTracer) {
+        self.active_tracer.mirrors.rcu(|mirrors| {
+            let mut mirrors = Vec::clone(mirrors);
+            mirrors.push(mirror.active_tracer.clone());
+            mirrors
+        });
+    }
+
+    pub fn clear_mirrors(&self) {
+        self.active_tracer.mirrors.store(Arc::default());
+    }
+
+    /// Same as `TraceFlusher::flush_now()`.
+    pub fn flush_now(&self, timeout: Duration) -> Result<(), TraceError> {
+        self.flusher().flush_now(timeout)
+    }
+
+    /// Returns a handle to flush this tracer from elsewhere, e.g. a panic hook or a signal
+    /// handling thread, without keeping the tracer itself alive.
+    pub fn flusher(&self) -> TraceFlusher {
+        TraceFlusher {
+            session: Arc::downgrade(&self.active_tracer.session),
+        }
+    }
+
+    /// Chains a panic hook which flushes this tracer before calling the previous hook, so that
+    /// the events leading up to a panic aren't lost in the write buffer when the process aborts
+    /// (e.g. by `solana_metrics::set_panic_hook()`). Only the first panic flushes, and not if
+    /// it's the tracer thread panicking, which couldn't flush anyway. The flush is given up after
+    /// `timeout` otherwise.
+    ///
+    /// Meant to be called once per process: the hook stays installed for good, and each call
+    /// chains another one.
+    pub fn install_flush_on_panic_hook(&self, timeout: Duration) {
+        let flusher = self.flusher();
+        let is_flushed = AtomicBool::default();
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |panic_info| {
+            let is_tracer_thread = thread::current().name() == Some(TRACER_THREAD_NAME);
+            if !is_tracer_thread && !is_flushed.swap(true, Ordering::Relaxed) {
+                if let Err(err) = flusher.flush_now(timeout) {
+                    error!("failed to flush banking trace on panic: {err:?}");
+                }
+            }
+            previous_hook(panic_info);
+        }));
+    }
+
+    pub fn status(&self) -> BankingTracerStatus {
+        BankingTracerStatus {
+            is_enabled: self.is_enabled(),
+            is_packet_tracing_suspended: self.is_packet_tracing_suspended(),
+            untraced_labels: self.untraced_labels(),
+            min_available_bytes: self.min_available_bytes(),
+        }
+    }
+
+    /// Returns the settings the tracer is actually running with, including the ones resolved
+    /// by the sink (e.g. the rotation threshold derived from the byte limit), as asked to the
+    /// tracer thread. The sink's are left out if it doesn't answer within
+    /// `SINK_COMMAND_TIMEOUT`.
+    pub fn effective_config(&self) -> BankingTracerEffectiveConfig {
+        let sink = self.active_tracer.session.load_full().and_then(|session| {
+            let (config_sender, config_receiver) = bounded(1);
+            session
+                .command_sender
+                .send(SinkCommand::EffectiveConfig(config_sender))
+                .ok()?;
+            drop(session);
+            // disconnected if the tracer thread has exited
+            config_receiver.recv_timeout(SINK_COMMAND_TIMEOUT).ok()
+        });
+        BankingTracerEffectiveConfig {
+            sink,
+            write_interval: self.write_interval(),
+            serialize_in_sender: self.serialize_in_sender(),
+            coarse_clock: self.coarse_clock(),
+            untraced_labels: self.untraced_labels(),
+            min_available_bytes: self.min_available_bytes(),
+        }
+    }
+
+    fn untraced_labels(&self) -> Vec<ChannelLabel> {
+        [
+            ChannelLabel::NonVote,
+            ChannelLabel::TpuVote,
+            ChannelLabel::GossipVote,
+        ]
+        .into_iter()
+        .filter(|label| !self.is_label_traced(*label))
+        .collect()
+    }
+
+    fn min_available_bytes(&self) -> u64 {
+        self.active_tracer
+            .disk_space_watchdog
+            .min_available_bytes
+            .load(Ordering::Relaxed)
+    }
+
+    /// Writes the events traced so far and closes the current trace file right away, so that
+    /// everything up to this moment can be copied off from the completed files. Sinks without
+    /// any notion of files are just flushed. Does nothing unless enabled. Fails with
+    /// `TraceError::FlushTimeout` if not done within `SINK_COMMAND_TIMEOUT`.
+    pub fn rotate_now(&self) -> Result<(), TraceError> {
+        self.active_tracer
+            .session
+            .load_full()
+            .and_then(|session| session.execute_command(SinkCommand::Rotate, SINK_COMMAND_TIMEOUT))
+            .unwrap_or(Ok(()))
+    }
+
+    /// Collects the events written from now on, until `max_count` of them are collected or
+    /// `timeout` elapses, e.g. to watch a live validator remotely. Returns nothing unless
+    /// enabled. A `timeout` too long to be represented waits until `max_count` events are
+    /// collected or the tracer thread exits.
+    pub fn tail(&self, max_count: usize, timeout: Duration) -> Vec<TimedTracedEvent> {
+        let deadline = Instant::now().checked_add(timeout);
+        let Some(session) = self.active_tracer.session.load_full() else {
+            return vec![];
+        };
+        if max_count == 0 {
+            return vec![];
+        }
+        let (tap, tapped_events) = bounded(max_count);
+        if session.command_sender.send(SinkCommand::Tap(tap)).is_err() {
+            return vec![];
+        }
+        drop(session);
+        // the tap is dropped once full, or on the exit of the tracer thread
+        iter::from_fn(|| match deadline {
+            Some(deadline) => tapped_events.recv_deadline(deadline).ok(),
+            None => tapped_events.recv().ok(),
+        })
+        .take(max_count)
+        .collect()
+    }
+
+    fn join_tracer_thread(tracer_thread: TracerThread) -> TracerThreadResult {
+        match tracer_thread {
+            Some(tracer_thread) => tracer_thread
+                .join()
+                .unwrap_or_else(|err| panic::resume_unwind(err)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn create_channels(&self, unify_channels: bool) -> Channels {
+        if unify_channels {
+            // Returning the same channel is needed when unified scheduler supports block
+            // production because unified scheduler doesn't distinguish them and treats them as
+            // unified as the single source of incoming transactions. This is to reduce the number
+            // of recv operation per loop and load balance evenly as much as possible there.
+            self.create_channels_grouped(&[&[
+                ChannelLabel::NonVote,
+                ChannelLabel::TpuVote,
+                ChannelLabel::GossipVote,
+            ]])
+        } else {
+            self.create_channels_grouped(&[])
+        }
+    }
+
+    /// Like `create_channels()`, but only the labels within each of `groups` share a single
+    /// channel, e.g. `&[&[ChannelLabel::TpuVote, ChannelLabel::GossipVote]]` unifies the vote
+    /// channels while keeping the non-vote one separate. Labels not in any group get a channel
+    /// of their own. Panics if a label is in more than one group.
+    pub fn create_channels_grouped(&self, groups: &[&[ChannelLabel]]) -> Channels {
+        let mut channels =
+            BTreeMap::<ChannelLabel, (BankingPacketSender, BankingPacketReceiver)>::new();
+        #[cfg(not(feature = "banking-trace-noop"))]
+        let gate = Arc::<ChannelGate>::default();
+        for label in [
+            ChannelLabel::NonVote,
+            ChannelLabel::TpuVote,
+            ChannelLabel::GossipVote,
+        ] {
+            let mut containing_groups = groups.iter().filter(|group| group.contains(&label));
+            let group = containing_groups.next();
+            assert!(
+                containing_groups.next().is_none(),
+                "{label:?} is in more than one group"
+            );
+            // Tap into some private helper fns so that banking trace labelling works as before.
+            let channel = match group
+                .and_then(|group| group.iter().find_map(|unified| channels.get(unified)))
+            {
+                Some((sender, receiver)) => self.create_unified_channel(label, sender, receiver),
+                None => self.create_channel(label),
+            };
+            #[cfg(not(feature = "banking-trace-noop"))]
+            let channel = (channel.0.with_channel_gate(Arc::clone(&gate)), channel.1);
+            channels.insert(label, channel);
+        }
+
+        let mut take_channel = |label| channels.remove(&label).unwrap();
+        let (non_vote_sender, non_vote_receiver) = take_channel(ChannelLabel::NonVote);
+        let (tpu_vote_sender, tpu_vote_receiver) = take_channel(ChannelLabel::TpuVote);
+        let (gossip_vote_sender, gossip_vote_receiver) = take_channel(ChannelLabel::GossipVote);
+        Channels {
+            non_vote_sender,
+            non_vote_receiver,
+            tpu_vote_sender,
+            tpu_vote_receiver,
+            gossip_vote_sender,
+            gossip_vote_receiver,
+        }
+    }
+
+    /// Snapshots the counters of the channels created so far, in the order of creation. Unified
+    /// channels are listed once, under the first of their labels, counting the sends to all of
+    /// them. The sends are only counted while tracing is enabled.
+    pub fn channel_stats(&self) -> Vec<(ChannelLabel, ChannelStatsSnapshot)> {
+        self.channel_stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(label, stats)| (*label, stats.snapshot()))
+            .collect()
+    }
+
+    fn register_channel_stats(
+        &self,
+        (sender, receiver): (BankingPacketSender, BankingPacketReceiver),
+    ) -> (BankingPacketSender, BankingPacketReceiver) {
+        #[cfg(not(feature = "banking-trace-noop"))]
+        self.channel_stats
+            .lock()
+            .unwrap()
+            .push((sender.label, Arc::clone(&sender.stats)));
+        (sender, receiver)
+    }
+
+    fn create_channel(&self, label: ChannelLabel) -> (BankingPacketSender, BankingPacketReceiver) {
+        self.register_channel_stats(Self::channel(label, Some(self.active_tracer.clone())))
+    }
+
+    pub fn create_channel_non_vote(&self) -> (BankingPacketSender, BankingPacketReceiver) {
+        self.create_channel(ChannelLabel::NonVote)
+    }
+
+    /// Like `create_channel()`, but the channel holds up to `capacity` batches, beyond which
+    /// sends are handled according to `overflow_policy`.
+    ///
+    /// With `OverflowPolicy::DropOldest`, the sender holds a receiver to evict batches with, so
+    /// sends keep succeeding even after the returned receiver is dropped. It's ignored with
+    /// `banking-trace-noop`, under which sends block on a full channel instead.
+    pub fn create_bounded_channel(
+        &self,
+        label: ChannelLabel,
+        capacity: usize,
+        overflow_policy: OverflowPolicy,
+    ) -> (BankingPacketSender, BankingPacketReceiver) {
+        let (sender, receiver) = bounded(capacity);
+        let (sender, receiver) =
+            Self::channel_inner(label, Some(self.active_tracer.clone()), sender, receiver);
+        #[cfg(not(feature = "banking-trace-noop"))]
+        let sender = if overflow_policy == OverflowPolicy::DropOldest {
+            sender.with_evicting_receiver(receiver.clone())
+        } else {
+            sender
+        };
+        #[cfg(feature = "banking-trace-noop")]
+        if overflow_policy == OverflowPolicy::DropOldest {
+            warn!(
+                "sends to {label:?} block instead of dropping the oldest with banking-trace-noop"
+            );
+        }
+        self.register_channel_stats((sender, receiver))
+    }
+
+    fn create_unified_channel(
+        &self,
+        label: ChannelLabel,
+        sender: &TracedSender,
+        receiver: &BankingPacketReceiver,
+    ) -> (BankingPacketSender, BankingPacketReceiver) {
+        let (unified_sender, receiver) = Self::channel_inner(
+            label,
+            Some(self.active_tracer.clone()),
+            sender.sender.clone(),
+            receiver.clone(),
+        );
+        // counted along with the channel unified into, which is registered already
+        #[cfg(not(feature = "banking-trace-noop"))]
+        let unified_sender = unified_sender.with_stats(Arc::clone(&sender.stats));
+        (unified_sender, receiver)
+    }
+
+    pub fn hash_event(&self, slot: Slot, blockhash: &Hash, bank_hash: &Hash) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::BlockAndBankHash(slot, *blockhash, *bank_hash),
+            )
+        })
+    }
+
+    pub fn leader_slot_boundary_event(&self, slot: Slot, start: bool) {
+        self.trace_event(|| {
+            TimedTracedEvent(self.now(), TracedEvent::LeaderSlotBoundary { slot, start })
+        })
+    }
+
+    /// Inserts `text` into the trace as a marker (e.g. "started stress test") to be shown inline
+    /// with the other events.
+    pub fn annotate(&self, text: &str) {
+        self.trace_event(|| TimedTracedEvent(self.now(), TracedEvent::Annotation(text.to_owned())))
+    }
+
+    pub fn scheduler_task_event(&self, task_index: usize, state: SchedulerTaskState) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::SchedulerTask {
+                    task_index: task_index as u64,
+                    state,
+                },
+            )
+        })
+    }
+
+    /// Traces the `(priority, age)`s of the transactions left buffered when our leader `slot`
+    /// ends.
+    pub fn buffered_transactions_event(
+        &self,
+        slot: Slot,
+        buffered: impl IntoIterator<Item = (u64, Duration)>,
+    ) {
+        self.trace_event(|| {
+            let (by_priority, by_age_ms) = bucket_buffered_transactions(buffered);
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::BufferedTransactions {
+                    slot,
+                    by_priority,
+                    by_age_ms,
+                },
+            )
+        })
+    }
+
+    pub fn poh_tick_event(&self, slot: Slot, tick_height: u64, last_tick_in_slot: bool) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::PohTick {
+                    slot,
+                    tick_height,
+                    last_tick_in_slot,
+                },
+            )
+        })
+    }
+
+    pub fn packets_dropped_event(
+        &self,
+        label: ChannelLabel,
+        count: usize,
+        reason: PacketDropReason,
+    ) {
+        if count > 0 {
+            self.trace_event(|| packets_dropped_event(self.now(), label, count, reason))
+        }
+    }
+
+    pub fn transaction_batch_scheduled_event(
+        &self,
+        worker_id: u32,
+        batch_id: u64,
+        signatures: impl IntoIterator<Item = Signature>,
+    ) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::TransactionBatchScheduled {
+                    worker_id,
+                    batch_id,
+                    signatures: signatures.into_iter().collect(),
+                },
+            )
+        })
+    }
+
+    pub fn transaction_batch_outcome_event(
+        &self,
+        worker_id: u32,
+        batch_id: u64,
+        outcomes: Vec<TransactionOutcome>,
+        error: Option<BatchErrorClass>,
+    ) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::TransactionBatchOutcome {
+                    worker_id,
+                    batch_id,
+                    outcomes,
+                    error,
+                },
+            )
+        })
+    }
+
+    pub fn transaction_results_event(
+        &self,
+        worker_id: u32,
+        batch_id: u64,
+        results: impl IntoIterator<Item = (Signature, TransactionResult<()>)>,
+    ) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::TransactionResults {
+                    worker_id,
+                    batch_id,
+                    results: results.into_iter().collect(),
+                },
+            )
+        })
+    }
+
+    pub fn bank_frozen_event(&self, slot: Slot, tick_height: u64, transaction_count: u64) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::BankFrozen {
+                    slot,
+                    tick_height,
+                    transaction_count,
+                },
+            )
+        })
+    }
+
+    pub fn forwarded_event(&self, label: ChannelLabel, count: usize, target: Option<SocketAddr>) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::Forwarded {
+                    label,
+                    count: count as u64,
+                    target,
+                },
+            )
+        })
+    }
+
+    pub fn cost_limit_exceeded_event(
+        &self,
+        worker_id: u32,
+        batch_id: u64,
+        slot: Slot,
+        rejections: impl IntoIterator<Item = (u32, CostLimit)>,
+    ) {
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::CostLimitExceeded {
+                    worker_id,
+                    batch_id,
+                    slot,
+                    rejections: rejections.into_iter().collect(),
+                },
+            )
+        })
+    }
+
+    /// Nothing is traced if there are no `compute_unit_prices`.
+    pub fn priority_fee_snapshot_event(
+        &self,
+        leader_slot: Option<Slot>,
+        compute_unit_prices: impl IntoIterator<Item = u64>,
+    ) {
+        if !self.active_tracer.is_tracing() {
+            return;
+        }
+        let Some(compute_unit_prices) =
+            ComputeUnitPriceDistribution::from_prices(compute_unit_prices.into_iter().collect())
+        else {
+            return;
+        };
+        self.trace_event(|| {
+            TimedTracedEvent(
+                self.now(),
+                TracedEvent::PriorityFeeSnapshot {
+                    leader_slot,
+                    compute_unit_prices,
+                },
+            )
+        })
+    }
+
+    fn trace_event(&self, on_trace: impl FnOnce() -> TimedTracedEvent) {
+        self.active_tracer.trace_event(on_trace);
+    }
+
+    fn now(&self) -> SystemTime {
+        self.active_tracer.now()
+    }
+
+    pub fn channel_for_test() -> (TracedSender, Receiver<BankingPacketBatch>) {
+        Self::channel(ChannelLabel::Dummy, None)
+    }
+
+    fn channel(
+        label: ChannelLabel,
+        active_tracer: Option<ActiveTracer>,
+    ) -> (TracedSender, Receiver<BankingPacketBatch>) {
+        let (sender, receiver) = unbounded();
+        Self::channel_inner(label, active_tracer, sender, receiver)
+    }
+
+    fn channel_inner(
+        label: ChannelLabel,
+        active_tracer: Option<ActiveTracer>,
+        sender: Sender<BankingPacketBatch>,
+        receiver: BankingPacketReceiver,
+    ) -> (TracedSender, Receiver<BankingPacketBatch>) {
+        (TracedSender::new(label, sender, active_tracer), receiver)
+    }
+
+    pub fn ensure_cleanup_path(path: &PathBuf) -> Result<(), io::Error> {
+        remove_dir_all(path).or_else(|err| {
+            if err.kind() == io::ErrorKind::NotFound {
+                Ok(())
+            } else {
+                Err(err)
+            }
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_background_thread<S: TraceSink>(
+        trace_receiver: Receiver<TraceMessage>,
+        pending_events: Arc<Receiver<TraceMessage>>,
+        command_receiver: Receiver<SinkCommand>,
+        exited_sender: Sender<()>,
+        sink: S,
+        disk_space_watchdog: Arc<DiskSpaceWatchdog>,
+        write_interval: Arc<WriteInterval>,
+        clock: Arc<TraceClock>,
+        exit: Arc<AtomicBool>,
+        channel_stats: ChannelStatsRegistry,
+    ) -> Result<JoinHandle<TracerThreadResult>, TraceError> {
+        let thread = thread::Builder::new()
+            .name(TRACER_THREAD_NAME.into())
+            .spawn(move || -> TracerThreadResult {
+                // dropped on exit, including on errors
+                let _exited_sender = exited_sender;
+                let last_packet_time = Cell::new(Instant::now());
+                let last_disk_space_check_time = Cell::new(None);
+                // shared with on_idle, so that commands are handled even while idle
+                let mut state = RefCell::new(TracerThreadState {
+                    sink,
+                    lost_events: LostEvents::default(),
+                    stats: TracerThreadStats::default(),
+                    taps: Vec::new(),
+                    coalesced_events: Vec::new(),
+                    coalesced_frames: Vec::new(),
+                });
+                let trace = |state: &mut TracerThreadState<S>, message: TraceMessage| {
+                    let TraceMessage { event, frame } = message;
+                    if matches!(event.1, TracedEvent::PacketBatch(..)) {
+                        last_packet_time.set(Instant::now());
+                    }
+                    state.write_event(event, frame);
+                };
+                let execute_commands = |state: &mut TracerThreadState<S>| {
+                    for command in command_receiver.try_iter() {
+                        // the events traced before the command was sent are queued by now
+                        for message in pending_events.try_iter() {
+                            trace(state, message);
+                        }
+                        state.write_coalesced_events();
+                        command.execute(state);
+                    }
+                };
+                clock.update();
+                // The channel depths are sampled every write interval at most.
+                let result = receiving_loop_with_idle_messages(
+                    exit,
+                    trace_receiver,
+                    |message| -> Result<(), TraceError> {
+                        let mut state = state.borrow_mut();
+                        let state = &mut *state;
+                        // updated here too, as the sleep is skipped while flooded
+                        clock.update();
+                        trace(state, message);
+                        execute_commands(state);
+                        state.stats.report_if_due(state.sink.rotation_count());
+                        Ok(())
+                    },
+                    || {
+                        let mut state = state.borrow_mut();
+                        // all of the events received so far have been drained
+                        state.write_coalesced_events();
+                        execute_commands(&mut state);
+                        Self::channel_depths_event(&channel_stats)
+                            .into_iter()
+                            .chain(Self::heartbeat_event(&last_packet_time))
+                            .chain(
+                                disk_space_watchdog.check(&state.sink, &last_disk_space_check_time),
+                            )
+                            .map(TraceMessage::from)
+                    },
+                    || clock.sleep(write_interval.load()),
+                );
+                clock.reset();
+                result?;
+                state.get_mut().write_coalesced_events();
+                let TracerThreadState {
+                    mut sink,
+                    lost_events,
+                    ..
+                } = state.into_inner();
+                if let Some(gap_event) = lost_events.gap_event() {
+                    sink.write_event(&gap_event)?;
+                }
+                sink.flush()?;
+                Ok(())
+            })?;
+
+        Ok(thread)
+    }
+
+    /// Writes `event` after the `TraceGap` of the events lost so far, if any. Unlike
+    /// `TraceSink::write_event()`, failures don't stop tracing; `event` is just recorded as lost.
+    fn write_event_or_record_loss(
+        sink: &mut impl TraceSink,
+        lost_events: &mut LostEvents,
+        event: &TimedTracedEvent,
+    ) {
+        let mut result = Ok(());
+        if let Some(gap_event) = lost_events.gap_event() {
+            result = sink.write_event(&gap_event);
+            if result.is_ok() {
+                *lost_events = LostEvents::default();
+            }
+        }
+        if result.is_ok() {
+            result = sink.write_event(event);
+        }
+        if let Err(err) = result {
+            if lost_events.count == 0 {
+                error!("failed to write banking trace events; recording a gap...: {err:?}");
+            }
+            lost_events.record(event.0);
+        }
+    }
+
+    /// Like `write_event_or_record_loss()`, but writes `events` coalesced by
+    /// `TraceSink::write_serialized_events()`. Returns the number of them written.
+    fn write_events_or_record_loss(
+        sink: &mut impl TraceSink,
+        lost_events: &mut LostEvents,
+        events: &[TimedTracedEvent],
+        frames: &[Option<Vec<u8>>],
+    ) -> usize {
+        let mut result = Ok(());
+        if let Some(gap_event) = lost_events.gap_event() {
+            result = sink.write_event(&gap_event).map_err(|err| (0, err));
+            if result.is_ok() {
+                *lost_events = LostEvents::default();
+            }
+        }
+        if result.is_ok() {
+            result = sink.write_serialized_events(events, frames);
+        }
+        match result {
+            Ok(()) => events.len(),
+            Err((written_count, err)) => {
+                if lost_events.count == 0 {
+                    error!("failed to write banking trace events; recording a gap...: {err:?}");
+                }
+                for TimedTracedEvent(time, _) in &events[written_count..] {
+                    lost_events.record(*time);
+                }
+                written_count
+            }
+        }
+    }
+
+    /// Samples the depths recorded by the senders, instead of holding receivers of the channels,
+    /// which would keep them connected after the actual receivers are dropped.
+    fn channel_depths_event(channel_stats: &ChannelStatsRegistry) -> Option<TimedTracedEvent> {
+        let depths = channel_stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(label, stats)| (*label, stats.snapshot().depth))
+            .collect::<Vec<_>>();
+        (!depths.is_empty())
+            .then(|| TimedTracedEvent(SystemTime::now(), TracedEvent::ChannelDepths { depths }))
+    }
+
+    /// Returns a heartbeat if no packets have arrived for `HEARTBEAT_INTERVAL` since
+    /// `last_packet_time`, which is then reset to now to space out subsequent heartbeats.
+    fn heartbeat_event(last_packet_time: &Cell<Instant>) -> Option<TimedTracedEvent> {
+        let now = Instant::now();
+        (now.duration_since(last_packet_time.get()) >= HEARTBEAT_INTERVAL).then(|| {
+            last_packet_time.set(now);
+            TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat)
+        })
+    }
+}
+
+/// Counts `(priority, age)`s by priority and by age into the buckets of
+/// `TracedEvent::BufferedTransactions`, omitting empty ones.
+fn bucket_buffered_transactions(
+    buffered: impl IntoIterator<Item = (u64, Duration)>,
+) -> (BucketCounts, BucketCounts) {
+    let mut by_priority = BTreeMap::<u64, u64>::new();
+    let mut by_age_ms = BTreeMap::<u64, u64>::new();
+    for (priority, age) in buffered {
+        let priority_bucket = priority.checked_ilog10().map_or(0, |exp| 10_u64.pow(exp));
+        *by_priority.entry(priority_bucket).or_default() += 1;
+        let age_ms = age.as_millis() as u64;
+        let age_bucket = BUFFERED_AGE_BUCKETS_MS
+            .into_iter()
+            .rev()
+            .find(|lower_bound| age_ms >= *lower_bound)
+            .unwrap_or_default();
+        *by_age_ms.entry(age_bucket).or_default() += 1;
+    }
+    (
+        by_priority.into_iter().collect(),
+        by_age_ms.into_iter().collect(),
+    )
+}
+
+/// Owned by the tracer thread, shared by its receiving loop and idle handling.
+struct TracerThreadState<S> {
+    sink: S,
+    lost_events: LostEvents,
+    stats: TracerThreadStats,
+    /// Added by `SinkCommand::Tap`, and dropped once disconnected or full.
+    taps: Vec<Sender<TimedTracedEvent>>,
+    /// The events received in the current iteration of the tracer thread, written at once by
+    /// `write_coalesced_events()`.
+    coalesced_events: Vec<TimedTracedEvent>,
+    /// The frames of `coalesced_events` serialized by the senders, if any.
+    coalesced_frames: Vec<Option<Vec<u8>>>,
+}
+
+impl<S: TraceSink> TracerThreadState<S> {
+    fn write_event(&mut self, event: TimedTracedEvent, frame: Option<Vec<u8>>) {
+        self.coalesced_events.push(event);
+        self.coalesced_frames.push(frame);
+        if self.coalesced_events.len() >= MAX_COALESCED_EVENT_COUNT {
+            self.write_coalesced_events();
+        }
+    }
+
+    fn write_coalesced_events(&mut self) {
+        if self.coalesced_events.is_empty() {
+            return;
+        }
+        let start = Instant::now();
+        let written_count = BankingTracer::write_events_or_record_loss(
+            &mut self.sink,
+            &mut self.lost_events,
+            &self.coalesced_events,
+            &self.coalesced_frames,
+        );
+        self.coalesced_frames.clear();
+        // amortized over the coalesced events
+        let write_time = start.elapsed() / self.coalesced_events.len() as u32;
+        for (index, event) in self.coalesced_events.drain(..).enumerate() {
+            self.stats
+                .record_write(&event, write_time, index >= written_count);
+            // a slow tapper must not hold back the tracer thread
+            self.taps.retain(|tap| tap.try_send(event.clone()).is_ok());
+        }
+    }
+}
+
+/// Events lost by the tracer thread since it last managed to write.
+#[derive(Debug, Default)]
+struct LostEvents {
+    count: u64,
+    first_time: Option<SystemTime>,
+    last_time: Option<SystemTime>,
+}
+
+impl LostEvents {
+    fn record(&mut self, time: SystemTime) {
+        self.count += 1;
+        self.first_time.get_or_insert(time);
+        self.last_time = Some(time);
+    }
+
+    fn gap_event(&self) -> Option<TimedTracedEvent> {
+        let (first_time, last_time) = self.first_time.zip(self.last_time)?;
+        Some(TimedTracedEvent(
+            first_time,
+            TracedEvent::TraceGap {
+                dropped_count: self.count,
+                span: last_time.duration_since(first_time).unwrap_or_default(),
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct LabelStats {
+    packet_batches: u64,
+    packets: u64,
+}
+
+/// Stats of the tracer thread, reported as the `banking_trace` datapoint every
+/// `STATS_REPORT_INTERVAL`.
+#[derive(Debug)]
+struct TracerThreadStats {
+    last_report_time: Instant,
+    non_vote: LabelStats,
+    tpu_vote: LabelStats,
+    gossip_vote: LabelStats,
+    written_count: u64,
+    lost_count: u64,
+    write_us: u64,
+    max_write_us: u64,
+    last_rotation_count: u64,
+}
+
+impl Default for TracerThreadStats {
+    fn default() -> Self {
+        Self {
+            last_report_time: Instant::now(),
+            non_vote: LabelStats::default(),
+            tpu_vote: LabelStats::default(),
+            gossip_vote: LabelStats::default(),
+            written_count: u64::default(),
+            lost_count: u64::default(),
+            write_us: u64::default(),
+            max_write_us: u64::default(),
+            last_rotation_count: u64::default(),
+        }
+    }
+}
+
+impl TracerThreadStats {
+    fn record_write(&mut self, event: &TimedTracedEvent, write_time: Duration, is_lost: bool) {
+        if let TimedTracedEvent(_, TracedEvent::PacketBatch(label, batch)) = event {
+            let label_stats = match label {
+                ChannelLabel::NonVote => &mut self.non_vote,
+                ChannelLabel::TpuVote => &mut self.tpu_vote,
+                ChannelLabel::GossipVote => &mut self.gossip_vote,
+                ChannelLabel::Dummy => return,
+            };
+            label_stats.packet_batches += 1;
+            label_stats.packets += batch.iter().map(|batch| batch.len() as u64).sum::<u64>();
+        }
+        let write_us = write_time.as_micros() as u64;
+        self.write_us += write_us;
+        self.max_write_us = self.max_write_us.max(write_us);
+        if is_lost {
+            self.lost_count += 1;
+        } else {
+            self.written_count += 1;
+        }
+    }
+
+    fn report_if_due(&mut self, rotation_count: u64) {
+        let interval = self.last_report_time.elapsed();
+        if interval < STATS_REPORT_INTERVAL {
+            return;
+        }
+        datapoint_info!(
+            "banking_trace",
+            ("interval_us", interval.as_micros() as i64, i64),
+            ("non_vote_packet_batches", self.non_vote.packet_batches, i64),
+            ("non_vote_packets", self.non_vote.packets, i64),
+            ("tpu_vote_packet_batches", self.tpu_vote.packet_batches, i64),
+            ("tpu_vote_packets", self.tpu_vote.packets, i64),
+            (
+                "gossip_vote_packet_batches",
+                self.gossip_vote.packet_batches,
+                i64
+            ),
+            ("gossip_vote_packets", self.gossip_vote.packets, i64),
+            ("written_events", self.written_count, i64),
+            ("lost_events", self.lost_count, i64),
+            ("write_us", self.write_us, i64),
+            ("max_write_us", self.max_write_us, i64),
+            (
+                "rotations",
+                rotation_count.saturating_sub(self.last_rotation_count),
+                i64
+            ),
+        );
+        *self = Self {
+            last_rotation_count: rotation_count,
+            ..Self::default()
+        };
+    }
+}
+
+fn packets_dropped_event(
+    time: SystemTime,
+    label: ChannelLabel,
+    count: usize,
+    reason: PacketDropReason,
+) -> TimedTracedEvent {
+    TimedTracedEvent(
+        time,
+        TracedEvent::PacketsDropped {
+            label,
+            count: count as u64,
+            reason,
+        },
+    )
+}
+
+#[cfg(not(feature = "banking-trace-noop"))]
+pub struct TracedSender {
+    label: ChannelLabel,
+    sender: Sender<BankingPacketBatch>,
+    active_tracer: Option<ActiveTracer>,
+    stats: Arc<ChannelStats>,
+    /// Set only for bounded channels with `OverflowPolicy::DropOldest`.
+    evicting_receiver: Option<BankingPacketReceiver>,
+    rate_limiter: Option<Mutex<RateLimiter>>,
+    filter: Option<SendFilter>,
+    /// Shared by the channels created together, so that they're paused together.
+    gate: Arc<ChannelGate>,
+}
+
+#[cfg(not(feature = "banking-trace-noop"))]
+impl TracedSender {
+    fn new(
+        label: ChannelLabel,
+        sender: Sender<BankingPacketBatch>,
+        active_tracer: Option<ActiveTracer>,
+    ) -> Self {
+        Self {
+            label,
+            sender,
+            active_tracer,
+            stats: Arc::default(),
+            evicting_receiver: None,
+            rate_limiter: None,
+            filter: None,
+            gate: Arc::default(),
+        }
+    }
+
+    fn with_channel_gate(mut self, gate: Arc<ChannelGate>) -> Self {
+        self.gate = gate;
+        self
+    }
+
+    pub fn channel_gate(&self) -> &Arc<ChannelGate> {
+        &self.gate
+    }
+
+    fn with_stats(mut self, stats: Arc<ChannelStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn with_evicting_receiver(mut self, evicting_receiver: BankingPacketReceiver) -> Self {
+        self.evicting_receiver = Some(evicting_receiver);
+        self
+    }
+
+    /// Runs `filter` on each batch before anything else, tracing the packets it discards as
+    /// dropped.
+    pub fn with_filter(mut self, filter: SendFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Drops the batches over `rate_limit` instead of sending them, tracing them as dropped.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limiter = Some(Mutex::new(RateLimiter::new(rate_limit)));
+        self
+    }
+
+    /// Whether the batches sent now are traced into the tracer of this sender or its mirrors.
+    fn is_tracing_enabled(&self) -> bool {
+        self.active_tracer
+            .as_ref()
+            .is_some_and(ActiveTracer::is_tracing)
+    }
+
+    /// Whether the batches go through anything but the channel regardless of tracing.
+    fn has_send_policies(&self) -> bool {
+        self.filter.is_some() || self.rate_limiter.is_some() || self.evicting_receiver.is_some()
+    }
+
+    pub fn send(&self, batch: BankingPacketBatch) -> Result<(), SendError<BankingPacketBatch>> {
+        // tracing is disabled by default, so don't make every validator pay for the tracer, the
+        // gate or the stats
+        let is_tracing_enabled = self.is_tracing_enabled();
+        if !is_tracing_enabled && !self.has_send_policies() && !self.gate.is_paused() {
+            return self.sender.send(batch);
+        }
+        let batch = match &self.filter {
+            None => batch,
+            Some(filter) => {
+                let accepted_count = send_filter::accepted_packet_count(&batch);
+                let filtered = filter(batch);
+                let filtered_accepted_count = filtered
+                    .as_ref()
+                    .map(send_filter::accepted_packet_count)
+                    .unwrap_or_default();
+                self.trace_packets_dropped(
+                    accepted_count.saturating_sub(filtered_accepted_count),
+                    PacketDropReason::Filtered,
+                );
+                let Some(filtered) = filtered else {
+                    return Ok(());
+                };
+                filtered
+            }
+        };
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let counts = TraceCounts::from_batch(&batch);
+            if !rate_limiter
+                .lock()
+                .unwrap()
+                .try_acquire(&counts, Instant::now())
+            {
+                self.stats.record_throttle(counts.packet_count as usize);
+                self.trace_packets_dropped(
+                    counts.packet_count as usize,
+                    PacketDropReason::RateLimited,
+                );
+                return Ok(());
+            }
+        }
+        // the traced event shares the batch, which is handed back as is on failures
+        if let Some(active_tracer) = &self.active_tracer {
+            if active_tracer
+                .trace_packet_batch(self.label, &batch)
+                .is_err()
+            {
+                return Err(SendError(batch));
+            }
+        }
+        let packet_count = batch.iter().map(|batch| batch.len()).sum();
+        let evicting_receiver = self.evicting_receiver.as_ref();
+        self.gate.pass(
+            &self.sender,
+            evicting_receiver,
+            batch,
+            |batch| match evicting_receiver {
+                None => self.sender.send(batch),
+                Some(evicting_receiver) => self.send_evicting_oldest(evicting_receiver, batch),
+            },
+        )?;
+        if is_tracing_enabled {
+            self.stats.record_send(packet_count, self.sender.len());
+        }
+        Ok(())
+    }
+
+    fn send_evicting_oldest(
+        &self,
+        evicting_receiver: &BankingPacketReceiver,
+        batch: BankingPacketBatch,
+    ) -> Result<(), SendError<BankingPacketBatch>> {
+        channel_gate::send_evicting_oldest(&self.sender, evicting_receiver, batch, |evicted| {
+            let count = evicted.iter().map(|batch| batch.len()).sum();
+            self.stats.record_eviction(count);
+            self.trace_packets_dropped(count, PacketDropReason::ChannelOverflow);
+        })
+    }
+
+    /// Sends `batches` coalesced into a single batch, so that a burst is traced as a single
+    /// `PacketBatch` event and pushed with a single channel operation. The packet batches are
+    /// moved out of `batches` unless shared elsewhere, in which case they're cloned.
+    pub fn send_many(
+        &self,
+        batches: Vec<BankingPacketBatch>,
+    ) -> Result<(), SendError<BankingPacketBatch>> {
+        match batches.len() {
+            0 => Ok(()),
+            1 => self.send(batches.into_iter().next().unwrap()),
+            _ => self.send(BankingPacketBatch::new(
+                batches
+                    .into_iter()
+                    .flat_map(BankingPacketBatch::unwrap_or_clone)
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Snapshots the counters of this channel, sampling its depth now.
+    pub fn stats(&self) -> ChannelStatsSnapshot {
+        ChannelStatsSnapshot {
+            depth: self.sender.len() as u64,
+            ..self.stats.snapshot()
+        }
+    }
+
+    /// Traces packets dropped before they were sent to this channel.
+    pub fn trace_packets_dropped(&self, count: usize, reason: PacketDropReason) {
+        if let Some(active_tracer) = &self.active_tracer {
+            if count > 0 {
+                active_tracer.trace_event(|| {
+                    packets_dropped_event(active_tracer.now(), self.label, count, reason)
+                });
+            }
+        }
+    }
+
+    /// Traces the counts of packets verified, failed to be verified and dropped as duplicates
+    /// during the last `interval` before being sent to this channel.
+    pub fn trace_sigverify_stats(
+        &self,
+        verified: usize,
+        failed: usize,
+        dedup: usize,
+        interval: Duration,
+    ) {
+        if let Some(active_tracer) = &self.active_tracer {
+            active_tracer.trace_event(|| {
+                TimedTracedEvent(
+                    active_tracer.now(),
+                    TracedEvent::SigverifyStats {
+                        label: self.label,
+                        verified: verified as u64,
+                        failed: failed as u64,
+                        dedup: dedup as u64,
+                        interval_us: interval.as_micros() as u64,
+                    },
+                )
+            });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sender.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(any(test, feature = "dev-context-only-utils"))]
+pub mod for_test {
+    use {
+        super::*,
+        solana_perf::{packet::to_packet_batches, test_tx::test_tx},
+        tempfile::TempDir,
+    };
+
+    pub fn sample_packet_batch() -> BankingPacketBatch {
+        BankingPacketBatch::new(to_packet_batches(&vec![test_tx(); 4], 10))
+    }
+
+    pub fn drop_and_clean_temp_dir_unless_suppressed(temp_dir: TempDir) {
+        std::env::var("BANKING_TRACE_LEAVE_FILES").is_ok().then(|| {
+            warn!("prevented to remove {:?}", temp_dir.path());
+            drop(temp_dir.keep());
+        });
+    }
+
+    /// Writes `events` as `BankingTracer` does, without spawning its background thread.
+    pub fn write_events(
+        path: &PathBuf,
+        rotate_threshold_size: u64,
+        events: impl IntoIterator<Item = TimedTracedEvent>,
+    ) {
+        let mut sink =
+            RollingFileSink::with_rotate_threshold_size(path, rotate_threshold_size).unwrap();
+        for event in events {
+            sink.write_event(&event).unwrap();
+        }
+        sink.flush().unwrap();
+    }
+
+    pub fn terminate_tracer(
+        tracer: Arc<BankingTracer>,
+        tracer_thread: TracerThread,
+        main_thread: JoinHandle<TracerThreadResult>,
+        sender: TracedSender,
+        exit: Option<Arc<AtomicBool>>,
+    ) {
+        if let Some(exit) = exit {
+            exit.store(true, Ordering::Relaxed);
+        }
+        drop((sender, tracer));
+        main_thread.join().unwrap().unwrap();
+        if let Some(tracer_thread) = tracer_thread {
+            tracer_thread.join().unwrap().unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            reader::{TraceFileReader, TraceReader},
+            *,
+        },
+        tempfile::TempDir,
+    };
+
+    #[test]
+    fn test_new_disabled() {
+        let exit = Arc::<AtomicBool>::default();
+
+        let tracer = BankingTracer::new_disabled();
+        let (non_vote_sender, non_vote_receiver) = tracer.create_channel_non_vote();
+
+        let dummy_main_thread = thread::spawn(move || {
+            receiving_loop_with_minimized_sender_overhead::<_, TraceError, 0>(
+                exit,
+                non_vote_receiver,
+                |_packet_batch| Ok(()),
+            )
+        });
+
+        non_vote_sender
+            .send(BankingPacketBatch::new(vec![]))
+            .unwrap();
+        for_test::terminate_tracer(tracer, None, dummy_main_thread, non_vote_sender, None);
+    }
+
+    #[test]
+    fn test_receiving_loop_with_exit_notification() {
+        let (exit_sender, exit_receiver) = crossbeam_channel::bounded(1);
+        let (sender, receiver) = BankingTracer::channel_for_test();
+        let main_thread = thread::spawn(move || {
+            let mut received_count = 0;
+            receiving_loop_with_exit_notification::<_, TraceError>(
+                exit_receiver,
+                receiver,
+                |_packet_batch| {
+                    received_count += 1;
+                    Ok(())
+                },
+            )
+            .map(|()| received_count)
+        });
+
+        sender.send(for_test::sample_packet_batch()).unwrap();
+        sender.send(for_test::sample_packet_batch()).unwrap();
+        // wait for the messages to be handled, so that the exit isn't prioritized over them
+        while !sender.is_empty() {
+            sleep(Duration::from_millis(1));
+        }
+        exit_sender.send(()).unwrap();
+        assert_eq!(main_thread.join().unwrap().unwrap(), 2);
+        // the sender is kept connected until here
+        drop(sender);
+    }
+
+    #[test]
+    fn test_create_channels_grouped() {
+        let channels = BankingTracer::new_disabled()
+            .create_channels_grouped(&[&[ChannelLabel::TpuVote, ChannelLabel::GossipVote]]);
+        assert!(channels
+            .tpu_vote_receiver
+            .same_channel(&channels.gossip_vote_receiver));
+        assert!(!channels
+            .non_vote_receiver
+            .same_channel(&channels.tpu_vote_receiver));
+
+        channels
+            .gossip_vote_sender
+            .send(for_test::sample_packet_batch())
+            .unwrap();
+        assert_eq!(channels.tpu_vote_receiver.len(), 1);
+        assert!(channels.non_vote_receiver.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "TpuVote is in more than one group")]
+    fn test_create_channels_grouped_overlapping() {
+        BankingTracer::new_disabled().create_channels_grouped(&[
+            &[ChannelLabel::NonVote, ChannelLabel::TpuVote],
+            &[ChannelLabel::TpuVote, ChannelLabel::GossipVote],
+        ]);
+    }
+
+    #[test]
+    fn test_send_after_exited() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) =
+            BankingTracer::new(Some((&path, exit.clone(), DirByteLimit::MAX))).unwrap();
+        let (non_vote_sender, non_vote_receiver) = tracer.create_channel_non_vote();
+
+        let exit_for_dummy_thread = Arc::<AtomicBool>::default();
+        let exit_for_dummy_thread2 = exit_for_dummy_thread.clone();
+        let dummy_main_thread = thread::spawn(move || {
+            receiving_loop_with_minimized_sender_overhead::<_, TraceError, 0>(
+                exit_for_dummy_thread,
+                non_vote_receiver,
+                |_packet_batch| Ok(()),
+            )
+        });
+
+        // kill and join the tracer thread
+        exit.store(true, Ordering::Relaxed);
+        tracer_thread.unwrap().join().unwrap().unwrap();
+
+        // .hash_event() must succeed even after exit is already set to true
+        let blockhash = Hash::from_str("B1ockhash1111111111111111111111111111111111").unwrap();
+        let bank_hash = Hash::from_str("BankHash11111111111111111111111111111111111").unwrap();
+        tracer.hash_event(4, &blockhash, &bank_hash);
+
+        drop(tracer);
+
+        // .send() must succeed even after exit is already set to true and further tracer is
+        // already dropped
+        non_vote_sender
+            .send(for_test::sample_packet_batch())
+            .unwrap();
+
+        // finally terminate and join the main thread
+        exit_for_dummy_thread2.store(true, Ordering::Relaxed);
+        dummy_main_thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_bucket_buffered_transactions() {
+        assert_eq!(bucket_buffered_transactions([]), (vec![], vec![]));
+        assert_eq!(
+            bucket_buffered_transactions([
+                (0, Duration::from_millis(50)),
+                (9, Duration::from_millis(100)),
+                (10, Duration::from_millis(399)),
+                (12_345, Duration::from_secs(2)),
+                (99_999, Duration::from_secs(60)),
+            ]),
+            (
+                vec![(0, 1), (1, 1), (10, 1), (10_000, 2)],
+                vec![(0, 1), (100, 2), (1_000, 1), (30_000, 1)],
+            )
+        );
+    }
+
+    #[test]
+    fn test_lost_events() {
+        let mut lost_events = LostEvents::default();
+        assert_matches!(lost_events.gap_event(), None);
+
+        let first_time = SystemTime::now();
+        lost_events.record(first_time);
+        lost_events.record(first_time + Duration::from_secs(1));
+        lost_events.record(first_time + Duration::from_secs(3));
+        assert_matches!(
+            lost_events.gap_event(),
+            Some(TimedTracedEvent(
+                time,
+                TracedEvent::TraceGap {
+                    dropped_count: 3,
+                    span,
+                }
+            )) if time == first_time && span == Duration::from_secs(3)
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_event() {
+        let last_packet_time = Cell::new(Instant::now());
+        assert_matches!(BankingTracer::heartbeat_event(&last_packet_time), None);
+
+        let long_ago = Instant::now()
+            .checked_sub(HEARTBEAT_INTERVAL + Duration::from_secs(1))
+            .unwrap();
+        last_packet_time.set(long_ago);
+        assert_matches!(
+            BankingTracer::heartbeat_event(&last_packet_time),
+            Some(TimedTracedEvent(_, TracedEvent::Heartbeat))
+        );
+        assert!(last_packet_time.get() > long_ago);
+        // the next one is due only after another interval
+        assert_matches!(BankingTracer::heartbeat_event(&last_packet_time), None);
+    }
+
+    #[test]
+    fn test_compute_unit_price_distribution() {
+        assert_eq!(ComputeUnitPriceDistribution::from_prices(vec![]), None);
+        assert_eq!(
+            ComputeUnitPriceDistribution::from_prices(vec![7]),
+            Some(ComputeUnitPriceDistribution {
+                count: 1,
+                min: 7,
+                p25: 7,
+                median: 7,
+                p75: 7,
+                p90: 7,
+                max: 7,
+            })
+        );
+        assert_eq!(
+            ComputeUnitPriceDistribution::from_prices((0..=100).rev().collect()),
+            Some(ComputeUnitPriceDistribution {
+                count: 101,
+                min: 0,
+                p25: 25,
+                median: 50,
+                p75: 75,
+                p90: 90,
+                max: 100,
+            })
+        );
+    }
+
+    #[derive(Default)]
+    struct VecSink {
+        events: Arc<Mutex<Vec<TimedTracedEvent>>>,
+        flushed: Arc<AtomicBool>,
+    }
+
+    impl TraceSink for VecSink {
+        fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), TraceError> {
+            self.flushed.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    /// Fails to write until `is_failing` is cleared.
+    struct FailingSink {
+        is_failing: Arc<AtomicBool>,
+        inner: VecSink,
+    }
+
+    impl TraceSink for FailingSink {
+        fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+            if self.is_failing.load(Ordering::Relaxed) {
+                return Err(io::Error::other("failing").into());
+            }
+            self.inner.write_event(event)
+        }
+
+        fn flush(&mut self) -> Result<(), TraceError> {
+            self.inner.flush()
+        }
+    }
+
+    /// Blocks on writing until `unblock` is disconnected, notifying `entered` beforehand.
+    struct BlockingSink {
+        entered: Sender<()>,
+        unblock: Receiver<()>,
+    }
+
+    impl TraceSink for BlockingSink {
+        fn write_event(&mut self, _event: &TimedTracedEvent) -> Result<(), TraceError> {
+            let _ = self.entered.send(());
+            let _ = self.unblock.recv();
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), TraceError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_traced_event_accessors() {
+        let event = TracedEvent::PacketsDropped {
+            label: ChannelLabel::TpuVote,
+            count: 1,
+            reason: PacketDropReason::Dedup,
+        };
+        assert_eq!(
+            (event.kind(), event.label(), event.slot()),
+            ("PacketsDropped", Some(ChannelLabel::TpuVote), None)
+        );
+        let event = TracedEvent::LeaderSlotBoundary {
+            slot: 3,
+            start: true,
+        };
+        assert_eq!(
+            (event.kind(), event.label(), event.slot()),
+            ("LeaderSlotBoundary", None, Some(3))
+        );
+        let event = TracedEvent::Heartbeat;
+        assert_eq!(
+            (event.kind(), event.label(), event.slot()),
+            ("Heartbeat", None, None)
+        );
+    }
+
+    #[test]
+    fn test_trace_sink_config_from_str() {
+        #[cfg(feature = "banking-trace-tcp")]
+        assert_eq!(
+            "tcp://collector:9000".parse::<TraceSinkConfig>().unwrap(),
+            TraceSinkConfig::Tcp("collector:9000".into())
+        );
+        assert_eq!(
+            "uds:///run/banking-trace.sock"
+                .parse::<TraceSinkConfig>()
+                .unwrap(),
+            TraceSinkConfig::Uds("/run/banking-trace.sock".into())
+        );
+        #[cfg(feature = "banking-trace-shm")]
+        assert_eq!(
+            "shm:///dev/shm/banking-trace"
+                .parse::<TraceSinkConfig>()
+                .unwrap(),
+            TraceSinkConfig::Shm("/dev/shm/banking-trace".into())
+        );
+        #[cfg(feature = "banking-trace-websocket")]
+        {
+            assert_eq!(
+                "ws://0.0.0.0:8912?strip-payloads"
+                    .parse::<TraceSinkConfig>()
+                    .unwrap(),
+                TraceSinkConfig::WebSocket {
+                    addr: SocketAddr::from(([0, 0, 0, 0], 8912)),
+                    strip_payloads: true,
+                }
+            );
+            assert_matches!(
+                "ws://0.0.0.0:8912?foo".parse::<TraceSinkConfig>(),
+                Err(TraceError::UnsupportedSink(_))
+            );
+        }
+        #[cfg(feature = "banking-trace-prometheus")]
+        assert_eq!(
+            "prometheus://0.0.0.0:9184"
+                .parse::<TraceSinkConfig>()
+                .unwrap(),
+            TraceSinkConfig::Prometheus(SocketAddr::from(([0, 0, 0, 0], 9184)))
+        );
+        #[cfg(feature = "banking-trace-kafka")]
+        {
+            assert_eq!(
+                "kafka://broker1:9092,broker2:9092/banking-trace"
+                    .parse::<TraceSinkConfig>()
+                    .unwrap(),
+                TraceSinkConfig::Kafka {
+                    brokers: "broker1:9092,broker2:9092".into(),
+                    topic: "banking-trace".into(),
+                }
+            );
+            assert_matches!(
+                "kafka://broker1:9092".parse::<TraceSinkConfig>(),
+                Err(TraceError::UnsupportedSink(_))
+            );
+        }
+        #[cfg(feature = "banking-trace-otel")]
+        {
+            assert_eq!(
+                "otlp://collector:4318".parse::<TraceSinkConfig>().unwrap(),
+                TraceSinkConfig::Otlp("http://collector:4318/v1/traces".into())
+            );
+            assert_eq!(
+                "otlp://collector:4318/traces"
+                    .parse::<TraceSinkConfig>()
+                    .unwrap(),
+                TraceSinkConfig::Otlp("http://collector:4318/traces".into())
+            );
+        }
+        #[cfg(feature = "banking-trace-bulk-insert")]
+        {
+            assert_eq!(
+                "postgres://tracer@db/trace"
+                    .parse::<TraceSinkConfig>()
+                    .unwrap(),
+                TraceSinkConfig::Postgres("postgres://tracer@db/trace".into())
+            );
+            assert_eq!(
+                "clickhouse://db:8123".parse::<TraceSinkConfig>().unwrap(),
+                TraceSinkConfig::ClickHouse("http://db:8123".into())
+            );
+        }
+        #[cfg(feature = "banking-trace-sqlite")]
+        assert_eq!(
+            "sqlite:///var/lib/banking-trace.sqlite?payloads"
+                .parse::<TraceSinkConfig>()
+                .unwrap(),
+            TraceSinkConfig::Sqlite {
+                path: "/var/lib/banking-trace.sqlite".into(),
+                store_payloads: true,
+            }
+        );
+        assert_matches!(
+            "tcp://".parse::<TraceSinkConfig>(),
+            Err(TraceError::UnsupportedSink(_))
+        );
+        assert_matches!(
+            "collector:9000".parse::<TraceSinkConfig>(),
+            Err(TraceError::UnsupportedSink(_))
+        );
+    }
+
+    #[test]
+    fn test_rotate_count() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        assert_matches!(
+            RollingFileSink::new_with_rotate_count(&path, DirByteLimit::MAX, 0).err(),
+            Some(TraceError::ZeroRotateCount)
+        );
+
+        // each file can hold only a single event
+        let config = RollingFileSinkConfig {
+            rotate_count: 2,
+            ..RollingFileSinkConfig::default()
+        };
+        let mut sink = RollingFileSink::with_rotate_threshold_size_and_config(
+            &path,
+            DirByteLimit::MAX,
+            1,
+            config,
+        )
+        .unwrap();
+        for _ in 0..4 {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+                .unwrap();
+        }
+        sink.flush().unwrap();
+        assert_eq!(sink.rotation_count(), 3);
+        assert!(path.join("events.1").exists());
+        assert!(!path.join("events.2").exists());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_file_name_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let config = |basename: &str, file_name_template: &str| RollingFileSinkConfig {
+            rotate_count: 2,
+            basename: basename.into(),
+            file_name_template: Some(file_name_template.into()),
+            ..RollingFileSinkConfig::default()
+        };
+        for (basename, file_name_template) in [("a/b", "events"), ("events", "events-%")] {
+            assert_matches!(
+                RollingFileSink::new_with_config(&path, 2, config(basename, file_name_template))
+                    .err(),
+                Some(TraceError::InvalidFileName(_))
+            );
+        }
+
+        // each file can hold only a single event
+        let mut sink = RollingFileSink::with_rotate_threshold_size_and_config(
+            &path,
+            DirByteLimit::MAX,
+            1,
+            config("tool", "tool-{slot}-%Y"),
+        )
+        .unwrap();
+        let year = Local::now().format("%Y");
+        let hash = Hash::default();
+        for event in [
+            TracedEvent::BlockAndBankHash(7, hash, hash),
+            TracedEvent::Heartbeat,
+            TracedEvent::Heartbeat,
+        ] {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), event))
+                .unwrap();
+        }
+        sink.flush().unwrap();
+        assert!(!path.join(format!("tool-7-{year}")).exists());
+        assert!(path.join(format!("tool-unknown-{year}")).exists());
+        assert!(!path.join("tool.1").exists());
+        assert_eq!(
+            TraceReader::open_dir_with_basename(&path, "tool")
+                .unwrap()
+                .event_file_paths(),
+            [path.join("tool")]
+        );
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        create_dir_all(&path).unwrap();
+        // left by a previous run with a larger rotate count, and by other tools before that
+        for (file_name, age_secs) in [("events.5", 10), ("tool-trace", 20), ("events.4.tmp", 30)] {
+            let file = File::create(path.join(file_name)).unwrap();
+            file.set_len(100).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+                .unwrap();
+        }
+
+        let mut sink = RollingFileSink::new_with_rotate_count(&path, 150, 3).unwrap();
+        assert!(path.join("events.5").exists());
+        sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+            .unwrap();
+        sink.flush().unwrap();
+
+        sink.set_dir_byte_limit(30).unwrap();
+        assert!(!path.join("events.5").exists());
+        assert!(path.join(BASENAME).exists());
+        // not trace files
+        assert!(path.join("tool-trace").exists());
+        assert!(path.join("events.4.tmp").exists());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_collect_garbage_retained_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let mut sink = RollingFileSink::new_with_rotate_count(&path, DirByteLimit::MAX, 3).unwrap();
+        // rotated into events.2 and events.1, from the oldest
+        for age_secs in [20, 10] {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+                .unwrap();
+            sink.rotate_now().unwrap();
+            File::options()
+                .write(true)
+                .open(path.join(format!("{BASENAME}.1")))
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+                .unwrap();
+        }
+        sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+            .unwrap();
+        sink.flush().unwrap();
+        let file_len = |file_name: &str| fs::metadata(path.join(file_name)).unwrap().len();
+        let rotated_len = file_len(&format!("{BASENAME}.1"));
+        assert_eq!(file_len(&format!("{BASENAME}.2")), rotated_len);
+
+        // the retained files alone exceed the limit, which fits two of them
+        sink.set_dir_byte_limit(file_len(BASENAME) + rotated_len)
+            .unwrap();
+        assert!(!path.join(format!("{BASENAME}.2")).exists());
+        assert!(path.join(format!("{BASENAME}.1")).exists());
+        assert!(path.join(BASENAME).exists());
+
+        // the file being written into is kept regardless
+        sink.set_dir_byte_limit(3).unwrap();
+        assert!(!path.join(format!("{BASENAME}.1")).exists());
+        assert!(path.join(BASENAME).exists());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_fsync_policy() {
+        assert_eq!(
+            "on-rotation".parse::<FsyncPolicy>().unwrap(),
+            FsyncPolicy::OnRotation
+        );
+        assert_eq!(
+            "every-5s".parse::<FsyncPolicy>().unwrap(),
+            FsyncPolicy::Interval(Duration::from_secs(5))
+        );
+        assert_matches!(
+            "every-0s".parse::<FsyncPolicy>(),
+            Err(TraceError::InvalidFsyncPolicy(_))
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        for (index, fsync_policy, is_written_through) in [
+            (0, FsyncPolicy::Never, false),
+            (1, FsyncPolicy::Interval(Duration::ZERO), true),
+        ] {
+            let path = temp_dir.path().join(format!("banking-trace-{index}"));
+            let config = RollingFileSinkConfig {
+                fsync_policy,
+                ..RollingFileSinkConfig::default()
+            };
+            let mut sink =
+                RollingFileSink::new_with_config(&path, DirByteLimit::MAX, config).unwrap();
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+                .unwrap();
+            let file_len = fs::metadata(path.join(BASENAME)).unwrap().len();
+            assert_eq!(file_len > 0, is_written_through);
+        }
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_buf_writer_capacity() {
+        let temp_dir = TempDir::new().unwrap();
+        for (index, buf_writer_capacity, is_written_through) in [
+            (0, TRACE_FILE_DEFAULT_BUF_WRITER_CAPACITY, false),
+            (1, 0, true),
+        ] {
+            let path = temp_dir.path().join(format!("banking-trace-{index}"));
+            let config = RollingFileSinkConfig {
+                buf_writer_capacity,
+                ..RollingFileSinkConfig::default()
+            };
+            let mut sink =
+                RollingFileSink::new_with_config(&path, DirByteLimit::MAX, config).unwrap();
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+                .unwrap();
+            let file_len = fs::metadata(path.join(BASENAME)).unwrap().len();
+            assert_eq!(file_len > 0, is_written_through);
+        }
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_io_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        for io_backend in [FileIoBackend::IoUring, FileIoBackend::DoubleBuffered] {
+            let path = temp_dir.path().join(format!("banking-trace-{io_backend}"));
+            let config = RollingFileSinkConfig {
+                buf_writer_capacity: 16,
+                io_backend,
+                ..RollingFileSinkConfig::default()
+            };
+            let mut sink =
+                RollingFileSink::new_with_config(&path, DirByteLimit::MAX, config).unwrap();
+            // io_uring falls back to buffered writes unless available
+            let expected_io_backend = match io_backend {
+                #[cfg(not(all(target_os = "linux", feature = "banking-trace-io-uring")))]
+                FileIoBackend::IoUring => FileIoBackend::Buffered,
+                io_backend => io_backend,
+            };
+            if expected_io_backend != FileIoBackend::IoUring {
+                assert_matches!(
+                    sink.effective_config(),
+                    SinkEffectiveConfig::RollingFile {
+                        config: RollingFileSinkConfig { io_backend, .. },
+                        ..
+                    } if io_backend == expected_io_backend
+                );
+            }
+            for index in 0..10 {
+                sink.write_event(&TimedTracedEvent(
+                    SystemTime::now(),
+                    TracedEvent::Annotation(index.to_string()),
+                ))
+                .unwrap();
+            }
+            sink.flush().unwrap();
+
+            let reader = TraceReader::open_dir(&path).unwrap();
+            let annotations = reader
+                .iter()
+                .filter_map(|event| match event.unwrap() {
+                    TimedTracedEvent(_, TracedEvent::Annotation(text)) => Some(text),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                annotations,
+                (0..10).map(|index| index.to_string()).collect::<Vec<_>>()
+            );
+        }
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[cfg(feature = "banking-trace-compression")]
+    #[test]
+    fn test_compression() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let config = RollingFileSinkConfig {
+            rotate_count: 3,
+            compression: Some(TraceCompression::Zstd),
+            ..RollingFileSinkConfig::default()
+        };
+        let mut sink = RollingFileSink::new_with_config(&path, DirByteLimit::MAX, config).unwrap();
+        for index in 0..5 {
+            sink.write_event(&TimedTracedEvent(
+                SystemTime::now(),
+                TracedEvent::Annotation(index.to_string()),
+            ))
+            .unwrap();
+            if index < 4 {
+                sink.rotate_now().unwrap();
+            }
+        }
+        // waits for the compressions still pending
+        drop(sink);
+
+        let file_names = |path: &Path| {
+            let mut file_names = fs::read_dir(path)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>();
+            file_names.sort();
+            file_names
+        };
+        assert_eq!(
+            file_names(&path),
+            vec!["compress", "events", "events.1.zst", "events.2.zst"]
+        );
+        assert!(file_names(&path.join("compress")).is_empty());
+        let reader = TraceReader::open_dir(&path).unwrap();
+        let annotations = reader
+            .iter()
+            .filter_map(|event| match event.unwrap() {
+                TimedTracedEvent(_, TracedEvent::Annotation(text)) => Some(text),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(annotations, vec!["2", "3", "4"]);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "banking-trace-fs"))]
+    #[test]
+    fn test_preallocate() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let rotate_threshold_size = 1024 * 1024;
+        let config = RollingFileSinkConfig {
+            rotate_count: 3,
+            preallocate: true,
+            ..RollingFileSinkConfig::default()
+        };
+        let mut sink =
+            RollingFileSink::new_with_config(&path, 3 * rotate_threshold_size, config).unwrap();
+        let allocated_bytes = |file_name: &str| {
+            let metadata = fs::metadata(path.join(file_name)).unwrap();
+            (metadata.len(), metadata.blocks() * 512)
+        };
+        for index in 0..2 {
+            sink.write_event(&TimedTracedEvent(
+                SystemTime::now(),
+                TracedEvent::Annotation(index.to_string()),
+            ))
+            .unwrap();
+            sink.flush().unwrap();
+            // reserved without changing the size
+            let (len, allocated) = allocated_bytes("events");
+            assert!(len < rotate_threshold_size);
+            assert!(allocated >= rotate_threshold_size);
+            if index == 0 {
+                sink.rotate_now().unwrap();
+                let (_, allocated) = allocated_bytes("events.1");
+                assert!(allocated < rotate_threshold_size);
+            }
+        }
+        drop(sink);
+        let (_, allocated) = allocated_bytes("events");
+        assert!(allocated < rotate_threshold_size);
+
+        let reader = TraceReader::open_dir(&path).unwrap();
+        let annotations = reader
+            .iter()
+            .filter_map(|event| match event.unwrap() {
+                TimedTracedEvent(_, TracedEvent::Annotation(text)) => Some(text),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(annotations, vec!["0", "1"]);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_rotation_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let heartbeat = || TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat);
+
+        // each file can hold only a single event unless never rotated
+        let mut sink = RollingFileSink::with_rotate_threshold_size(&path, 1)
+            .unwrap()
+            .with_rotation_policy(RotationPolicy::Never);
+        sink.write_event(&heartbeat()).unwrap();
+        sink.write_event(&heartbeat()).unwrap();
+        assert_eq!(sink.rotation_count(), 0);
+
+        sink.file_appender
+            .condition_mut()
+            .set_rotation_policy(RotationPolicy::Size);
+        sink.write_event(&heartbeat()).unwrap();
+        sink.flush().unwrap();
+        assert_eq!(sink.rotation_count(), 1);
+        assert_eq!(
+            "hourly".parse::<RotationPolicy>().unwrap(),
+            RotationPolicy::Hourly
+        );
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_rotate_hint() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+
+        let mut sink = RollingFileSink::with_rotate_threshold_size(
+            &path,
+            TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD,
+        )
+        .unwrap();
+        // no rollover of the blank file
+        sink.rotate_hint();
+        sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+            .unwrap();
+        sink.rotate_hint();
+        sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+            .unwrap();
+        sink.write_event(&TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat))
+            .unwrap();
+        sink.flush().unwrap();
+        assert_eq!(sink.rotation_count(), 1);
+
+        let frame_count = |file_name: &str| {
+            TraceFileReader::open(path.join(file_name))
+                .unwrap()
+                .map(Result::unwrap)
+                .count()
+        };
+        assert_eq!(frame_count("events.1"), 1);
+        assert_eq!(frame_count("events"), 2);
+        assert!(!path.join("events.2").exists());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_new_with_sink() {
+        let sink = VecSink::default();
+        let (events, flushed) = (sink.events.clone(), sink.flushed.clone());
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit).unwrap();
+        tracer.annotate("foo");
+        // disconnect the tracer thread, which drains and flushes before exiting
+        drop(tracer);
+        tracer_thread.unwrap().join().unwrap().unwrap();
+
+        assert_matches!(
+            &events.lock().unwrap()[..],
+            [TimedTracedEvent(_, TracedEvent::Annotation(text))] if text == "foo"
+        );
+        assert!(flushed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_set_dir_byte_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) =
+            BankingTracer::new(Some((&path, exit, DirByteLimit::MAX))).unwrap();
+        tracer.annotate("foo");
+        assert_matches!(
+            tracer.set_dir_byte_limit(TRACE_FILE_DEFAULT_ROTATE_COUNT - 1),
+            Err(TraceError::TooSmallDirByteLimit(..))
+        );
+        assert!(!path.join("events.1").exists());
+
+        // each file can hold only a single event from now on, while the directory can still
+        // hold all of them
+        tracer
+            .set_dir_byte_limit(20 * TRACE_FILE_DEFAULT_ROTATE_COUNT)
+            .unwrap();
+        tracer.annotate("bar");
+        // otherwise written together as coalesced
+        tracer.flush_now(Duration::from_secs(10)).unwrap();
+        tracer.annotate("baz");
+        drop(tracer);
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        assert!(path.join("events.2").exists());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_flush_now() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) =
+            BankingTracer::new(Some((&path, exit, DirByteLimit::MAX))).unwrap();
+        let flusher = tracer.flusher();
+        tracer.annotate("foo");
+        flusher.flush_now(Duration::from_secs(10)).unwrap();
+        let file_len = fs::metadata(path.join(BASENAME)).unwrap().len();
+        assert!(file_len > TRACE_FILE_MAGIC.len() as u64);
+
+        drop(tracer);
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        // no-op once the tracer thread has exited
+        flusher.flush_now(Duration::from_secs(10)).unwrap();
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
 
     #[test]
-    fn test_new_disabled() {
+    fn test_rotate_now() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
         let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) =
+            BankingTracer::new(Some((&path, exit, DirByteLimit::MAX))).unwrap();
+        tracer.annotate("foo");
+        tracer.rotate_now().unwrap();
+        let rotated_path = path.join(format!("{BASENAME}.1"));
+        let rotated_len = fs::metadata(&rotated_path).unwrap().len();
+        assert!(rotated_len > TRACE_FILE_MAGIC.len() as u64);
+        assert_eq!(fs::metadata(path.join(BASENAME)).unwrap().len(), 0);
+
+        // a blank file isn't rotated
+        tracer.rotate_now().unwrap();
+        assert_eq!(fs::metadata(&rotated_path).unwrap().len(), rotated_len);
+        assert!(!path.join(format!("{BASENAME}.2")).exists());
+
+        tracer.annotate("bar");
+        drop(tracer);
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        assert!(fs::metadata(path.join(BASENAME)).unwrap().len() > TRACE_FILE_MAGIC.len() as u64);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
 
+    #[test]
+    fn test_tail() {
         let tracer = BankingTracer::new_disabled();
-        let (non_vote_sender, non_vote_receiver) = tracer.create_channel_non_vote();
+        assert!(tracer.tail(1, Duration::ZERO).is_empty());
+        assert!(tracer.tail(1, Duration::MAX).is_empty());
+        tracer.rotate_now().unwrap();
 
-        let dummy_main_thread = thread::spawn(move || {
-            receiving_loop_with_minimized_sender_overhead::<_, TraceError, 0>(
-                exit,
-                non_vote_receiver,
-                |_packet_batch| Ok(()),
-            )
+        tracer
+            .enable_with_sink(VecSink::default(), Arc::<AtomicBool>::default())
+            .unwrap();
+        tracer.set_label_traced(ChannelLabel::TpuVote, false);
+        assert_eq!(
+            tracer.status(),
+            BankingTracerStatus {
+                is_enabled: true,
+                is_packet_tracing_suspended: false,
+                untraced_labels: vec![ChannelLabel::TpuVote],
+                min_available_bytes: 0,
+            }
+        );
+        let tail_thread = thread::spawn({
+            let tracer = tracer.clone();
+            move || tracer.tail(2, Duration::from_secs(10))
         });
+        // the tap is added by the tracer thread asynchronously
+        while !tail_thread.is_finished() {
+            tracer.annotate("foo");
+            sleep(Duration::from_millis(10));
+        }
+        let events = tail_thread.join().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|TimedTracedEvent(_, event)| matches!(event, TracedEvent::Annotation(_))));
+        tracer.rotate_now().unwrap();
+        tracer.disable().unwrap();
+    }
 
-        non_vote_sender
-            .send(BankingPacketBatch::new(vec![]))
+    #[test]
+    fn test_shutdown() {
+        let tracer = BankingTracer::new_disabled();
+        assert_eq!(tracer.shutdown(Duration::ZERO).unwrap(), 0);
+
+        let sink = VecSink::default();
+        let (events, flushed) = (sink.events.clone(), sink.flushed.clone());
+        tracer
+            .enable_with_sink(sink, Arc::<AtomicBool>::default())
             .unwrap();
-        for_test::terminate_tracer(tracer, None, dummy_main_thread, non_vote_sender, None);
+        tracer.annotate("foo");
+        assert_eq!(tracer.shutdown(Duration::from_secs(10)).unwrap(), 0);
+        assert!(!tracer.is_enabled());
+        assert!(flushed.load(Ordering::Relaxed));
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        let (entered_sender, entered_receiver) = unbounded();
+        let (unblock_sender, unblock_receiver) = unbounded();
+        let sink = BlockingSink {
+            entered: entered_sender,
+            unblock: unblock_receiver,
+        };
+        tracer
+            .enable_with_sink(sink, Arc::<AtomicBool>::default())
+            .unwrap();
+        tracer.annotate("foo");
+        entered_receiver.recv().unwrap();
+        tracer.annotate("bar");
+        tracer.annotate("baz");
+        assert_matches!(
+            tracer.flusher().flush_now(Duration::from_millis(10)),
+            Err(TraceError::FlushTimeout(_))
+        );
+        assert_eq!(tracer.shutdown(Duration::from_millis(10)).unwrap(), 2);
+        assert!(!tracer.is_enabled());
+        drop(unblock_sender);
     }
 
     #[test]
-    fn test_send_after_exited() {
+    fn test_env_overrides() {
+        let vars = |vars: &[(&str, &str)]| {
+            EnvOverrides::from_var_os(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            })
+        };
+        assert_eq!(vars(&[("PATH", "/bin")]).unwrap(), EnvOverrides::default());
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            let non_unicode = || OsString::from_vec(vec![b'1', 0xff]);
+            assert_matches!(
+                EnvOverrides::from_var_os(
+                    |name| (name == "BANKING_TRACE_DIR_BYTE_LIMIT").then(non_unicode)
+                ),
+                Err(TraceError::InvalidEnvOverride(
+                    "BANKING_TRACE_DIR_BYTE_LIMIT",
+                    _
+                ))
+            );
+            assert_eq!(
+                EnvOverrides::from_var_os(|name| (name == "BANKING_TRACE_DIR").then(non_unicode))
+                    .unwrap()
+                    .dir,
+                Some(PathBuf::from(non_unicode()))
+            );
+        }
+        assert_matches!(
+            vars(&[("BANKING_TRACE_DIR_BYTE_LIMIT", "1GB")]),
+            Err(TraceError::InvalidEnvOverride(
+                "BANKING_TRACE_DIR_BYTE_LIMIT",
+                _
+            ))
+        );
+        assert_matches!(
+            vars(&[("BANKING_TRACE_COMPRESSION", "gzip")]),
+            Err(TraceError::InvalidEnvOverride(
+                "BANKING_TRACE_COMPRESSION",
+                _
+            ))
+        );
+        assert_matches!(
+            vars(&[("BANKING_TRACE_SKIP_LABELS", "dummy")]),
+            Err(TraceError::InvalidEnvOverride(
+                "BANKING_TRACE_SKIP_LABELS",
+                _
+            ))
+        );
+
         let temp_dir = TempDir::new().unwrap();
         let path = temp_dir.path().join("banking-trace");
+        let overridden_path = temp_dir.path().join("overridden");
+        let mut overrides = vars(&[
+            ("BANKING_TRACE_DIR", overridden_path.to_str().unwrap()),
+            ("BANKING_TRACE_DIR_BYTE_LIMIT", "1000000"),
+            ("BANKING_TRACE_COMPRESSION", "lz4"),
+            ("BANKING_TRACE_SKIP_LABELS", "tpu-vote, gossip-vote"),
+        ])
+        .unwrap();
+        assert_eq!(overrides.compression, Some(TraceCompression::Lz4));
+        if !cfg!(feature = "banking-trace-compression") {
+            overrides.compression = None;
+        }
+        let compression = overrides.compression;
         let exit = Arc::<AtomicBool>::default();
-        let (tracer, tracer_thread) =
-            BankingTracer::new(Some((&path, exit.clone(), DirByteLimit::MAX))).unwrap();
-        let (non_vote_sender, non_vote_receiver) = tracer.create_channel_non_vote();
+        let (tracer, tracer_thread) = BankingTracer::new_with_env_overrides(
+            Some((&path, exit.clone(), DirByteLimit::MAX)),
+            overrides,
+        )
+        .unwrap();
+        assert!(overridden_path.join(BASENAME).exists());
+        assert!(!path.exists());
+        assert_matches!(
+            tracer.effective_config().sink,
+            Some(SinkEffectiveConfig::RollingFile {
+                dir_byte_limit: 1_000_000,
+                config: RollingFileSinkConfig {
+                    compression: effective_compression,
+                    ..
+                },
+                ..
+            }) if effective_compression == compression
+        );
+        assert!(tracer.is_label_traced(ChannelLabel::NonVote));
+        assert!(!tracer.is_label_traced(ChannelLabel::TpuVote));
+        assert!(!tracer.is_label_traced(ChannelLabel::GossipVote));
+        drop(tracer);
+        tracer_thread.unwrap().join().unwrap().unwrap();
 
-        let exit_for_dummy_thread = Arc::<AtomicBool>::default();
-        let exit_for_dummy_thread2 = exit_for_dummy_thread.clone();
-        let dummy_main_thread = thread::spawn(move || {
-            receiving_loop_with_minimized_sender_overhead::<_, TraceError, 0>(
-                exit_for_dummy_thread,
-                non_vote_receiver,
-                |_packet_batch| Ok(()),
-            )
-        });
+        let overrides = vars(&[("BANKING_TRACE_DIR_BYTE_LIMIT", "0")]).unwrap();
+        let (tracer, tracer_thread) = BankingTracer::new_with_env_overrides(
+            Some((&path, exit, DirByteLimit::MAX)),
+            overrides,
+        )
+        .unwrap();
+        assert!(!tracer.is_enabled());
+        assert!(tracer_thread.is_none());
 
-        // kill and join the tracer thread
-        exit.store(true, Ordering::Relaxed);
-        tracer_thread.unwrap().join().unwrap().unwrap();
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
 
-        // .hash_event() must succeed even after exit is already set to true
-        let blockhash = Hash::from_str("B1ockhash1111111111111111111111111111111111").unwrap();
-        let bank_hash = Hash::from_str("BankHash11111111111111111111111111111111111").unwrap();
-        tracer.hash_event(4, &blockhash, &bank_hash);
+    #[test]
+    fn test_env_overrides_with_file_sink_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut path = temp_dir.path().join("banking-trace");
+        let overridden_path = temp_dir.path().join("overridden");
+        let overrides = EnvOverrides {
+            dir: Some(overridden_path.clone()),
+            dir_byte_limit: Some(1_000_000),
+            ..EnvOverrides::default()
+        };
+        let mut dir_byte_limit = DirByteLimit::MAX;
+        let mut config = RollingFileSinkConfig {
+            rotate_count: 3,
+            basename: "custom".to_string(),
+            ..RollingFileSinkConfig::default()
+        };
+        overrides.apply(&mut path, &mut dir_byte_limit, &mut config);
+        assert_eq!((&path, dir_byte_limit), (&overridden_path, 1_000_000));
+        assert_eq!(config.basename, "custom");
 
+        let (tracer, tracer_thread) = BankingTracer::new_with_file_sink_config(
+            Some((&path, Arc::<AtomicBool>::default(), dir_byte_limit)),
+            config,
+        )
+        .unwrap();
+        assert!(overridden_path.join("custom").exists());
+        assert_matches!(
+            tracer.effective_config().sink,
+            Some(SinkEffectiveConfig::RollingFile {
+                dir_byte_limit: 1_000_000,
+                config: RollingFileSinkConfig {
+                    rotate_count: 3,
+                    ..
+                },
+                ..
+            })
+        );
         drop(tracer);
+        tracer_thread.unwrap().join().unwrap().unwrap();
 
-        // .send() must succeed even after exit is already set to true and further tracer is
-        // already dropped
-        non_vote_sender
-            .send(for_test::sample_packet_batch())
-            .unwrap();
-
-        // finally terminate and join the main thread
-        exit_for_dummy_thread2.store(true, Ordering::Relaxed);
-        dummy_main_thread.join().unwrap().unwrap();
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
     }
 
     #[test]
-    fn test_record_and_restore() {
+    fn test_effective_config() {
+        let tracer = BankingTracer::new_disabled();
+        assert_eq!(
+            tracer.effective_config(),
+            BankingTracerEffectiveConfig {
+                sink: None,
+                write_interval: TRACE_FILE_DEFAULT_WRITE_INTERVAL,
+                serialize_in_sender: false,
+                coarse_clock: false,
+                untraced_labels: vec![],
+                min_available_bytes: 0,
+            }
+        );
+
         let temp_dir = TempDir::new().unwrap();
         let path = temp_dir.path().join("banking-trace");
         let exit = Arc::<AtomicBool>::default();
-        let (tracer, tracer_thread) =
-            BankingTracer::new(Some((&path, exit.clone(), DirByteLimit::MAX))).unwrap();
-        let (non_vote_sender, non_vote_receiver) = tracer.create_channel_non_vote();
+        tracer
+            .enable((&path, exit.clone(), BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT))
+            .unwrap();
+        tracer.set_label_traced(ChannelLabel::TpuVote, false);
+        assert_eq!(
+            tracer.effective_config(),
+            BankingTracerEffectiveConfig {
+                sink: Some(SinkEffectiveConfig::RollingFile {
+                    path: path.clone(),
+                    dir_byte_limit: BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT,
+                    rotate_threshold_size: TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD,
+                    config: RollingFileSinkConfig::default(),
+                }),
+                write_interval: TRACE_FILE_DEFAULT_WRITE_INTERVAL,
+                serialize_in_sender: false,
+                coarse_clock: false,
+                untraced_labels: vec![ChannelLabel::TpuVote],
+                min_available_bytes: 0,
+            }
+        );
 
-        let dummy_main_thread = thread::spawn(move || {
-            receiving_loop_with_minimized_sender_overhead::<_, TraceError, 0>(
-                exit,
-                non_vote_receiver,
-                |_packet_batch| Ok(()),
-            )
-        });
+        let sinks: [Box<dyn TraceSink>; 2] = [
+            Box::new(sampling_sink::SamplingSink::new(VecSink::default(), 10)),
+            Box::new(VecSink::default()),
+        ];
+        tracer.enable_with_sink(TeeSink::new(sinks), exit).unwrap();
+        let vec_sink = SinkEffectiveConfig::Other(std::any::type_name::<VecSink>());
+        assert_eq!(
+            tracer.effective_config().sink,
+            Some(SinkEffectiveConfig::Tee(vec![
+                SinkEffectiveConfig::Sampling {
+                    rate: 10,
+                    inner: Box::new(vec_sink.clone()),
+                },
+                vec_sink,
+            ]))
+        );
+        tracer.disable().unwrap();
 
-        non_vote_sender
-            .send(for_test::sample_packet_batch())
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_set_write_interval() {
+        let tracer = BankingTracer::new_disabled();
+        assert_eq!(tracer.write_interval(), TRACE_FILE_DEFAULT_WRITE_INTERVAL);
+        tracer.set_write_interval(Duration::from_millis(1));
+        assert_eq!(tracer.write_interval(), Duration::from_millis(1));
+
+        let sink = VecSink::default();
+        let events = sink.events.clone();
+        tracer
+            .enable_with_sink(sink, Arc::<AtomicBool>::default())
             .unwrap();
-        let blockhash = Hash::from_str("B1ockhash1111111111111111111111111111111111").unwrap();
-        let bank_hash = Hash::from_str("BankHash11111111111111111111111111111111111").unwrap();
-        tracer.hash_event(4, &blockhash, &bank_hash);
+        tracer.annotate("foo");
+        tracer.disable().unwrap();
+        assert_eq!(events.lock().unwrap().len(), 1);
 
-        for_test::terminate_tracer(
-            tracer,
-            tracer_thread,
-            dummy_main_thread,
-            non_vote_sender,
-            None,
-        );
+        // saturated
+        tracer.set_write_interval(Duration::MAX);
+        assert_eq!(tracer.write_interval(), Duration::from_micros(u64::MAX));
+    }
 
-        let mut stream = BufReader::new(File::open(path.join(BASENAME)).unwrap());
-        let results = (0..=3)
-            .map(|_| bincode::deserialize_from::<_, TimedTracedEvent>(&mut stream))
-            .collect::<Vec<_>>();
+    #[test]
+    fn test_coarse_clock() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let tracer = BankingTracer::new_disabled();
+        assert!(!tracer.coarse_clock());
+        tracer.set_coarse_clock(true);
+        tracer.set_write_interval(Duration::from_millis(5));
+        assert!(tracer.effective_config().coarse_clock);
+        let clock = &tracer.active_tracer.clock;
+
+        // falls back to the system clock without the tracer thread
+        let before = SystemTime::now();
+        let time = clock.now();
+        assert!(before <= time && time <= SystemTime::now());
 
-        let mut i = 0;
+        let sink = RollingFileSink::new(&path, DirByteLimit::MAX).unwrap();
+        tracer
+            .enable_with_sink(sink, Arc::<AtomicBool>::default())
+            .unwrap();
+        while clock.coarse_micros.load(Ordering::Relaxed) == 0 {
+            sleep(Duration::from_millis(1));
+        }
+        let first_time = clock.now();
+        // kept ticking by the tracer thread every write interval, even while idle
+        sleep(Duration::from_millis(20));
+        assert!(clock.now() > first_time);
+        tracer.annotate("foo");
+        let last_time = SystemTime::now();
+        tracer.disable().unwrap();
+        assert_eq!(clock.coarse_micros.load(Ordering::Relaxed), 0);
+
+        let reader = TraceReader::open_dir(&path).unwrap();
+        let events = reader
+            .iter()
+            .map(|event| event.unwrap())
+            .collect::<Vec<_>>();
         assert_matches!(
-            results[i],
-            Ok(TimedTracedEvent(
-                _,
-                TracedEvent::PacketBatch(ChannelLabel::NonVote, _)
-            ))
+            &events[..],
+            [TimedTracedEvent(time, TracedEvent::Annotation(text))]
+                if first_time < *time && *time <= last_time && text == "foo"
         );
-        i += 1;
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_tee_sink() {
+        let healthy_sink = VecSink::default();
+        let healthy_events = healthy_sink.events.clone();
+        let is_failing = Arc::new(AtomicBool::new(true));
+        let failing_sink = FailingSink {
+            is_failing: is_failing.clone(),
+            inner: VecSink::default(),
+        };
+        let failing_events = failing_sink.inner.events.clone();
+        let mut sink = TeeSink::new([
+            Box::new(healthy_sink) as Box<dyn TraceSink>,
+            Box::new(failing_sink),
+        ]);
+
+        let annotation =
+            |text: &str| TimedTracedEvent(SystemTime::now(), TracedEvent::Annotation(text.into()));
+        sink.write_event(&annotation("a")).unwrap();
+        sink.write_event(&annotation("b")).unwrap();
+        is_failing.store(false, Ordering::Relaxed);
+        sink.write_event(&annotation("c")).unwrap();
+        sink.flush().unwrap();
+
         assert_matches!(
-            results[i],
-            Ok(TimedTracedEvent(
-                _,
-                TracedEvent::BlockAndBankHash(4, actual_blockhash, actual_bank_hash)
-            )) if actual_blockhash == blockhash && actual_bank_hash == bank_hash
+            &healthy_events.lock().unwrap()[..],
+            [
+                TimedTracedEvent(_, TracedEvent::Annotation(a)),
+                TimedTracedEvent(_, TracedEvent::Annotation(b)),
+                TimedTracedEvent(_, TracedEvent::Annotation(c)),
+            ] if (a.as_str(), b.as_str(), c.as_str()) == ("a", "b", "c")
         );
-        i += 1;
         assert_matches!(
-            results[i],
-            Err(ref err) if matches!(
-                **err,
-                BincodeIoError(ref error) if error.kind() == UnexpectedEof
-            )
+            &failing_events.lock().unwrap()[..],
+            [
+                TimedTracedEvent(_, TracedEvent::TraceGap { dropped_count: 2, .. }),
+                TimedTracedEvent(_, TracedEvent::Annotation(c)),
+            ] if c == "c"
         );
+    }
+
+    #[test]
+    fn test_write_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let annotation =
+            |text: &str| TimedTracedEvent(SystemTime::now(), TracedEvent::Annotation(text.into()));
+
+        // each file can hold only a single group of coalesced events
+        let mut sink = RollingFileSink::with_rotate_threshold_size(&path, 1).unwrap();
+        sink.write_events(&[annotation("a"), annotation("b"), annotation("c")])
+            .unwrap();
+        assert_eq!(sink.rotation_count(), 0);
+        sink.write_event(&annotation("d")).unwrap();
+        sink.flush().unwrap();
+        assert_eq!(sink.rotation_count(), 1);
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert_eq!(reader.event_file_paths().len(), 2);
+        let annotations = reader
+            .iter()
+            .filter_map(|event| match event.unwrap() {
+                TimedTracedEvent(_, TracedEvent::Annotation(text)) => Some(text),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(annotations, vec!["a", "b", "c", "d"]);
+
+        // by default, the events are written one by one until failing
+        let is_failing = Arc::new(AtomicBool::new(false));
+        let mut sink = FailingSink {
+            is_failing: is_failing.clone(),
+            inner: VecSink::default(),
+        };
+        sink.write_events(&[annotation("a")]).unwrap();
+        is_failing.store(true, Ordering::Relaxed);
+        assert_matches!(sink.write_events(&[annotation("b")]), Err((0, _)));
+        assert_eq!(sink.inner.events.lock().unwrap().len(), 1);
 
         for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
     }
@@ -650,8 +5500,12 @@ mod tests {
         let path = temp_dir.path().join("banking-trace");
         const REALLY_SMALL_ROTATION_THRESHOLD: u64 = 1;
 
-        let mut file_appender =
-            BankingTracer::create_file_appender(&path, REALLY_SMALL_ROTATION_THRESHOLD).unwrap();
+        let mut file_appender = RollingFileSink::create_file_appender(
+            &path,
+            REALLY_SMALL_ROTATION_THRESHOLD,
+            &RollingFileSinkConfig::default(),
+        )
+        .unwrap();
         file_appender.write_all(b"foo").unwrap();
         file_appender.condition_mut().reset();
         file_appender.write_all(b"bar").unwrap();
@@ -676,17 +5530,23 @@ mod tests {
 
         let path = temp_dir.path().join("banking-trace");
 
-        let mut file_appender =
-            BankingTracer::create_file_appender(&path, TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD)
-                .unwrap();
+        let mut file_appender = RollingFileSink::create_file_appender(
+            &path,
+            TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD,
+            &RollingFileSinkConfig::default(),
+        )
+        .unwrap();
         // assume this is unclean write
         file_appender.write_all(b"f").unwrap();
         file_appender.flush().unwrap();
 
         // reopen while shadow-dropping the old tracer
-        let mut file_appender =
-            BankingTracer::create_file_appender(&path, TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD)
-                .unwrap();
+        let mut file_appender = RollingFileSink::create_file_appender(
+            &path,
+            TRACE_FILE_DEFAULT_ROTATE_BYTE_THRESHOLD,
+            &RollingFileSinkConfig::default(),
+        )
+        .unwrap();
         // new file won't be created as appender is lazy
         assert_eq!(
             [
@@ -721,4 +5581,615 @@ mod tests {
 
         for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
     }
+
+    /// Tests of what `TracedSender` traces, which is compiled out with `banking-trace-noop`.
+    #[cfg(not(feature = "banking-trace-noop"))]
+    mod traced_sender {
+        use {
+            super::*,
+            crate::banking_trace::reader::VoteCounts,
+            solana_perf::{packet::to_packet_batches, test_tx::test_tx},
+            solana_transaction_error::TransactionError,
+        };
+
+        #[test]
+        fn test_send_many() {
+            let (sender, receiver) = BankingTracer::channel_for_test();
+            sender.send_many(vec![]).unwrap();
+            assert!(receiver.is_empty());
+
+            let batches = vec![
+                for_test::sample_packet_batch(),
+                for_test::sample_packet_batch(),
+                for_test::sample_packet_batch(),
+            ];
+            let packet_batch_count = batches.iter().map(|batch| batch.len()).sum::<usize>();
+            sender.send_many(batches).unwrap();
+            assert_eq!(receiver.len(), 1);
+            assert_eq!(receiver.recv().unwrap().len(), packet_batch_count);
+        }
+
+        #[test]
+        fn test_record_and_restore() {
+            let temp_dir = TempDir::new().unwrap();
+            let path = temp_dir.path().join("banking-trace");
+            let exit = Arc::<AtomicBool>::default();
+            let (tracer, tracer_thread) =
+                BankingTracer::new(Some((&path, exit.clone(), DirByteLimit::MAX))).unwrap();
+            let (non_vote_sender, non_vote_receiver) = tracer.create_channel_non_vote();
+
+            let dummy_main_thread = thread::spawn(move || {
+                receiving_loop_with_minimized_sender_overhead::<_, TraceError, 0>(
+                    exit,
+                    non_vote_receiver,
+                    |_packet_batch| Ok(()),
+                )
+            });
+
+            non_vote_sender
+                .send(for_test::sample_packet_batch())
+                .unwrap();
+            non_vote_sender.trace_packets_dropped(3, PacketDropReason::Dedup);
+            // no event for nothing dropped
+            non_vote_sender.trace_packets_dropped(0, PacketDropReason::Dedup);
+            non_vote_sender.trace_sigverify_stats(5, 1, 3, Duration::from_secs(2));
+            let target = SocketAddr::from(([127, 0, 0, 1], 8001));
+            tracer.forwarded_event(ChannelLabel::NonVote, 2, Some(target));
+            let blockhash = Hash::from_str("B1ockhash1111111111111111111111111111111111").unwrap();
+            let bank_hash = Hash::from_str("BankHash11111111111111111111111111111111111").unwrap();
+            tracer.leader_slot_boundary_event(4, true);
+            let signatures = [Signature::from([1; 64]), Signature::from([2; 64])];
+            tracer.transaction_batch_scheduled_event(3, 7, signatures);
+            tracer.cost_limit_exceeded_event(3, 7, 4, [(1, CostLimit::Account)]);
+            tracer.transaction_batch_outcome_event(
+                3,
+                7,
+                vec![TransactionOutcome::Committed, TransactionOutcome::Retryable],
+                None,
+            );
+            tracer.transaction_results_event(
+                3,
+                7,
+                signatures
+                    .into_iter()
+                    .zip([Ok(()), Err(TransactionError::AccountInUse)]),
+            );
+            tracer.poh_tick_event(4, 320, true);
+            tracer.bank_frozen_event(4, 64, 2);
+            tracer.hash_event(4, &blockhash, &bank_hash);
+            tracer.leader_slot_boundary_event(4, false);
+            tracer.annotate("restarted RPC");
+            tracer.scheduler_task_event(
+                9,
+                SchedulerTaskState::Created {
+                    signature: signatures[0],
+                },
+            );
+            tracer.scheduler_task_event(9, SchedulerTaskState::Completed { committed: true });
+
+            for_test::terminate_tracer(
+                tracer,
+                tracer_thread,
+                dummy_main_thread,
+                non_vote_sender,
+                None,
+            );
+
+            let reader = TraceReader::open_dir(&path).unwrap();
+            // the channel depths are sampled by the tracer thread at its own pace
+            let results = reader
+                .iter()
+                .filter(|event| {
+                    !matches!(
+                        event,
+                        Ok(TimedTracedEvent(_, TracedEvent::ChannelDepths { .. }))
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let mut i = 0;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::PacketBatch(ChannelLabel::NonVote, _)
+                ))
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::PacketsDropped {
+                        label: ChannelLabel::NonVote,
+                        count: 3,
+                        reason: PacketDropReason::Dedup,
+                    }
+                ))
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::SigverifyStats {
+                        label: ChannelLabel::NonVote,
+                        verified: 5,
+                        failed: 1,
+                        dedup: 3,
+                        interval_us: 2_000_000,
+                    }
+                ))
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::Forwarded {
+                        label: ChannelLabel::NonVote,
+                        count: 2,
+                        target: Some(actual_target),
+                    }
+                )) if actual_target == target
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::LeaderSlotBoundary {
+                        slot: 4,
+                        start: true
+                    }
+                ))
+            );
+            i += 1;
+            assert_matches!(
+                &results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::TransactionBatchScheduled {
+                        worker_id: 3,
+                        batch_id: 7,
+                        signatures: actual_signatures,
+                    }
+                )) if actual_signatures[..] == signatures[..]
+            );
+            i += 1;
+            assert_matches!(
+                &results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::CostLimitExceeded {
+                        worker_id: 3,
+                        batch_id: 7,
+                        slot: 4,
+                        rejections,
+                    }
+                )) if rejections == &[(1, CostLimit::Account)]
+            );
+            i += 1;
+            assert_matches!(
+                &results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::TransactionBatchOutcome {
+                        worker_id: 3,
+                        batch_id: 7,
+                        outcomes,
+                        error: None,
+                    }
+                )) if outcomes == &[TransactionOutcome::Committed, TransactionOutcome::Retryable]
+            );
+            i += 1;
+            assert_matches!(
+                &results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::TransactionResults {
+                        worker_id: 3,
+                        batch_id: 7,
+                        results,
+                    }
+                )) if results == &[
+                    (signatures[0], Ok(())),
+                    (signatures[1], Err(TransactionError::AccountInUse)),
+                ]
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::PohTick {
+                        slot: 4,
+                        tick_height: 320,
+                        last_tick_in_slot: true,
+                    }
+                ))
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::BankFrozen {
+                        slot: 4,
+                        tick_height: 64,
+                        transaction_count: 2,
+                    }
+                ))
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::BlockAndBankHash(4, actual_blockhash, actual_bank_hash)
+                )) if actual_blockhash == blockhash && actual_bank_hash == bank_hash
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::LeaderSlotBoundary {
+                        slot: 4,
+                        start: false
+                    }
+                ))
+            );
+            i += 1;
+            assert_matches!(
+                &results[i],
+                Ok(TimedTracedEvent(_, TracedEvent::Annotation(text))) if text == "restarted RPC"
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::SchedulerTask {
+                        task_index: 9,
+                        state: SchedulerTaskState::Created { signature },
+                    }
+                )) if signature == signatures[0]
+            );
+            i += 1;
+            assert_matches!(
+                results[i],
+                Ok(TimedTracedEvent(
+                    _,
+                    TracedEvent::SchedulerTask {
+                        task_index: 9,
+                        state: SchedulerTaskState::Completed { committed: true },
+                    }
+                ))
+            );
+            i += 1;
+            assert_eq!(results.len(), i);
+
+            for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+        }
+
+        #[test]
+        fn test_vote_classification() {
+            let temp_dir = TempDir::new().unwrap();
+            let path = temp_dir.path().join("banking-trace");
+            let exit = Arc::<AtomicBool>::default();
+            let (tracer, tracer_thread) =
+                BankingTracer::new(Some((&path, exit.clone(), DirByteLimit::MAX))).unwrap();
+            let Channels {
+                tpu_vote_sender,
+                tpu_vote_receiver,
+                ..
+            } = tracer.create_channels(false);
+
+            let mut batches = to_packet_batches(&vec![test_tx(); 4], 10);
+            let mut packets = batches[0].iter_mut();
+            packets.next().unwrap().meta_mut().set_simple_vote(true);
+            packets.next().unwrap().meta_mut().set_discard(true);
+            tpu_vote_sender
+                .send(BankingPacketBatch::new(batches))
+                .unwrap();
+
+            drop((tracer, tpu_vote_sender, tpu_vote_receiver));
+            tracer_thread.unwrap().join().unwrap().unwrap();
+
+            let reader = TraceReader::open_dir(&path).unwrap();
+            assert_eq!(
+                reader.summarize().unwrap().votes,
+                BTreeMap::from([(
+                    ChannelLabel::TpuVote,
+                    VoteCounts {
+                        simple_votes: 1,
+                        others: 2,
+                    }
+                )])
+            );
+
+            for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+        }
+
+        #[test]
+        fn test_enable_and_disable() {
+            let tracer = BankingTracer::new_disabled();
+            let (sender, receiver) = tracer.create_channel_non_vote();
+            sender.send(for_test::sample_packet_batch()).unwrap();
+
+            let sink = VecSink::default();
+            let (events, flushed) = (sink.events.clone(), sink.flushed.clone());
+            tracer
+                .enable_with_sink(sink, Arc::<AtomicBool>::default())
+                .unwrap();
+            assert!(tracer.is_enabled());
+            sender.send(for_test::sample_packet_batch()).unwrap();
+            tracer.disable().unwrap();
+            assert!(!tracer.is_enabled());
+            assert!(flushed.load(Ordering::Relaxed));
+            sender.send(for_test::sample_packet_batch()).unwrap();
+
+            assert_eq!(receiver.len(), 3);
+            let batch_count = events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|TimedTracedEvent(_, event)| matches!(event, TracedEvent::PacketBatch(..)))
+                .count();
+            assert_eq!(batch_count, 1);
+        }
+
+        #[test]
+        fn test_serialize_in_sender() {
+            let temp_dir = TempDir::new().unwrap();
+            let path = temp_dir.path().join("banking-trace");
+            let tracer = BankingTracer::new_disabled();
+            assert!(!tracer.serialize_in_sender());
+            tracer.set_serialize_in_sender(true);
+            assert!(tracer.effective_config().serialize_in_sender);
+
+            let channels = tracer.create_channels(false);
+            let sink = RollingFileSink::new(&path, DirByteLimit::MAX).unwrap();
+            tracer
+                .enable_with_sink(sink, Arc::<AtomicBool>::default())
+                .unwrap();
+            let batch = for_test::sample_packet_batch();
+            channels.non_vote_sender.send(batch.clone()).unwrap();
+            tracer.annotate("foo");
+            tracer.disable().unwrap();
+
+            // written as is, along with the events serialized by the tracer thread
+            let reader = TraceReader::open_dir(&path).unwrap();
+            // the channel depths are sampled by the tracer thread at its own pace
+            let events = reader
+                .iter()
+                .map(|event| event.unwrap().1)
+                .filter(|event| !matches!(event, TracedEvent::ChannelDepths { .. }))
+                .collect::<Vec<_>>();
+            assert_matches!(
+                &events[..],
+                [
+                    TracedEvent::PacketBatch(ChannelLabel::NonVote, traced_batch),
+                    TracedEvent::Annotation(text),
+                ] if traced_batch.len() == batch.len() && text == "foo"
+            );
+
+            for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+        }
+
+        #[test]
+        fn test_mirror_into() {
+            let (sink, mirror_sink) = (VecSink::default(), VecSink::default());
+            let (events, mirror_events) = (sink.events.clone(), mirror_sink.events.clone());
+            let tracer = BankingTracer::new_disabled();
+            let channels = tracer.create_channels(false);
+            tracer
+                .enable_with_sink(sink, Arc::<AtomicBool>::default())
+                .unwrap();
+            let mirror = BankingTracer::new_disabled();
+            mirror
+                .enable_with_sink(mirror_sink, Arc::<AtomicBool>::default())
+                .unwrap();
+            mirror.set_label_traced(ChannelLabel::GossipVote, false);
+            tracer.mirror_into(&mirror);
+
+            for sender in [&channels.non_vote_sender, &channels.gossip_vote_sender] {
+                sender.send(for_test::sample_packet_batch()).unwrap();
+            }
+            tracer.annotate("foo");
+            // the mirrors of a mirror aren't followed
+            mirror.mirror_into(&tracer);
+            mirror.annotate("bar");
+            tracer.clear_mirrors();
+            tracer.annotate("baz");
+            tracer.disable().unwrap();
+            mirror.disable().unwrap();
+
+            let kinds = |events: &Arc<Mutex<Vec<TimedTracedEvent>>>| {
+                events
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter_map(|TimedTracedEvent(_, event)| match event {
+                        TracedEvent::PacketBatch(label, _) => Some(format!("{label:?}")),
+                        TracedEvent::Annotation(text) => Some(text.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                kinds(&events),
+                ["NonVote", "GossipVote", "foo", "bar", "baz"]
+            );
+            assert_eq!(kinds(&mirror_events), ["NonVote", "foo", "bar"]);
+        }
+
+        #[test]
+        fn test_set_label_traced() {
+            let sink = VecSink::default();
+            let events = sink.events.clone();
+            let (tracer, tracer_thread) =
+                BankingTracer::new_with_sink(sink, Arc::<AtomicBool>::default()).unwrap();
+            let channels = tracer.create_channels(false);
+            tracer.set_label_traced(ChannelLabel::GossipVote, false);
+            assert!(!tracer.is_label_traced(ChannelLabel::GossipVote));
+            assert!(tracer.is_label_traced(ChannelLabel::NonVote));
+            for sender in [&channels.non_vote_sender, &channels.gossip_vote_sender] {
+                sender.send(for_test::sample_packet_batch()).unwrap();
+            }
+            tracer.set_label_traced(ChannelLabel::GossipVote, true);
+            channels
+                .gossip_vote_sender
+                .send(for_test::sample_packet_batch())
+                .unwrap();
+            drop((tracer, channels));
+            tracer_thread.unwrap().join().unwrap().unwrap();
+
+            let labels = events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|TimedTracedEvent(_, event)| match event {
+                    TracedEvent::PacketBatch(label, _) => Some(*label),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(labels, [ChannelLabel::NonVote, ChannelLabel::GossipVote]);
+            assert_eq!(
+                "gossip-vote".parse::<ChannelLabel>().unwrap(),
+                ChannelLabel::GossipVote
+            );
+            assert!("dummy".parse::<ChannelLabel>().is_err());
+        }
+
+        #[test]
+        fn test_disk_space_watchdog() {
+            /// Reports `available_bytes` as the free space.
+            struct LowSpaceSink {
+                inner: VecSink,
+                available_bytes: Arc<AtomicU64>,
+            }
+
+            impl TraceSink for LowSpaceSink {
+                fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+                    self.inner.write_event(event)
+                }
+
+                fn flush(&mut self) -> Result<(), TraceError> {
+                    self.inner.flush()
+                }
+
+                fn available_bytes(&self) -> Option<u64> {
+                    Some(self.available_bytes.load(Ordering::Relaxed))
+                }
+            }
+
+            let sink = LowSpaceSink {
+                inner: VecSink::default(),
+                available_bytes: Arc::new(AtomicU64::new(50)),
+            };
+            let (events, available_bytes) =
+                (sink.inner.events.clone(), sink.available_bytes.clone());
+            let tracer = BankingTracer::new_disabled();
+            tracer.set_min_available_bytes(100);
+            let (sender, _receiver) = tracer.create_channel_non_vote();
+            tracer
+                .enable_with_sink(sink, Arc::<AtomicBool>::default())
+                .unwrap();
+            let wait_until = |is_suspended| {
+                while tracer.is_packet_tracing_suspended() != is_suspended {
+                    sleep(Duration::from_millis(10));
+                }
+            };
+            wait_until(true);
+            sender.send(for_test::sample_packet_batch()).unwrap();
+            tracer.annotate("foo");
+            available_bytes.store(200, Ordering::Relaxed);
+            wait_until(false);
+            sender.send(for_test::sample_packet_batch()).unwrap();
+            tracer.disable().unwrap();
+
+            let events = events.lock().unwrap();
+            let events = events
+                .iter()
+                .map(|TimedTracedEvent(_, event)| event)
+                // the channel depths are sampled by the tracer thread at its own pace
+                .filter(|event| {
+                    !matches!(
+                        event,
+                        TracedEvent::Heartbeat | TracedEvent::ChannelDepths { .. }
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_matches!(
+                &events[..],
+                [
+                    TracedEvent::PacketTracingSuspended {
+                        is_suspended: true,
+                        available_bytes: Some(50),
+                    },
+                    TracedEvent::Annotation(_),
+                    TracedEvent::PacketTracingSuspended {
+                        is_suspended: false,
+                        available_bytes: Some(200),
+                    },
+                    TracedEvent::PacketBatch(ChannelLabel::NonVote, _),
+                    ..
+                ]
+            );
+        }
+
+        #[test]
+        fn test_channel_depths() {
+            let temp_dir = TempDir::new().unwrap();
+            let path = temp_dir.path().join("banking-trace");
+            let exit = Arc::<AtomicBool>::default();
+            let (tracer, tracer_thread) =
+                BankingTracer::new(Some((&path, exit.clone(), DirByteLimit::MAX))).unwrap();
+            let Channels {
+                tpu_vote_sender,
+                tpu_vote_receiver,
+                ..
+            } = tracer.create_channels(false);
+
+            tpu_vote_sender
+                .send(for_test::sample_packet_batch())
+                .unwrap();
+            tpu_vote_sender
+                .send(for_test::sample_packet_batch())
+                .unwrap();
+            // let the tracer thread wake up at least once after sending
+            sleep(TRACE_FILE_DEFAULT_WRITE_INTERVAL * 3);
+            // the depths aren't sampled through receivers kept by the tracer thread
+            drop(tpu_vote_receiver);
+            assert!(tpu_vote_sender
+                .send(for_test::sample_packet_batch())
+                .is_err());
+
+            exit.store(true, Ordering::Relaxed);
+            tracer_thread.unwrap().join().unwrap().unwrap();
+            drop((tracer, tpu_vote_sender));
+
+            let reader = TraceReader::open_dir(&path).unwrap();
+            let depths = reader
+                .iter()
+                .filter_map(|event| match event.unwrap() {
+                    TimedTracedEvent(_, TracedEvent::ChannelDepths { depths }) => Some(depths),
+                    _ => None,
+                })
+                .last()
+                .unwrap();
+            assert_eq!(
+                depths,
+                vec![
+                    (ChannelLabel::NonVote, 0),
+                    (ChannelLabel::TpuVote, 2),
+                    (ChannelLabel::GossipVote, 0),
+                ]
+            );
+
+            for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+        }
+    }
 }
@@ -0,0 +1,248 @@
+//! A/B harness replaying the same trace into two banking stage configurations (e.g. unified
+//! scheduler vs. central scheduler) side by side and comparing the blocks produced by them.
+
+use {
+    super::{
+        reader::{CountsByLabel, TraceReader},
+        replayer::{BankingTraceReplayer, ReplayError, ReplayTarget},
+        TimedTracedEvent, TracedEvent,
+    },
+    solana_clock::Slot,
+    solana_entry::entry::Entry,
+    solana_ledger::blockstore::Blockstore,
+    solana_signature::Signature,
+    std::{collections::BTreeMap, ops::RangeInclusive, thread, time::SystemTime},
+};
+
+/// Transactions of a block produced by a replay in the scheduled order, along with the time when
+/// the block was frozen.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplayedBlock {
+    pub signatures: Vec<Signature>,
+    pub freeze_time: Option<SystemTime>,
+}
+
+impl ReplayedBlock {
+    pub fn from_entries(entries: &[Entry], freeze_time: Option<SystemTime>) -> Self {
+        let signatures = entries
+            .iter()
+            .flat_map(|entry| entry.transactions.iter())
+            .filter_map(|transaction| transaction.signatures.first().copied())
+            .collect();
+        Self {
+            signatures,
+            freeze_time,
+        }
+    }
+}
+
+pub type ReplayedBlocks = BTreeMap<Slot, ReplayedBlock>;
+
+/// Loads the blocks of `slots` from `blockstore`, taking the freeze times from the
+/// `BlockAndBankHash` events of `trace`, which is the one written while producing the blocks.
+/// Slots without any entries are skipped.
+pub fn load_replayed_blocks(
+    blockstore: &Blockstore,
+    trace: &TraceReader,
+    slots: RangeInclusive<Slot>,
+) -> Result<ReplayedBlocks, ReplayError> {
+    let mut freeze_times = BTreeMap::new();
+    for event in trace.iter() {
+        if let TimedTracedEvent(event_time, TracedEvent::BlockAndBankHash(slot, _, _)) = event? {
+            freeze_times.insert(slot, event_time);
+        }
+    }
+
+    let mut blocks = ReplayedBlocks::new();
+    for slot in slots {
+        let entries = blockstore.get_slot_entries(slot, 0)?;
+        if entries.is_empty() {
+            continue;
+        }
+        let block = ReplayedBlock::from_entries(&entries, freeze_times.get(&slot).copied());
+        blocks.insert(slot, block);
+    }
+    Ok(blocks)
+}
+
+/// Difference of the blocks of a slot produced by A and B. A block missing on either side is
+/// treated as empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotComparison {
+    pub slot: Slot,
+    /// The index of the first transaction scheduled differently; `None` if both blocks consist
+    /// of the same transactions in the same order.
+    pub first_order_divergence: Option<usize>,
+    /// The numbers of transactions of A and B.
+    pub transaction_counts: (usize, usize),
+    /// How much later B was frozen than A in milliseconds, if both were frozen.
+    pub freeze_time_delta_ms: Option<i64>,
+}
+
+impl SlotComparison {
+    pub fn is_identical(&self) -> bool {
+        self.first_order_divergence.is_none()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AbReport {
+    pub slots: Vec<SlotComparison>,
+}
+
+impl AbReport {
+    pub fn compare(a: &ReplayedBlocks, b: &ReplayedBlocks) -> Self {
+        let empty_block = ReplayedBlock::default();
+        let mut slots = a.keys().chain(b.keys()).copied().collect::<Vec<_>>();
+        slots.sort_unstable();
+        slots.dedup();
+
+        let slots = slots
+            .into_iter()
+            .map(|slot| {
+                let block_a = a.get(&slot).unwrap_or(&empty_block);
+                let block_b = b.get(&slot).unwrap_or(&empty_block);
+                let first_order_divergence = block_a
+                    .signatures
+                    .iter()
+                    .zip(&block_b.signatures)
+                    .position(|(signature_a, signature_b)| signature_a != signature_b)
+                    .or_else(|| {
+                        (block_a.signatures.len() != block_b.signatures.len())
+                            .then(|| block_a.signatures.len().min(block_b.signatures.len()))
+                    });
+                let freeze_time_delta_ms = block_a.freeze_time.zip(block_b.freeze_time).map(
+                    |(time_a, time_b)| match time_b.duration_since(time_a) {
+                        Ok(delta) => delta.as_millis() as i64,
+                        Err(err) => -(err.duration().as_millis() as i64),
+                    },
+                );
+
+                SlotComparison {
+                    slot,
+                    first_order_divergence,
+                    transaction_counts: (block_a.signatures.len(), block_b.signatures.len()),
+                    freeze_time_delta_ms,
+                }
+            })
+            .collect();
+
+        Self { slots }
+    }
+
+    pub fn divergences(&self) -> impl Iterator<Item = &SlotComparison> {
+        self.slots
+            .iter()
+            .filter(|comparison| !comparison.is_identical())
+    }
+
+    pub fn log(&self) {
+        for comparison in &self.slots {
+            info!("A/B replay comparison: {comparison:?}");
+        }
+        info!(
+            "A/B replay: {} out of {} slots diverged",
+            self.divergences().count(),
+            self.slots.len()
+        );
+    }
+}
+
+/// Drives two replayers of the same trace at the same time, each into a target backed by a
+/// differently configured banking stage. The produced blocks are then compared with
+/// `AbReport::compare()`.
+pub struct AbReplayHarness {
+    replayer_a: BankingTraceReplayer,
+    replayer_b: BankingTraceReplayer,
+}
+
+impl AbReplayHarness {
+    pub fn new(replayer_a: BankingTraceReplayer, replayer_b: BankingTraceReplayer) -> Self {
+        Self {
+            replayer_a,
+            replayer_b,
+        }
+    }
+
+    pub fn replay(
+        &mut self,
+        target_a: &(impl ReplayTarget + Sync),
+        target_b: &(impl ReplayTarget + Sync),
+    ) -> Result<(CountsByLabel, CountsByLabel), ReplayError> {
+        let Self {
+            replayer_a,
+            replayer_b,
+        } = self;
+        thread::scope(|scope| {
+            let thread_a = thread::Builder::new()
+                .name("solReplayA".into())
+                .spawn_scoped(scope, || replayer_a.replay(target_a))?;
+            let sent_b = replayer_b.replay(target_b)?;
+            let sent_a = thread_a.join().unwrap()?;
+            Ok((sent_a, sent_b))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration};
+
+    #[test]
+    fn test_compare() {
+        let [signature1, signature2, signature3] = [1, 2, 3].map(|n| Signature::from([n; 64]));
+        let base_time = SystemTime::now();
+        let block = |signatures: &[Signature], freeze_delay_ms| ReplayedBlock {
+            signatures: signatures.to_vec(),
+            freeze_time: Some(base_time + Duration::from_millis(freeze_delay_ms)),
+        };
+        let a = ReplayedBlocks::from([
+            (1, block(&[signature1, signature2], 400)),
+            (2, block(&[signature1, signature2], 800)),
+            (3, block(&[signature1], 1200)),
+        ]);
+        let b = ReplayedBlocks::from([
+            (1, block(&[signature1, signature2], 450)),
+            (2, block(&[signature2, signature1, signature3], 700)),
+            (4, block(&[signature3], 1600)),
+        ]);
+
+        let report = AbReport::compare(&a, &b);
+        assert_eq!(
+            report.slots,
+            vec![
+                SlotComparison {
+                    slot: 1,
+                    first_order_divergence: None,
+                    transaction_counts: (2, 2),
+                    freeze_time_delta_ms: Some(50),
+                },
+                SlotComparison {
+                    slot: 2,
+                    first_order_divergence: Some(0),
+                    transaction_counts: (2, 3),
+                    freeze_time_delta_ms: Some(-100),
+                },
+                SlotComparison {
+                    slot: 3,
+                    first_order_divergence: Some(0),
+                    transaction_counts: (1, 0),
+                    freeze_time_delta_ms: None,
+                },
+                SlotComparison {
+                    slot: 4,
+                    first_order_divergence: Some(0),
+                    transaction_counts: (0, 1),
+                    freeze_time_delta_ms: None,
+                },
+            ]
+        );
+        assert_eq!(
+            report
+                .divergences()
+                .map(|comparison| comparison.slot)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+    }
+}
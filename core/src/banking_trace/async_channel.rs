@@ -0,0 +1,159 @@
+//! Async (tokio) variants of the banking channels for downstream stages which can't block on
+//! `BankingPacketReceiver`. The batches are traced exactly as `TracedSender` does.
+//!
+//! Unlike the blocking channels, the depths of these channels aren't sampled into the trace as
+//! `ChannelDepths`.
+
+use {
+    super::{ActiveTracer, BankingTracer, ChannelLabel, PacketDropReason},
+    agave_banking_stage_ingress_types::BankingPacketBatch,
+    std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    },
+    tokio::sync::mpsc::{
+        error::{SendError, TryRecvError},
+        unbounded_channel, UnboundedReceiver, UnboundedSender,
+    },
+};
+
+pub type AsyncBankingPacketReceiver = UnboundedReceiver<BankingPacketBatch>;
+
+#[derive(Clone)]
+pub struct AsyncTracedSender {
+    label: ChannelLabel,
+    sender: UnboundedSender<BankingPacketBatch>,
+    active_tracer: Option<ActiveTracer>,
+}
+
+impl AsyncTracedSender {
+    /// Never blocks, so it can be called from both async and blocking contexts.
+    pub fn send(&self, batch: BankingPacketBatch) -> Result<(), SendError<BankingPacketBatch>> {
+        if let Some(active_tracer) = &self.active_tracer {
+            if active_tracer
+                .trace_packet_batch(self.label, &batch)
+                .is_err()
+            {
+                return Err(SendError(batch));
+            }
+        }
+        self.sender.send(batch)
+    }
+
+    /// Traces packets dropped before they were sent to this channel.
+    pub fn trace_packets_dropped(&self, count: usize, reason: PacketDropReason) {
+        if let Some(active_tracer) = &self.active_tracer {
+            if count > 0 {
+                active_tracer.trace_event(|| {
+                    super::packets_dropped_event(active_tracer.now(), self.label, count, reason)
+                });
+            }
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+impl BankingTracer {
+    pub fn create_async_channel(
+        &self,
+        label: ChannelLabel,
+    ) -> (AsyncTracedSender, AsyncBankingPacketReceiver) {
+        let (sender, receiver) = unbounded_channel();
+        (
+            AsyncTracedSender {
+                label,
+                sender,
+                active_tracer: Some(self.active_tracer.clone()),
+            },
+            receiver,
+        )
+    }
+
+    pub fn async_channel_for_test() -> (AsyncTracedSender, AsyncBankingPacketReceiver) {
+        Self::new_disabled().create_async_channel(ChannelLabel::Dummy)
+    }
+}
+
+/// Async version of `receiving_loop_with_minimized_sender_overhead()`. Likewise, the receiver
+/// is polled every `SLEEP_MS` once drained instead of being awaited, so that senders don't have
+/// to wake up the receiving task.
+pub async fn async_receiving_loop_with_minimized_sender_overhead<T, E, const SLEEP_MS: u64>(
+    exit: Arc<AtomicBool>,
+    mut receiver: UnboundedReceiver<T>,
+    mut on_recv: impl FnMut(T) -> Result<(), E>,
+) -> Result<(), E> {
+    'outer: while !exit.load(Ordering::Relaxed) {
+        'inner: loop {
+            match receiver.try_recv() {
+                Ok(message) => on_recv(message)?,
+                Err(TryRecvError::Empty) => break 'inner,
+                Err(TryRecvError::Disconnected) => {
+                    break 'outer;
+                }
+            };
+            if exit.load(Ordering::Relaxed) {
+                break 'outer;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(SLEEP_MS)).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+            TimedTracedEvent, TraceError, TracedEvent,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_async_channel() {
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = sink.recent_events();
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit.clone()).unwrap();
+        let (sender, receiver) = tracer.create_async_channel(ChannelLabel::NonVote);
+
+        sender.send(sample_packet_batch()).unwrap();
+        sender.send(sample_packet_batch()).unwrap();
+        drop(sender);
+        let mut received_count = 0;
+        async_receiving_loop_with_minimized_sender_overhead::<_, TraceError, 0>(
+            exit,
+            receiver,
+            |_batch| {
+                received_count += 1;
+                Ok(())
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(received_count, 2);
+
+        drop(tracer);
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        let batch_count = recent_events
+            .recent_events(&EventFilter::default())
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    TimedTracedEvent(_, TracedEvent::PacketBatch(ChannelLabel::NonVote, _))
+                )
+            })
+            .count();
+        assert_eq!(batch_count, 2);
+    }
+}
@@ -0,0 +1,121 @@
+//! Fan-out variant of the banking channels, where each of several independent consumers (e.g.
+//! the banking stage along with a mempool indexer) receives every batch. The batches are shared,
+//! not copied, and each send is traced as a single `PacketBatch` as `TracedSender` does.
+//!
+//! Like the async channels, the depths of these channels aren't sampled into the trace as
+//! `ChannelDepths`.
+
+use {
+    super::{ActiveTracer, BankingTracer, ChannelLabel},
+    agave_banking_stage_ingress_types::{BankingPacketBatch, BankingPacketReceiver},
+    crossbeam_channel::{unbounded, SendError, Sender},
+};
+
+pub struct BroadcastTracedSender {
+    label: ChannelLabel,
+    senders: Vec<Sender<BankingPacketBatch>>,
+    active_tracer: Option<ActiveTracer>,
+}
+
+impl BroadcastTracedSender {
+    /// Sends `batch` to all of the receivers which are still alive, so that a consumer going
+    /// away doesn't affect the others. Fails only once all of them are gone.
+    pub fn send(&self, batch: BankingPacketBatch) -> Result<(), SendError<BankingPacketBatch>> {
+        if let Some(active_tracer) = &self.active_tracer {
+            if active_tracer
+                .trace_packet_batch(self.label, &batch)
+                .is_err()
+            {
+                return Err(SendError(batch));
+            }
+        }
+        let sent_count = self
+            .senders
+            .iter()
+            .filter(|sender| sender.send(BankingPacketBatch::clone(&batch)).is_ok())
+            .count();
+        if sent_count == 0 {
+            return Err(SendError(batch));
+        }
+        Ok(())
+    }
+
+    /// The depth of the most backed up receiver.
+    pub fn max_len(&self) -> usize {
+        self.senders
+            .iter()
+            .map(|sender| sender.len())
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl BankingTracer {
+    /// Creates a channel of `label` with `receiver_count` receivers, each of which receives all
+    /// of the batches.
+    pub fn create_broadcast_channel(
+        &self,
+        label: ChannelLabel,
+        receiver_count: usize,
+    ) -> (BroadcastTracedSender, Vec<BankingPacketReceiver>) {
+        let (senders, receivers) = (0..receiver_count).map(|_| unbounded()).unzip();
+        (
+            BroadcastTracedSender {
+                label,
+                senders,
+                active_tracer: Some(self.active_tracer.clone()),
+            },
+            receivers,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+            TimedTracedEvent, TracedEvent,
+        },
+        std::{
+            sync::{atomic::AtomicBool, Arc},
+            time::Duration,
+        },
+    };
+
+    #[test]
+    fn test_broadcast() {
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = sink.recent_events();
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit).unwrap();
+        let (sender, mut receivers) = tracer.create_broadcast_channel(ChannelLabel::NonVote, 2);
+
+        let batch = sample_packet_batch();
+        sender.send(batch.clone()).unwrap();
+        for receiver in &receivers {
+            assert!(Arc::ptr_eq(&receiver.recv().unwrap(), &batch));
+        }
+        sender.send(sample_packet_batch()).unwrap();
+        assert_eq!(sender.max_len(), 1);
+
+        // the remaining consumer keeps receiving
+        let receiver = receivers.pop().unwrap();
+        drop(receivers);
+        sender.send(sample_packet_batch()).unwrap();
+        assert_eq!(receiver.len(), 2);
+        drop(receiver);
+        assert!(sender.send(sample_packet_batch()).is_err());
+
+        drop((sender, tracer));
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        let batch_count = recent_events
+            .recent_events(&EventFilter::default())
+            .iter()
+            .filter(|TimedTracedEvent(_, event)| matches!(event, TracedEvent::PacketBatch(..)))
+            .count();
+        assert_eq!(batch_count, 4);
+    }
+}
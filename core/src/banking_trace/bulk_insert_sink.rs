@@ -0,0 +1,429 @@
+//! `TraceSink` bulk-inserting summaries of trace events into PostgreSQL or ClickHouse for
+//! long-term retention, so that fleet-wide traces can be analyzed over weeks.
+
+use {
+    super::{TimedTracedEvent, TraceError, TraceSink},
+    crossbeam_channel::{bounded, Receiver, Sender, TrySendError},
+    serde::Serialize,
+    solana_clock::Slot,
+    std::{
+        error::Error,
+        fmt::Write as _,
+        io::{self, Write as _},
+        mem,
+        thread::{self, sleep, JoinHandle},
+        time::{Duration, Instant, UNIX_EPOCH},
+    },
+};
+
+pub const BULK_INSERT_DEFAULT_POOL_SIZE: usize = 4;
+pub const BULK_INSERT_DEFAULT_BATCH_SIZE: usize = 4096;
+// Batches waiting for a connection; beyond this, events fail to be written.
+const MAX_PENDING_BATCHES: usize = 64;
+// Bounds how stale the inserted rows can be while the trace is quiet.
+const MAX_BATCH_AGE: Duration = Duration::from_secs(1);
+const MAX_INSERT_ATTEMPTS: u32 = 5;
+const MIN_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+const TABLE_NAME: &str = "banking_trace_events";
+
+/// The columns of a row of `banking_trace_events`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct EventSummary {
+    pub timestamp_us: u64,
+    pub kind: &'static str,
+    pub label: Option<String>,
+    pub slot: Option<Slot>,
+}
+
+impl From<&TimedTracedEvent> for EventSummary {
+    fn from(event: &TimedTracedEvent) -> Self {
+        let TimedTracedEvent(time, traced_event) = event;
+        Self {
+            timestamp_us: time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros() as u64,
+            kind: traced_event.kind(),
+            label: traced_event.label().map(|label| format!("{label:?}")),
+            slot: traced_event.slot(),
+        }
+    }
+}
+
+/// Inserts batches of rows into a database over a connection of its own.
+pub trait BulkInserter: Send + 'static {
+    fn insert(&mut self, rows: &[EventSummary]) -> Result<(), Box<dyn Error>>;
+}
+
+/// Connects to PostgreSQL with `config` (e.g. `postgres://user@host/db`), creating the table if
+/// needed, and inserts with `COPY`.
+pub struct PostgresInserter {
+    config: String,
+    client: Option<postgres::Client>,
+}
+
+impl PostgresInserter {
+    pub fn new(config: impl Into<String>) -> Self {
+        Self {
+            config: config.into(),
+            client: None,
+        }
+    }
+
+    fn copy_data(rows: &[EventSummary]) -> String {
+        let mut data = String::new();
+        for EventSummary {
+            timestamp_us,
+            kind,
+            label,
+            slot,
+        } in rows
+        {
+            // writing into String never fails
+            let _ = writeln!(
+                data,
+                "{timestamp_us}\t{kind}\t{}\t{}",
+                label.as_deref().unwrap_or("\\N"),
+                slot.map_or_else(|| "\\N".into(), |slot| slot.to_string()),
+            );
+        }
+        data
+    }
+}
+
+impl BulkInserter for PostgresInserter {
+    fn insert(&mut self, rows: &[EventSummary]) -> Result<(), Box<dyn Error>> {
+        let client = match &mut self.client {
+            Some(client) => client,
+            client @ None => {
+                let mut new_client = postgres::Client::connect(&self.config, postgres::NoTls)?;
+                new_client.batch_execute(&format!(
+                    "CREATE TABLE IF NOT EXISTS {TABLE_NAME} (timestamp_us BIGINT NOT NULL, \
+                     kind TEXT NOT NULL, label TEXT, slot BIGINT)"
+                ))?;
+                client.insert(new_client)
+            }
+        };
+        let result = client
+            .copy_in(&format!(
+                "COPY {TABLE_NAME} (timestamp_us, kind, label, slot) FROM STDIN"
+            ))
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|mut writer| {
+                writer.write_all(Self::copy_data(rows).as_bytes())?;
+                writer.finish()?;
+                Ok(())
+            });
+        if result.is_err() {
+            // reconnect on the next attempt
+            self.client = None;
+        }
+        result
+    }
+}
+
+/// Inserts into ClickHouse over its HTTP interface at `url` (e.g. `http://host:8123`), creating
+/// the table if needed.
+pub struct ClickHouseInserter {
+    url: String,
+    client: reqwest::blocking::Client,
+    is_table_created: bool,
+}
+
+impl ClickHouseInserter {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::blocking::Client::new(),
+            is_table_created: false,
+        }
+    }
+
+    fn execute(&self, query: &str, body: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        self.client
+            .post(&self.url)
+            .query(&[("query", query)])
+            .body(body)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    fn json_rows(rows: &[EventSummary]) -> serde_json::Result<Vec<u8>> {
+        let mut body = vec![];
+        for row in rows {
+            serde_json::to_writer(&mut body, row)?;
+            body.push(b'\n');
+        }
+        Ok(body)
+    }
+}
+
+impl BulkInserter for ClickHouseInserter {
+    fn insert(&mut self, rows: &[EventSummary]) -> Result<(), Box<dyn Error>> {
+        if !self.is_table_created {
+            self.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {TABLE_NAME} (timestamp_us UInt64, kind \
+                     LowCardinality(String), label LowCardinality(Nullable(String)), slot \
+                     Nullable(UInt64)) ENGINE = MergeTree ORDER BY timestamp_us"
+                ),
+                vec![],
+            )?;
+            self.is_table_created = true;
+        }
+        self.execute(
+            &format!("INSERT INTO {TABLE_NAME} FORMAT JSONEachRow"),
+            Self::json_rows(rows)?,
+        )
+    }
+}
+
+/// Batches the summaries of events and inserts the batches from a pool of worker threads, each
+/// with its own connection. Failed inserts are retried with exponential backoff, reconnecting;
+/// batches which still fail are dropped.
+///
+/// Batches are queued for the workers up to `MAX_PENDING_BATCHES`. Once the workers fall that
+/// far behind, events fail to be written, so that they're traced as a `TraceGap` once the
+/// database catches up.
+pub struct BulkInsertSink {
+    sender: Option<Sender<Vec<EventSummary>>>,
+    batch: Vec<EventSummary>,
+    batch_size: usize,
+    batch_start_time: Instant,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl BulkInsertSink {
+    /// Starts a worker thread for each of `inserters`.
+    pub fn new(
+        inserters: impl IntoIterator<Item = Box<dyn BulkInserter>>,
+        batch_size: usize,
+    ) -> Result<Self, TraceError> {
+        let (sender, receiver) = bounded(MAX_PENDING_BATCHES);
+        let workers = inserters
+            .into_iter()
+            .enumerate()
+            .map(|(i, inserter)| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("solBnkTrcIns{i:02}"))
+                    .spawn(move || Self::insert_batches(inserter, receiver))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            sender: Some(sender),
+            batch: Vec::with_capacity(batch_size),
+            batch_size,
+            batch_start_time: Instant::now(),
+            workers,
+        })
+    }
+
+    /// Inserts into PostgreSQL with the default pool and batch sizes.
+    pub fn postgres(config: &str) -> Result<Self, TraceError> {
+        Self::new(
+            (0..BULK_INSERT_DEFAULT_POOL_SIZE)
+                .map(|_| Box::new(PostgresInserter::new(config)) as Box<dyn BulkInserter>),
+            BULK_INSERT_DEFAULT_BATCH_SIZE,
+        )
+    }
+
+    /// Inserts into ClickHouse with the default pool and batch sizes.
+    pub fn clickhouse(url: &str) -> Result<Self, TraceError> {
+        Self::new(
+            (0..BULK_INSERT_DEFAULT_POOL_SIZE)
+                .map(|_| Box::new(ClickHouseInserter::new(url)) as Box<dyn BulkInserter>),
+            BULK_INSERT_DEFAULT_BATCH_SIZE,
+        )
+    }
+
+    fn insert_batches(mut inserter: Box<dyn BulkInserter>, receiver: Receiver<Vec<EventSummary>>) {
+        for batch in receiver {
+            let mut retry_interval = MIN_RETRY_INTERVAL;
+            for attempt in 1..=MAX_INSERT_ATTEMPTS {
+                match inserter.insert(&batch) {
+                    Ok(()) => break,
+                    Err(err) if attempt < MAX_INSERT_ATTEMPTS => {
+                        debug!("failed to insert banking trace events (attempt {attempt}): {err}");
+                        sleep(retry_interval);
+                        retry_interval *= 2;
+                    }
+                    Err(err) => warn!(
+                        "dropped {} banking trace events which failed to be inserted: {err}",
+                        batch.len()
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Hands the current batch to the workers unless they're too far behind.
+    fn send_batch(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        let batch = mem::replace(&mut self.batch, Vec::with_capacity(self.batch_size));
+        match self.sender.as_ref().unwrap().try_send(batch) {
+            Ok(()) => self.batch_start_time = Instant::now(),
+            Err(TrySendError::Full(batch) | TrySendError::Disconnected(batch)) => {
+                self.batch = batch;
+            }
+        }
+    }
+}
+
+impl TraceSink for BulkInsertSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        if self.batch.len() >= self.batch_size || self.batch_start_time.elapsed() >= MAX_BATCH_AGE {
+            self.send_batch();
+        }
+        if self.batch.len() >= self.batch_size {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("{MAX_PENDING_BATCHES} batches are pending to be inserted"),
+            )
+            .into());
+        }
+        if self.batch.is_empty() {
+            self.batch_start_time = Instant::now();
+        }
+        self.batch.push(EventSummary::from(event));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.send_batch();
+        Ok(())
+    }
+}
+
+impl Drop for BulkInsertSink {
+    fn drop(&mut self) {
+        let batch = mem::take(&mut self.batch);
+        let sender = self.sender.take().unwrap();
+        if !batch.is_empty() && sender.send(batch).is_err() {
+            warn!("banking trace insert workers have gone");
+        }
+        // let the workers finish the pending batches
+        drop(sender);
+        for worker in self.workers.drain(..) {
+            if let Err(err) = worker.join() {
+                warn!("banking trace insert worker panicked: {err:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{ChannelLabel, PacketDropReason, TracedEvent},
+        std::{
+            sync::{Arc, Mutex},
+            time::SystemTime,
+        },
+    };
+
+    fn heartbeat() -> TimedTracedEvent {
+        TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat)
+    }
+
+    #[derive(Default, Clone)]
+    struct VecInserter {
+        batches: Arc<Mutex<Vec<Vec<EventSummary>>>>,
+        remaining_failures: Arc<Mutex<u32>>,
+        blocker: Option<Arc<Mutex<()>>>,
+    }
+
+    impl BulkInserter for VecInserter {
+        fn insert(&mut self, rows: &[EventSummary]) -> Result<(), Box<dyn Error>> {
+            let _blocked = self.blocker.as_ref().map(|blocker| blocker.lock().unwrap());
+            let mut remaining_failures = self.remaining_failures.lock().unwrap();
+            if *remaining_failures > 0 {
+                *remaining_failures -= 1;
+                return Err("unavailable".into());
+            }
+            self.batches.lock().unwrap().push(rows.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_batches_with_retry() {
+        let inserter = VecInserter {
+            remaining_failures: Arc::new(Mutex::new(2)),
+            ..VecInserter::default()
+        };
+        let mut sink = BulkInsertSink::new([Box::new(inserter.clone()) as _], 2).unwrap();
+        for _ in 0..5 {
+            sink.write_event(&heartbeat()).unwrap();
+        }
+        drop(sink);
+
+        let batch_sizes = inserter
+            .batches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(Vec::len)
+            .collect::<Vec<_>>();
+        assert_eq!(batch_sizes, [2, 2, 1]);
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let blocker = Arc::new(Mutex::new(()));
+        let inserter = VecInserter {
+            blocker: Some(blocker.clone()),
+            ..VecInserter::default()
+        };
+        let blocked = blocker.lock().unwrap();
+        let mut sink = BulkInsertSink::new([Box::new(inserter.clone()) as _], 1).unwrap();
+        // one batch is taken by the blocked worker, the others are pending
+        let mut written_count = 0;
+        let err = loop {
+            match sink.write_event(&heartbeat()) {
+                Ok(()) => written_count += 1,
+                Err(err) => break err,
+            }
+        };
+        assert_matches!(err, TraceError::IoError(err) if err.kind() == io::ErrorKind::WouldBlock);
+        assert!((MAX_PENDING_BATCHES + 1..=MAX_PENDING_BATCHES + 2).contains(&written_count));
+        drop(blocked);
+        drop(sink);
+        assert_eq!(inserter.batches.lock().unwrap().len(), written_count);
+    }
+
+    #[test]
+    fn test_row_formats() {
+        let rows = [
+            EventSummary::from(&TimedTracedEvent(
+                UNIX_EPOCH + Duration::from_micros(1),
+                TracedEvent::PacketsDropped {
+                    label: ChannelLabel::TpuVote,
+                    count: 1,
+                    reason: PacketDropReason::Dedup,
+                },
+            )),
+            EventSummary::from(&TimedTracedEvent(
+                UNIX_EPOCH + Duration::from_micros(2),
+                TracedEvent::BankFrozen {
+                    slot: 5,
+                    tick_height: 64,
+                    transaction_count: 0,
+                },
+            )),
+        ];
+        assert_eq!(
+            PostgresInserter::copy_data(&rows),
+            "1\tPacketsDropped\tTpuVote\t\\N\n2\tBankFrozen\t\\N\t5\n"
+        );
+        assert_eq!(
+            String::from_utf8(ClickHouseInserter::json_rows(&rows).unwrap()).unwrap(),
+            "{\"timestamp_us\":1,\"kind\":\"PacketsDropped\",\"label\":\"TpuVote\",\"slot\":null}\n\
+             {\"timestamp_us\":2,\"kind\":\"BankFrozen\",\"label\":null,\"slot\":5}\n"
+        );
+    }
+}
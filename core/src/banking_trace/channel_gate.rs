@@ -0,0 +1,229 @@
+//! Pausing of the banking channels created together by `BankingTracer::create_channels()`, so
+//! that snapshot or restart sequences can drain the banking stage deterministically. While
+//! paused, sends succeed as usual (including tracing), but the batches are held back at the
+//! senders instead of reaching the consumers, until resumed.
+#![cfg_attr(feature = "banking-trace-noop", allow(dead_code))]
+
+use {
+    agave_banking_stage_ingress_types::{BankingPacketBatch, BankingPacketReceiver},
+    crossbeam_channel::{SendError, Sender, TrySendError},
+    std::{
+        collections::VecDeque,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Mutex,
+        },
+    },
+};
+
+pub const CHANNEL_GATE_DEFAULT_HELD_BATCH_LIMIT: usize = 100_000;
+
+/// A batch held back along with where to deliver it.
+struct HeldBatch {
+    sender: Sender<BankingPacketBatch>,
+    /// Set only for senders with `OverflowPolicy::DropOldest`.
+    evicting_receiver: Option<BankingPacketReceiver>,
+    batch: BankingPacketBatch,
+}
+
+/// Shared by all the senders of a set of channels.
+pub struct ChannelGate {
+    /// Checked before anything else, so that sends don't touch the lock unless paused. Only
+    /// changed with `held_batches` locked.
+    paused: AtomicBool,
+    /// The batches held back since paused, in the order they were sent.
+    held_batches: Mutex<VecDeque<HeldBatch>>,
+    /// Beyond this, the oldest held back batches are dropped.
+    held_batch_limit: usize,
+    dropped_batches: AtomicU64,
+}
+
+impl Default for ChannelGate {
+    fn default() -> Self {
+        Self::new(CHANNEL_GATE_DEFAULT_HELD_BATCH_LIMIT)
+    }
+}
+
+impl std::fmt::Debug for ChannelGate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChannelGate")
+            .field("paused", &self.is_paused())
+            .field("held_batch_limit", &self.held_batch_limit)
+            .field("dropped_batches", &self.dropped_batch_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChannelGate {
+    /// Holds back up to `held_batch_limit` batches while paused, dropping the oldest beyond it.
+    pub fn new(held_batch_limit: usize) -> Self {
+        Self {
+            paused: AtomicBool::default(),
+            held_batches: Mutex::default(),
+            held_batch_limit: held_batch_limit.max(1),
+            dropped_batches: AtomicU64::default(),
+        }
+    }
+
+    /// Once this returns, no more batches reach the consumers until resumed, but the ones of
+    /// the sends in flight.
+    pub fn pause(&self) {
+        let _held_batches = self.held_batches.lock().unwrap();
+        self.paused.store(true, Ordering::Release);
+    }
+
+    /// Delivers the held back batches in the order they were sent, returning their count. They
+    /// are delivered as the senders would have: evicting the oldest from a full channel with
+    /// `OverflowPolicy::DropOldest`, and blocking otherwise. Batches to disconnected channels
+    /// are discarded, and the ones sent meanwhile may overtake those still being delivered.
+    pub fn resume(&self) -> usize {
+        let held_batches = {
+            let mut held_batches = self.held_batches.lock().unwrap();
+            self.paused.store(false, Ordering::Release);
+            std::mem::take(&mut *held_batches)
+        };
+        let count = held_batches.len();
+        for HeldBatch {
+            sender,
+            evicting_receiver,
+            batch,
+        } in held_batches
+        {
+            let _ = match &evicting_receiver {
+                None => sender.send(batch),
+                Some(evicting_receiver) => {
+                    send_evicting_oldest(&sender, evicting_receiver, batch, |_evicted| {
+                        self.dropped_batches.fetch_add(1, Ordering::Relaxed);
+                    })
+                }
+            };
+        }
+        count
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// The number of held back batches dropped so far, beyond the limit or evicted on resume.
+    pub fn dropped_batch_count(&self) -> u64 {
+        self.dropped_batches.load(Ordering::Relaxed)
+    }
+
+    /// Sends `batch` with `send` unless paused, in which case it's held back for `sender`
+    /// (with its `evicting_receiver`, if any). No lock is held while sending.
+    pub(crate) fn pass(
+        &self,
+        sender: &Sender<BankingPacketBatch>,
+        evicting_receiver: Option<&BankingPacketReceiver>,
+        batch: BankingPacketBatch,
+        send: impl FnOnce(BankingPacketBatch) -> Result<(), SendError<BankingPacketBatch>>,
+    ) -> Result<(), SendError<BankingPacketBatch>> {
+        if self.is_paused() {
+            let mut held_batches = self.held_batches.lock().unwrap();
+            // otherwise, resumed in the meantime
+            if self.paused.load(Ordering::Relaxed) {
+                if held_batches.len() >= self.held_batch_limit {
+                    held_batches.pop_front();
+                    self.dropped_batches.fetch_add(1, Ordering::Relaxed);
+                }
+                held_batches.push_back(HeldBatch {
+                    sender: sender.clone(),
+                    evicting_receiver: evicting_receiver.cloned(),
+                    batch,
+                });
+                return Ok(());
+            }
+        }
+        send(batch)
+    }
+}
+
+/// Sends `batch` to the bounded channel of `sender`, evicting the oldest batches with
+/// `evicting_receiver` while it's full. `on_evicted` is called with each evicted batch.
+pub(crate) fn send_evicting_oldest(
+    sender: &Sender<BankingPacketBatch>,
+    evicting_receiver: &BankingPacketReceiver,
+    mut batch: BankingPacketBatch,
+    mut on_evicted: impl FnMut(BankingPacketBatch),
+) -> Result<(), SendError<BankingPacketBatch>> {
+    loop {
+        match sender.try_send(batch) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Disconnected(batch)) => return Err(SendError(batch)),
+            Err(TrySendError::Full(full_batch)) => {
+                batch = full_batch;
+                // the receiver may have made room in the meantime; just retry then
+                if let Ok(evicted) = evicting_receiver.try_recv() {
+                    on_evicted(evicted);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "banking-trace-noop")))]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch, BankingTracer, ChannelLabel, OverflowPolicy,
+        },
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_pause_and_resume() {
+        let channels = BankingTracer::new_disabled().create_channels(false);
+        let batches = (0..3).map(|_| sample_packet_batch()).collect::<Vec<_>>();
+        channels.non_vote_sender.send(batches[0].clone()).unwrap();
+
+        channels.pause();
+        assert!(channels.tpu_vote_sender.channel_gate().is_paused());
+        channels.tpu_vote_sender.send(batches[1].clone()).unwrap();
+        channels.non_vote_sender.send(batches[2].clone()).unwrap();
+        assert_eq!(channels.non_vote_receiver.len(), 1);
+        assert!(channels.tpu_vote_receiver.is_empty());
+
+        assert_eq!(channels.resume(), 2);
+        assert!(!channels.non_vote_sender.channel_gate().is_paused());
+        for (receiver, expected) in [
+            (&channels.non_vote_receiver, &batches[0]),
+            (&channels.tpu_vote_receiver, &batches[1]),
+            (&channels.non_vote_receiver, &batches[2]),
+        ] {
+            assert!(Arc::ptr_eq(&receiver.try_recv().unwrap(), expected));
+        }
+    }
+
+    #[test]
+    fn test_held_batch_limit_and_drop_oldest_on_resume() {
+        let gate = ChannelGate::new(2);
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let batches = (0..3).map(|_| sample_packet_batch()).collect::<Vec<_>>();
+        gate.pause();
+        for batch in &batches {
+            gate.pass(&sender, Some(&receiver), batch.clone(), |_| unreachable!())
+                .unwrap();
+        }
+        assert_eq!(gate.dropped_batch_count(), 1);
+
+        // the second one is evicted by the third one instead of blocking
+        assert_eq!(gate.resume(), 2);
+        assert_eq!(gate.dropped_batch_count(), 2);
+        assert!(Arc::ptr_eq(&receiver.try_recv().unwrap(), &batches[2]));
+
+        let (sender, receiver) = BankingTracer::new_disabled().create_bounded_channel(
+            ChannelLabel::NonVote,
+            1,
+            OverflowPolicy::DropOldest,
+        );
+        let sender = sender.with_channel_gate(Arc::new(gate));
+        sender.channel_gate().pause();
+        sender.send(batches[0].clone()).unwrap();
+        sender.send(batches[1].clone()).unwrap();
+        assert!(receiver.is_empty());
+        assert_eq!(sender.channel_gate().resume(), 2);
+        assert!(Arc::ptr_eq(&receiver.try_recv().unwrap(), &batches[1]));
+    }
+}
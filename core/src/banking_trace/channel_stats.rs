@@ -0,0 +1,236 @@
+//! Counters of the banking channels, updated by `TracedSender`s so that operators can see which
+//! ingress lane is backed up in real time. The sends and the depths are only sampled while
+//! tracing is enabled, keeping the sends with tracing disabled as cheap as the plain channel,
+//! while the evictions and the throttles are always counted.
+#![cfg_attr(feature = "banking-trace-noop", allow(dead_code))]
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Default)]
+pub(crate) struct ChannelStats {
+    sent_batches: AtomicU64,
+    sent_packets: AtomicU64,
+    depth: AtomicU64,
+    max_depth: AtomicU64,
+    evicted_batches: AtomicU64,
+    evicted_packets: AtomicU64,
+    throttled_batches: AtomicU64,
+    throttled_packets: AtomicU64,
+}
+
+impl ChannelStats {
+    /// Records a batch of `packet_count` packets just sent, after which the channel held `depth`
+    /// batches.
+    pub(crate) fn record_send(&self, packet_count: usize, depth: usize) {
+        self.sent_batches.fetch_add(1, Ordering::Relaxed);
+        self.sent_packets
+            .fetch_add(packet_count as u64, Ordering::Relaxed);
+        self.depth.store(depth as u64, Ordering::Relaxed);
+        self.max_depth.fetch_max(depth as u64, Ordering::Relaxed);
+    }
+
+    /// Records a batch of `packet_count` packets evicted to make room for a send.
+    pub(crate) fn record_eviction(&self, packet_count: usize) {
+        self.evicted_batches.fetch_add(1, Ordering::Relaxed);
+        self.evicted_packets
+            .fetch_add(packet_count as u64, Ordering::Relaxed);
+    }
+
+    /// Records a batch of `packet_count` packets dropped for being over the rate limit.
+    pub(crate) fn record_throttle(&self, packet_count: usize) {
+        self.throttled_batches.fetch_add(1, Ordering::Relaxed);
+        self.throttled_packets
+            .fetch_add(packet_count as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> ChannelStatsSnapshot {
+        ChannelStatsSnapshot {
+            sent_batches: self.sent_batches.load(Ordering::Relaxed),
+            sent_packets: self.sent_packets.load(Ordering::Relaxed),
+            depth: self.depth.load(Ordering::Relaxed),
+            max_depth: self.max_depth.load(Ordering::Relaxed),
+            evicted_batches: self.evicted_batches.load(Ordering::Relaxed),
+            evicted_packets: self.evicted_packets.load(Ordering::Relaxed),
+            throttled_batches: self.throttled_batches.load(Ordering::Relaxed),
+            throttled_packets: self.throttled_packets.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time copy of the counters of a channel since its creation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelStatsSnapshot {
+    pub sent_batches: u64,
+    pub sent_packets: u64,
+    /// The number of batches buffered in the channel. It's sampled at the last send while
+    /// tracing, unless the snapshot is taken by `TracedSender::stats()`, which samples it live.
+    pub depth: u64,
+    /// The maximum of the depths sampled at sends while tracing.
+    pub max_depth: u64,
+    /// Batches evicted from a full channel with `OverflowPolicy::DropOldest`.
+    pub evicted_batches: u64,
+    pub evicted_packets: u64,
+    /// Batches dropped for being over the `RateLimit` of the sender.
+    pub throttled_batches: u64,
+    pub throttled_packets: u64,
+}
+
+#[cfg(all(test, not(feature = "banking-trace-noop")))]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+            BankingTracer, ChannelLabel, OverflowPolicy, PacketDropReason, TimedTracedEvent,
+            TracedEvent, TracerThread,
+        },
+        std::{
+            sync::{atomic::AtomicBool, Arc},
+            time::Duration,
+        },
+    };
+
+    fn new_tracer() -> (Arc<BankingTracer>, TracerThread) {
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        BankingTracer::new_with_sink(sink, Arc::<AtomicBool>::default()).unwrap()
+    }
+
+    #[test]
+    fn test_channel_stats() {
+        let (tracer, tracer_thread) = new_tracer();
+        let channels = tracer.create_channels(false);
+        let packet_count = sample_packet_batch()
+            .iter()
+            .map(|batch| batch.len() as u64)
+            .sum::<u64>();
+
+        for _ in 0..3 {
+            channels
+                .tpu_vote_sender
+                .send(sample_packet_batch())
+                .unwrap();
+        }
+        channels.tpu_vote_receiver.recv().unwrap();
+        channels.tpu_vote_receiver.recv().unwrap();
+        channels
+            .tpu_vote_sender
+            .send(sample_packet_batch())
+            .unwrap();
+
+        let expected = ChannelStatsSnapshot {
+            sent_batches: 4,
+            sent_packets: packet_count * 4,
+            depth: 2,
+            max_depth: 3,
+            ..ChannelStatsSnapshot::default()
+        };
+        assert_eq!(channels.tpu_vote_sender.stats(), expected);
+        assert_eq!(
+            tracer.channel_stats(),
+            [
+                (ChannelLabel::NonVote, ChannelStatsSnapshot::default()),
+                (ChannelLabel::TpuVote, expected),
+                (ChannelLabel::GossipVote, ChannelStatsSnapshot::default()),
+            ]
+        );
+
+        channels.tpu_vote_receiver.recv().unwrap();
+        assert_eq!(
+            channels.tpu_vote_sender.stats(),
+            ChannelStatsSnapshot {
+                depth: 1,
+                ..expected
+            }
+        );
+
+        drop((channels, tracer));
+        tracer_thread.unwrap().join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_channel_stats_disabled() {
+        let tracer = BankingTracer::new_disabled();
+        let channels = tracer.create_channels(false);
+        channels
+            .tpu_vote_sender
+            .send(sample_packet_batch())
+            .unwrap();
+
+        assert_eq!(channels.tpu_vote_receiver.len(), 1);
+        assert_eq!(
+            channels.tpu_vote_sender.stats(),
+            ChannelStatsSnapshot {
+                depth: 1,
+                ..ChannelStatsSnapshot::default()
+            }
+        );
+        assert!(tracer
+            .channel_stats()
+            .iter()
+            .all(|(_label, stats)| *stats == ChannelStatsSnapshot::default()));
+    }
+
+    #[test]
+    fn test_unified_channel_stats() {
+        let (tracer, tracer_thread) = new_tracer();
+        let channels = tracer.create_channels(true);
+        channels
+            .tpu_vote_sender
+            .send(sample_packet_batch())
+            .unwrap();
+        channels
+            .gossip_vote_sender
+            .send(sample_packet_batch())
+            .unwrap();
+
+        let stats = tracer.channel_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].1.sent_batches, stats[0].1.depth), (2, 2));
+        assert_eq!(channels.gossip_vote_sender.stats(), stats[0].1);
+
+        drop((channels, tracer));
+        tracer_thread.unwrap().join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = sink.recent_events();
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit).unwrap();
+        let (sender, receiver) =
+            tracer.create_bounded_channel(ChannelLabel::NonVote, 2, OverflowPolicy::DropOldest);
+        let batches = (0..3).map(|_| sample_packet_batch()).collect::<Vec<_>>();
+        for batch in &batches {
+            sender.send(batch.clone()).unwrap();
+        }
+        assert!(Arc::ptr_eq(&receiver.recv().unwrap(), &batches[1]));
+        assert!(Arc::ptr_eq(&receiver.recv().unwrap(), &batches[2]));
+        let packet_count = batches[0]
+            .iter()
+            .map(|batch| batch.len() as u64)
+            .sum::<u64>();
+        let stats = sender.stats();
+        assert_eq!(
+            (
+                stats.sent_batches,
+                stats.evicted_batches,
+                stats.evicted_packets
+            ),
+            (3, 1, packet_count)
+        );
+
+        drop((sender, receiver, tracer));
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        let drops = recent_events
+            .recent_events(&EventFilter::default())
+            .into_iter()
+            .filter_map(|TimedTracedEvent(_, event)| match event {
+                TracedEvent::PacketsDropped { count, reason, .. } => Some((count, reason)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(drops, [(packet_count, PacketDropReason::ChannelOverflow)]);
+    }
+}
@@ -0,0 +1,334 @@
+//! Conversion of trace files into the Chrome trace event format, so that traces can be explored
+//! visually in `ui.perfetto.dev` or `chrome://tracing`.
+
+use {
+    super::{
+        reader::{TraceCounts, TraceReader},
+        ChannelLabel, TimedTracedEvent, TraceError, TracedEvent, TransactionOutcome,
+    },
+    serde_json::{json, Value},
+    std::{
+        collections::BTreeSet,
+        io::{self, Write},
+        time::UNIX_EPOCH,
+    },
+};
+
+const PID: u32 = 1;
+
+// Threads of the trace viewer, which events are laid out in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Lane {
+    LeaderSlots,
+    Channel(ChannelLabel),
+    Worker(u32),
+    Other,
+}
+
+impl Lane {
+    // Also orders the lanes in the viewer.
+    fn tid(&self) -> u64 {
+        match self {
+            Self::LeaderSlots => 1,
+            Self::Channel(label) => 10 + *label as u64,
+            Self::Worker(worker_id) => 100 + u64::from(*worker_id),
+            Self::Other => 1000,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::LeaderSlots => "leader slots".into(),
+            Self::Channel(label) => format!("{label:?}"),
+            Self::Worker(worker_id) => format!("worker {worker_id}"),
+            Self::Other => "other".into(),
+        }
+    }
+}
+
+/// Writes trace events as a JSON object of the Chrome trace event format, with lanes for:
+///
+/// * leader slots, as spans from `LeaderSlotBoundary` to `LeaderSlotBoundary`,
+/// * each `ChannelLabel`, with the events about the channel as instants,
+/// * each banking worker, with its transaction batches as spans from being scheduled to their
+///   outcome,
+/// * the other events, as instants.
+///
+/// Slots are additionally marked across all lanes as their `BlockAndBankHash` is traced, and
+/// `ChannelDepths` are shown as counters.
+pub struct ChromeTraceWriter<W: Write> {
+    writer: W,
+    event_count: u64,
+    named_lanes: BTreeSet<Lane>,
+}
+
+impl<W: Write> ChromeTraceWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(b"{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n")?;
+        let mut chrome_trace_writer = Self {
+            writer,
+            event_count: 0,
+            named_lanes: BTreeSet::new(),
+        };
+        chrome_trace_writer.write(&json!({
+            "ph": "M",
+            "pid": PID,
+            "name": "process_name",
+            "args": { "name": "banking trace" },
+        }))?;
+        Ok(chrome_trace_writer)
+    }
+
+    pub fn write_event(&mut self, event: &TimedTracedEvent) -> io::Result<()> {
+        let TimedTracedEvent(time, traced_event) = event;
+        let ts = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64;
+        let name = traced_event.kind();
+        match traced_event {
+            TracedEvent::PacketBatch(label, batch) => {
+                let TraceCounts {
+                    batch_count: _,
+                    packet_count,
+                    byte_count,
+                } = TraceCounts::from_batch(batch);
+                self.write_instant(
+                    Lane::Channel(*label),
+                    ts,
+                    name,
+                    json!({ "packet_count": packet_count, "byte_count": byte_count }),
+                )
+            }
+            TracedEvent::LeaderSlotBoundary { slot, start } => self.write_lane_event(
+                Lane::LeaderSlots,
+                json!({
+                    "ph": if *start { "B" } else { "E" },
+                    "ts": ts,
+                    "name": format!("slot {slot}"),
+                }),
+            ),
+            TracedEvent::BlockAndBankHash(slot, blockhash, bank_hash) => self.write(&json!({
+                "ph": "i",
+                "s": "g",
+                "pid": PID,
+                "ts": ts,
+                "name": format!("slot {slot}"),
+                "args": { "blockhash": blockhash.to_string(), "bank_hash": bank_hash.to_string() },
+            })),
+            TracedEvent::BankFrozen { .. } => {
+                self.write_instant(Lane::LeaderSlots, ts, name, Self::args(traced_event)?)
+            }
+            TracedEvent::TransactionBatchScheduled {
+                worker_id,
+                batch_id,
+                signatures,
+            } => self.write_lane_event(
+                Lane::Worker(*worker_id),
+                json!({
+                    "ph": "B",
+                    "ts": ts,
+                    "name": format!("batch {batch_id}"),
+                    "args": { "transaction_count": signatures.len() },
+                }),
+            ),
+            TracedEvent::TransactionBatchOutcome {
+                worker_id,
+                outcomes,
+                error,
+                ..
+            } => {
+                let count = |outcome| outcomes.iter().filter(|o| **o == outcome).count();
+                self.write_lane_event(
+                    Lane::Worker(*worker_id),
+                    json!({
+                        "ph": "E",
+                        "ts": ts,
+                        "args": {
+                            "committed_count": count(TransactionOutcome::Committed),
+                            "retryable_count": count(TransactionOutcome::Retryable),
+                            "dropped_count": count(TransactionOutcome::Dropped),
+                            "error": error.map(|error| format!("{error:?}")),
+                        },
+                    }),
+                )
+            }
+            TracedEvent::CostLimitExceeded { worker_id, .. } => {
+                self.write_instant(Lane::Worker(*worker_id), ts, name, Self::args(traced_event)?)
+            }
+            TracedEvent::ChannelDepths { depths } => self.write(&json!({
+                "ph": "C",
+                "pid": PID,
+                "ts": ts,
+                "name": "channel depths",
+                "args": depths
+                    .iter()
+                    .map(|(label, depth)| (format!("{label:?}"), json!(depth)))
+                    .collect::<serde_json::Map<_, _>>(),
+            })),
+            TracedEvent::TransactionResults { worker_id, results, .. } => self.write_instant(
+                Lane::Worker(*worker_id),
+                ts,
+                name,
+                json!({ "transaction_count": results.len() }),
+            ),
+            traced_event => {
+                let lane = traced_event.label().map_or(Lane::Other, Lane::Channel);
+                self.write_instant(lane, ts, name, Self::args(traced_event)?)
+            }
+        }
+    }
+
+    /// Terminates the JSON object, returning the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(b"\n]}\n")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    // The fields of the event, e.g. `{"slot": 5, "tick_height": 64, ...}`.
+    fn args(traced_event: &TracedEvent) -> io::Result<Value> {
+        Ok(match serde_json::to_value(traced_event)? {
+            Value::Object(mut variant) => variant
+                .remove(traced_event.kind())
+                .map(|fields| match fields {
+                    fields @ Value::Object(_) => fields,
+                    value => json!({ "value": value }),
+                })
+                .unwrap_or_default(),
+            _ => json!({}),
+        })
+    }
+
+    fn write_instant(&mut self, lane: Lane, ts: u64, name: &str, args: Value) -> io::Result<()> {
+        self.write_lane_event(
+            lane,
+            json!({ "ph": "i", "s": "t", "ts": ts, "name": name, "args": args }),
+        )
+    }
+
+    fn write_lane_event(&mut self, lane: Lane, mut event: Value) -> io::Result<()> {
+        if self.named_lanes.insert(lane) {
+            for (name, args) in [
+                ("thread_name", json!({ "name": lane.name() })),
+                ("thread_sort_index", json!({ "sort_index": lane.tid() })),
+            ] {
+                self.write(&json!({
+                    "ph": "M",
+                    "pid": PID,
+                    "tid": lane.tid(),
+                    "name": name,
+                    "args": args,
+                }))?;
+            }
+        }
+        event["pid"] = json!(PID);
+        event["tid"] = json!(lane.tid());
+        self.write(&event)
+    }
+
+    fn write(&mut self, event: &Value) -> io::Result<()> {
+        if self.event_count > 0 {
+            self.writer.write_all(b",\n")?;
+        }
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.event_count += 1;
+        Ok(())
+    }
+}
+
+/// Converts all events of `reader` with `ChromeTraceWriter`.
+pub fn export_chrome_trace<W: Write>(reader: &TraceReader, writer: W) -> Result<W, TraceError> {
+    let mut chrome_trace_writer = ChromeTraceWriter::new(writer)?;
+    for event in reader.iter() {
+        chrome_trace_writer.write_event(&event?)?;
+    }
+    Ok(chrome_trace_writer.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{for_test, BatchErrorClass},
+        solana_hash::Hash,
+        solana_signature::Signature,
+        std::time::Duration,
+        tempfile::TempDir,
+    };
+
+    #[test]
+    fn test_export_chrome_trace() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let at_micros = |micros| UNIX_EPOCH + Duration::from_micros(micros);
+        for_test::write_events(
+            &path,
+            u64::MAX,
+            [
+                TracedEvent::LeaderSlotBoundary {
+                    slot: 5,
+                    start: true,
+                },
+                TracedEvent::PacketBatch(ChannelLabel::NonVote, for_test::sample_packet_batch()),
+                TracedEvent::TransactionBatchScheduled {
+                    worker_id: 2,
+                    batch_id: 7,
+                    signatures: vec![Signature::default()],
+                },
+                TracedEvent::TransactionBatchOutcome {
+                    worker_id: 2,
+                    batch_id: 7,
+                    outcomes: vec![TransactionOutcome::Dropped],
+                    error: Some(BatchErrorClass::MaxHeightReached),
+                },
+                TracedEvent::ChannelDepths {
+                    depths: vec![(ChannelLabel::NonVote, 3)],
+                },
+                TracedEvent::LeaderSlotBoundary {
+                    slot: 5,
+                    start: false,
+                },
+                TracedEvent::BlockAndBankHash(5, Hash::default(), Hash::default()),
+                TracedEvent::Annotation("hello".into()),
+            ]
+            .into_iter()
+            .zip(1..)
+            .map(|(event, micros)| TimedTracedEvent(at_micros(micros), event)),
+        );
+
+        let json = export_chrome_trace(&TraceReader::open_dir(&path).unwrap(), vec![]).unwrap();
+        let trace = serde_json::from_slice::<Value>(&json).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let lane_names = events
+            .iter()
+            .filter(|event| event["name"] == "thread_name")
+            .map(|event| event["args"]["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lane_names, ["leader slots", "NonVote", "worker 2", "other"]);
+        let timed_events = events
+            .iter()
+            .filter(|event| event["ph"] != "M")
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timed_events
+                .iter()
+                .map(|event| (event["ts"].as_u64().unwrap(), event["ph"].as_str().unwrap()))
+                .collect::<Vec<_>>(),
+            [
+                (1, "B"),
+                (2, "i"),
+                (3, "B"),
+                (4, "E"),
+                (5, "C"),
+                (6, "E"),
+                (7, "i"),
+                (8, "i")
+            ]
+        );
+        assert_eq!(timed_events[1]["args"]["packet_count"], 4);
+        assert_eq!(timed_events[3]["args"]["dropped_count"], 1);
+        assert_eq!(timed_events[3]["args"]["error"], "MaxHeightReached");
+        assert_eq!(timed_events[4]["args"]["NonVote"], 3);
+        assert_eq!(timed_events[6]["s"], "g");
+        assert_eq!(timed_events[7]["args"]["value"], "hello");
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+}
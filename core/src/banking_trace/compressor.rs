@@ -0,0 +1,261 @@
+//! Worker pool compressing the trace files completed by `RollingFileSink`, so that enabling
+//! compression doesn't stall the tracer thread on each rotation.
+
+use {
+    super::{reader::TraceCompression, TraceError},
+    crossbeam_channel::{unbounded, Receiver, Sender},
+    std::{
+        fs::{self, File},
+        io,
+        path::{Path, PathBuf},
+        thread::{self, JoinHandle},
+    },
+};
+
+// Compressed files are written here until handed back, so that they don't show up as part of
+// the trace while being written.
+const COMPRESS_DIR: &str = "compress";
+
+struct CompressionJob<T> {
+    // opened by the tracer thread, so that the file can be rotated or renamed meanwhile
+    file: File,
+    compressed_path: PathBuf,
+    tag: T,
+}
+
+/// A file compressed by a worker, tagged as it was handed over.
+#[derive(Debug)]
+pub struct CompressedFile<T> {
+    pub path: PathBuf,
+    pub tag: T,
+}
+
+/// Compresses completed trace files on a small pool of worker threads. The original files are
+/// left as is; it's up to the caller to move each compressed file into place and remove the
+/// original, as only it knows where the original has been rotated to meanwhile.
+pub struct RotatedFileCompressor<T> {
+    compression: TraceCompression,
+    compress_dir: PathBuf,
+    job_sender: Option<Sender<CompressionJob<T>>>,
+    compressed_receiver: Receiver<CompressedFile<T>>,
+    threads: Vec<JoinHandle<()>>,
+    next_job_id: u64,
+}
+
+impl<T: Send + 'static> RotatedFileCompressor<T> {
+    /// Starts `thread_count` workers (at least one), removing the files left half-compressed
+    /// by the last shutdown. Their originals are still in place. Fails unless built with
+    /// `banking-trace-compression`.
+    pub fn new(
+        trace_dir: &Path,
+        compression: TraceCompression,
+        thread_count: usize,
+    ) -> Result<Self, TraceError> {
+        if !cfg!(feature = "banking-trace-compression") {
+            return Err(TraceError::UnsupportedCompression(compression));
+        }
+        let compress_dir = trace_dir.join(COMPRESS_DIR);
+        if compress_dir.exists() {
+            fs::remove_dir_all(&compress_dir)?;
+        }
+        fs::create_dir_all(&compress_dir)?;
+
+        let (job_sender, job_receiver) = unbounded();
+        let (compressed_sender, compressed_receiver) = unbounded();
+        let threads = (0..thread_count.max(1))
+            .map(|index| {
+                let job_receiver = job_receiver.clone();
+                let compressed_sender = compressed_sender.clone();
+                thread::Builder::new()
+                    .name(format!("solBnkTrcCmp{index:02}"))
+                    .spawn(move || {
+                        Self::compress_loop(compression, job_receiver, compressed_sender)
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            compression,
+            compress_dir,
+            job_sender: Some(job_sender),
+            compressed_receiver,
+            threads,
+            next_job_id: 0,
+        })
+    }
+
+    pub fn compression(&self) -> TraceCompression {
+        self.compression
+    }
+
+    /// Hands over the completed trace file at `path` to the workers. Only opening it is done
+    /// on the calling thread.
+    pub fn compress(&mut self, path: &Path, tag: T) -> Result<(), TraceError> {
+        let file = File::open(path)?;
+        let compressed_path = self.compress_dir.join(format!(
+            "{}.{}",
+            self.next_job_id,
+            self.compression.extension()
+        ));
+        self.next_job_id += 1;
+        if let Some(job_sender) = &self.job_sender {
+            job_sender
+                .send(CompressionJob {
+                    file,
+                    compressed_path,
+                    tag,
+                })
+                .unwrap();
+        }
+        Ok(())
+    }
+
+    /// Returns a file compressed so far, if any, without blocking.
+    pub fn try_recv(&self) -> Option<CompressedFile<T>> {
+        self.compressed_receiver.try_recv().ok()
+    }
+
+    /// Waits for the pending compressions to finish, returning the files compressed but not
+    /// received yet.
+    pub fn finish(&mut self) -> Vec<CompressedFile<T>> {
+        drop(self.job_sender.take());
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        self.compressed_receiver.try_iter().collect()
+    }
+
+    fn compress_loop(
+        compression: TraceCompression,
+        job_receiver: Receiver<CompressionJob<T>>,
+        compressed_sender: Sender<CompressedFile<T>>,
+    ) {
+        for CompressionJob {
+            file,
+            compressed_path,
+            tag,
+        } in job_receiver
+        {
+            match Self::compress_file(compression, file, &compressed_path) {
+                Ok(()) => {
+                    let _ = compressed_sender.send(CompressedFile {
+                        path: compressed_path,
+                        tag,
+                    });
+                }
+                Err(err) => {
+                    warn!(
+                        "failed to compress banking trace file into {compressed_path:?}: {err:?}"
+                    );
+                    let _ = fs::remove_file(&compressed_path);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "banking-trace-compression")]
+    fn compress_file(
+        compression: TraceCompression,
+        mut file: File,
+        compressed_path: &Path,
+    ) -> Result<(), io::Error> {
+        use std::io::Write;
+
+        let compressed_file = File::create(compressed_path)?;
+        let mut compressed_file = match compression {
+            TraceCompression::Zstd => {
+                let mut encoder = zstd::Encoder::new(compressed_file, 0)?;
+                io::copy(&mut file, &mut encoder)?;
+                encoder.finish()?
+            }
+            TraceCompression::Lz4 => {
+                let mut encoder = lz4::EncoderBuilder::new().build(compressed_file)?;
+                io::copy(&mut file, &mut encoder)?;
+                let (compressed_file, result) = encoder.finish();
+                result?;
+                compressed_file
+            }
+        };
+        // the original is removed once this is moved into place
+        compressed_file.flush()?;
+        compressed_file.sync_data()
+    }
+
+    #[cfg(not(feature = "banking-trace-compression"))]
+    fn compress_file(
+        _compression: TraceCompression,
+        _file: File,
+        _compressed_path: &Path,
+    ) -> Result<(), io::Error> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl<T> Drop for RotatedFileCompressor<T> {
+    /// Lets the workers finish the pending compressions in the background. Their results are
+    /// left in the compress directory and removed on the next start.
+    fn drop(&mut self) {
+        drop(self.job_sender.take());
+    }
+}
+
+#[cfg(all(test, feature = "banking-trace-compression"))]
+mod tests {
+    use {super::*, std::io::Read, tempfile::TempDir};
+
+    #[test]
+    fn test_compress() {
+        let trace_dir = TempDir::new().unwrap();
+        // a leftover of the last run
+        fs::create_dir_all(trace_dir.path().join(COMPRESS_DIR)).unwrap();
+        fs::write(trace_dir.path().join(COMPRESS_DIR).join("0.zst"), b"foo").unwrap();
+
+        for compression in [TraceCompression::Zstd, TraceCompression::Lz4] {
+            let mut compressor =
+                RotatedFileCompressor::new(trace_dir.path(), compression, 2).unwrap();
+            assert_eq!(
+                fs::read_dir(trace_dir.path().join(COMPRESS_DIR))
+                    .unwrap()
+                    .count(),
+                0
+            );
+            let data = (0..10_000u32)
+                .flat_map(u32::to_le_bytes)
+                .collect::<Vec<_>>();
+            for index in 0..3 {
+                let path = trace_dir.path().join(format!("events.{index}"));
+                fs::write(&path, &data).unwrap();
+                compressor.compress(&path, index).unwrap();
+                // the workers don't care about the original path anymore
+                fs::remove_file(&path).unwrap();
+            }
+
+            let mut compressed_files = compressor.finish();
+            compressed_files.sort_by_key(|compressed_file| compressed_file.tag);
+            assert_eq!(
+                compressed_files
+                    .iter()
+                    .map(|compressed_file| compressed_file.tag)
+                    .collect::<Vec<_>>(),
+                vec![0, 1, 2]
+            );
+            for compressed_file in compressed_files {
+                assert_eq!(
+                    TraceCompression::from_path(&compressed_file.path),
+                    Some(compression)
+                );
+                let file = File::open(&compressed_file.path).unwrap();
+                let mut decompressed = vec![];
+                match compression {
+                    TraceCompression::Zstd => zstd::Decoder::new(file)
+                        .unwrap()
+                        .read_to_end(&mut decompressed),
+                    TraceCompression::Lz4 => lz4::Decoder::new(file)
+                        .unwrap()
+                        .read_to_end(&mut decompressed),
+                }
+                .unwrap();
+                assert_eq!(decompressed, data);
+            }
+        }
+    }
+}
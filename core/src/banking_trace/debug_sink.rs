@@ -0,0 +1,124 @@
+//! `TraceSink` pretty-printing a summary line per event, handy when developing against
+//! `BankingTracer` locally without loading trace files into analysis tools.
+
+use {
+    super::{reader::TraceCounts, TimedTracedEvent, TraceError, TraceSink, TracedEvent},
+    chrono::{DateTime, SecondsFormat, Utc},
+    std::{
+        fmt::Write as _,
+        io::{self, Stdout, Write},
+    },
+};
+
+/// Writes a line per event into `writer`, like:
+///
+/// `2024-01-02T03:04:05.678901Z PacketBatch label=NonVote packets=4 bytes=1232`
+///
+/// The label, counts and slot are included only if applicable to the event.
+pub struct DebugSink<W> {
+    writer: W,
+    line_buf: String,
+}
+
+impl DebugSink<Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write + Send + 'static> DebugSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            line_buf: String::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn format_line(line_buf: &mut String, event: &TimedTracedEvent) {
+        let TimedTracedEvent(time, traced_event) = event;
+        line_buf.clear();
+        let _ = write!(
+            line_buf,
+            "{} {}",
+            DateTime::<Utc>::from(*time).to_rfc3339_opts(SecondsFormat::Micros, true),
+            traced_event.kind(),
+        );
+        if let Some(label) = traced_event.label() {
+            let _ = write!(line_buf, " label={label:?}");
+        }
+        if let TracedEvent::PacketBatch(_, batch) = traced_event {
+            let TraceCounts {
+                batch_count: _,
+                packet_count,
+                byte_count,
+            } = TraceCounts::from_batch(batch);
+            let _ = write!(line_buf, " packets={packet_count} bytes={byte_count}");
+        }
+        if let Some(slot) = traced_event.slot() {
+            let _ = write!(line_buf, " slot={slot}");
+        }
+        line_buf.push('\n');
+    }
+}
+
+/// The line written by `DebugSink` for `event`, without the trailing newline.
+pub fn summary_line(event: &TimedTracedEvent) -> String {
+    let mut line_buf = String::new();
+    DebugSink::<Stdout>::format_line(&mut line_buf, event);
+    line_buf.pop();
+    line_buf
+}
+
+impl<W: Write + Send + 'static> TraceSink for DebugSink<W> {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        Self::format_line(&mut self.line_buf, event);
+        self.writer.write_all(self.line_buf.as_bytes())?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{for_test::sample_packet_batch, ChannelLabel},
+        std::time::{Duration, UNIX_EPOCH},
+    };
+
+    #[test]
+    fn test_summary_lines() {
+        let time = UNIX_EPOCH + Duration::from_micros(1_704_164_645_678_901);
+        let mut sink = DebugSink::new(vec![]);
+        for event in [
+            TracedEvent::PacketBatch(ChannelLabel::NonVote, sample_packet_batch()),
+            TracedEvent::LeaderSlotBoundary {
+                slot: 7,
+                start: true,
+            },
+            TracedEvent::Heartbeat,
+        ] {
+            sink.write_event(&TimedTracedEvent(time, event)).unwrap();
+        }
+        sink.flush().unwrap();
+
+        let batch_bytes = TraceCounts::from_batch(&sample_packet_batch()).byte_count;
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            format!(
+                "2024-01-02T03:04:05.678901Z PacketBatch label=NonVote packets=4 \
+                 bytes={batch_bytes}\n\
+                 2024-01-02T03:04:05.678901Z LeaderSlotBoundary slot=7\n\
+                 2024-01-02T03:04:05.678901Z Heartbeat\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Double-buffered backend of `RollingFileSink`: the tracer thread fills one buffer while an I/O
+//! helper thread writes out the other, so that a slow disk doesn't stall draining the events.
+
+use {
+    super::rotating_appender::AppenderWriter,
+    crossbeam_channel::{bounded, Receiver, Sender},
+    std::{
+        fs::File,
+        io::{self, Seek, SeekFrom, Write},
+        mem,
+        thread::{self, JoinHandle},
+    },
+};
+
+type WrittenBuffer = (Vec<u8>, io::Result<()>);
+
+/// Writes a file sequentially from `offset`, handing each filled buffer to an I/O helper thread
+/// and continuing with the other one. Writing only waits if the other buffer is still being
+/// written out once this one is filled again.
+pub(super) struct DoubleBufferedWriter {
+    buffer: Vec<u8>,
+    buffer_capacity: usize,
+    filled_sender: Option<Sender<Vec<u8>>>,
+    written_receiver: Receiver<WrittenBuffer>,
+    // None while being written out by the helper
+    spare_buffer: Option<Vec<u8>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AppenderWriter for DoubleBufferedWriter {
+    fn new(mut file: File, offset: u64, buffer_capacity: usize) -> io::Result<Self> {
+        file.seek(SeekFrom::Start(offset))?;
+        // at most one buffer is in flight at once, so neither channel ever blocks
+        let (filled_sender, filled_receiver) = bounded(1);
+        let (written_sender, written_receiver) = bounded(1);
+        let thread = thread::Builder::new()
+            .name("solBnkTrcIo".into())
+            .spawn(move || Self::write_loop(file, filled_receiver, written_sender))?;
+        Ok(Self {
+            buffer: Vec::with_capacity(buffer_capacity),
+            buffer_capacity,
+            filled_sender: Some(filled_sender),
+            written_receiver,
+            spare_buffer: Some(Vec::with_capacity(buffer_capacity)),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl DoubleBufferedWriter {
+    fn write_loop(
+        mut file: File,
+        filled_receiver: Receiver<Vec<u8>>,
+        written_sender: Sender<WrittenBuffer>,
+    ) {
+        for mut buffer in filled_receiver {
+            let result = file.write_all(&buffer);
+            buffer.clear();
+            if written_sender.send((buffer, result)).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Waits for the buffer being written out, if any, taking it back as the spare one.
+    fn wait_for_written(&mut self) -> io::Result<()> {
+        if self.spare_buffer.is_some() {
+            return Ok(());
+        }
+        let (buffer, result) = self
+            .written_receiver
+            .recv()
+            .map_err(|_| io::Error::other("the banking trace I/O helper has exited"))?;
+        self.spare_buffer = Some(buffer);
+        result
+    }
+
+    /// Hands over the current buffer to the helper and continues with the spare one.
+    fn submit_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.wait_for_written()?;
+        let buffer = mem::replace(&mut self.buffer, self.spare_buffer.take().unwrap());
+        self.filled_sender
+            .as_ref()
+            .unwrap()
+            .send(buffer)
+            .map_err(|_| io::Error::other("the banking trace I/O helper has exited"))
+    }
+}
+
+impl Write for DoubleBufferedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= self.buffer_capacity {
+            self.submit_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit_buffer()?;
+        self.wait_for_written()
+    }
+}
+
+impl Drop for DoubleBufferedWriter {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            warn!("failed to flush the trace file: {err:?}");
+        }
+        drop(self.filled_sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            rotating_appender::RotatingAppender, RollingConditionGrouped, RotationPolicy,
+        },
+        std::fs,
+        tempfile::TempDir,
+    };
+
+    #[test]
+    fn test_double_buffered_writer() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("events");
+        fs::write(&path, b"old").unwrap();
+        let condition = RollingConditionGrouped::new(RotationPolicy::default(), 1024);
+        let mut appender =
+            RotatingAppender::<DoubleBufferedWriter>::new(&path, condition, 2, 10).unwrap();
+        let data = (0..100u8).collect::<Vec<_>>();
+        for chunk in data.chunks(7) {
+            appender.write_all(chunk).unwrap();
+            appender.condition_mut().reset();
+        }
+        appender.flush().unwrap();
+        // the non-blank file is rotated on the first write
+        assert_eq!(fs::read(path.with_extension("1")).unwrap(), b"old");
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+}
@@ -0,0 +1,215 @@
+//! `TraceSink` wrapper forwarding only the events matching a predicate, for targeted capture
+//! instead of recording the firehose.
+
+use {
+    super::{
+        ChannelLabel, DirByteLimit, SinkEffectiveConfig, TimedTracedEvent, TraceError, TraceSink,
+        TracedEvent,
+    },
+    agave_banking_stage_ingress_types::BankingPacketBatch,
+    agave_transaction_view::transaction_view::SanitizedTransactionView,
+    solana_pubkey::Pubkey,
+    solana_signature::Signature,
+    std::collections::{BTreeSet, HashSet},
+};
+
+/// Forwards the events for which `predicate` returns true to `inner`. The `PacketBatchLane`
+/// events derived from a filtered out `PacketBatch` are filtered out as well, without asking
+/// `predicate`.
+pub struct FilterSink<S, P> {
+    inner: S,
+    predicate: P,
+    /// Labels whose last `PacketBatch` was filtered out.
+    skipping_labels: BTreeSet<ChannelLabel>,
+}
+
+impl<S: TraceSink, P: FnMut(&TracedEvent) -> bool + Send + 'static> FilterSink<S, P> {
+    pub fn new(inner: S, predicate: P) -> Self {
+        Self {
+            inner,
+            predicate,
+            skipping_labels: BTreeSet::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn should_forward(&mut self, event: &TracedEvent) -> bool {
+        match event {
+            TracedEvent::PacketBatch(label, _) => {
+                let is_matched = (self.predicate)(event);
+                if is_matched {
+                    self.skipping_labels.remove(label);
+                } else {
+                    self.skipping_labels.insert(*label);
+                }
+                is_matched
+            }
+            TracedEvent::PacketBatchLane { label, .. } if self.skipping_labels.contains(label) => {
+                false
+            }
+            _ => (self.predicate)(event),
+        }
+    }
+}
+
+impl<S: TraceSink, P: FnMut(&TracedEvent) -> bool + Send + 'static> TraceSink for FilterSink<S, P> {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        if self.should_forward(&event.1) {
+            self.inner.write_event(event)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.inner.flush()
+    }
+
+    fn rotate_hint(&mut self) {
+        self.inner.rotate_hint()
+    }
+
+    fn rotate_now(&mut self) -> Result<(), TraceError> {
+        self.inner.rotate_now()
+    }
+
+    fn rotation_count(&self) -> u64 {
+        self.inner.rotation_count()
+    }
+
+    fn set_dir_byte_limit(&mut self, dir_byte_limit: DirByteLimit) -> Result<(), TraceError> {
+        self.inner.set_dir_byte_limit(dir_byte_limit)
+    }
+
+    fn available_bytes(&self) -> Option<u64> {
+        self.inner.available_bytes()
+    }
+
+    fn effective_config(&self) -> SinkEffectiveConfig {
+        SinkEffectiveConfig::Filter {
+            inner: Box::new(self.inner.effective_config()),
+        }
+    }
+}
+
+/// Whether any non-discarded packet of `batch` is a transaction satisfying `predicate`.
+/// Packets which fail to be parsed as transactions are ignored.
+pub fn any_transaction(
+    batch: &BankingPacketBatch,
+    mut predicate: impl FnMut(&SanitizedTransactionView<&[u8]>) -> bool,
+) -> bool {
+    batch
+        .iter()
+        .flat_map(|batch| batch.iter())
+        .filter(|packet| !packet.meta().discard())
+        .filter_map(|packet| packet.data(..))
+        .filter_map(|data| SanitizedTransactionView::try_new_sanitized(data).ok())
+        .any(|transaction| predicate(&transaction))
+}
+
+/// Predicate for `FilterSink` keeping only the packet batches with a transaction referencing
+/// `account` (e.g. a program id) among its static account keys, along with all the other events.
+pub fn batches_referencing(account: Pubkey) -> impl FnMut(&TracedEvent) -> bool + Send + 'static {
+    move |event| match event {
+        TracedEvent::PacketBatch(_, batch) => any_transaction(batch, |transaction| {
+            transaction.static_account_keys().contains(&account)
+        }),
+        _ => true,
+    }
+}
+
+/// Predicate for `FilterSink` keeping only the packet batches with a transaction signed with
+/// any of `signatures`, along with all the other events.
+pub fn batches_signed_with(
+    signatures: impl IntoIterator<Item = Signature>,
+) -> impl FnMut(&TracedEvent) -> bool + Send + 'static {
+    let signatures = signatures.into_iter().collect::<HashSet<_>>();
+    move |event| match event {
+        TracedEvent::PacketBatch(_, batch) => any_transaction(batch, |transaction| {
+            transaction
+                .signatures()
+                .iter()
+                .any(|signature| signatures.contains(signature))
+        }),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+            PriorityLane,
+        },
+        std::time::{Duration, SystemTime},
+    };
+
+    fn filtered_kinds(
+        batch: BankingPacketBatch,
+        predicate: impl FnMut(&TracedEvent) -> bool + Send + 'static,
+    ) -> Vec<&'static str> {
+        let inner = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = inner.recent_events();
+        let mut sink = FilterSink::new(inner, predicate);
+        for event in [
+            TracedEvent::PacketBatch(ChannelLabel::NonVote, batch),
+            TracedEvent::PacketBatchLane {
+                label: ChannelLabel::NonVote,
+                lane: PriorityLane::High,
+            },
+            TracedEvent::LeaderSlotBoundary {
+                slot: 1,
+                start: true,
+            },
+        ] {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), event))
+                .unwrap();
+        }
+        recent_events
+            .recent_events(&EventFilter::default())
+            .into_iter()
+            .map(|TimedTracedEvent(_, event)| event.kind())
+            .collect()
+    }
+
+    #[test]
+    fn test_batches_referencing() {
+        assert_eq!(
+            filtered_kinds(
+                sample_packet_batch(),
+                batches_referencing(solana_sdk_ids::system_program::id())
+            ),
+            ["PacketBatch", "PacketBatchLane", "LeaderSlotBoundary"]
+        );
+        assert_eq!(
+            filtered_kinds(
+                sample_packet_batch(),
+                batches_referencing(Pubkey::new_unique())
+            ),
+            ["LeaderSlotBoundary"]
+        );
+    }
+
+    #[test]
+    fn test_batches_signed_with() {
+        let batch = sample_packet_batch();
+        let mut signature = None;
+        any_transaction(&batch, |transaction| {
+            signature = Some(transaction.signatures()[0]);
+            true
+        });
+        assert_eq!(
+            filtered_kinds(batch.clone(), batches_signed_with(signature)),
+            ["PacketBatch", "PacketBatchLane", "LeaderSlotBoundary"]
+        );
+        assert_eq!(
+            filtered_kinds(batch, batches_signed_with([Signature::from([7; 64])])),
+            ["LeaderSlotBoundary"]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! `TraceSink` notifying Geyser plugins of trace events, so that existing plugin infrastructure
+//! can consume the banking ingress alongside account and transaction updates.
+
+use {
+    super::{TimedTracedEvent, TraceError, TraceSink},
+    solana_geyser_plugin_manager::banking_trace_notifier_interface::BankingTraceNotifierArc,
+    std::time::UNIX_EPOCH,
+};
+
+/// Notifies each event to the plugins interested in banking trace events, with its payload
+/// serialized as in the frames of trace files.
+pub struct GeyserSink {
+    notifier: BankingTraceNotifierArc,
+    payload_buf: Vec<u8>,
+}
+
+impl GeyserSink {
+    pub fn new(notifier: BankingTraceNotifierArc) -> Self {
+        Self {
+            notifier,
+            payload_buf: vec![],
+        }
+    }
+}
+
+impl TraceSink for GeyserSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        let TimedTracedEvent(time, traced_event) = event;
+        self.payload_buf.clear();
+        bincode::serialize_into(&mut self.payload_buf, event)?;
+        let label = traced_event.label().map(|label| format!("{label:?}"));
+        self.notifier.notify_banking_trace_event(
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros() as u64,
+            traced_event.kind(),
+            label.as_deref(),
+            traced_event.slot(),
+            &self.payload_buf,
+        );
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{ChannelLabel, PacketDropReason, TracedEvent},
+        solana_clock::Slot,
+        solana_geyser_plugin_manager::banking_trace_notifier_interface::BankingTraceNotifier,
+        std::{
+            sync::{Arc, Mutex},
+            time::SystemTime,
+        },
+    };
+
+    type Notification = (String, Option<String>, Option<Slot>, Vec<u8>);
+
+    #[derive(Default)]
+    struct VecNotifier(Mutex<Vec<Notification>>);
+
+    impl BankingTraceNotifier for VecNotifier {
+        fn notify_banking_trace_event(
+            &self,
+            _timestamp_us: u64,
+            kind: &str,
+            label: Option<&str>,
+            slot: Option<Slot>,
+            payload: &[u8],
+        ) {
+            self.0.lock().unwrap().push((
+                kind.into(),
+                label.map(Into::into),
+                slot,
+                payload.to_vec(),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_notify() {
+        let notifier = Arc::new(VecNotifier::default());
+        let mut sink = GeyserSink::new(notifier.clone());
+        for event in [
+            TracedEvent::PacketsDropped {
+                label: ChannelLabel::TpuVote,
+                count: 1,
+                reason: PacketDropReason::Dedup,
+            },
+            TracedEvent::BankFrozen {
+                slot: 5,
+                tick_height: 64,
+                transaction_count: 0,
+            },
+        ] {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), event))
+                .unwrap();
+        }
+        sink.flush().unwrap();
+
+        let notifications = notifier.0.lock().unwrap();
+        assert_matches!(
+            &notifications[..],
+            [
+                (dropped, Some(label), None, _),
+                (frozen, None, Some(5), payload),
+            ] if (dropped.as_str(), label.as_str(), frozen.as_str())
+                == ("PacketsDropped", "TpuVote", "BankFrozen")
+                && matches!(
+                    bincode::deserialize(payload),
+                    Ok(TimedTracedEvent(_, TracedEvent::BankFrozen { slot: 5, .. }))
+                )
+        );
+    }
+}
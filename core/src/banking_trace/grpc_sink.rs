@@ -0,0 +1,276 @@
+//! `TraceSink` serving the live trace over gRPC (`SubscribeBankingTrace`), giving dashboards
+//! Geyser-like access to the banking ingress without a plugin.
+
+use {
+    super::{TimedTracedEvent, TraceError, TraceSink},
+    bincode::serialize,
+    proto::{
+        banking_trace_server::{BankingTrace, BankingTraceServer},
+        BankingTraceEvent, SubscribeBankingTraceRequest,
+    },
+    solana_clock::Slot,
+    std::{
+        net::SocketAddr,
+        pin::Pin,
+        sync::Arc,
+        thread::{self, JoinHandle},
+        time::UNIX_EPOCH,
+    },
+    tokio::sync::{broadcast, oneshot},
+    tokio_stream::{
+        wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, TcpListenerStream},
+        Stream, StreamExt,
+    },
+    tonic::{transport::Server, Request, Response, Status},
+};
+
+pub mod proto {
+    // the generated server code is outside of our control
+    #![allow(clippy::default_trait_access)]
+
+    include!(concat!(env!("OUT_DIR"), "/solana.banking_trace.rs"));
+}
+
+// Subscribers lagging behind by more than this are disconnected.
+const SUBSCRIBER_BUFFER_CAPACITY: usize = 16 * 1024;
+
+/// Serves the events traced after each subscription to all subscribers whose filters match.
+pub struct GrpcSink {
+    local_addr: SocketAddr,
+    // Taken on drop to end the subscriptions, so that the server can shut down gracefully.
+    sender: Option<broadcast::Sender<Arc<BankingTraceEvent>>>,
+    shutdown_sender: Option<oneshot::Sender<()>>,
+    server_thread: Option<JoinHandle<Result<(), tonic::transport::Error>>>,
+}
+
+impl GrpcSink {
+    /// Binds to `addr` and starts serving from a dedicated thread.
+    pub fn new(addr: SocketAddr) -> Result<Self, TraceError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let listener = runtime.block_on(tokio::net::TcpListener::bind(addr))?;
+        let local_addr = listener.local_addr()?;
+        let (sender, _) = broadcast::channel(SUBSCRIBER_BUFFER_CAPACITY);
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel();
+        let service = BankingTraceServer::new(TraceService {
+            sender: sender.downgrade(),
+        });
+        let server_thread =
+            thread::Builder::new()
+                .name("solBnkTrcGrpc".into())
+                .spawn(move || {
+                    runtime.block_on(
+                        Server::builder()
+                            .add_service(service)
+                            .serve_with_incoming_shutdown(
+                                TcpListenerStream::new(listener),
+                                async {
+                                    let _ = shutdown_receiver.await;
+                                },
+                            ),
+                    )
+                })?;
+        info!("serving banking trace over gRPC at {local_addr}");
+
+        Ok(Self {
+            local_addr,
+            sender: Some(sender),
+            shutdown_sender: Some(shutdown_sender),
+            server_thread: Some(server_thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl TraceSink for GrpcSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        let Some(sender) = self
+            .sender
+            .as_ref()
+            .filter(|sender| sender.receiver_count() > 0)
+        else {
+            return Ok(());
+        };
+        let TimedTracedEvent(time, traced_event) = event;
+        let event = BankingTraceEvent {
+            timestamp_us: time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros() as u64,
+            kind: traced_event.kind().into(),
+            label: traced_event.label().map(|label| format!("{label:?}")),
+            slot: traced_event.slot(),
+            payload: serialize(event)?,
+        };
+        // the only error is that all subscribers have gone in the meantime
+        let _ = sender.send(Arc::new(event));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+impl Drop for GrpcSink {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(shutdown_sender) = self.shutdown_sender.take() {
+            let _ = shutdown_sender.send(());
+        }
+        if let Some(server_thread) = self.server_thread.take() {
+            match server_thread.join() {
+                Ok(Ok(())) => (),
+                Ok(Err(err)) => warn!("banking trace gRPC server failed: {err:?}"),
+                Err(err) => warn!("banking trace gRPC server panicked: {err:?}"),
+            }
+        }
+    }
+}
+
+struct SubscriptionFilter {
+    labels: Vec<String>,
+    min_slot: Option<Slot>,
+    max_slot: Option<Slot>,
+}
+
+impl SubscriptionFilter {
+    fn matches(&self, event: &BankingTraceEvent) -> bool {
+        let label_matches = match &event.label {
+            Some(label) => self.labels.is_empty() || self.labels.contains(label),
+            None => true,
+        };
+        let slot_matches = match event.slot {
+            Some(slot) => {
+                self.min_slot.is_none_or(|min_slot| slot >= min_slot)
+                    && self.max_slot.is_none_or(|max_slot| slot <= max_slot)
+            }
+            None => true,
+        };
+        label_matches && slot_matches
+    }
+}
+
+impl From<SubscribeBankingTraceRequest> for SubscriptionFilter {
+    fn from(request: SubscribeBankingTraceRequest) -> Self {
+        let SubscribeBankingTraceRequest {
+            labels,
+            min_slot,
+            max_slot,
+        } = request;
+        Self {
+            labels,
+            min_slot,
+            max_slot,
+        }
+    }
+}
+
+struct TraceService {
+    sender: broadcast::WeakSender<Arc<BankingTraceEvent>>,
+}
+
+#[tonic::async_trait]
+impl BankingTrace for TraceService {
+    type SubscribeBankingTraceStream =
+        Pin<Box<dyn Stream<Item = Result<BankingTraceEvent, Status>> + Send>>;
+
+    async fn subscribe_banking_trace(
+        &self,
+        request: Request<SubscribeBankingTraceRequest>,
+    ) -> Result<Response<Self::SubscribeBankingTraceStream>, Status> {
+        let filter = SubscriptionFilter::from(request.into_inner());
+        let receiver = self
+            .sender
+            .upgrade()
+            .ok_or_else(|| Status::unavailable("banking trace is shutting down"))?
+            .subscribe();
+        let stream = BroadcastStream::new(receiver).filter_map(move |event| match event {
+            Ok(event) => filter.matches(&event).then(|| Ok((*event).clone())),
+            // a lagging subscriber ends with this error and should subscribe again
+            Err(BroadcastStreamRecvError::Lagged(count)) => Some(Err(Status::data_loss(format!(
+                "subscriber lagged behind by {count} events"
+            )))),
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{proto::banking_trace_client::BankingTraceClient, *},
+        crate::banking_trace::{ChannelLabel, PacketDropReason, TracedEvent},
+        bincode::deserialize,
+        std::time::SystemTime,
+    };
+
+    #[test]
+    fn test_subscribe_with_filters() {
+        let mut sink = GrpcSink::new("127.0.0.1:0".parse().unwrap()).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut stream = runtime.block_on(async {
+            let mut client = BankingTraceClient::connect(format!("http://{}", sink.local_addr()))
+                .await
+                .unwrap();
+            client
+                .subscribe_banking_trace(SubscribeBankingTraceRequest {
+                    labels: vec!["TpuVote".into()],
+                    min_slot: Some(5),
+                    max_slot: None,
+                })
+                .await
+                .unwrap()
+                .into_inner()
+        });
+
+        let dropped = |label| TracedEvent::PacketsDropped {
+            label,
+            count: 1,
+            reason: PacketDropReason::Dedup,
+        };
+        let bank_frozen = |slot| TracedEvent::BankFrozen {
+            slot,
+            tick_height: 64,
+            transaction_count: 0,
+        };
+        for event in [
+            dropped(ChannelLabel::NonVote),
+            bank_frozen(4),
+            dropped(ChannelLabel::TpuVote),
+            bank_frozen(5),
+            TracedEvent::Heartbeat,
+        ] {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), event))
+                .unwrap();
+        }
+        drop(sink);
+
+        let events = runtime.block_on(async {
+            let mut events = vec![];
+            while let Some(event) = stream.next().await {
+                events.push(event.unwrap());
+            }
+            events
+        });
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.kind.as_str(), event.label.as_deref(), event.slot))
+                .collect::<Vec<_>>(),
+            [
+                ("PacketsDropped", Some("TpuVote"), None),
+                ("BankFrozen", None, Some(5)),
+                ("Heartbeat", None, None),
+            ]
+        );
+        assert_matches!(
+            deserialize(&events[1].payload),
+            Ok(TimedTracedEvent(_, TracedEvent::BankFrozen { slot: 5, .. }))
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! `TraceSink` publishing trace events to a Kafka topic, so that banking traces are ingested by
+//! the same data platform as everything else from the validator fleet.
+
+use {
+    super::{TimedTracedEvent, TraceError, TraceSink, TracedEvent},
+    rdkafka::{
+        config::ClientConfig,
+        producer::{BaseProducer, BaseRecord, Producer},
+    },
+    std::time::Duration,
+};
+
+// Bounds how long the tracer thread can be stalled by unreachable brokers.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Publishes each event to `topic` with its bincode serialization as the payload, keyed by the
+/// label of its channel (e.g. `NonVote`) or else by its slot, so that the events of a channel
+/// or a slot keep their order within a partition. Other events are keyed by their kind.
+///
+/// Events are queued by the producer without blocking; ones which don't fit into its queue fail
+/// to be written, so that they're traced as a `TraceGap` once the brokers catch up.
+pub struct KafkaSink {
+    producer: BaseProducer,
+    topic: String,
+    key_buf: String,
+    payload_buf: Vec<u8>,
+}
+
+impl KafkaSink {
+    /// `brokers` is the comma-separated list of `HOST:PORT` to bootstrap from.
+    pub fn new(brokers: &str, topic: impl Into<String>) -> Result<Self, TraceError> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("client.id", "solana-banking-trace")
+            .create()?;
+        let topic = topic.into();
+        info!("publishing banking trace to Kafka topic {topic} at {brokers}");
+        Ok(Self {
+            producer,
+            topic,
+            key_buf: String::new(),
+            payload_buf: vec![],
+        })
+    }
+}
+
+fn write_record_key(key_buf: &mut String, event: &TracedEvent) {
+    use std::fmt::Write;
+
+    key_buf.clear();
+    // writing into String never fails
+    let _ = match (event.label(), event.slot()) {
+        (Some(label), _) => write!(key_buf, "{label:?}"),
+        (None, Some(slot)) => write!(key_buf, "{slot}"),
+        (None, None) => write!(key_buf, "{}", event.kind()),
+    };
+}
+
+impl TraceSink for KafkaSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        let TimedTracedEvent(_, traced_event) = event;
+        write_record_key(&mut self.key_buf, traced_event);
+        self.payload_buf.clear();
+        bincode::serialize_into(&mut self.payload_buf, event)?;
+        // serve the delivery reports of earlier events so that the queue doesn't fill up
+        self.producer.poll(Duration::ZERO);
+        self.producer
+            .send(
+                BaseRecord::to(&self.topic)
+                    .key(&self.key_buf)
+                    .payload(&self.payload_buf),
+            )
+            .map_err(|(err, _record)| err)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(self.producer.flush(FLUSH_TIMEOUT)?)
+    }
+}
+
+impl Drop for KafkaSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.producer.flush(FLUSH_TIMEOUT) {
+            warn!("failed to flush banking trace to Kafka: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{ChannelLabel, PacketDropReason},
+    };
+
+    #[test]
+    fn test_record_key() {
+        let mut key_buf = String::new();
+        let mut key = |event| {
+            write_record_key(&mut key_buf, &event);
+            key_buf.clone()
+        };
+        assert_eq!(
+            key(TracedEvent::PacketsDropped {
+                label: ChannelLabel::TpuVote,
+                count: 1,
+                reason: PacketDropReason::Dedup,
+            }),
+            "TpuVote"
+        );
+        assert_eq!(
+            key(TracedEvent::BankFrozen {
+                slot: 5,
+                tick_height: 64,
+                transaction_count: 0,
+            }),
+            "5"
+        );
+        assert_eq!(key(TracedEvent::Heartbeat), "Heartbeat");
+    }
+
+    #[test]
+    fn test_queue_without_brokers() {
+        // nothing listens on the discard port
+        let mut sink = KafkaSink::new("127.0.0.1:9", "banking-trace").unwrap();
+        sink.write_event(&TimedTracedEvent(
+            std::time::SystemTime::now(),
+            TracedEvent::Heartbeat,
+        ))
+        .unwrap();
+        assert_matches!(sink.flush(), Err(TraceError::KafkaError(_)));
+    }
+}
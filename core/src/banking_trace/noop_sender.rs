@@ -0,0 +1,95 @@
+//! `TracedSender` compiled down to a plain `Sender` newtype with the `banking-trace-noop` feature,
+//! for operators who never trace and want the minimum cost on the hot path.
+//!
+//! Sends are forwarded to the channel as is: batches are never traced, and nothing but the depth
+//! is counted into `ChannelStatsSnapshot`. Send filters, rate limits, pausing and
+//! `OverflowPolicy::DropOldest` aren't available either. As with any feature, this applies to the
+//! whole build once enabled by any crate in it.
+
+use {
+    super::{ActiveTracer, ChannelLabel, ChannelStatsSnapshot, PacketDropReason},
+    agave_banking_stage_ingress_types::BankingPacketBatch,
+    crossbeam_channel::{SendError, Sender},
+    std::time::Duration,
+};
+
+#[repr(transparent)]
+pub struct TracedSender {
+    pub(super) sender: Sender<BankingPacketBatch>,
+}
+
+impl TracedSender {
+    pub(super) fn new(
+        _label: ChannelLabel,
+        sender: Sender<BankingPacketBatch>,
+        _active_tracer: Option<ActiveTracer>,
+    ) -> Self {
+        Self { sender }
+    }
+
+    #[inline]
+    pub fn send(&self, batch: BankingPacketBatch) -> Result<(), SendError<BankingPacketBatch>> {
+        self.sender.send(batch)
+    }
+
+    #[inline]
+    pub fn send_many(
+        &self,
+        batches: Vec<BankingPacketBatch>,
+    ) -> Result<(), SendError<BankingPacketBatch>> {
+        batches
+            .into_iter()
+            .try_for_each(|batch| self.sender.send(batch))
+    }
+
+    /// Only the depth is available.
+    pub fn stats(&self) -> ChannelStatsSnapshot {
+        ChannelStatsSnapshot {
+            depth: self.sender.len() as u64,
+            ..ChannelStatsSnapshot::default()
+        }
+    }
+
+    #[inline]
+    pub fn trace_packets_dropped(&self, _count: usize, _reason: PacketDropReason) {}
+
+    #[inline]
+    pub fn trace_sigverify_stats(
+        &self,
+        _verified: usize,
+        _failed: usize,
+        _dedup: usize,
+        _interval: Duration,
+    ) {
+    }
+
+    pub fn len(&self) -> usize {
+        self.sender.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::banking_trace::{for_test::sample_packet_batch, BankingTracer},
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_noop_sender() {
+        let (sender, receiver) = BankingTracer::new_disabled().create_channel_non_vote();
+        let batches = (0..3).map(|_| sample_packet_batch()).collect::<Vec<_>>();
+        sender.send(batches[0].clone()).unwrap();
+        sender.send_many(batches[1..].to_vec()).unwrap();
+        assert_eq!(sender.stats().depth, 3);
+        for batch in &batches {
+            assert!(Arc::ptr_eq(&receiver.try_recv().unwrap(), batch));
+        }
+        drop(receiver);
+        assert!(sender.send(sample_packet_batch()).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+//! `TraceSink` mapping trace events onto OpenTelemetry spans exported over OTLP, so that the
+//! banking ingress shows up in the same observability backend as the rest of the
+//! infrastructure.
+
+use {
+    super::{TimedTracedEvent, TraceError, TraceSink, TracedEvent, TransactionOutcome},
+    opentelemetry::{
+        trace::{Span as _, Status, TraceContextExt, Tracer as _, TracerProvider as _},
+        Context, KeyValue,
+    },
+    opentelemetry_otlp::WithExportConfig,
+    opentelemetry_sdk::{
+        trace::{SdkTracer, SdkTracerProvider, Span},
+        Resource,
+    },
+    solana_clock::Slot,
+    std::{collections::HashMap, io, time::SystemTime},
+};
+
+// Batches whose outcome is never traced (e.g. lost events) mustn't accumulate without bound.
+const MAX_OPEN_BATCH_SPANS: usize = 16 * 1024;
+
+/// Traces a `leader_slot` span for each of our leader slots, from the start to the end of its
+/// processing by the banking stage, with `BankFrozen` as an event. Within it, a
+/// `transaction_batch` span is traced for each batch from being scheduled to its outcome, with
+/// the counts of the outcomes as attributes and `CostLimitExceeded` as events.
+///
+/// Other events aren't exported.
+pub struct OtelSink {
+    provider: SdkTracerProvider,
+    tracer: SdkTracer,
+    slot_span: Option<(Slot, Span)>,
+    batch_spans: HashMap<(u32, u64), Span>,
+}
+
+impl OtelSink {
+    /// Exports over OTLP/HTTP to `endpoint`, e.g. `http://collector:4318/v1/traces`, in batches
+    /// from a thread of the exporter.
+    pub fn new(endpoint: &str) -> Result<Self, TraceError> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(io::Error::other)?;
+        info!("exporting banking trace spans to {endpoint}");
+        Ok(Self::with_provider(
+            SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(
+                    Resource::builder()
+                        .with_service_name("solana-banking-trace")
+                        .build(),
+                )
+                .build(),
+        ))
+    }
+
+    fn with_provider(provider: SdkTracerProvider) -> Self {
+        Self {
+            tracer: provider.tracer("banking_trace"),
+            provider,
+            slot_span: None,
+            batch_spans: HashMap::default(),
+        }
+    }
+
+    fn start_slot_span(&mut self, time: SystemTime, slot: Slot) {
+        self.end_slot_span(time);
+        let span = self
+            .tracer
+            .span_builder("leader_slot")
+            .with_start_time(time)
+            .with_attributes([KeyValue::new("slot", slot as i64)])
+            .start(&self.tracer);
+        self.slot_span = Some((slot, span));
+    }
+
+    fn end_slot_span(&mut self, time: SystemTime) {
+        if let Some((_slot, mut span)) = self.slot_span.take() {
+            span.end_with_timestamp(time);
+        }
+    }
+
+    fn start_batch_span(
+        &mut self,
+        time: SystemTime,
+        worker_id: u32,
+        batch_id: u64,
+        transaction_count: usize,
+    ) {
+        if self.batch_spans.len() >= MAX_OPEN_BATCH_SPANS {
+            return;
+        }
+        let parent_context = self
+            .slot_span
+            .as_ref()
+            .map(|(_slot, span)| {
+                Context::new().with_remote_span_context(span.span_context().clone())
+            })
+            .unwrap_or_default();
+        let span = self
+            .tracer
+            .span_builder("transaction_batch")
+            .with_start_time(time)
+            .with_attributes([
+                KeyValue::new("worker_id", i64::from(worker_id)),
+                KeyValue::new("batch_id", batch_id as i64),
+                KeyValue::new("transaction_count", transaction_count as i64),
+            ])
+            .start_with_context(&self.tracer, &parent_context);
+        self.batch_spans.insert((worker_id, batch_id), span);
+    }
+}
+
+impl TraceSink for OtelSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        let TimedTracedEvent(time, traced_event) = event;
+        let time = *time;
+        match traced_event {
+            TracedEvent::LeaderSlotBoundary { slot, start: true } => {
+                self.start_slot_span(time, *slot)
+            }
+            TracedEvent::LeaderSlotBoundary { slot, start: false } => {
+                if matches!(self.slot_span, Some((span_slot, _)) if span_slot == *slot) {
+                    self.end_slot_span(time);
+                }
+            }
+            TracedEvent::BankFrozen {
+                slot,
+                tick_height,
+                transaction_count,
+            } => {
+                if let Some((_, span)) = self
+                    .slot_span
+                    .as_mut()
+                    .filter(|(span_slot, _)| span_slot == slot)
+                {
+                    span.add_event_with_timestamp(
+                        "BankFrozen",
+                        time,
+                        vec![
+                            KeyValue::new("tick_height", *tick_height as i64),
+                            KeyValue::new("transaction_count", *transaction_count as i64),
+                        ],
+                    );
+                }
+            }
+            TracedEvent::TransactionBatchScheduled {
+                worker_id,
+                batch_id,
+                signatures,
+            } => self.start_batch_span(time, *worker_id, *batch_id, signatures.len()),
+            TracedEvent::CostLimitExceeded {
+                worker_id,
+                batch_id,
+                rejections,
+                ..
+            } => {
+                if let Some(span) = self.batch_spans.get_mut(&(*worker_id, *batch_id)) {
+                    span.add_event_with_timestamp(
+                        "CostLimitExceeded",
+                        time,
+                        vec![KeyValue::new("rejected_count", rejections.len() as i64)],
+                    );
+                }
+            }
+            TracedEvent::TransactionBatchOutcome {
+                worker_id,
+                batch_id,
+                outcomes,
+                error,
+            } => {
+                if let Some(mut span) = self.batch_spans.remove(&(*worker_id, *batch_id)) {
+                    let count = |outcome| outcomes.iter().filter(|o| **o == outcome).count() as i64;
+                    span.set_attributes([
+                        KeyValue::new("committed_count", count(TransactionOutcome::Committed)),
+                        KeyValue::new("retryable_count", count(TransactionOutcome::Retryable)),
+                        KeyValue::new("dropped_count", count(TransactionOutcome::Dropped)),
+                    ]);
+                    if let Some(error) = error {
+                        span.set_status(Status::error(format!("{error:?}")));
+                    }
+                    span.end_with_timestamp(time);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.provider.force_flush().map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+impl Drop for OtelSink {
+    fn drop(&mut self) {
+        let now = SystemTime::now();
+        self.end_slot_span(now);
+        for (_, mut span) in self.batch_spans.drain() {
+            span.end_with_timestamp(now);
+        }
+        if let Err(err) = self.provider.shutdown() {
+            warn!("failed to shut down banking trace span exporter: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::BatchErrorClass,
+        opentelemetry::trace::SpanId,
+        opentelemetry_sdk::{
+            error::OTelSdkResult,
+            trace::{SpanData, SpanExporter},
+        },
+        solana_signature::Signature,
+        std::sync::{Arc, Mutex},
+    };
+
+    #[derive(Debug, Default, Clone)]
+    struct VecExporter(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for VecExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            self.0.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slot_and_batch_spans() {
+        let exporter = VecExporter::default();
+        let mut sink = OtelSink::with_provider(
+            SdkTracerProvider::builder()
+                .with_simple_exporter(exporter.clone())
+                .build(),
+        );
+        let scheduled = |batch_id| TracedEvent::TransactionBatchScheduled {
+            worker_id: 1,
+            batch_id,
+            signatures: vec![Signature::default(); 2],
+        };
+        let outcome = |batch_id, error| TracedEvent::TransactionBatchOutcome {
+            worker_id: 1,
+            batch_id,
+            outcomes: vec![TransactionOutcome::Committed, TransactionOutcome::Retryable],
+            error,
+        };
+        for event in [
+            TracedEvent::LeaderSlotBoundary {
+                slot: 5,
+                start: true,
+            },
+            scheduled(1),
+            scheduled(2),
+            outcome(1, None),
+            outcome(2, Some(BatchErrorClass::MaxHeightReached)),
+            TracedEvent::BankFrozen {
+                slot: 5,
+                tick_height: 64,
+                transaction_count: 1,
+            },
+            TracedEvent::LeaderSlotBoundary {
+                slot: 5,
+                start: false,
+            },
+        ] {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), event))
+                .unwrap();
+        }
+        drop(sink);
+
+        let spans = exporter.0.lock().unwrap();
+        let [batch1, batch2, slot] = &spans[..] else {
+            panic!("unexpected spans: {spans:?}");
+        };
+        assert_eq!(slot.name, "leader_slot");
+        assert_eq!(slot.parent_span_id, SpanId::INVALID);
+        assert!(slot.attributes.contains(&KeyValue::new("slot", 5_i64)));
+        assert_eq!(slot.events.events[0].name, "BankFrozen");
+        for batch in [batch1, batch2] {
+            assert_eq!(batch.name, "transaction_batch");
+            assert_eq!(batch.parent_span_id, slot.span_context.span_id());
+            assert!(batch
+                .attributes
+                .contains(&KeyValue::new("committed_count", 1_i64)));
+        }
+        assert_eq!(batch1.status, Status::Unset);
+        assert_eq!(batch2.status, Status::error("MaxHeightReached"));
+    }
+}
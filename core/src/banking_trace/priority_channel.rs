@@ -0,0 +1,196 @@
+//! Two-lane variant of the banking channels, whose receiver drains the high priority lane (e.g.
+//! votes or transactions from an allowlist of fee payers) before the low priority one, so that
+//! consumers get prioritization built in instead of a single FIFO.
+//!
+//! Each batch is traced as `TracedSender` does, followed by a `PacketBatchLane` event with the
+//! lane it was sent to. Like the async channels, the depths of these channels aren't sampled
+//! into the trace as `ChannelDepths`.
+
+use {
+    super::{
+        ActiveTracer, BankingTracer, ChannelLabel, PriorityLane, TimedTracedEvent, TracedEvent,
+    },
+    agave_banking_stage_ingress_types::BankingPacketBatch,
+    crossbeam_channel::{
+        select_biased, unbounded, Receiver, RecvError, SendError, Sender, TryRecvError,
+    },
+};
+
+pub struct PriorityTracedSender {
+    label: ChannelLabel,
+    high_sender: Sender<BankingPacketBatch>,
+    low_sender: Sender<BankingPacketBatch>,
+    active_tracer: Option<ActiveTracer>,
+}
+
+impl PriorityTracedSender {
+    pub fn send(
+        &self,
+        batch: BankingPacketBatch,
+        lane: PriorityLane,
+    ) -> Result<(), SendError<BankingPacketBatch>> {
+        if let Some(active_tracer) = &self.active_tracer {
+            if active_tracer
+                .trace_packet_batch(self.label, &batch)
+                .is_err()
+            {
+                return Err(SendError(batch));
+            }
+            active_tracer.trace_event(|| {
+                TimedTracedEvent(
+                    active_tracer.now(),
+                    TracedEvent::PacketBatchLane {
+                        label: self.label,
+                        lane,
+                    },
+                )
+            });
+        }
+        match lane {
+            PriorityLane::High => self.high_sender.send(batch),
+            PriorityLane::Low => self.low_sender.send(batch),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.high_sender.len() + self.low_sender.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Receives from the high priority lane as long as it isn't empty. The lanes are disconnected
+/// together, once the `PriorityTracedSender` is dropped.
+#[derive(Clone)]
+pub struct PriorityBankingPacketReceiver {
+    high_receiver: Receiver<BankingPacketBatch>,
+    low_receiver: Receiver<BankingPacketBatch>,
+}
+
+impl PriorityBankingPacketReceiver {
+    pub fn recv(&self) -> Result<BankingPacketBatch, RecvError> {
+        if let Ok(batch) = self.high_receiver.try_recv() {
+            return Ok(batch);
+        }
+        // Either lane being disconnected means the other one is only left to be drained.
+        select_biased! {
+            recv(self.high_receiver) -> batch => {
+                batch.or_else(|RecvError| self.low_receiver.recv())
+            }
+            recv(self.low_receiver) -> batch => {
+                batch.or_else(|RecvError| self.high_receiver.recv())
+            }
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<BankingPacketBatch, TryRecvError> {
+        self.high_receiver
+            .try_recv()
+            .or_else(|_| self.low_receiver.try_recv())
+    }
+
+    pub fn lane_len(&self, lane: PriorityLane) -> usize {
+        match lane {
+            PriorityLane::High => self.high_receiver.len(),
+            PriorityLane::Low => self.low_receiver.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.high_receiver.len() + self.low_receiver.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl BankingTracer {
+    pub fn create_priority_channel(
+        &self,
+        label: ChannelLabel,
+    ) -> (PriorityTracedSender, PriorityBankingPacketReceiver) {
+        let (high_sender, high_receiver) = unbounded();
+        let (low_sender, low_receiver) = unbounded();
+        (
+            PriorityTracedSender {
+                label,
+                high_sender,
+                low_sender,
+                active_tracer: Some(self.active_tracer.clone()),
+            },
+            PriorityBankingPacketReceiver {
+                high_receiver,
+                low_receiver,
+            },
+        )
+    }
+
+    pub fn priority_channel_for_test() -> (PriorityTracedSender, PriorityBankingPacketReceiver) {
+        Self::new_disabled().create_priority_channel(ChannelLabel::Dummy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+        },
+        solana_perf::packet::{PacketBatch, PinnedPacketBatch},
+        std::{
+            sync::{atomic::AtomicBool, Arc},
+            time::Duration,
+        },
+    };
+
+    fn batch_of_len(len: usize) -> BankingPacketBatch {
+        BankingPacketBatch::new(vec![PacketBatch::from(PinnedPacketBatch::default()); len])
+    }
+
+    #[test]
+    fn test_high_lane_drained_first() {
+        let (sender, receiver) = BankingTracer::priority_channel_for_test();
+        sender.send(batch_of_len(1), PriorityLane::Low).unwrap();
+        sender.send(batch_of_len(2), PriorityLane::High).unwrap();
+        sender.send(batch_of_len(3), PriorityLane::Low).unwrap();
+        sender.send(batch_of_len(4), PriorityLane::High).unwrap();
+        assert_eq!(receiver.lane_len(PriorityLane::High), 2);
+        assert_eq!(receiver.len(), 4);
+
+        assert_eq!(receiver.recv().unwrap().len(), 2);
+        assert_eq!(receiver.try_recv().unwrap().len(), 4);
+        assert_eq!(receiver.recv().unwrap().len(), 1);
+        drop(sender);
+        assert_eq!(receiver.recv().unwrap().len(), 3);
+        assert_eq!(receiver.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_lane_traced() {
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = sink.recent_events();
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit).unwrap();
+        let (sender, _receiver) = tracer.create_priority_channel(ChannelLabel::TpuVote);
+        sender
+            .send(sample_packet_batch(), PriorityLane::High)
+            .unwrap();
+        drop((sender, tracer));
+        tracer_thread.unwrap().join().unwrap().unwrap();
+
+        let lanes = recent_events
+            .recent_events(&EventFilter::default())
+            .into_iter()
+            .filter_map(|TimedTracedEvent(_, event)| match event {
+                TracedEvent::PacketBatchLane { label, lane } => Some((label, lane)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lanes, [(ChannelLabel::TpuVote, PriorityLane::High)]);
+    }
+}
@@ -0,0 +1,298 @@
+//! `TraceSink` deriving metrics from trace events and exposing them in the Prometheus text
+//! format, so that tracing doubles as monitoring of the banking ingress.
+
+use {
+    super::{ChannelLabel, PacketDropReason, TimedTracedEvent, TraceError, TraceSink, TracedEvent},
+    std::{
+        collections::BTreeMap,
+        fmt::Write as _,
+        io::{self, Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{self, JoinHandle},
+        time::Duration,
+    },
+};
+
+// Scrapers which don't send their request or read the response within this are disconnected.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Counters and gauges derived from the trace events, shared with the exporter.
+#[derive(Debug, Default)]
+pub struct TracerMetrics {
+    state: Mutex<MetricsState>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    events: BTreeMap<&'static str, u64>,
+    bytes: u64,
+    packet_batches: BTreeMap<ChannelLabel, u64>,
+    packets: BTreeMap<ChannelLabel, u64>,
+    channel_depths: BTreeMap<ChannelLabel, u64>,
+    dropped_packets: BTreeMap<(ChannelLabel, PacketDropReason), u64>,
+    lost_events: u64,
+}
+
+impl TracerMetrics {
+    pub fn record(&self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        let TimedTracedEvent(_, traced_event) = event;
+        let bytes = bincode::serialized_size(event)?;
+        let mut state = self.state.lock().unwrap();
+        *state.events.entry(traced_event.kind()).or_default() += 1;
+        state.bytes = state.bytes.saturating_add(bytes);
+        match traced_event {
+            TracedEvent::PacketBatch(label, batch) => {
+                *state.packet_batches.entry(*label).or_default() += 1;
+                *state.packets.entry(*label).or_default() +=
+                    batch.iter().map(|batch| batch.len() as u64).sum::<u64>();
+            }
+            TracedEvent::ChannelDepths { depths } => {
+                state.channel_depths.extend(depths.iter().copied())
+            }
+            TracedEvent::PacketsDropped {
+                label,
+                count,
+                reason,
+            } => *state.dropped_packets.entry((*label, *reason)).or_default() += count,
+            TracedEvent::TraceGap { dropped_count, .. } => state.lost_events += dropped_count,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Encodes the metrics in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut text = String::new();
+        // writing into String never fails
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, u64)>| {
+            let _ = writeln!(text, "# HELP {name} {help}\n# TYPE {name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(text, "{name}{labels} {value}");
+            }
+        };
+        let by_label = |values: &BTreeMap<ChannelLabel, u64>| {
+            values
+                .iter()
+                .map(|(label, value)| (format!("{{label=\"{label:?}\"}}"), *value))
+                .collect::<Vec<_>>()
+        };
+        metric(
+            "banking_trace_events_total",
+            "counter",
+            "Traced events by kind.",
+            state
+                .events
+                .iter()
+                .map(|(kind, count)| (format!("{{kind=\"{kind}\"}}"), *count))
+                .collect(),
+        );
+        metric(
+            "banking_trace_bytes_total",
+            "counter",
+            "Serialized size of the traced events.",
+            vec![(String::new(), state.bytes)],
+        );
+        metric(
+            "banking_trace_packet_batches_total",
+            "counter",
+            "Traced packet batches by channel.",
+            by_label(&state.packet_batches),
+        );
+        metric(
+            "banking_trace_packets_total",
+            "counter",
+            "Packets of the traced packet batches by channel.",
+            by_label(&state.packets),
+        );
+        metric(
+            "banking_trace_channel_depth",
+            "gauge",
+            "Batches waiting in each channel as last sampled.",
+            by_label(&state.channel_depths),
+        );
+        metric(
+            "banking_trace_dropped_packets_total",
+            "counter",
+            "Packets dropped before making it into a block by channel and reason.",
+            state
+                .dropped_packets
+                .iter()
+                .map(|((label, reason), count)| {
+                    (
+                        format!("{{label=\"{label:?}\",reason=\"{reason:?}\"}}"),
+                        *count,
+                    )
+                })
+                .collect(),
+        );
+        metric(
+            "banking_trace_lost_events_total",
+            "counter",
+            "Events which failed to be written, as recorded by trace gaps.",
+            vec![(String::new(), state.lost_events)],
+        );
+        text
+    }
+}
+
+/// Maintains `TracerMetrics` from the events and serves them over HTTP at `addr` for any path,
+/// unless `addr` is `None`, in which case they're only accessible with `metrics()`, e.g. to be
+/// exported with another registry.
+pub struct PrometheusSink {
+    metrics: Arc<TracerMetrics>,
+    exporter: Option<(SocketAddr, Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl PrometheusSink {
+    pub fn new(addr: Option<SocketAddr>) -> Result<Self, TraceError> {
+        let metrics = Arc::<TracerMetrics>::default();
+        let exporter = addr
+            .map(|addr| -> Result<_, TraceError> {
+                let listener = TcpListener::bind(addr)?;
+                let local_addr = listener.local_addr()?;
+                let exit = Arc::<AtomicBool>::default();
+                let exporter_thread =
+                    thread::Builder::new().name("solBnkTrcProm".into()).spawn({
+                        let metrics = metrics.clone();
+                        let exit = exit.clone();
+                        move || Self::serve(listener, &metrics, &exit)
+                    })?;
+                info!("exporting banking trace metrics at http://{local_addr}/metrics");
+                Ok((local_addr, exit, exporter_thread))
+            })
+            .transpose()?;
+        Ok(Self { metrics, exporter })
+    }
+
+    pub fn metrics(&self) -> Arc<TracerMetrics> {
+        self.metrics.clone()
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.exporter.as_ref().map(|(addr, ..)| *addr)
+    }
+
+    fn serve(listener: TcpListener, metrics: &TracerMetrics, exit: &AtomicBool) {
+        for stream in listener.incoming() {
+            if exit.load(Ordering::Relaxed) {
+                break;
+            }
+            if let Err(err) = stream.and_then(|stream| Self::respond(stream, metrics)) {
+                debug!("failed to export banking trace metrics: {err:?}");
+            }
+        }
+    }
+
+    fn respond(mut stream: TcpStream, metrics: &TracerMetrics) -> io::Result<()> {
+        stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+        stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+        // Whatever the request is, it's answered with the metrics once its head has been read.
+        let mut request = vec![];
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let len = stream.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..len]);
+        }
+        let body = metrics.encode();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+             {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+}
+
+impl TraceSink for PrometheusSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        self.metrics.record(event)
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+impl Drop for PrometheusSink {
+    fn drop(&mut self) {
+        if let Some((addr, exit, exporter_thread)) = self.exporter.take() {
+            exit.store(true, Ordering::Relaxed);
+            // wake up the exporter thread blocked on accepting
+            let _ = TcpStream::connect_timeout(&addr, SCRAPE_TIMEOUT);
+            if let Err(err) = exporter_thread.join() {
+                warn!("banking trace metrics exporter panicked: {err:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::for_test,
+        std::{net::Shutdown, time::SystemTime},
+    };
+
+    fn scrape(addr: SocketAddr) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_export_metrics() {
+        let mut sink = PrometheusSink::new(Some("127.0.0.1:0".parse().unwrap())).unwrap();
+        for event in [
+            TracedEvent::PacketBatch(ChannelLabel::NonVote, for_test::sample_packet_batch()),
+            TracedEvent::PacketBatch(ChannelLabel::NonVote, for_test::sample_packet_batch()),
+            TracedEvent::PacketsDropped {
+                label: ChannelLabel::TpuVote,
+                count: 3,
+                reason: PacketDropReason::Dedup,
+            },
+            TracedEvent::ChannelDepths {
+                depths: vec![(ChannelLabel::NonVote, 7)],
+            },
+            TracedEvent::TraceGap {
+                dropped_count: 2,
+                span: Duration::ZERO,
+            },
+        ] {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), event))
+                .unwrap();
+        }
+
+        let response = scrape(sink.local_addr().unwrap());
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        for sample in [
+            "banking_trace_events_total{kind=\"PacketBatch\"} 2",
+            "banking_trace_packet_batches_total{label=\"NonVote\"} 2",
+            "banking_trace_packets_total{label=\"NonVote\"} 8",
+            "banking_trace_channel_depth{label=\"NonVote\"} 7",
+            "banking_trace_dropped_packets_total{label=\"TpuVote\",reason=\"Dedup\"} 3",
+            "banking_trace_lost_events_total 2",
+        ] {
+            assert!(response.lines().any(|line| line == sample), "{sample}");
+        }
+        let bytes = response
+            .lines()
+            .find_map(|line| line.strip_prefix("banking_trace_bytes_total "))
+            .unwrap();
+        assert!(bytes.parse::<u64>().unwrap() > 0);
+        drop(sink);
+    }
+}
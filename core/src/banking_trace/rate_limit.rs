@@ -0,0 +1,166 @@
+//! Token-bucket rate limiting of `TracedSender`, so that a misbehaving upstream source can be
+//! contained at the channel boundary. Batches over the limit are dropped before being traced as
+//! `PacketBatch`, and traced as `PacketsDropped` with `PacketDropReason::RateLimited` instead.
+#![cfg_attr(feature = "banking-trace-noop", allow(dead_code))]
+
+use {
+    super::reader::TraceCounts,
+    std::time::{Duration, Instant},
+};
+
+/// Limits per second, each of which allows bursts of up to a second worth of its rate.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub packets_per_sec: Option<u64>,
+    pub bytes_per_sec: Option<u64>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+}
+
+impl TokenBucket {
+    fn new(rate: u64) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.tokens = (self.tokens + self.rate * elapsed.as_secs_f64()).min(self.rate);
+    }
+
+    /// Costs beyond the burst are capped, so that a huge batch passes once the bucket is full
+    /// rather than never.
+    fn has(&self, cost: u64) -> bool {
+        self.tokens >= (cost as f64).min(self.rate)
+    }
+
+    fn take(&mut self, cost: u64) {
+        self.tokens -= (cost as f64).min(self.rate);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    packet_bucket: Option<TokenBucket>,
+    byte_bucket: Option<TokenBucket>,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            packet_bucket: limit.packets_per_sec.map(TokenBucket::new),
+            byte_bucket: limit.bytes_per_sec.map(TokenBucket::new),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Whether a batch of `counts` is within the limits as of `now`, consuming the tokens if so.
+    pub(crate) fn try_acquire(&mut self, counts: &TraceCounts, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = now;
+        let buckets = [
+            (self.packet_bucket.as_mut(), counts.packet_count),
+            (self.byte_bucket.as_mut(), counts.byte_count),
+        ];
+        let mut buckets = buckets
+            .into_iter()
+            .filter_map(|(bucket, cost)| bucket.map(|bucket| (bucket, cost)))
+            .collect::<Vec<_>>();
+        for (bucket, _) in &mut buckets {
+            bucket.refill(elapsed);
+        }
+        let is_acquired = buckets.iter().all(|(bucket, cost)| bucket.has(*cost));
+        if is_acquired {
+            for (bucket, cost) in buckets {
+                bucket.take(cost);
+            }
+        }
+        is_acquired
+    }
+}
+
+#[cfg(all(test, not(feature = "banking-trace-noop")))]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+            BankingTracer, PacketDropReason, TimedTracedEvent, TracedEvent,
+        },
+        std::sync::{atomic::AtomicBool, Arc},
+    };
+
+    fn counts(packet_count: u64, byte_count: u64) -> TraceCounts {
+        TraceCounts {
+            batch_count: 1,
+            packet_count,
+            byte_count,
+        }
+    }
+
+    #[test]
+    fn test_token_buckets() {
+        let mut limiter = RateLimiter::new(RateLimit {
+            packets_per_sec: Some(10),
+            bytes_per_sec: Some(1_000),
+        });
+        let start = limiter.last_refill;
+        assert!(limiter.try_acquire(&counts(6, 100), start));
+        // short of packets
+        assert!(!limiter.try_acquire(&counts(6, 100), start));
+        // short of bytes, without consuming packets
+        assert!(!limiter.try_acquire(&counts(4, 901), start));
+        assert!(limiter.try_acquire(&counts(4, 900), start));
+
+        // half of the rates are refilled after half a second
+        let later = start + Duration::from_millis(500);
+        assert!(!limiter.try_acquire(&counts(6, 0), later));
+        assert!(limiter.try_acquire(&counts(5, 500), later));
+
+        // a batch beyond the burst passes once the bucket is full
+        let much_later = later + Duration::from_secs(10);
+        assert!(limiter.try_acquire(&counts(100, 0), much_later));
+        assert!(!limiter.try_acquire(&counts(1, 0), much_later));
+    }
+
+    #[test]
+    fn test_throttled_batches_traced() {
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = sink.recent_events();
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit).unwrap();
+        let (sender, receiver) = tracer.create_channel_non_vote();
+        let packet_count = TraceCounts::from_batch(&sample_packet_batch()).packet_count;
+        let sender = sender.with_rate_limit(RateLimit {
+            packets_per_sec: Some(packet_count),
+            bytes_per_sec: None,
+        });
+        sender.send(sample_packet_batch()).unwrap();
+        sender.send(sample_packet_batch()).unwrap();
+        assert_eq!(receiver.len(), 1);
+        assert_eq!(sender.stats().throttled_batches, 1);
+
+        drop((sender, tracer));
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        let kinds = recent_events
+            .recent_events(&EventFilter::default())
+            .into_iter()
+            .filter_map(|TimedTracedEvent(_, event)| match event {
+                TracedEvent::PacketBatch(..) => Some(None),
+                TracedEvent::PacketsDropped { count, reason, .. } => Some(Some((count, reason))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [None, Some((packet_count, PacketDropReason::RateLimited))]
+        );
+    }
+}
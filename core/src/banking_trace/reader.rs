@@ -0,0 +1,1319 @@
+//! Reading of trace files written by `BankingTracer`, both in the recorded order and in the
+//! reverse (newest-first) order. Files written by upstream Agave validators are recognized and
+//! read as well.
+
+use {
+    super::{
+        ChannelLabel, TimedTracedEvent, TraceError, TracedEvent, BASENAME, FRAME_LEN_SIZE,
+        TRACE_FILE_MAGIC, TRACE_INDEX_MAGIC,
+    },
+    agave_banking_stage_ingress_types::BankingPacketBatch,
+    itertools::Either,
+    solana_clock::Slot,
+    solana_hash::Hash,
+    std::{
+        collections::{BTreeMap, HashSet},
+        ffi::OsString,
+        fs::{read_dir, File},
+        io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+        iter,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        sync::OnceLock,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    strum_macros::EnumString,
+};
+
+const HEADER_SIZE: u64 = TRACE_FILE_MAGIC.len() as u64;
+const FRAME_OVERHEAD: u64 = 2 * FRAME_LEN_SIZE as u64;
+// The event count and the magic at the very end of the index footer
+const INDEX_TRAILER_SIZE: u64 = (size_of::<u64>() + TRACE_INDEX_MAGIC.len()) as u64;
+
+pub type TraceReadResult = Result<TimedTracedEvent, TraceError>;
+pub type CountsByLabel = BTreeMap<ChannelLabel, TraceCounts>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceCounts {
+    pub batch_count: u64,
+    pub packet_count: u64,
+    pub byte_count: u64,
+}
+
+impl TraceCounts {
+    pub fn from_batch(batch: &BankingPacketBatch) -> Self {
+        Self {
+            batch_count: 1,
+            packet_count: batch.iter().map(|batch| batch.len() as u64).sum(),
+            byte_count: batch
+                .iter()
+                .flat_map(|batch| batch.iter())
+                .map(|packet| packet.meta().size as u64)
+                .sum(),
+        }
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        self.batch_count += other.batch_count;
+        self.packet_count += other.packet_count;
+        self.byte_count += other.byte_count;
+    }
+}
+
+/// The number of non-discarded packets which are simple vote transactions and the others.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VoteCounts {
+    pub simple_votes: u64,
+    pub others: u64,
+}
+
+impl VoteCounts {
+    pub fn from_batch(batch: &BankingPacketBatch) -> Self {
+        let mut counts = Self::default();
+        for packet in batch.iter().flat_map(|batch| batch.iter()) {
+            if packet.meta().discard() {
+                continue;
+            }
+            if packet.meta().is_simple_vote_tx() {
+                counts.simple_votes += 1;
+            } else {
+                counts.others += 1;
+            }
+        }
+        counts
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        self.simple_votes += other.simple_votes;
+        self.others += other.others;
+    }
+}
+
+/// Aggregated counts of traced `PacketBatch` events.
+#[derive(Debug, Default)]
+pub struct TraceSummary {
+    pub total: CountsByLabel,
+    /// Only of the vote channels.
+    pub votes: BTreeMap<ChannelLabel, VoteCounts>,
+    /// The number of non-discarded packets by their source address and whether it's staked.
+    pub sources: BTreeMap<ChannelLabel, BTreeMap<(SocketAddr, bool), u64>>,
+    /// Keyed by whole seconds since the UNIX epoch of the event time.
+    pub by_second: BTreeMap<u64, CountsByLabel>,
+    /// Keyed by the slot of the most recent preceding `BlockAndBankHash` event; `None` for
+    /// batches traced before any of it.
+    pub by_slot: BTreeMap<Option<Slot>, CountsByLabel>,
+    last_slot: Option<Slot>,
+}
+
+impl TraceSummary {
+    fn add_event(&mut self, TimedTracedEvent(event_time, event): &TimedTracedEvent) {
+        match event {
+            TracedEvent::PacketBatch(label, batch) => {
+                let counts = TraceCounts::from_batch(batch);
+                let second = event_time
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_secs())
+                    .unwrap_or_default();
+
+                for counts_by_label in [
+                    &mut self.total,
+                    self.by_second.entry(second).or_default(),
+                    self.by_slot.entry(self.last_slot).or_default(),
+                ] {
+                    counts_by_label.entry(*label).or_default().add(&counts);
+                }
+                let sources = self.sources.entry(*label).or_default();
+                for packet in batch.iter().flat_map(|batch| batch.iter()) {
+                    let meta = packet.meta();
+                    if !meta.discard() {
+                        *sources
+                            .entry((meta.socket_addr(), meta.is_from_staked_node()))
+                            .or_default() += 1;
+                    }
+                }
+                if matches!(label, ChannelLabel::TpuVote | ChannelLabel::GossipVote) {
+                    self.votes
+                        .entry(*label)
+                        .or_default()
+                        .add(&VoteCounts::from_batch(batch));
+                }
+            }
+            TracedEvent::BlockAndBankHash(slot, _, _) => {
+                self.last_slot = Some(*slot);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads trace events from a set of rotated trace files.
+///
+/// Files are held in the chronological order (i.e. oldest first), which is the reverse of the
+/// rotation numbering (`events.N`, ..., `events.1`, `events`).
+#[derive(Debug)]
+pub struct TraceReader {
+    event_file_paths: Vec<PathBuf>,
+    index: OnceLock<TraceIndex>,
+}
+
+// Event boundaries of all files, built on the first random access. Framed files are indexed
+// by their index footers if any, or only by their frame lengths without decoding any event.
+#[derive(Debug)]
+struct TraceIndex {
+    files: Vec<IndexedFile>,
+    // The global index of the first event of each file
+    first_event_indexes: Vec<usize>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct IndexedFile {
+    format: TraceFormat,
+    boundaries: Vec<u64>,
+}
+
+impl TraceIndex {
+    fn build(event_file_paths: &[PathBuf]) -> Result<Self, TraceError> {
+        let mut files = Vec::with_capacity(event_file_paths.len());
+        let mut first_event_indexes = Vec::with_capacity(event_file_paths.len());
+        let mut len = 0;
+        for path in event_file_paths {
+            let (mut file, format) = open_seekable(path)?;
+            let boundaries = event_boundaries(&mut file, format)?;
+            first_event_indexes.push(len);
+            len += boundaries.len().saturating_sub(1);
+            files.push(IndexedFile { format, boundaries });
+        }
+
+        Ok(Self {
+            files,
+            first_event_indexes,
+            len,
+        })
+    }
+
+    // Returns the position of the file containing the `index`-th event and the position of the
+    // event in it.
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        // skip over empty files sharing the same first event index.
+        let file_position = self
+            .first_event_indexes
+            .partition_point(|&first_event_index| first_event_index <= index)
+            - 1;
+        Some((
+            file_position,
+            index - self.first_event_indexes[file_position],
+        ))
+    }
+}
+
+impl TraceReader {
+    pub fn new(event_file_paths: Vec<PathBuf>) -> Self {
+        Self {
+            event_file_paths,
+            index: OnceLock::new(),
+        }
+    }
+
+    /// Discovers the trace files in `path` as laid out by `BankingTracer`. Rotated files are
+    /// followed only as long as their numbering is contiguous. Each of them can be compressed
+    /// separately, named with the extension of `TraceCompression` appended.
+    pub fn open_dir(path: impl AsRef<Path>) -> Result<Self, TraceError> {
+        Self::open_dir_with_basename(path, BASENAME)
+    }
+
+    /// Like `open_dir()`, but for the trace files written with `basename` by
+    /// `RollingFileSink`. Files renamed after its template aren't discovered; they can be read
+    /// by passing them to `new()` instead.
+    pub fn open_dir_with_basename(
+        path: impl AsRef<Path>,
+        basename: &str,
+    ) -> Result<Self, TraceError> {
+        let path = path.as_ref();
+        let mut entry_names = read_dir(path)?
+            .flat_map(|entry| entry.ok().map(|entry| entry.file_name()))
+            .collect::<HashSet<OsString>>();
+
+        let mut event_file_paths = vec![];
+        for index in 0.. {
+            let event_file_name = rotated_file_name(basename, index);
+            let Some(event_file_name) = iter::once(OsString::from(&event_file_name))
+                .chain(TraceCompression::ALL.iter().map(|compression| {
+                    OsString::from(format!("{event_file_name}.{}", compression.extension()))
+                }))
+                .find(|event_file_name| entry_names.remove(event_file_name))
+            else {
+                break;
+            };
+            event_file_paths.push(path.join(event_file_name));
+        }
+        event_file_paths.reverse();
+
+        Ok(Self::new(event_file_paths))
+    }
+
+    pub fn event_file_paths(&self) -> &[PathBuf] {
+        &self.event_file_paths
+    }
+
+    /// Iterates over all events from the oldest to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = TraceReadResult> + '_ {
+        self.event_file_paths
+            .iter()
+            .flat_map(|path| match TraceFileReader::open(path) {
+                Ok(reader) => Either::Left(reader),
+                Err(err) => Either::Right(iter::once(Err(err))),
+            })
+    }
+
+    /// Iterates over events like `iter()`, but resuming right after `position` if specified.
+    /// Each event is paired with the position right after it.
+    pub fn iter_from(
+        &self,
+        position: Option<&TracePosition>,
+    ) -> impl Iterator<Item = Result<(TimedTracedEvent, TracePosition), TraceError>> + '_ {
+        let (file_position, offset) = match position {
+            None => (0, None),
+            Some(TracePosition {
+                event_file_path,
+                offset,
+            }) => match self
+                .event_file_paths
+                .iter()
+                .position(|path| path == event_file_path)
+            {
+                Some(file_position) => (file_position, Some(*offset)),
+                None => {
+                    let err = TraceError::UnknownEventFile(event_file_path.clone());
+                    return Either::Right(iter::once(Err(err)));
+                }
+            },
+        };
+
+        Either::Left(
+            self.event_file_paths[file_position..]
+                .iter()
+                .zip(iter::once(offset).chain(iter::repeat(None)))
+                .flat_map(|(path, offset)| {
+                    let reader = match offset {
+                        Some(offset) => TraceFileReader::open_at(path, offset),
+                        None => TraceFileReader::open(path),
+                    };
+                    match reader {
+                        Ok(mut reader) => Either::Left(iter::from_fn(move || {
+                            let event = reader.next()?;
+                            Some(event.map(|event| (event, reader.position())))
+                        })),
+                        Err(err) => Either::Right(iter::once(Err(err))),
+                    }
+                }),
+        )
+    }
+
+    fn index(&self) -> Result<&TraceIndex, TraceError> {
+        if let Some(index) = self.index.get() {
+            return Ok(index);
+        }
+        let index = TraceIndex::build(&self.event_file_paths)?;
+        Ok(self.index.get_or_init(|| index))
+    }
+
+    /// Returns the number of complete events across all files.
+    ///
+    /// This is only a hint as events appended after the first call of either this or `nth()`
+    /// aren't accounted for. Only the files without an index footer, such as the one still
+    /// being written, are scanned for their events.
+    pub fn len_hint(&self) -> Result<usize, TraceError> {
+        Ok(self.index()?.len)
+    }
+
+    /// Returns the `index`-th event counted from the oldest one, or `None` if it's out of range.
+    ///
+    /// Each call decodes only the requested event, so this is suitable for bisecting a trace
+    /// along with `len_hint()`. Compressed files aren't supported.
+    pub fn nth(&self, index: usize) -> Option<TraceReadResult> {
+        let trace_index = match self.index() {
+            Ok(trace_index) => trace_index,
+            Err(err) => return Some(Err(err)),
+        };
+        let (file_position, event_position) = trace_index.locate(index)?;
+        let IndexedFile { format, boundaries } = &trace_index.files[file_position];
+        let mut file = match File::open(&self.event_file_paths[file_position]) {
+            Ok(file) => file,
+            Err(err) => return Some(Err(err.into())),
+        };
+        Some(read_event_at(
+            &mut file,
+            *format,
+            boundaries[event_position],
+            boundaries[event_position + 1],
+        ))
+    }
+
+    /// Counts batches, packets and bytes per `ChannelLabel` across all events, in total and
+    /// bucketed per second and per slot, along with the packet sources and the classification
+    /// of the packets of the vote channels.
+    pub fn summarize(&self) -> Result<TraceSummary, TraceError> {
+        let mut summary = TraceSummary::default();
+        for event in self.iter() {
+            summary.add_event(&event?);
+        }
+        Ok(summary)
+    }
+
+    /// Collects `(blockhash, bank_hash)` of all `BlockAndBankHash` events by slot. The latest
+    /// event wins if a slot is traced multiple times.
+    pub fn hashes_by_slot(&self) -> Result<BTreeMap<Slot, (Hash, Hash)>, TraceError> {
+        let mut hashes_by_slot = BTreeMap::new();
+        for event in self.iter() {
+            if let TimedTracedEvent(_, TracedEvent::BlockAndBankHash(slot, blockhash, bank_hash)) =
+                event?
+            {
+                hashes_by_slot.insert(slot, (blockhash, bank_hash));
+            }
+        }
+        Ok(hashes_by_slot)
+    }
+
+    /// Iterates over all events from the newest to the oldest, without decoding files forward.
+    /// Compressed files aren't supported.
+    pub fn iter_rev(&self) -> impl Iterator<Item = TraceReadResult> + '_ {
+        self.event_file_paths
+            .iter()
+            .rev()
+            .flat_map(|path| match RevTraceFileReader::open(path) {
+                Ok(reader) => Either::Left(reader),
+                Err(err) => Either::Right(iter::once(Err(err))),
+            })
+    }
+}
+
+#[cfg(any(test, feature = "dev-context-only-utils"))]
+pub(crate) fn event_file_name(index: usize) -> String {
+    rotated_file_name(BASENAME, index)
+}
+
+fn rotated_file_name(basename: &str, index: usize) -> String {
+    if index == 0 {
+        basename.to_string()
+    } else {
+        format!("{basename}.{index}")
+    }
+}
+
+/// On-disk layout of a trace file, detected by its leading bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Framed events after `TRACE_FILE_MAGIC`, as written by this `BankingTracer`.
+    Framed,
+    /// Back-to-back bincode-serialized events without any header, as written by upstream Agave
+    /// validators.
+    Unframed,
+}
+
+impl TraceFormat {
+    // Consumes the magic if any. Otherwise, the caller must start over from the beginning.
+    fn detect(reader: &mut impl Read) -> Result<Self, io::Error> {
+        let mut magic = [0; TRACE_FILE_MAGIC.len()];
+        match reader.read_exact(&mut magic) {
+            Ok(()) if &magic == TRACE_FILE_MAGIC => Ok(Self::Framed),
+            Ok(()) => Ok(Self::Unframed),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(Self::Unframed),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn header_size(&self) -> u64 {
+        match self {
+            Self::Framed => HEADER_SIZE,
+            Self::Unframed => 0,
+        }
+    }
+}
+
+/// Compression of archived trace files, recognized by the file extension. Only decompressed when
+/// built with `banking-trace-compression`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum TraceCompression {
+    Zstd,
+    Lz4,
+}
+
+impl TraceCompression {
+    const ALL: [Self; 2] = [Self::Zstd, Self::Lz4];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Zstd => "zst",
+            Self::Lz4 => "lz4",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?;
+        Self::ALL
+            .into_iter()
+            .find(|compression| extension == compression.extension())
+    }
+}
+
+// Reads a trace file, decompressing it if needed, while tracking the position in the
+// decompressed stream.
+struct DecompressingReader {
+    inner: Box<dyn Read + Send>,
+    position: u64,
+}
+
+impl DecompressingReader {
+    fn open(path: &Path) -> Result<Self, io::Error> {
+        let file = File::open(path)?;
+        let inner: Box<dyn Read + Send> = match TraceCompression::from_path(path) {
+            None => Box::new(file),
+            #[cfg(feature = "banking-trace-compression")]
+            Some(TraceCompression::Zstd) => Box::new(zstd::Decoder::new(file)?),
+            #[cfg(feature = "banking-trace-compression")]
+            Some(TraceCompression::Lz4) => Box::new(lz4::Decoder::new(file)?),
+            #[cfg(not(feature = "banking-trace-compression"))]
+            Some(_) => return Err(io::ErrorKind::Unsupported.into()),
+        };
+        Ok(Self { inner, position: 0 })
+    }
+}
+
+impl Read for DecompressingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl std::fmt::Debug for DecompressingReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecompressingReader")
+            .field("position", &self.position)
+            .finish_non_exhaustive()
+    }
+}
+
+// Opens a trace file for random access, which isn't supported for compressed ones.
+fn open_seekable(path: &Path) -> Result<(File, TraceFormat), TraceError> {
+    if TraceCompression::from_path(path).is_some() {
+        return Err(TraceError::CompressedFile(path.to_path_buf()));
+    }
+    let mut file = File::open(path)?;
+    let format = TraceFormat::detect(&mut file)?;
+    if format == TraceFormat::Unframed {
+        file.rewind()?;
+    }
+    Ok((file, format))
+}
+
+// Unframed files are only written by upstream validators, so they are always decoded with
+// upstream's event layout, which is converted afterwards. This keeps the local TracedEvent free
+// to diverge from it.
+#[derive(Deserialize)]
+struct UpstreamTimedTracedEvent(SystemTime, UpstreamTracedEvent);
+
+#[derive(Deserialize)]
+enum UpstreamTracedEvent {
+    PacketBatch(ChannelLabel, BankingPacketBatch),
+    BlockAndBankHash(Slot, Hash, Hash),
+}
+
+impl From<UpstreamTimedTracedEvent> for TimedTracedEvent {
+    fn from(UpstreamTimedTracedEvent(event_time, event): UpstreamTimedTracedEvent) -> Self {
+        let event = match event {
+            UpstreamTracedEvent::PacketBatch(label, batch) => {
+                TracedEvent::PacketBatch(label, batch)
+            }
+            UpstreamTracedEvent::BlockAndBankHash(slot, blockhash, bank_hash) => {
+                TracedEvent::BlockAndBankHash(slot, blockhash, bank_hash)
+            }
+        };
+        TimedTracedEvent(event_time, event)
+    }
+}
+
+fn is_unexpected_eof(err: &bincode::Error) -> bool {
+    matches!(
+        &**err,
+        bincode::ErrorKind::Io(io_err) if io_err.kind() == io::ErrorKind::UnexpectedEof
+    )
+}
+
+fn read_frame_len(file: &mut impl Read) -> Result<u64, io::Error> {
+    let mut frame_len = [0; FRAME_LEN_SIZE];
+    file.read_exact(&mut frame_len)?;
+    Ok(u32::from_le_bytes(frame_len).into())
+}
+
+fn frame_len_at(file: &mut File, offset: u64) -> Result<u64, io::Error> {
+    file.seek(SeekFrom::Start(offset))?;
+    read_frame_len(file)
+}
+
+// Returns the event boundaries recorded in the index footer of `file` if it's intact. The last
+// one of them is where the footer starts.
+fn read_index_footer(file: &mut File, file_len: u64) -> Result<Option<Vec<u64>>, io::Error> {
+    let Some(trailer_start) = file_len.checked_sub(INDEX_TRAILER_SIZE) else {
+        return Ok(None);
+    };
+    let mut trailer = [0; INDEX_TRAILER_SIZE as usize];
+    file.seek(SeekFrom::Start(trailer_start))?;
+    file.read_exact(&mut trailer)?;
+    let (count, magic) = trailer.split_at(size_of::<u64>());
+    if magic != TRACE_INDEX_MAGIC {
+        return Ok(None);
+    }
+    let Some(footer_start) = u64::from_le_bytes(count.try_into().unwrap())
+        .checked_mul(size_of::<u64>() as u64)
+        .and_then(|boundaries_len| boundaries_len.checked_add(TRACE_INDEX_MAGIC.len() as u64))
+        .and_then(|footer_len| trailer_start.checked_sub(footer_len))
+        .filter(|&footer_start| footer_start >= HEADER_SIZE)
+    else {
+        return Ok(None);
+    };
+    let mut footer = vec![0; usize::try_from(trailer_start - footer_start).unwrap()];
+    file.seek(SeekFrom::Start(footer_start))?;
+    file.read_exact(&mut footer)?;
+    let (magic, boundaries) = footer.split_at(TRACE_INDEX_MAGIC.len());
+    if magic != TRACE_INDEX_MAGIC {
+        return Ok(None);
+    }
+    let boundaries = boundaries
+        .chunks_exact(size_of::<u64>())
+        .map(|boundary| u64::from_le_bytes(boundary.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    let is_intact = boundaries.first() == Some(&HEADER_SIZE)
+        && boundaries.last() == Some(&footer_start)
+        && boundaries
+            .windows(2)
+            .all(|pair| pair[0] + FRAME_OVERHEAD <= pair[1]);
+    Ok(is_intact.then_some(boundaries))
+}
+
+// Skips over the length prefixes from the start of the file without reading any payload.
+fn framed_event_boundaries(file: &mut File, file_len: u64) -> Result<Vec<u64>, io::Error> {
+    let mut boundaries = vec![HEADER_SIZE.min(file_len)];
+    let mut offset = HEADER_SIZE;
+    while offset + FRAME_LEN_SIZE as u64 <= file_len {
+        let next_offset = offset + frame_len_at(file, offset)? + FRAME_OVERHEAD;
+        if next_offset > file_len {
+            break;
+        }
+        boundaries.push(next_offset);
+        offset = next_offset;
+    }
+    Ok(boundaries)
+}
+
+fn unframed_event_boundaries(file: &mut File) -> Result<Vec<u64>, TraceError> {
+    let mut reader = BufReader::new(file);
+    reader.rewind()?;
+    let mut boundaries = vec![0];
+    while !reader.fill_buf()?.is_empty() {
+        match bincode::deserialize_from::<_, UpstreamTimedTracedEvent>(&mut reader) {
+            Ok(_event) => boundaries.push(reader.stream_position()?),
+            Err(err) if is_unexpected_eof(&err) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(boundaries)
+}
+
+/// Returns the offsets of the starts of all complete events in `file`, followed by the end of
+/// the last one.
+fn event_boundaries(file: &mut File, format: TraceFormat) -> Result<Vec<u64>, TraceError> {
+    match format {
+        TraceFormat::Framed => {
+            let file_len = file.metadata()?.len();
+            match read_index_footer(file, file_len)? {
+                Some(boundaries) => Ok(boundaries),
+                None => Ok(framed_event_boundaries(file, file_len)?),
+            }
+        }
+        TraceFormat::Unframed => unframed_event_boundaries(file),
+    }
+}
+
+/// Decodes the event between the boundaries `start` and `end`.
+fn read_event_at(file: &mut File, format: TraceFormat, start: u64, end: u64) -> TraceReadResult {
+    let (start, end) = match format {
+        TraceFormat::Framed => (start + FRAME_LEN_SIZE as u64, end - FRAME_LEN_SIZE as u64),
+        TraceFormat::Unframed => (start, end),
+    };
+    let mut payload = vec![0; usize::try_from(end - start)?];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut payload)?;
+    match format {
+        TraceFormat::Framed => Ok(bincode::deserialize(&payload)?),
+        TraceFormat::Unframed => {
+            Ok(bincode::deserialize::<UpstreamTimedTracedEvent>(&payload)?.into())
+        }
+    }
+}
+
+/// Position right after an event in a trace, from which reading can be resumed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TracePosition {
+    pub event_file_path: PathBuf,
+    /// The offset into the decompressed content of the file.
+    pub offset: u64,
+}
+
+/// Forward reader of a single trace file of either `TraceFormat`, compressed or not.
+///
+/// A truncated trailing event is expected after unclean validator shutdown or while the file is
+/// still being written. It silently terminates the iteration (with a warning) rather than being
+/// reported as an error.
+#[derive(Debug)]
+pub struct TraceFileReader {
+    path: PathBuf,
+    reader: BufReader<DecompressingReader>,
+    format: TraceFormat,
+    offset: u64,
+    is_done: bool,
+}
+
+impl TraceFileReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TraceError> {
+        let path = path.as_ref().to_path_buf();
+        let mut reader = DecompressingReader::open(&path)?;
+        let format = TraceFormat::detect(&mut reader)?;
+        if format == TraceFormat::Unframed {
+            reader = DecompressingReader::open(&path)?;
+        }
+
+        Ok(Self {
+            path,
+            reader: BufReader::new(reader),
+            format,
+            offset: format.header_size(),
+            is_done: false,
+        })
+    }
+
+    /// Opens `path` to resume reading at `offset`, which must be a position previously returned
+    /// by `position()`. The content up to `offset` is read through rather than seeked over, so
+    /// that compressed files are supported as well.
+    pub fn open_at(path: impl AsRef<Path>, offset: u64) -> Result<Self, TraceError> {
+        let mut file_reader = Self::open(path)?;
+        let skipped_len = offset.saturating_sub(file_reader.offset);
+        let actual_len = io::copy(
+            &mut (&mut file_reader.reader).take(skipped_len),
+            &mut io::sink(),
+        )?;
+        if actual_len != skipped_len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        file_reader.offset += skipped_len;
+        Ok(file_reader)
+    }
+
+    pub fn format(&self) -> TraceFormat {
+        self.format
+    }
+
+    /// Returns the position right after the last read event.
+    pub fn position(&self) -> TracePosition {
+        TracePosition {
+            event_file_path: self.path.clone(),
+            offset: self.offset,
+        }
+    }
+
+    // Returns `None` at the index footer, which follows the last frame.
+    fn read_frame(&mut self) -> Result<Option<Vec<u8>>, io::Error> {
+        let mut frame_len = [0; FRAME_LEN_SIZE];
+        self.reader.read_exact(&mut frame_len)?;
+        // don't trust frame_len for allocation until the whole frame is confirmed to exist
+        let mut payload = vec![];
+        if frame_len == TRACE_INDEX_MAGIC[..FRAME_LEN_SIZE] {
+            // otherwise, this is the start of a frame that long after all
+            (&mut self.reader)
+                .take(FRAME_LEN_SIZE as u64)
+                .read_to_end(&mut payload)?;
+            if payload == TRACE_INDEX_MAGIC[FRAME_LEN_SIZE..] {
+                return Ok(None);
+            }
+        }
+        let frame_len = u64::from(u32::from_le_bytes(frame_len));
+        (&mut self.reader)
+            .take(frame_len.saturating_sub(payload.len() as u64))
+            .read_to_end(&mut payload)?;
+        if payload.len() as u64 != frame_len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if read_frame_len(&mut self.reader)? != frame_len {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        Ok(Some(payload))
+    }
+
+    fn read_framed_event(&mut self) -> Result<Option<TimedTracedEvent>, TraceError> {
+        let payload = match self.read_frame() {
+            Ok(Some(payload)) => payload,
+            Ok(None) => return Ok(None),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                warn!("Torn trace frame in {:?} at {}", self.path, self.offset);
+                return Ok(None);
+            }
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                return Err(TraceError::CorruptedFrame(self.path.clone(), self.offset));
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        self.offset += payload.len() as u64 + FRAME_OVERHEAD;
+        Ok(Some(bincode::deserialize(&payload)?))
+    }
+
+    fn read_unframed_event(&mut self) -> Result<Option<TimedTracedEvent>, TraceError> {
+        match bincode::deserialize_from::<_, UpstreamTimedTracedEvent>(&mut self.reader) {
+            Ok(event) => {
+                self.offset = self.reader.get_ref().position - self.reader.buffer().len() as u64;
+                Ok(Some(event.into()))
+            }
+            Err(err) if is_unexpected_eof(&err) => {
+                warn!("Torn trace event in {:?} at {}", self.path, self.offset);
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Option<TimedTracedEvent>, TraceError> {
+        // EOF is reached at a correct event boundary or the file is just empty. We want to
+        // look-ahead the buf, so NOT calling reader.consume(..) is correct.
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        match self.format {
+            TraceFormat::Framed => self.read_framed_event(),
+            TraceFormat::Unframed => self.read_unframed_event(),
+        }
+    }
+}
+
+impl Iterator for TraceFileReader {
+    type Item = TraceReadResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
+        let result = self.read_event().transpose();
+        // stop at the first error as well; event boundaries can't be trusted anymore.
+        self.is_done = !matches!(result, Some(Ok(_)));
+        result
+    }
+}
+
+#[derive(Debug)]
+enum RevEventBoundaries {
+    // The end of the next frame to read
+    Framed(u64),
+    // The starts of all complete events, followed by the end of the last one
+    Unframed(Vec<u64>),
+}
+
+/// Reverse (newest-first) reader of a single trace file of either `TraceFormat`.
+///
+/// For `TraceFormat::Framed`, frames are located from the end of the file or its index footer
+/// using their trailing length, so only the returned events are decoded. If the last frame is
+/// torn, complete frames are found by skipping over the length prefixes from the start of the
+/// file, which still doesn't decode any payload.
+///
+/// `TraceFormat::Unframed` doesn't allow that. So the whole file is decoded forward once to
+/// collect event boundaries only, then events are decoded again one by one as they're returned.
+#[derive(Debug)]
+pub struct RevTraceFileReader {
+    path: PathBuf,
+    file: File,
+    boundaries: RevEventBoundaries,
+}
+
+impl RevTraceFileReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TraceError> {
+        let path = path.as_ref().to_path_buf();
+        let (mut file, format) = open_seekable(&path)?;
+        let file_len = file.metadata()?.len();
+
+        let (boundaries, end) = match format {
+            TraceFormat::Framed => match read_index_footer(&mut file, file_len)? {
+                // the footer starts right after the last frame
+                Some(boundaries) => {
+                    let end = *boundaries.last().unwrap();
+                    return Ok(Self {
+                        path,
+                        file,
+                        boundaries: RevEventBoundaries::Framed(end),
+                    });
+                }
+                None => {
+                    let end = Self::last_frame_end(&mut file, file_len)?;
+                    (RevEventBoundaries::Framed(end), end)
+                }
+            },
+            TraceFormat::Unframed => {
+                let starts = unframed_event_boundaries(&mut file)?;
+                let end = starts.last().copied().unwrap_or_default();
+                (RevEventBoundaries::Unframed(starts), end)
+            }
+        };
+        if end != file_len {
+            warn!("Torn trace event in {path:?} at {end}");
+        }
+
+        Ok(Self {
+            path,
+            file,
+            boundaries,
+        })
+    }
+
+    fn last_frame_end(file: &mut File, file_len: u64) -> Result<u64, io::Error> {
+        if file_len <= HEADER_SIZE {
+            return Ok(HEADER_SIZE.min(file_len));
+        }
+        if let Some(frame_start) = Self::frame_start_before(file, file_len)? {
+            if frame_start >= HEADER_SIZE {
+                return Ok(file_len);
+            }
+        }
+
+        Ok(*framed_event_boundaries(file, file_len)?.last().unwrap())
+    }
+
+    // Returns the start of the frame ending at `frame_end` if the trailing and leading lengths
+    // of it agree.
+    fn frame_start_before(file: &mut File, frame_end: u64) -> Result<Option<u64>, io::Error> {
+        let Some(trailing_len_offset) = frame_end.checked_sub(FRAME_LEN_SIZE as u64) else {
+            return Ok(None);
+        };
+        let frame_len = frame_len_at(file, trailing_len_offset)?;
+        let Some(frame_start) = frame_end.checked_sub(frame_len + FRAME_OVERHEAD) else {
+            return Ok(None);
+        };
+        Ok((frame_len_at(file, frame_start)? == frame_len).then_some(frame_start))
+    }
+
+    fn read_event(&mut self) -> Option<TraceReadResult> {
+        match &mut self.boundaries {
+            RevEventBoundaries::Framed(frame_end) => {
+                let frame_end = *frame_end;
+                if frame_end <= HEADER_SIZE {
+                    return None;
+                }
+                let frame_start = match Self::frame_start_before(&mut self.file, frame_end) {
+                    Ok(Some(frame_start)) if frame_start >= HEADER_SIZE => frame_start,
+                    Ok(_) => {
+                        return Some(Err(TraceError::CorruptedFrame(
+                            self.path.clone(),
+                            frame_end,
+                        )))
+                    }
+                    Err(err) => return Some(Err(err.into())),
+                };
+                self.boundaries = RevEventBoundaries::Framed(frame_start);
+                Some(read_event_at(
+                    &mut self.file,
+                    TraceFormat::Framed,
+                    frame_start,
+                    frame_end,
+                ))
+            }
+            RevEventBoundaries::Unframed(boundaries) => {
+                if boundaries.len() < 2 {
+                    return None;
+                }
+                let end = boundaries.pop().unwrap();
+                let start = *boundaries.last().unwrap();
+                Some(read_event_at(
+                    &mut self.file,
+                    TraceFormat::Unframed,
+                    start,
+                    end,
+                ))
+            }
+        }
+    }
+}
+
+impl Iterator for RevTraceFileReader {
+    type Item = TraceReadResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.read_event();
+        if matches!(result, Some(Err(_))) {
+            // stop at the first error; event boundaries can't be trusted anymore.
+            self.boundaries = RevEventBoundaries::Unframed(vec![]);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{for_test, TracedEvent},
+        solana_clock::Slot,
+        solana_hash::Hash,
+        std::{fs::OpenOptions, io::Write, time::SystemTime},
+        tempfile::TempDir,
+    };
+
+    fn write_events(
+        path: &Path,
+        rotate_threshold_size: u64,
+        events: impl IntoIterator<Item = TracedEvent>,
+    ) {
+        for_test::write_events(
+            &path.to_path_buf(),
+            rotate_threshold_size,
+            events
+                .into_iter()
+                .map(|event| TimedTracedEvent(SystemTime::now(), event)),
+        );
+    }
+
+    fn write_hash_events(
+        path: &Path,
+        rotate_threshold_size: u64,
+        slots: impl Iterator<Item = Slot>,
+    ) {
+        write_events(
+            path,
+            rotate_threshold_size,
+            slots.map(|slot| TracedEvent::BlockAndBankHash(slot, Hash::default(), Hash::default())),
+        );
+    }
+
+    fn slots(events: impl Iterator<Item = TraceReadResult>) -> Vec<Slot> {
+        events
+            .map(|event| match event.unwrap() {
+                TimedTracedEvent(_, TracedEvent::BlockAndBankHash(slot, _, _)) => slot,
+                event => panic!("unexpected event: {event:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_iter_rev_across_rotated_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        // cause rotation every 2 events or so
+        write_hash_events(&path, 100, 0..10);
+
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert!(reader.event_file_paths().len() > 1);
+        assert_eq!(slots(reader.iter()), (0..10).collect::<Vec<_>>());
+        assert_eq!(slots(reader.iter_rev()), (0..10).rev().collect::<Vec<_>>());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_iter_from() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_hash_events(&path, 100, 0..10);
+
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert!(reader.event_file_paths().len() > 1);
+        let positions = reader
+            .iter_from(None)
+            .map(|event| event.unwrap().1)
+            .collect::<Vec<_>>();
+        assert_eq!(positions.len(), 10);
+        for (index, position) in positions.iter().enumerate() {
+            assert_eq!(
+                slots(
+                    reader
+                        .iter_from(Some(position))
+                        .map(|event| event.map(|(event, _)| event))
+                ),
+                (index as Slot + 1..10).collect::<Vec<_>>()
+            );
+        }
+
+        let unknown_position = TracePosition {
+            event_file_path: path.join("unknown"),
+            offset: 0,
+        };
+        assert_matches!(
+            reader.iter_from(Some(&unknown_position)).next(),
+            Some(Err(TraceError::UnknownEventFile(_)))
+        );
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_nth() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_hash_events(&path, 100, 0..10);
+
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert!(reader.event_file_paths().len() > 1);
+        assert_eq!(reader.len_hint().unwrap(), 10);
+        assert_eq!(
+            slots((0..10).map(|index| reader.nth(index).unwrap())),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert_matches!(reader.nth(10), None);
+
+        // bisect for the first event of slot 7 or later
+        let (mut low, mut high) = (0, reader.len_hint().unwrap());
+        while low < high {
+            let middle = (low + high) / 2;
+            if slots(reader.nth(middle).into_iter()) < vec![7] {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        assert_eq!(low, 7);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[cfg(feature = "banking-trace-compression")]
+    #[test]
+    fn test_compressed_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_hash_events(&path, 100, 0..10);
+        let compress = |index, compression: TraceCompression| {
+            let plain_path = path.join(event_file_name(index));
+            let data = std::fs::read(&plain_path).unwrap();
+            let compressed_path = path.join(format!(
+                "{}.{}",
+                event_file_name(index),
+                compression.extension()
+            ));
+            let compressed_file = File::create(&compressed_path).unwrap();
+            match compression {
+                TraceCompression::Zstd => {
+                    zstd::stream::copy_encode(&data[..], compressed_file, 0).unwrap();
+                }
+                TraceCompression::Lz4 => {
+                    let mut encoder = lz4::EncoderBuilder::new().build(compressed_file).unwrap();
+                    encoder.write_all(&data).unwrap();
+                    encoder.finish().1.unwrap();
+                }
+            }
+            std::fs::remove_file(plain_path).unwrap();
+            compressed_path
+        };
+        let zstd_path = compress(1, TraceCompression::Zstd);
+        compress(2, TraceCompression::Lz4);
+
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert!(reader.event_file_paths().contains(&zstd_path));
+        assert_eq!(slots(reader.iter()), (0..10).collect::<Vec<_>>());
+        assert!(reader
+            .iter_rev()
+            .any(|event| matches!(event, Err(TraceError::CompressedFile(_)))));
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_torn_trailing_frame() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_hash_events(&path, u64::MAX, 0..3);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(path.join(BASENAME))
+            .unwrap();
+        file.write_all(&100_u32.to_le_bytes()).unwrap();
+        file.write_all(&[1, 2, 3]).unwrap();
+
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert_eq!(slots(reader.iter()), vec![0, 1, 2]);
+        assert_eq!(slots(reader.iter_rev()), vec![2, 1, 0]);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_index_footer() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_hash_events(&path, 100, 0..10);
+
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert!(reader.event_file_paths().len() > 1);
+        // both the rotated files and the last one have their footers
+        for path in reader.event_file_paths() {
+            let mut file = File::open(path).unwrap();
+            let file_len = file.metadata().unwrap().len();
+            let boundaries = read_index_footer(&mut file, file_len).unwrap().unwrap();
+            let footer_start = *boundaries.last().unwrap();
+            assert!(footer_start < file_len);
+            assert_eq!(
+                framed_event_boundaries(&mut file, footer_start).unwrap(),
+                boundaries
+            );
+        }
+        assert_eq!(reader.len_hint().unwrap(), 10);
+        assert_eq!(
+            slots((0..10).map(|index| reader.nth(index).unwrap())),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert_eq!(slots(reader.iter()), (0..10).collect::<Vec<_>>());
+        assert_eq!(slots(reader.iter_rev()), (0..10).rev().collect::<Vec<_>>());
+
+        // damaged footers are scanned over instead
+        let last_path = path.join(BASENAME);
+        let mut data = std::fs::read(&last_path).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        std::fs::write(&last_path, data).unwrap();
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert_eq!(reader.len_hint().unwrap(), 10);
+        assert_eq!(slots(reader.iter()), (0..10).collect::<Vec<_>>());
+        assert_eq!(slots(reader.iter_rev()), (0..10).rev().collect::<Vec<_>>());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let batch = for_test::sample_packet_batch();
+        let packet_count = batch.iter().map(|batch| batch.len() as u64).sum::<u64>();
+        let byte_count = batch
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .map(|packet| packet.meta().size as u64)
+            .sum::<u64>();
+        write_events(
+            &path,
+            u64::MAX,
+            [
+                TracedEvent::PacketBatch(ChannelLabel::NonVote, batch.clone()),
+                TracedEvent::BlockAndBankHash(3, Hash::default(), Hash::default()),
+                TracedEvent::PacketBatch(ChannelLabel::NonVote, batch.clone()),
+                TracedEvent::PacketBatch(ChannelLabel::TpuVote, batch),
+            ],
+        );
+
+        let summary = TraceReader::open_dir(&path).unwrap().summarize().unwrap();
+        let counts = |batch_count| TraceCounts {
+            batch_count,
+            packet_count: packet_count * batch_count,
+            byte_count: byte_count * batch_count,
+        };
+        assert_eq!(
+            summary.total,
+            CountsByLabel::from([
+                (ChannelLabel::NonVote, counts(2)),
+                (ChannelLabel::TpuVote, counts(1)),
+            ])
+        );
+        assert_eq!(
+            summary.by_slot,
+            BTreeMap::from([
+                (
+                    None,
+                    CountsByLabel::from([(ChannelLabel::NonVote, counts(1))])
+                ),
+                (
+                    Some(3),
+                    CountsByLabel::from([
+                        (ChannelLabel::NonVote, counts(1)),
+                        (ChannelLabel::TpuVote, counts(1)),
+                    ])
+                ),
+            ])
+        );
+        assert_eq!(
+            summary
+                .by_second
+                .values()
+                .flat_map(|counts_by_label| counts_by_label.values())
+                .map(|counts| counts.batch_count)
+                .sum::<u64>(),
+            3
+        );
+        assert_eq!(
+            summary.votes,
+            BTreeMap::from([(
+                ChannelLabel::TpuVote,
+                VoteCounts {
+                    simple_votes: 0,
+                    others: packet_count,
+                }
+            )])
+        );
+        let sources =
+            |packet_count| BTreeMap::from([((SocketAddr::from(([0; 4], 0)), false), packet_count)]);
+        assert_eq!(
+            summary.sources,
+            BTreeMap::from([
+                (ChannelLabel::NonVote, sources(packet_count * 2)),
+                (ChannelLabel::TpuVote, sources(packet_count)),
+            ])
+        );
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_unframed_upstream_format() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        std::fs::create_dir(&path).unwrap();
+        let mut file = File::create(path.join(BASENAME)).unwrap();
+        for slot in 0..3 {
+            let event = TimedTracedEvent(
+                SystemTime::now(),
+                TracedEvent::BlockAndBankHash(slot, Hash::default(), Hash::default()),
+            );
+            bincode::serialize_into(&mut file, &event).unwrap();
+        }
+        // torn trailing event
+        file.write_all(&[1, 0, 0, 0]).unwrap();
+        drop(file);
+
+        assert_eq!(
+            TraceFileReader::open(path.join(BASENAME)).unwrap().format(),
+            TraceFormat::Unframed
+        );
+        let reader = TraceReader::open_dir(&path).unwrap();
+        assert_eq!(slots(reader.iter()), vec![0, 1, 2]);
+        assert_eq!(slots(reader.iter_rev()), vec![2, 1, 0]);
+        assert_eq!(reader.len_hint().unwrap(), 3);
+        assert_eq!(slots(reader.nth(1).into_iter()), vec![1]);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_unframed_garbage() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(BASENAME);
+        std::fs::write(&path, b"not a banking trace").unwrap();
+
+        let mut reader = TraceFileReader::open(&path).unwrap();
+        assert_matches!(reader.next(), Some(Err(TraceError::SerializeError(_))));
+        assert_matches!(reader.next(), None);
+        assert_matches!(
+            RevTraceFileReader::open(&path),
+            Err(TraceError::SerializeError(_))
+        );
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+}
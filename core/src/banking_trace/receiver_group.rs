@@ -0,0 +1,233 @@
+//! Multi-threaded consumption of a banking channel (typically the unified one), as done by the
+//! unified scheduler, available as a reusable component.
+//!
+//! Each worker grabs a few batches at a time from the channel into its own deque, which is
+//! drained by the worker in FIFO order and stolen from by the other workers once they run out of
+//! batches, so that a worker stuck on a heavy batch doesn't hold the ones queued behind it.
+
+use {
+    agave_banking_stage_ingress_types::{BankingPacketBatch, BankingPacketReceiver},
+    crossbeam_channel::{RecvTimeoutError, TryRecvError},
+    crossbeam_deque::{Steal, Stealer, Worker},
+    std::{
+        io, iter,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        thread::{self, JoinHandle},
+        time::Duration,
+    },
+};
+
+/// The number of batches grabbed from the channel at a time.
+const GRAB_BATCH_COUNT: usize = 4;
+const IDLE_RECV_TIMEOUT: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Default)]
+struct WorkerStats {
+    handled_batches: AtomicU64,
+    handled_packets: AtomicU64,
+    stolen_batches: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerStatsSnapshot {
+    pub handled_batches: u64,
+    pub handled_packets: u64,
+    /// Of `handled_batches`, the ones stolen from the other workers.
+    pub stolen_batches: u64,
+}
+
+pub struct ReceiverGroup {
+    worker_threads: Vec<JoinHandle<()>>,
+    worker_stats: Vec<Arc<WorkerStats>>,
+}
+
+impl ReceiverGroup {
+    /// Spawns `worker_count` threads, each handling batches from `receiver` with the handler
+    /// created by `new_handler` with the index of the worker. The workers exit once `exit` is
+    /// set, or the channel is disconnected and drained.
+    pub fn new<H: FnMut(BankingPacketBatch) + Send + 'static>(
+        receiver: BankingPacketReceiver,
+        worker_count: usize,
+        exit: Arc<AtomicBool>,
+        mut new_handler: impl FnMut(usize) -> H,
+    ) -> Result<Self, io::Error> {
+        let deques = iter::repeat_with(Worker::new_fifo)
+            .take(worker_count)
+            .collect::<Vec<_>>();
+        let stealers = Arc::new(deques.iter().map(Worker::stealer).collect::<Vec<_>>());
+        let worker_stats = iter::repeat_with(Arc::<WorkerStats>::default)
+            .take(worker_count)
+            .collect::<Vec<_>>();
+        let worker_threads = deques
+            .into_iter()
+            .enumerate()
+            .map(|(index, deque)| {
+                let mut worker = GroupWorker {
+                    index,
+                    deque,
+                    stealers: Arc::clone(&stealers),
+                    receiver: receiver.clone(),
+                    exit: exit.clone(),
+                    stats: Arc::clone(&worker_stats[index]),
+                };
+                let mut handler = new_handler(index);
+                thread::Builder::new()
+                    .name(format!("solBnkRcvGrp{index:02}"))
+                    .spawn(move || {
+                        while let Some(batch) = worker.next_batch() {
+                            let packet_count = batch.iter().map(|batch| batch.len() as u64).sum();
+                            handler(batch);
+                            worker.stats.handled_batches.fetch_add(1, Ordering::Relaxed);
+                            worker
+                                .stats
+                                .handled_packets
+                                .fetch_add(packet_count, Ordering::Relaxed);
+                        }
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            worker_threads,
+            worker_stats,
+        })
+    }
+
+    pub fn worker_stats(&self) -> Vec<WorkerStatsSnapshot> {
+        Self::snapshot(&self.worker_stats)
+    }
+
+    /// Waits for the workers to exit, returning their final stats.
+    pub fn join(self) -> thread::Result<Vec<WorkerStatsSnapshot>> {
+        for worker_thread in self.worker_threads {
+            worker_thread.join()?;
+        }
+        Ok(Self::snapshot(&self.worker_stats))
+    }
+
+    fn snapshot(worker_stats: &[Arc<WorkerStats>]) -> Vec<WorkerStatsSnapshot> {
+        worker_stats
+            .iter()
+            .map(|stats| WorkerStatsSnapshot {
+                handled_batches: stats.handled_batches.load(Ordering::Relaxed),
+                handled_packets: stats.handled_packets.load(Ordering::Relaxed),
+                stolen_batches: stats.stolen_batches.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+struct GroupWorker {
+    index: usize,
+    deque: Worker<BankingPacketBatch>,
+    stealers: Arc<Vec<Stealer<BankingPacketBatch>>>,
+    receiver: BankingPacketReceiver,
+    exit: Arc<AtomicBool>,
+    stats: Arc<WorkerStats>,
+}
+
+impl GroupWorker {
+    /// Returns `None` once exiting, or the channel is disconnected and there's nothing left to
+    /// steal.
+    fn next_batch(&mut self) -> Option<BankingPacketBatch> {
+        while !self.exit.load(Ordering::Relaxed) {
+            if let Some(batch) = self.deque.pop() {
+                return Some(batch);
+            }
+            let is_disconnected = match self.grab() {
+                Ok(()) => continue,
+                Err(TryRecvError::Empty) => false,
+                Err(TryRecvError::Disconnected) => true,
+            };
+            if let Some(batch) = self.steal() {
+                self.stats.stolen_batches.fetch_add(1, Ordering::Relaxed);
+                return Some(batch);
+            }
+            if is_disconnected {
+                return None;
+            }
+            match self.receiver.recv_timeout(IDLE_RECV_TIMEOUT) {
+                Ok(batch) => return Some(batch),
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => continue,
+            }
+        }
+        None
+    }
+
+    /// Moves up to `GRAB_BATCH_COUNT` batches from the channel into the deque.
+    fn grab(&mut self) -> Result<(), TryRecvError> {
+        self.deque.push(self.receiver.try_recv()?);
+        for batch in self.receiver.try_iter().take(GRAB_BATCH_COUNT - 1) {
+            self.deque.push(batch);
+        }
+        Ok(())
+    }
+
+    fn steal(&self) -> Option<BankingPacketBatch> {
+        let others = self
+            .stealers
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != self.index)
+            .map(|(_, stealer)| stealer);
+        // retry as long as any of the steals conflicted with others
+        iter::repeat_with(|| others.clone().map(Stealer::steal).collect::<Steal<_>>())
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{for_test::sample_packet_batch, BankingTracer},
+        std::sync::Mutex,
+    };
+
+    #[test]
+    fn test_receiver_group() {
+        let (sender, receiver) = BankingTracer::channel_for_test();
+        let exit = Arc::<AtomicBool>::default();
+        let handled_by = Arc::new(Mutex::new(vec![]));
+        let group = ReceiverGroup::new(receiver, 3, exit, |index| {
+            let handled_by = Arc::clone(&handled_by);
+            move |_batch| {
+                handled_by.lock().unwrap().push(index);
+                thread::sleep(Duration::from_millis(1));
+            }
+        })
+        .unwrap();
+        for _ in 0..30 {
+            sender.send(sample_packet_batch()).unwrap();
+        }
+        drop(sender);
+
+        let worker_stats = group.join().unwrap();
+        let mut handled_counts = [0; 3];
+        for index in handled_by.lock().unwrap().iter() {
+            handled_counts[*index] += 1;
+        }
+        assert_eq!(
+            worker_stats
+                .iter()
+                .map(|stats| stats.handled_batches)
+                .collect::<Vec<_>>(),
+            handled_counts,
+        );
+        let packet_count = sample_packet_batch()
+            .iter()
+            .map(|batch| batch.len() as u64)
+            .sum::<u64>();
+        assert_eq!(
+            worker_stats
+                .iter()
+                .map(|stats| stats.handled_packets)
+                .sum::<u64>(),
+            packet_count * 30
+        );
+    }
+}
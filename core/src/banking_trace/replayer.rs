@@ -0,0 +1,1331 @@
+//! Replaying of recorded banking trace events into `Channels`, turning traces into reproducible
+//! inputs of `BankingStage`.
+
+use {
+    super::{
+        reader::{CountsByLabel, TraceCounts, TracePosition, TraceReader},
+        BankingPacketSender, ChannelLabel, Channels, TimedTracedEvent, TraceError, TracedEvent,
+        TracedSender,
+    },
+    agave_banking_stage_ingress_types::BankingPacketBatch,
+    bytes::Bytes,
+    crossbeam_channel::SendError,
+    solana_client::connection_cache::ConnectionCache,
+    solana_clock::Slot,
+    solana_connection_cache::client_connection::ClientConnection,
+    solana_hash::Hash,
+    solana_ledger::blockstore::BlockstoreError,
+    solana_packet::{Packet, PACKET_DATA_SIZE},
+    solana_perf::packet::{BytesPacket, PacketRefMut},
+    solana_transaction_error::TransportError,
+    std::{
+        collections::BTreeSet,
+        fs, io,
+        net::SocketAddr,
+        ops::{Range, RangeInclusive},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        thread::{sleep, yield_now},
+        time::{Duration, Instant, SystemTime},
+    },
+    thiserror::Error,
+};
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("Trace Error: {0}")]
+    TraceError(#[from] TraceError),
+
+    #[error("Channel for {0:?} is disconnected")]
+    Disconnected(ChannelLabel),
+
+    #[error("Transport Error: {0}")]
+    TransportError(#[from] TransportError),
+
+    #[error("Blockstore Error: {0}")]
+    BlockstoreError(#[from] BlockstoreError),
+
+    #[error("IO Error: {0}")]
+    IoError(#[from] io::Error),
+}
+
+/// Destination of replayed batches.
+pub trait ReplayTarget {
+    fn send(&self, label: ChannelLabel, batch: BankingPacketBatch) -> Result<(), ReplayError>;
+
+    /// Returns whether all sent batches have been taken by the consumers. This is polled before
+    /// advancing `VirtualClock`.
+    fn is_drained(&self) -> bool;
+
+    /// Returns the number of sent batches of `label` which are yet to be taken by the consumers.
+    /// This is polled for `BackpressurePolicy`.
+    fn len(&self, label: ChannelLabel) -> usize;
+}
+
+impl Channels {
+    fn sender(&self, label: ChannelLabel) -> Option<&BankingPacketSender> {
+        match label {
+            ChannelLabel::NonVote => Some(&self.non_vote_sender),
+            ChannelLabel::TpuVote => Some(&self.tpu_vote_sender),
+            ChannelLabel::GossipVote => Some(&self.gossip_vote_sender),
+            ChannelLabel::Dummy => None,
+        }
+    }
+}
+
+impl ReplayTarget for Channels {
+    fn send(&self, label: ChannelLabel, batch: BankingPacketBatch) -> Result<(), ReplayError> {
+        let Some(sender) = self.sender(label) else {
+            return Ok(());
+        };
+        sender
+            .send(batch)
+            .map_err(|SendError(_)| ReplayError::Disconnected(label))
+    }
+
+    fn is_drained(&self) -> bool {
+        self.non_vote_sender.is_empty()
+            && self.tpu_vote_sender.is_empty()
+            && self.gossip_vote_sender.is_empty()
+    }
+
+    fn len(&self, label: ChannelLabel) -> usize {
+        self.sender(label)
+            .map(TracedSender::len)
+            .unwrap_or_default()
+    }
+}
+
+/// Submits replayed packets to a remote validator's TPU with the given `ConnectionCache`, which
+/// is expected to be a QUIC one. Batches of all labels are sent to the same address; use
+/// `BankingTraceReplayer::with_labels()` to exclude votes if needed.
+pub struct RemoteTpuTarget {
+    connection_cache: Arc<ConnectionCache>,
+    tpu_address: SocketAddr,
+}
+
+impl RemoteTpuTarget {
+    pub fn new(connection_cache: Arc<ConnectionCache>, tpu_address: SocketAddr) -> Self {
+        Self {
+            connection_cache,
+            tpu_address,
+        }
+    }
+}
+
+impl ReplayTarget for RemoteTpuTarget {
+    fn send(&self, _label: ChannelLabel, batch: BankingPacketBatch) -> Result<(), ReplayError> {
+        let wire_transactions = batch
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .filter_map(|packet| packet.data(..).map(<[u8]>::to_vec))
+            .collect::<Vec<_>>();
+        if !wire_transactions.is_empty() {
+            self.connection_cache
+                .get_connection(&self.tpu_address)
+                .send_data_batch_async(wire_transactions)?;
+        }
+        Ok(())
+    }
+
+    // There's no visibility into the remote consumers.
+    fn is_drained(&self) -> bool {
+        true
+    }
+
+    fn len(&self, _label: ChannelLabel) -> usize {
+        0
+    }
+}
+
+/// Pauses replay while the target holds `high_watermark` or more batches of the label of the
+/// next batch, until they're drained down to `low_watermark`. The paused time is excluded from
+/// the recorded timing, so that the replay doesn't burst to catch up after resuming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackpressurePolicy {
+    pub high_watermark: usize,
+    pub low_watermark: usize,
+    pub poll_interval: Duration,
+}
+
+/// How fast recorded events are re-sent relative to the recorded timing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Divides the recorded inter-event delays by the factor; e.g. `10.0` replays 10x faster and
+    /// `0.5` replays 2x slower.
+    Factor(f64),
+    /// Ignores the recorded inter-event delays altogether.
+    AsFastAsPossible,
+}
+
+impl Default for ReplaySpeed {
+    fn default() -> Self {
+        Self::Factor(1.0)
+    }
+}
+
+impl ReplaySpeed {
+    fn scale(&self, elapsed: Duration) -> Option<Duration> {
+        match self {
+            Self::Factor(factor) => Some(elapsed.div_f64(*factor)),
+            Self::AsFastAsPossible => None,
+        }
+    }
+}
+
+/// Virtual time of a replay, which is advanced to the recorded offset of each event from the
+/// first replayed one, instead of actually sleeping for it.
+///
+/// Before advancing, the replayer waits for the `ReplayTarget` to be drained. So, batches are
+/// handed to consumers one at a time in the recorded order in every replay of the same trace
+/// regardless of host load, and a consumer reading the time with `elapsed()` instead of the wall
+/// clock after receiving a batch observes at least its recorded offset.
+#[derive(Debug, Default)]
+pub struct VirtualClock {
+    elapsed_nanos: AtomicU64,
+}
+
+impl VirtualClock {
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed_nanos.load(Ordering::Acquire))
+    }
+
+    fn advance_to(&self, elapsed: Duration) {
+        let elapsed_nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.elapsed_nanos
+            .fetch_max(elapsed_nanos, Ordering::AcqRel);
+    }
+}
+
+/// A slot whose hashes produced by a replay don't match the recorded ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashDivergence {
+    pub slot: Slot,
+    /// `(blockhash, bank_hash)` recorded in the replayed trace; `None` if the slot isn't traced.
+    pub recorded: Option<(Hash, Hash)>,
+    /// `(blockhash, bank_hash)` produced by the replay.
+    pub produced: (Hash, Hash),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repetition {
+    Times(u64),
+    Forever,
+}
+
+/// Rewrites a batch of a repeated replay pass, which is numbered from 1, before it's sent.
+pub type RepeatHook = Box<dyn FnMut(u64, BankingPacketBatch) -> BankingPacketBatch + Send>;
+
+/// Mutates a packet right before it's re-sent.
+pub type PacketMutator = Box<dyn FnMut(&mut Packet) + Send>;
+
+/// Progress of a replay, reported to `ReplayObserver`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplayProgress {
+    /// The current pass, numbered from 0.
+    pub pass: u64,
+    /// The number of processed events, including non-`PacketBatch` ones and suppressed ones.
+    pub event_count: usize,
+    /// The counts of sent batches of all labels.
+    pub sent: TraceCounts,
+    /// The recorded time of the latest processed event.
+    pub event_time: Option<SystemTime>,
+    /// The slot of the latest processed `BlockAndBankHash` event.
+    pub slot: Option<Slot>,
+    /// The estimated remaining time, extrapolated from the processed events. `None` if the total
+    /// number of events isn't known, like when looping forever or reading compressed files.
+    pub eta: Option<Duration>,
+}
+
+/// Receives periodic progress reports of a replay, including the final one at its end.
+pub trait ReplayObserver: Send {
+    fn on_progress(&mut self, progress: &ReplayProgress);
+}
+
+impl<F: FnMut(&ReplayProgress) + Send> ReplayObserver for F {
+    fn on_progress(&mut self, progress: &ReplayProgress) {
+        self(progress)
+    }
+}
+
+/// Logs each progress report.
+#[derive(Debug, Default)]
+pub struct LogReplayObserver;
+
+impl ReplayObserver for LogReplayObserver {
+    fn on_progress(&mut self, progress: &ReplayProgress) {
+        info!(
+            "replay progress: pass: {} events: {} batches: {} packets: {} bytes: {} slot: {:?} \
+             event time: {:?} eta: {:?}",
+            progress.pass,
+            progress.event_count,
+            progress.sent.batch_count,
+            progress.sent.packet_count,
+            progress.sent.byte_count,
+            progress.slot,
+            progress.event_time,
+            progress.eta,
+        );
+    }
+}
+
+/// Where a replay is at, saved periodically with `BankingTraceReplayer::with_checkpoints()` so
+/// that a crashed replay can be continued with `BankingTraceReplayer::resume_from()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayCheckpoint {
+    pub pass: u64,
+    /// The index of the next event to be processed.
+    pub event_index: usize,
+    /// The position in the trace right before the next event.
+    pub position: TracePosition,
+    /// The recorded time of the first sent batch of the pass.
+    pub base_event_time: Option<SystemTime>,
+    /// The virtual time when the pass started.
+    pub pass_offset: Duration,
+    /// The virtual time of the replay; i.e. the recorded offset of the latest sent batch from
+    /// the first replayed one.
+    pub elapsed: Duration,
+    pub sent: CountsByLabel,
+}
+
+impl ReplayCheckpoint {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TraceError> {
+        Ok(bincode::deserialize(&fs::read(path)?)?)
+    }
+
+    /// Saves to a temporary file first and renames it to `path`, not to leave a torn checkpoint
+    /// behind on crash.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TraceError> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, bincode::serialize(self)?)?;
+        fs::rename(temp_path, path)?;
+        Ok(())
+    }
+}
+
+struct ReplayState {
+    base_instant: Instant,
+    pass_offset: Duration,
+    base_event_time: Option<SystemTime>,
+    elapsed: Duration,
+    position: Option<TracePosition>,
+    sent: CountsByLabel,
+    progress: ReplayProgress,
+    total_event_count: Option<usize>,
+    last_reported: Instant,
+    last_checkpointed: Instant,
+}
+
+impl ReplayState {
+    fn report(&mut self, observer: &mut dyn ReplayObserver) {
+        let elapsed = self.base_instant.elapsed();
+        self.progress.eta = self.total_event_count.and_then(|total_event_count| {
+            estimate_eta(elapsed, self.progress.event_count, total_event_count)
+        });
+        observer.on_progress(&self.progress);
+        self.last_reported = Instant::now();
+    }
+
+    // Returns `None` if no event has been read in the current pass yet.
+    fn checkpoint(&self, event_index: usize) -> Option<ReplayCheckpoint> {
+        Some(ReplayCheckpoint {
+            pass: self.progress.pass,
+            event_index,
+            position: self.position.clone()?,
+            base_event_time: self.base_event_time,
+            pass_offset: self.pass_offset,
+            elapsed: self.elapsed,
+            sent: self.sent.clone(),
+        })
+    }
+}
+
+/// Extrapolates the time left to replay `total_event_count` events from the `elapsed` time of
+/// the first `event_count` ones. `None` until any has been replayed, or if out of range.
+fn estimate_eta(
+    elapsed: Duration,
+    event_count: usize,
+    total_event_count: usize,
+) -> Option<Duration> {
+    if event_count == 0 {
+        return None;
+    }
+    let remaining_event_count = total_event_count.saturating_sub(event_count);
+    // in floating point, so that neither count can overflow for long traces
+    let per_event_secs = elapsed.as_secs_f64() / event_count as f64;
+    Duration::try_from_secs_f64(per_event_secs * remaining_event_count as f64).ok()
+}
+
+/// Re-sends recorded `PacketBatch` events into the senders of `Channels` matching their
+/// `ChannelLabel`, while preserving the recorded inter-event delays.
+pub struct BankingTraceReplayer {
+    reader: TraceReader,
+    exit: Arc<AtomicBool>,
+    speed: ReplaySpeed,
+    slots: Option<RangeInclusive<Slot>>,
+    virtual_clock: Option<Arc<VirtualClock>>,
+    labels: Option<BTreeSet<ChannelLabel>>,
+    repetition: Repetition,
+    repeat_hook: Option<RepeatHook>,
+    mutator: Option<PacketMutator>,
+    backpressure: Option<BackpressurePolicy>,
+    observer: Option<(Box<dyn ReplayObserver>, Duration)>,
+    checkpoints: Option<(PathBuf, Duration)>,
+    resumed_checkpoint: Option<ReplayCheckpoint>,
+}
+
+impl BankingTraceReplayer {
+    pub fn new(reader: TraceReader, exit: Arc<AtomicBool>) -> Self {
+        Self {
+            reader,
+            exit,
+            speed: ReplaySpeed::default(),
+            slots: None,
+            virtual_clock: None,
+            labels: None,
+            repetition: Repetition::Times(1),
+            repeat_hook: None,
+            mutator: None,
+            backpressure: None,
+            observer: None,
+            checkpoints: None,
+            resumed_checkpoint: None,
+        }
+    }
+
+    pub fn open_dir(path: impl AsRef<Path>, exit: Arc<AtomicBool>) -> Result<Self, TraceError> {
+        Ok(Self::new(TraceReader::open_dir(path)?, exit))
+    }
+
+    pub fn with_speed(mut self, speed: ReplaySpeed) -> Self {
+        if let ReplaySpeed::Factor(factor) = speed {
+            assert!(
+                factor.is_finite() && factor > 0.0,
+                "invalid replay speed factor: {factor}"
+            );
+        }
+        self.speed = speed;
+        self
+    }
+
+    /// Drives `virtual_clock` in lockstep with the consumers of the target instead of sleeping.
+    /// `with_speed()` is ignored in this mode.
+    pub fn with_virtual_clock(mut self, virtual_clock: Arc<VirtualClock>) -> Self {
+        self.virtual_clock = Some(virtual_clock);
+        self
+    }
+
+    /// Re-sends batches of the given labels only, suppressing all others.
+    pub fn with_labels(mut self, labels: impl IntoIterator<Item = ChannelLabel>) -> Self {
+        self.labels = Some(labels.into_iter().collect());
+        self
+    }
+
+    /// Replays the trace `count` times in a row. Each pass starts right after the last batch of
+    /// the previous one, keeping the recorded inter-event delays.
+    pub fn repeat(mut self, count: u64) -> Self {
+        self.repetition = Repetition::Times(count);
+        self
+    }
+
+    /// Replays the trace over and over until `exit` is set, for sustained load testing.
+    pub fn loop_forever(mut self) -> Self {
+        self.repetition = Repetition::Forever;
+        self
+    }
+
+    /// Sets a hook to rewrite the batches of repeated passes. Recorded transactions refer to
+    /// recent blockhashes which expire soon after recording. So, the hook is expected to
+    /// replace them with fresh ones (re-signing the transactions) for repeated passes not to
+    /// be rejected entirely.
+    pub fn with_repeat_hook(
+        mut self,
+        repeat_hook: impl FnMut(u64, BankingPacketBatch) -> BankingPacketBatch + Send + 'static,
+    ) -> Self {
+        self.repeat_hook = Some(Box::new(repeat_hook));
+        self
+    }
+
+    /// Sets a hook to corrupt or permute every non-discarded packet as it's re-sent, for
+    /// trace-seeded fuzzing of packet handling. Packets not backed by `Packet` are converted to
+    /// and from it around the call.
+    pub fn with_mutator(mut self, mutator: impl FnMut(&mut Packet) + Send + 'static) -> Self {
+        self.mutator = Some(Box::new(mutator));
+        self
+    }
+
+    pub fn with_backpressure(mut self, backpressure: BackpressurePolicy) -> Self {
+        assert!(
+            backpressure.low_watermark < backpressure.high_watermark,
+            "invalid watermarks: {backpressure:?}"
+        );
+        self.backpressure = Some(backpressure);
+        self
+    }
+
+    /// Reports progress to `observer` every `interval` as well as at the end of the replay.
+    pub fn with_observer(
+        mut self,
+        observer: impl ReplayObserver + 'static,
+        interval: Duration,
+    ) -> Self {
+        self.observer = Some((Box::new(observer), interval));
+        self
+    }
+
+    /// Saves a `ReplayCheckpoint` to `path` every `interval`.
+    pub fn with_checkpoints(mut self, path: impl Into<PathBuf>, interval: Duration) -> Self {
+        self.checkpoints = Some((path.into(), interval));
+        self
+    }
+
+    /// Continues the replay from `checkpoint`, which must have been saved by a replayer of the
+    /// same trace and configuration, instead of from the start. The recorded timing and the
+    /// virtual clock are continued as well.
+    pub fn resume_from(mut self, checkpoint: ReplayCheckpoint) -> Self {
+        self.resumed_checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Limits replay to the packets received while the given slots were being processed, based
+    /// on the `BlockAndBankHash` events as anchors. That is, the packets after the anchor of the
+    /// greatest slot below `start` up to the anchor of the first slot at or above `end`. Either
+    /// side extends to the corresponding end of the trace if there's no such anchor.
+    pub fn for_slots(mut self, slots: RangeInclusive<Slot>) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
+    // Returns the range of event indexes to be replayed, scanning for anchors if needed.
+    fn event_index_range(&self) -> Result<Range<usize>, TraceError> {
+        let Some(slots) = &self.slots else {
+            return Ok(0..usize::MAX);
+        };
+
+        let mut range = 0..usize::MAX;
+        for (index, event) in self.reader.iter().enumerate() {
+            let TimedTracedEvent(_, TracedEvent::BlockAndBankHash(slot, _, _)) = event? else {
+                continue;
+            };
+            if slot < *slots.start() {
+                range.start = index + 1;
+            } else if slot >= *slots.end() {
+                range.end = index;
+                break;
+            }
+        }
+        Ok(range)
+    }
+
+    /// Compares the hashes of the slots traced in `produced` (e.g. the trace written by the
+    /// simulation bank which the packets were replayed into) against the ones recorded in the
+    /// replayed trace. Only the slots within `for_slots()` are compared if it's specified.
+    pub fn verify_hashes(&self, produced: &TraceReader) -> Result<Vec<HashDivergence>, TraceError> {
+        let recorded = self.reader.hashes_by_slot()?;
+        let divergences = produced
+            .hashes_by_slot()?
+            .into_iter()
+            .filter(|(slot, _)| self.slots.as_ref().is_none_or(|slots| slots.contains(slot)))
+            .filter_map(|(slot, produced)| {
+                let recorded = recorded.get(&slot).copied();
+                (recorded != Some(produced)).then_some(HashDivergence {
+                    slot,
+                    recorded,
+                    produced,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for divergence in &divergences {
+            warn!("replayed hashes diverged: {divergence:?}");
+        }
+        Ok(divergences)
+    }
+
+    /// Replays all events into `target` (usually `Channels`) on the calling thread until they're
+    /// exhausted or `exit` is set. Returns the counts of sent batches per `ChannelLabel`.
+    pub fn replay(&mut self, target: &impl ReplayTarget) -> Result<CountsByLabel, ReplayError> {
+        let event_index_range = self.event_index_range()?;
+        let mut resumed_checkpoint = self.resumed_checkpoint.take();
+        let (first_pass, pass_offset, base_event_time, elapsed, sent) = match &resumed_checkpoint {
+            Some(checkpoint) => (
+                checkpoint.pass,
+                checkpoint.pass_offset,
+                checkpoint.base_event_time,
+                checkpoint.elapsed,
+                checkpoint.sent.clone(),
+            ),
+            None => (
+                0,
+                Duration::ZERO,
+                None,
+                Duration::ZERO,
+                CountsByLabel::new(),
+            ),
+        };
+        if let Some(virtual_clock) = &self.virtual_clock {
+            virtual_clock.advance_to(elapsed);
+        }
+        // pretend that the replay has been running without interruption up to the checkpoint.
+        let now = Instant::now();
+        let base_instant = self
+            .speed
+            .scale(elapsed)
+            .and_then(|elapsed| now.checked_sub(elapsed))
+            .unwrap_or(now);
+        let mut progress = ReplayProgress::default();
+        for counts in sent.values() {
+            progress.sent.add(counts);
+        }
+        let mut state = ReplayState {
+            base_instant,
+            pass_offset,
+            base_event_time,
+            elapsed,
+            position: None,
+            sent,
+            progress,
+            total_event_count: self.total_event_count(&event_index_range),
+            last_reported: now,
+            last_checkpointed: now,
+        };
+
+        for pass in first_pass.. {
+            if matches!(self.repetition, Repetition::Times(count) if pass >= count) {
+                break;
+            }
+            state.progress.pass = pass;
+            let resume_at = resumed_checkpoint
+                .take()
+                .map(|checkpoint| (checkpoint.event_index, checkpoint.position));
+            let Some(pass_duration) =
+                self.replay_pass(target, &event_index_range, resume_at, &mut state)?
+            else {
+                break;
+            };
+            state.pass_offset += pass_duration;
+            state.base_event_time = None;
+        }
+
+        if let Some((observer, _interval)) = &mut self.observer {
+            state.report(observer.as_mut());
+        }
+        Ok(state.sent)
+    }
+
+    // Returns the number of events to be processed across all passes, if it's known upfront
+    // without decoding all of them.
+    fn total_event_count(&self, event_index_range: &Range<usize>) -> Option<usize> {
+        let Repetition::Times(count) = self.repetition else {
+            return None;
+        };
+        self.observer.as_ref()?;
+        let len = self.reader.len_hint().ok()?;
+        let event_count = event_index_range
+            .end
+            .min(len)
+            .saturating_sub(event_index_range.start);
+        event_count.checked_mul(usize::try_from(count).ok()?)
+    }
+
+    // Returns the elapsed time from the first to the last sent batch, or `None` if interrupted
+    // by exit or there was nothing to send.
+    fn replay_pass(
+        &mut self,
+        target: &impl ReplayTarget,
+        event_index_range: &Range<usize>,
+        resume_at: Option<(usize, TracePosition)>,
+        state: &mut ReplayState,
+    ) -> Result<Option<Duration>, ReplayError> {
+        let (resumed_event_index, position) = resume_at.unzip();
+        let resumed_event_index = resumed_event_index.unwrap_or_default();
+        let mut event_index = resumed_event_index.max(event_index_range.start);
+        let mut pass_duration = state
+            .base_event_time
+            .map(|_| state.elapsed.saturating_sub(state.pass_offset));
+        state.position = position.clone();
+
+        for event in self
+            .reader
+            .iter_from(position.as_ref())
+            .skip(event_index_range.start.saturating_sub(resumed_event_index))
+            .take(event_index_range.end.saturating_sub(event_index))
+        {
+            if let Some((path, interval)) = &self.checkpoints {
+                if state.last_checkpointed.elapsed() >= *interval {
+                    if let Some(checkpoint) = state.checkpoint(event_index) {
+                        checkpoint.save(path)?;
+                        state.last_checkpointed = Instant::now();
+                    }
+                }
+            }
+            if self.exit.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            let (TimedTracedEvent(event_time, event), position) = event?;
+            event_index += 1;
+            state.position = Some(position);
+            state.progress.event_count += 1;
+            state.progress.event_time = Some(event_time);
+            if let Some((observer, interval)) = &mut self.observer {
+                if state.last_reported.elapsed() >= *interval {
+                    state.report(observer.as_mut());
+                }
+            }
+            let (label, mut batch) = match event {
+                TracedEvent::PacketBatch(label, batch) => (label, batch),
+                TracedEvent::BlockAndBankHash(slot, _, _) => {
+                    state.progress.slot = Some(slot);
+                    continue;
+                }
+                _ => continue,
+            };
+            if label == ChannelLabel::Dummy
+                || self
+                    .labels
+                    .as_ref()
+                    .is_some_and(|labels| !labels.contains(&label))
+            {
+                continue;
+            }
+
+            let elapsed_in_pass =
+                Self::elapsed(*state.base_event_time.get_or_insert(event_time), event_time);
+            pass_duration = Some(elapsed_in_pass);
+            state.elapsed = state.pass_offset + elapsed_in_pass;
+            if let Some(virtual_clock) = &self.virtual_clock {
+                if !self.wait_for_drained(target) {
+                    return Ok(None);
+                }
+                virtual_clock.advance_to(state.elapsed);
+            } else if let Some(elapsed) = self.speed.scale(state.elapsed) {
+                Self::sleep_until(state.base_instant + elapsed);
+            }
+            if let Some(backpressure) = &self.backpressure {
+                let paused = Instant::now();
+                if !self.wait_for_backpressure(target, label, backpressure) {
+                    return Ok(None);
+                }
+                state.base_instant += paused.elapsed();
+            }
+
+            let pass = state.progress.pass;
+            if pass > 0 {
+                if let Some(repeat_hook) = &mut self.repeat_hook {
+                    batch = repeat_hook(pass, batch);
+                }
+            }
+            if let Some(mutator) = &mut self.mutator {
+                batch = Self::mutate(batch, mutator);
+            }
+            let counts = TraceCounts::from_batch(&batch);
+            target.send(label, batch)?;
+            state.sent.entry(label).or_default().add(&counts);
+            state.progress.sent.add(&counts);
+        }
+
+        Ok(pass_duration)
+    }
+
+    fn mutate(batch: BankingPacketBatch, mutator: &mut PacketMutator) -> BankingPacketBatch {
+        let mut batches = Arc::unwrap_or_clone(batch);
+        for packet in batches.iter_mut().flat_map(|batch| batch.iter_mut()) {
+            match packet {
+                PacketRefMut::Packet(packet) => {
+                    if !packet.meta().discard() {
+                        mutator(packet);
+                    }
+                }
+                PacketRefMut::Bytes(bytes_packet) => {
+                    if bytes_packet.meta().discard() {
+                        continue;
+                    }
+                    let data = bytes_packet.data(..).unwrap_or_default();
+                    let mut buffer = [0; PACKET_DATA_SIZE];
+                    buffer[..data.len()].copy_from_slice(data);
+                    let mut packet = Packet::new(buffer, bytes_packet.meta().clone());
+                    mutator(&mut packet);
+                    let data = packet.data(..).unwrap_or_default();
+                    *bytes_packet =
+                        BytesPacket::new(Bytes::copy_from_slice(data), packet.meta().clone());
+                }
+            }
+        }
+        Arc::new(batches)
+    }
+
+    // Returns false if interrupted by exit.
+    fn wait_for_backpressure(
+        &self,
+        target: &impl ReplayTarget,
+        label: ChannelLabel,
+        backpressure: &BackpressurePolicy,
+    ) -> bool {
+        if target.len(label) < backpressure.high_watermark {
+            return true;
+        }
+        while target.len(label) > backpressure.low_watermark {
+            if self.exit.load(Ordering::Relaxed) {
+                return false;
+            }
+            sleep(backpressure.poll_interval);
+        }
+        true
+    }
+
+    // Returns false if interrupted by exit.
+    fn wait_for_drained(&self, target: &impl ReplayTarget) -> bool {
+        while !target.is_drained() {
+            if self.exit.load(Ordering::Relaxed) {
+                return false;
+            }
+            yield_now();
+        }
+        true
+    }
+
+    // The recorded time can go backwards because it's the wall clock. Such events are sent
+    // immediately.
+    fn elapsed(base_event_time: SystemTime, event_time: SystemTime) -> Duration {
+        event_time
+            .duration_since(base_event_time)
+            .unwrap_or_default()
+    }
+
+    fn sleep_until(deadline: Instant) {
+        let now = Instant::now();
+        if deadline > now {
+            sleep(deadline - now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{for_test, BankingTracer},
+        solana_packet::Meta,
+        solana_perf::packet::PacketBatch,
+        std::path::PathBuf,
+        tempfile::TempDir,
+    };
+
+    fn write_batch_events(
+        path: &PathBuf,
+        events: impl IntoIterator<Item = (Duration, ChannelLabel)>,
+    ) {
+        let base_time = SystemTime::now();
+        for_test::write_events(
+            path,
+            u64::MAX,
+            events.into_iter().map(|(offset, label)| {
+                TimedTracedEvent(
+                    base_time + offset,
+                    TracedEvent::PacketBatch(label, for_test::sample_packet_batch()),
+                )
+            }),
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(
+            &path,
+            [
+                (Duration::ZERO, ChannelLabel::NonVote),
+                (Duration::from_millis(50), ChannelLabel::TpuVote),
+                (Duration::from_millis(100), ChannelLabel::GossipVote),
+                (Duration::from_millis(100), ChannelLabel::Dummy),
+            ],
+        );
+        let channels = BankingTracer::new_disabled().create_channels(false);
+
+        let mut replayer =
+            BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::default())).unwrap();
+        let started = Instant::now();
+        let sent = replayer.replay(&channels).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        assert_eq!(
+            sent.keys().copied().collect::<Vec<_>>(),
+            vec![
+                ChannelLabel::NonVote,
+                ChannelLabel::TpuVote,
+                ChannelLabel::GossipVote
+            ]
+        );
+        assert_eq!(channels.non_vote_receiver.len(), 1);
+        assert_eq!(channels.tpu_vote_receiver.len(), 1);
+        assert_eq!(channels.gossip_vote_receiver.len(), 1);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_speed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(
+            &path,
+            [
+                (Duration::ZERO, ChannelLabel::NonVote),
+                (Duration::from_secs(60), ChannelLabel::NonVote),
+            ],
+        );
+        let channels = BankingTracer::new_disabled().create_channels(false);
+        let exit = Arc::new(AtomicBool::default());
+
+        let started = Instant::now();
+        BankingTraceReplayer::open_dir(&path, exit.clone())
+            .unwrap()
+            .with_speed(ReplaySpeed::Factor(600.0))
+            .replay(&channels)
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        let started = Instant::now();
+        BankingTraceReplayer::open_dir(&path, exit)
+            .unwrap()
+            .with_speed(ReplaySpeed::AsFastAsPossible)
+            .replay(&channels)
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(60));
+        assert_eq!(channels.non_vote_receiver.len(), 4);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_for_slots() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        let batch = |label| TracedEvent::PacketBatch(label, for_test::sample_packet_batch());
+        let anchor = |slot| TracedEvent::BlockAndBankHash(slot, Hash::default(), Hash::default());
+        for_test::write_events(
+            &path,
+            u64::MAX,
+            [
+                batch(ChannelLabel::NonVote),
+                anchor(8),
+                batch(ChannelLabel::NonVote),
+                anchor(9),
+                batch(ChannelLabel::TpuVote),
+                anchor(10),
+                batch(ChannelLabel::GossipVote),
+                anchor(11),
+                batch(ChannelLabel::NonVote),
+            ]
+            .map(|event| TimedTracedEvent(SystemTime::now(), event)),
+        );
+        let channels = BankingTracer::new_disabled().create_channels(false);
+
+        let sent = BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::default()))
+            .unwrap()
+            .for_slots(10..=11)
+            .replay(&channels)
+            .unwrap();
+        assert_eq!(
+            sent.keys().copied().collect::<Vec<_>>(),
+            vec![ChannelLabel::TpuVote, ChannelLabel::GossipVote]
+        );
+        assert!(channels.non_vote_receiver.is_empty());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_verify_hashes() {
+        let temp_dir = TempDir::new().unwrap();
+        let recorded_path = temp_dir.path().join("recorded");
+        let produced_path = temp_dir.path().join("produced");
+        let anchor = |slot, bank_hash| {
+            TimedTracedEvent(
+                SystemTime::now(),
+                TracedEvent::BlockAndBankHash(slot, Hash::default(), bank_hash),
+            )
+        };
+        let (hash1, hash2) = (Hash::new_unique(), Hash::new_unique());
+        for_test::write_events(
+            &recorded_path,
+            u64::MAX,
+            [anchor(1, hash1), anchor(2, hash1), anchor(3, hash1)],
+        );
+        for_test::write_events(
+            &produced_path,
+            u64::MAX,
+            [
+                anchor(2, hash1),
+                anchor(3, hash2),
+                anchor(4, hash1),
+                anchor(5, hash2),
+            ],
+        );
+        let replayer =
+            BankingTraceReplayer::open_dir(&recorded_path, Arc::new(AtomicBool::default()))
+                .unwrap()
+                .for_slots(1..=4);
+        let produced = TraceReader::open_dir(&produced_path).unwrap();
+
+        assert_eq!(
+            replayer.verify_hashes(&produced).unwrap(),
+            vec![
+                HashDivergence {
+                    slot: 3,
+                    recorded: Some((Hash::default(), hash1)),
+                    produced: (Hash::default(), hash2),
+                },
+                HashDivergence {
+                    slot: 4,
+                    recorded: None,
+                    produced: (Hash::default(), hash1),
+                },
+            ]
+        );
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_with_virtual_clock() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(
+            &path,
+            [
+                (Duration::ZERO, ChannelLabel::NonVote),
+                (Duration::from_secs(60), ChannelLabel::TpuVote),
+                (Duration::from_secs(120), ChannelLabel::NonVote),
+            ],
+        );
+        let channels = BankingTracer::new_disabled().create_channels(true);
+        let virtual_clock = Arc::new(VirtualClock::default());
+
+        let consumer = {
+            let receiver = channels.non_vote_receiver.clone();
+            let virtual_clock = virtual_clock.clone();
+            std::thread::spawn(move || {
+                (0..3)
+                    .map(|_| {
+                        receiver.recv().unwrap();
+                        virtual_clock.elapsed()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        let started = Instant::now();
+        BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::default()))
+            .unwrap()
+            .with_virtual_clock(virtual_clock)
+            .replay(&channels)
+            .unwrap();
+
+        let observed = consumer.join().unwrap();
+        assert!(observed[1] >= Duration::from_secs(60));
+        assert_eq!(observed[2], Duration::from_secs(120));
+        assert!(started.elapsed() < Duration::from_secs(60));
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_with_labels() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(
+            &path,
+            [
+                (Duration::ZERO, ChannelLabel::NonVote),
+                (Duration::ZERO, ChannelLabel::TpuVote),
+                (Duration::ZERO, ChannelLabel::GossipVote),
+                (Duration::ZERO, ChannelLabel::TpuVote),
+            ],
+        );
+        let channels = BankingTracer::new_disabled().create_channels(false);
+
+        let sent = BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::default()))
+            .unwrap()
+            .with_labels([ChannelLabel::TpuVote])
+            .replay(&channels)
+            .unwrap();
+        assert_eq!(sent[&ChannelLabel::TpuVote].batch_count, 2);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(channels.tpu_vote_receiver.len(), 2);
+        assert!(channels.non_vote_receiver.is_empty());
+        assert!(channels.gossip_vote_receiver.is_empty());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_repeat() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(
+            &path,
+            [
+                (Duration::ZERO, ChannelLabel::NonVote),
+                (Duration::from_millis(50), ChannelLabel::NonVote),
+            ],
+        );
+        let channels = BankingTracer::new_disabled().create_channels(false);
+        let passes = Arc::new(std::sync::Mutex::new(vec![]));
+
+        let started = Instant::now();
+        let sent = BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::default()))
+            .unwrap()
+            .repeat(3)
+            .with_repeat_hook({
+                let passes = passes.clone();
+                move |pass, batch| {
+                    passes.lock().unwrap().push(pass);
+                    batch
+                }
+            })
+            .replay(&channels)
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(sent[&ChannelLabel::NonVote].batch_count, 6);
+        assert_eq!(*passes.lock().unwrap(), vec![1, 1, 2, 2]);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_with_mutator() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(&path, [(Duration::ZERO, ChannelLabel::NonVote)]);
+        let channels = BankingTracer::new_disabled().create_channels(false);
+
+        BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::default()))
+            .unwrap()
+            .with_mutator(|packet| {
+                packet.buffer_mut()[0] = u8::MAX;
+                packet.meta_mut().size = 1;
+            })
+            .replay(&channels)
+            .unwrap();
+        let batch = channels.non_vote_receiver.try_recv().unwrap();
+        assert!(batch
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .all(|packet| packet.data(..) == Some(&[u8::MAX][..])));
+        assert!(batch.iter().map(|batch| batch.len()).sum::<usize>() > 0);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_mutate_skips_discarded_bytes_packets() {
+        let mut packets = vec![
+            BytesPacket::new(Bytes::from_static(&[1]), Meta::default()),
+            BytesPacket::new(Bytes::from_static(&[2]), Meta::default()),
+        ];
+        for packet in &mut packets {
+            packet.meta_mut().size = 1;
+        }
+        packets[1].meta_mut().set_discard(true);
+        let batch = Arc::new(vec![PacketBatch::from(packets)]);
+
+        let mutated_count = Arc::new(AtomicU64::default());
+        let mut mutator: PacketMutator = Box::new({
+            let mutated_count = mutated_count.clone();
+            move |packet| {
+                mutated_count.fetch_add(1, Ordering::Relaxed);
+                packet.buffer_mut()[0] = u8::MAX;
+            }
+        });
+        let batch = BankingTraceReplayer::mutate(batch, &mut mutator);
+        assert_eq!(mutated_count.load(Ordering::Relaxed), 1);
+        let packets = batch
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .collect::<Vec<_>>();
+        assert_eq!(packets[0].data(..), Some(&[u8::MAX][..]));
+        assert!(packets[1].meta().discard());
+    }
+
+    #[test]
+    fn test_replay_to_remote_tpu() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(&path, [(Duration::ZERO, ChannelLabel::NonVote)]);
+        let connection_cache = Arc::new(ConnectionCache::new("connection_cache_test"));
+        let tpu_address = "127.0.0.1:8009".parse().unwrap();
+        let target = RemoteTpuTarget::new(connection_cache, tpu_address);
+
+        let sent = BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::default()))
+            .unwrap()
+            .with_speed(ReplaySpeed::AsFastAsPossible)
+            .replay(&target)
+            .unwrap();
+        assert_eq!(sent[&ChannelLabel::NonVote].batch_count, 1);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_with_backpressure() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(
+            &path,
+            (0..10).map(|_| (Duration::ZERO, ChannelLabel::NonVote)),
+        );
+        let channels = BankingTracer::new_disabled().create_channels(false);
+        let exit = Arc::new(AtomicBool::default());
+
+        let consumer = {
+            let receiver = channels.non_vote_receiver.clone();
+            let exit = exit.clone();
+            std::thread::spawn(move || {
+                let mut max_len = 0;
+                for _ in 0..10 {
+                    sleep(Duration::from_millis(10));
+                    max_len = max_len.max(receiver.len());
+                    receiver.recv().unwrap();
+                }
+                exit.store(true, Ordering::Relaxed);
+                max_len
+            })
+        };
+        BankingTraceReplayer::open_dir(&path, exit)
+            .unwrap()
+            .with_backpressure(BackpressurePolicy {
+                high_watermark: 3,
+                low_watermark: 1,
+                poll_interval: Duration::from_millis(1),
+            })
+            .replay(&channels)
+            .unwrap();
+        assert!(consumer.join().unwrap() <= 3);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_with_observer() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(
+            &path,
+            (0..4).map(|i| (Duration::from_millis(i * 20), ChannelLabel::NonVote)),
+        );
+        let channels = BankingTracer::new_disabled().create_channels(false);
+        let reports = Arc::new(std::sync::Mutex::new(vec![]));
+
+        BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::default()))
+            .unwrap()
+            .repeat(2)
+            .with_observer(
+                {
+                    let reports = reports.clone();
+                    move |progress: &ReplayProgress| reports.lock().unwrap().push(progress.clone())
+                },
+                Duration::from_millis(30),
+            )
+            .replay(&channels)
+            .unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert!(reports.len() >= 2);
+        assert!(reports[0].eta.is_some());
+        let last_report = reports.last().unwrap();
+        assert_eq!(last_report.pass, 1);
+        assert_eq!(last_report.event_count, 8);
+        assert_eq!(last_report.sent.batch_count, 8);
+        assert_eq!(last_report.eta, Some(Duration::ZERO));
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_estimate_eta() {
+        assert_eq!(estimate_eta(Duration::from_secs(1), 0, 10), None);
+        assert_eq!(
+            estimate_eta(Duration::from_secs(2), 4, 10),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(
+            estimate_eta(Duration::from_secs(2), 4, 2),
+            Some(Duration::ZERO)
+        );
+        // beyond u32::MAX events remaining
+        assert_eq!(
+            estimate_eta(Duration::from_secs(1), 1, 1 << 33),
+            Some(Duration::from_secs((1 << 33) - 1))
+        );
+        assert_eq!(estimate_eta(Duration::MAX, 1, usize::MAX), None);
+    }
+
+    #[test]
+    fn test_replay_resume_from_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(
+            &path,
+            (0..6).map(|i| (Duration::from_millis(i), ChannelLabel::NonVote)),
+        );
+        let checkpoint_path = temp_dir.path().join("checkpoint");
+        let channels = BankingTracer::new_disabled().create_channels(false);
+        let exit = Arc::new(AtomicBool::default());
+
+        // crash after sending 3 batches of the second pass; each batch consists of 4 packets.
+        let mut mutated_count = 0;
+        let sent = BankingTraceReplayer::open_dir(&path, exit.clone())
+            .unwrap()
+            .repeat(2)
+            .with_checkpoints(&checkpoint_path, Duration::ZERO)
+            .with_mutator({
+                let exit = exit.clone();
+                move |_packet| {
+                    mutated_count += 1;
+                    if mutated_count == 9 * 4 {
+                        exit.store(true, Ordering::Relaxed);
+                    }
+                }
+            })
+            .replay(&channels)
+            .unwrap();
+        assert_eq!(sent[&ChannelLabel::NonVote].batch_count, 9);
+
+        let checkpoint = ReplayCheckpoint::load(&checkpoint_path).unwrap();
+        assert_eq!(checkpoint.pass, 1);
+        assert_eq!(checkpoint.event_index, 3);
+        assert_eq!(checkpoint.sent, sent);
+
+        exit.store(false, Ordering::Relaxed);
+        let sent = BankingTraceReplayer::open_dir(&path, exit)
+            .unwrap()
+            .repeat(2)
+            .resume_from(checkpoint)
+            .replay(&channels)
+            .unwrap();
+        assert_eq!(sent[&ChannelLabel::NonVote].batch_count, 12);
+        assert_eq!(channels.non_vote_receiver.len(), 12);
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+
+    #[test]
+    fn test_replay_exit() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace");
+        write_batch_events(&path, [(Duration::ZERO, ChannelLabel::TpuVote)]);
+        let channels = BankingTracer::new_disabled().create_channels(false);
+
+        let mut replayer =
+            BankingTraceReplayer::open_dir(&path, Arc::new(AtomicBool::new(true))).unwrap();
+        assert!(replayer.replay(&channels).unwrap().is_empty());
+        assert!(channels.tpu_vote_receiver.is_empty());
+
+        for_test::drop_and_clean_temp_dir_unless_suppressed(temp_dir);
+    }
+}
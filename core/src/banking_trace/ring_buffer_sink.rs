@@ -0,0 +1,203 @@
+//! `TraceSink` retaining the most recent events in memory, so that admin tooling can show what
+//! has just hit the banking stage without touching disk.
+
+use {
+    super::{ChannelLabel, TimedTracedEvent, TraceError, TraceSink},
+    solana_clock::Slot,
+    std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime},
+    },
+};
+
+/// Which of the retained events `RecentEvents::recent_events()` returns. Each condition is
+/// ignored if unset, and events without a channel or a slot match the respective conditions.
+#[derive(Debug, Default, Clone)]
+pub struct EventFilter {
+    pub since: Option<SystemTime>,
+    /// Kinds as returned by `TracedEvent::kind()`, e.g. `"PacketBatch"`.
+    pub kinds: Vec<String>,
+    pub labels: Vec<ChannelLabel>,
+    pub min_slot: Option<Slot>,
+    pub max_slot: Option<Slot>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &TimedTracedEvent) -> bool {
+        let TimedTracedEvent(time, traced_event) = event;
+        let time_matches = self.since.is_none_or(|since| *time >= since);
+        let kind_matches =
+            self.kinds.is_empty() || self.kinds.iter().any(|kind| kind == traced_event.kind());
+        let label_matches = traced_event
+            .label()
+            .is_none_or(|label| self.labels.is_empty() || self.labels.contains(&label));
+        let slot_matches = traced_event.slot().is_none_or(|slot| {
+            self.min_slot.is_none_or(|min_slot| slot >= min_slot)
+                && self.max_slot.is_none_or(|max_slot| slot <= max_slot)
+        });
+        time_matches && kind_matches && label_matches && slot_matches
+    }
+}
+
+/// Handle to query the events retained by a `RingBufferSink`, which can be shared with other
+/// threads.
+#[derive(Debug, Clone, Default)]
+pub struct RecentEvents {
+    events: Arc<Mutex<VecDeque<TimedTracedEvent>>>,
+}
+
+impl RecentEvents {
+    /// Returns the retained events matching `filter`, oldest first.
+    pub fn recent_events(&self, filter: &EventFilter) -> Vec<TimedTracedEvent> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| filter.matches(event))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Retains the events traced within `retention` of the latest one, but at most
+/// `max_event_count` of them to bound the memory usage under heavy load.
+pub struct RingBufferSink {
+    retention: Duration,
+    max_event_count: usize,
+    recent_events: RecentEvents,
+}
+
+impl RingBufferSink {
+    pub fn new(retention: Duration, max_event_count: usize) -> Self {
+        Self {
+            retention,
+            max_event_count,
+            recent_events: RecentEvents::default(),
+        }
+    }
+
+    pub fn recent_events(&self) -> RecentEvents {
+        self.recent_events.clone()
+    }
+}
+
+impl TraceSink for RingBufferSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        let TimedTracedEvent(time, _) = event;
+        let oldest_time = time
+            .checked_sub(self.retention)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut events = self.recent_events.events.lock().unwrap();
+        while events.front().is_some_and(|TimedTracedEvent(time, _)| {
+            *time < oldest_time || events.len() >= self.max_event_count
+        }) {
+            events.pop_front();
+        }
+        if self.max_event_count > 0 {
+            events.push_back(event.clone());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{PacketDropReason, TracedEvent},
+        std::time::UNIX_EPOCH,
+    };
+
+    fn at_secs(secs: u64, event: TracedEvent) -> TimedTracedEvent {
+        TimedTracedEvent(UNIX_EPOCH + Duration::from_secs(secs), event)
+    }
+
+    fn dropped(label: ChannelLabel) -> TracedEvent {
+        TracedEvent::PacketsDropped {
+            label,
+            count: 1,
+            reason: PacketDropReason::Dedup,
+        }
+    }
+
+    fn bank_frozen(slot: Slot) -> TracedEvent {
+        TracedEvent::BankFrozen {
+            slot,
+            tick_height: 64,
+            transaction_count: 0,
+        }
+    }
+
+    fn times(events: Vec<TimedTracedEvent>) -> Vec<u64> {
+        events
+            .iter()
+            .map(|TimedTracedEvent(time, _)| time.duration_since(UNIX_EPOCH).unwrap().as_secs())
+            .collect()
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut sink = RingBufferSink::new(Duration::from_secs(10), 3);
+        let recent_events = sink.recent_events();
+        for secs in [1, 5, 12, 15] {
+            sink.write_event(&at_secs(secs, TracedEvent::Heartbeat))
+                .unwrap();
+        }
+        // 1 is older than 10 seconds
+        assert_eq!(
+            times(recent_events.recent_events(&EventFilter::default())),
+            [5, 12, 15]
+        );
+        sink.write_event(&at_secs(16, TracedEvent::Heartbeat))
+            .unwrap();
+        // 5 is evicted to retain at most 3 events
+        assert_eq!(
+            times(recent_events.recent_events(&EventFilter::default())),
+            [12, 15, 16]
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        for event in [
+            at_secs(1, dropped(ChannelLabel::NonVote)),
+            at_secs(2, dropped(ChannelLabel::TpuVote)),
+            at_secs(3, bank_frozen(4)),
+            at_secs(4, bank_frozen(5)),
+            at_secs(5, TracedEvent::Heartbeat),
+        ] {
+            sink.write_event(&event).unwrap();
+        }
+        let recent_events = |filter| times(sink.recent_events().recent_events(&filter));
+
+        assert_eq!(
+            recent_events(EventFilter {
+                since: Some(UNIX_EPOCH + Duration::from_secs(3)),
+                ..EventFilter::default()
+            }),
+            [3, 4, 5]
+        );
+        assert_eq!(
+            recent_events(EventFilter {
+                kinds: vec!["PacketsDropped".into()],
+                labels: vec![ChannelLabel::TpuVote],
+                ..EventFilter::default()
+            }),
+            [2]
+        );
+        assert_eq!(
+            recent_events(EventFilter {
+                labels: vec![ChannelLabel::TpuVote],
+                min_slot: Some(5),
+                ..EventFilter::default()
+            }),
+            [2, 4, 5]
+        );
+    }
+}
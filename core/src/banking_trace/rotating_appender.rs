@@ -0,0 +1,137 @@
+//! File rotation shared by the `RollingFileSink` backends which write asynchronously, so that
+//! they only need to provide how a single file is written.
+
+use {
+    super::RollingConditionGrouped,
+    chrono::{DateTime, Local},
+    rolling_file::RollingCondition,
+    std::{
+        fs::{self, File, OpenOptions},
+        io::{self, Write},
+        path::{Path, PathBuf},
+    },
+};
+
+/// Writes a single file sequentially from `offset`. Flushing waits for all of the writes issued
+/// so far, and so does dropping.
+pub(super) trait AppenderWriter: Write + Sized {
+    fn new(file: File, offset: u64, buffer_capacity: usize) -> io::Result<Self>;
+}
+
+/// Rotates `<filename>`, `<filename>.1`, ... up to `max_filecount` rotated files, in the same
+/// way as `RollingFileAppender`, writing each file through `W`.
+pub(super) struct RotatingAppender<W> {
+    condition: RollingConditionGrouped,
+    filename: PathBuf,
+    max_filecount: usize,
+    current_filesize: u64,
+    writer: Option<W>,
+    buffer_capacity: usize,
+}
+
+impl<W: AppenderWriter> RotatingAppender<W> {
+    pub(super) fn new(
+        filename: impl AsRef<Path>,
+        condition: RollingConditionGrouped,
+        max_filecount: usize,
+        buffer_capacity: usize,
+    ) -> io::Result<Self> {
+        let mut appender = Self {
+            condition,
+            filename: filename.as_ref().to_path_buf(),
+            max_filecount,
+            current_filesize: 0,
+            writer: None,
+            buffer_capacity,
+        };
+        appender.open_writer_if_needed()?;
+        Ok(appender)
+    }
+
+    pub(super) fn condition_ref(&self) -> &RollingConditionGrouped {
+        &self.condition
+    }
+
+    pub(super) fn condition_mut(&mut self) -> &mut RollingConditionGrouped {
+        &mut self.condition
+    }
+
+    /// Waits for the pending writes of the current file and starts a new one.
+    pub(super) fn rollover(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.writer = None;
+        self.current_filesize = 0;
+        self.rotate_files()?;
+        self.open_writer_if_needed()
+    }
+
+    pub(super) fn write_with_datetime(
+        &mut self,
+        buf: &[u8],
+        now: &DateTime<Local>,
+    ) -> io::Result<usize> {
+        if self.condition.should_rollover(now, self.current_filesize) {
+            if let Err(err) = self.rollover() {
+                warn!("failed to rotate {:?}: {err:?}", self.filename);
+            }
+        }
+        self.open_writer_if_needed()?;
+        let writer = self.writer.as_mut().unwrap();
+        writer.write_all(buf)?;
+        self.current_filesize += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn filename_for(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            return self.filename.clone();
+        }
+        let mut filename = self.filename.clone().into_os_string();
+        filename.push(format!(".{index}"));
+        filename.into()
+    }
+
+    fn rotate_files(&self) -> io::Result<()> {
+        // the oldest file may not exist
+        let _ = fs::remove_file(self.filename_for(self.max_filecount.max(1)));
+        let mut result = Ok(());
+        for index in (0..self.max_filecount.max(1)).rev() {
+            match fs::rename(self.filename_for(index), self.filename_for(index + 1)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => result = Err(err),
+                _ => {}
+            }
+        }
+        result
+    }
+
+    fn open_writer_if_needed(&mut self) -> io::Result<()> {
+        if self.writer.is_none() {
+            if let Some(parent) = self.filename.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // not appending, as the writes in flight at once may land at their own offsets
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&self.filename)?;
+            self.current_filesize = file.metadata()?.len();
+            self.writer = Some(W::new(file, self.current_filesize, self.buffer_capacity)?);
+        }
+        Ok(())
+    }
+}
+
+impl<W: AppenderWriter> Write for RotatingAppender<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_with_datetime(buf, &Local::now())
+    }
+
+    /// Waits for all of the writes submitted so far.
+    fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
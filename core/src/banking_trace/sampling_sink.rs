@@ -0,0 +1,180 @@
+//! `TraceSink` wrapper keeping only a fraction of the packet batches, so that long-running
+//! validators can keep a perpetual low-resolution trace within a small disk budget.
+
+use {
+    super::{
+        ChannelLabel, DirByteLimit, SinkEffectiveConfig, TimedTracedEvent, TraceError, TraceSink,
+        TracedEvent,
+    },
+    std::collections::BTreeMap,
+};
+
+#[derive(Debug, Default)]
+struct LabelState {
+    batch_count: u64,
+    /// Whether the last `PacketBatch` was skipped, so are the events derived from it.
+    is_skipping: bool,
+}
+
+/// Forwards 1 in `rate` `PacketBatch` events of each channel to `inner`, along with the
+/// `PacketBatchLane` events of the forwarded batches.
+/// All the other events (e.g. hashes and slot boundaries) are always forwarded.
+///
+/// The trace starts with an `Annotation` noting the sampling rate, so that counts taken from it
+/// can be scaled back.
+pub struct SamplingSink<S> {
+    inner: S,
+    rate: u64,
+    label_states: BTreeMap<ChannelLabel, LabelState>,
+    is_annotated: bool,
+}
+
+impl<S: TraceSink> SamplingSink<S> {
+    /// `rate` of 0 is treated as 1, i.e. no sampling.
+    pub fn new(inner: S, rate: u64) -> Self {
+        Self {
+            inner,
+            rate: rate.max(1),
+            label_states: BTreeMap::new(),
+            is_annotated: false,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn should_forward(&mut self, event: &TracedEvent) -> bool {
+        match event {
+            TracedEvent::PacketBatch(label, _) => {
+                let state = self.label_states.entry(*label).or_default();
+                state.is_skipping = state.batch_count % self.rate != 0;
+                state.batch_count += 1;
+                !state.is_skipping
+            }
+            TracedEvent::PacketBatchLane { label, .. } => !self
+                .label_states
+                .get(label)
+                .is_some_and(|state| state.is_skipping),
+            _ => true,
+        }
+    }
+}
+
+impl<S: TraceSink> TraceSink for SamplingSink<S> {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        let TimedTracedEvent(time, traced_event) = event;
+        if !self.is_annotated {
+            self.inner.write_event(&TimedTracedEvent(
+                *time,
+                TracedEvent::Annotation(format!("sampling 1 in {} packet batches", self.rate)),
+            ))?;
+            self.is_annotated = true;
+        }
+        if self.should_forward(traced_event) {
+            self.inner.write_event(event)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.inner.flush()
+    }
+
+    fn rotate_hint(&mut self) {
+        self.inner.rotate_hint()
+    }
+
+    fn rotate_now(&mut self) -> Result<(), TraceError> {
+        self.inner.rotate_now()
+    }
+
+    fn rotation_count(&self) -> u64 {
+        self.inner.rotation_count()
+    }
+
+    fn set_dir_byte_limit(&mut self, dir_byte_limit: DirByteLimit) -> Result<(), TraceError> {
+        self.inner.set_dir_byte_limit(dir_byte_limit)
+    }
+
+    fn available_bytes(&self) -> Option<u64> {
+        self.inner.available_bytes()
+    }
+
+    fn effective_config(&self) -> SinkEffectiveConfig {
+        SinkEffectiveConfig::Sampling {
+            rate: self.rate,
+            inner: Box::new(self.inner.effective_config()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+            PriorityLane,
+        },
+        std::time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn test_sampling() {
+        let inner = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = inner.recent_events();
+        let mut sink = SamplingSink::new(inner, 3);
+        let now = SystemTime::now();
+        for i in 0..4 {
+            for label in [ChannelLabel::NonVote, ChannelLabel::TpuVote] {
+                for event in [
+                    TracedEvent::PacketBatch(label, sample_packet_batch()),
+                    TracedEvent::PacketBatchLane {
+                        label,
+                        lane: PriorityLane::High,
+                    },
+                ] {
+                    sink.write_event(&TimedTracedEvent(now, event)).unwrap();
+                }
+            }
+            sink.write_event(&TimedTracedEvent(
+                now,
+                TracedEvent::LeaderSlotBoundary {
+                    slot: i,
+                    start: true,
+                },
+            ))
+            .unwrap();
+        }
+
+        let kinds = recent_events
+            .recent_events(&EventFilter::default())
+            .into_iter()
+            .map(|TimedTracedEvent(_, event)| (event.kind(), event.label()))
+            .collect::<Vec<_>>();
+        let batch = |label| {
+            [
+                ("PacketBatch", Some(label)),
+                ("PacketBatchLane", Some(label)),
+            ]
+        };
+        let boundary = [("LeaderSlotBoundary", None)];
+        assert_eq!(
+            kinds,
+            [
+                &[("Annotation", None)][..],
+                &batch(ChannelLabel::NonVote),
+                &batch(ChannelLabel::TpuVote),
+                &boundary,
+                &boundary,
+                &boundary,
+                &batch(ChannelLabel::NonVote),
+                &batch(ChannelLabel::TpuVote),
+                &boundary,
+            ]
+            .concat()
+        );
+    }
+}
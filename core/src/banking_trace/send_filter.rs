@@ -0,0 +1,122 @@
+//! Hooks run by `TracedSender` on each batch before it's traced and sent, turning the channel
+//! boundary into an enforcement point. Packets discarded by the filter are traced as
+//! `PacketsDropped` with `PacketDropReason::Filtered`.
+
+use {
+    agave_banking_stage_ingress_types::BankingPacketBatch,
+    std::{collections::HashSet, net::IpAddr},
+};
+
+/// Returns the batch to be sent in place of the given one, possibly with some packets discarded,
+/// or `None` to drop it altogether.
+pub type SendFilter =
+    Box<dyn Fn(BankingPacketBatch) -> Option<BankingPacketBatch> + Send + Sync + 'static>;
+
+#[cfg(not(feature = "banking-trace-noop"))]
+pub(crate) fn accepted_packet_count(batch: &BankingPacketBatch) -> usize {
+    batch
+        .iter()
+        .flat_map(|batch| batch.iter())
+        .filter(|packet| !packet.meta().discard())
+        .count()
+}
+
+/// Filter discarding the packets from any of `ips`. Batches without such packets are passed
+/// through as is; the others are copied unless not shared elsewhere.
+pub fn deny_source_ips(ips: impl IntoIterator<Item = IpAddr>) -> SendFilter {
+    let ips = ips.into_iter().collect::<HashSet<_>>();
+    Box::new(move |batch| {
+        let is_denied = |addr: &IpAddr| ips.contains(addr);
+        let has_denied = batch
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .any(|packet| !packet.meta().discard() && is_denied(&packet.meta().addr));
+        if !has_denied {
+            return Some(batch);
+        }
+        let mut batches = BankingPacketBatch::unwrap_or_clone(batch);
+        for mut packet in batches.iter_mut().flat_map(|batch| batch.iter_mut()) {
+            if is_denied(&packet.meta().addr) {
+                packet.meta_mut().set_discard(true);
+            }
+        }
+        Some(BankingPacketBatch::new(batches))
+    })
+}
+
+#[cfg(all(test, not(feature = "banking-trace-noop")))]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+            BankingTracer, ChannelLabel, PacketDropReason, TimedTracedEvent, TracedEvent,
+        },
+        std::{
+            net::Ipv4Addr,
+            sync::{atomic::AtomicBool, Arc},
+            time::Duration,
+        },
+    };
+
+    fn batch_from(addr: IpAddr) -> BankingPacketBatch {
+        let mut batches = BankingPacketBatch::unwrap_or_clone(sample_packet_batch());
+        for mut packet in batches.iter_mut().flat_map(|batch| batch.iter_mut()) {
+            packet.meta_mut().addr = addr;
+        }
+        BankingPacketBatch::new(batches)
+    }
+
+    #[test]
+    fn test_deny_source_ips() {
+        let denied = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let allowed = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let filter = deny_source_ips([denied]);
+
+        let batch = batch_from(allowed);
+        assert!(Arc::ptr_eq(&filter(batch.clone()).unwrap(), &batch));
+        let batch = filter(batch_from(denied)).unwrap();
+        assert_eq!(accepted_packet_count(&batch), 0);
+    }
+
+    #[test]
+    fn test_filtered_packets_traced() {
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = sink.recent_events();
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit).unwrap();
+        let (sender, receiver) = tracer.create_channel_non_vote();
+        let denied = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let sender = sender.with_filter(deny_source_ips([denied]));
+        sender
+            .send(batch_from(IpAddr::V4(Ipv4Addr::LOCALHOST)))
+            .unwrap();
+        sender.send(batch_from(denied)).unwrap();
+        assert_eq!(receiver.len(), 2);
+
+        drop((sender, tracer));
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        let events = recent_events
+            .recent_events(&EventFilter::default())
+            .into_iter()
+            .filter_map(|TimedTracedEvent(_, event)| match event {
+                TracedEvent::PacketBatch(label, _) => Some((label, None)),
+                TracedEvent::PacketsDropped {
+                    label,
+                    count,
+                    reason,
+                } => Some((label, Some((count, reason)))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (ChannelLabel::NonVote, None),
+                (ChannelLabel::NonVote, Some((4, PacketDropReason::Filtered))),
+                (ChannelLabel::NonVote, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,387 @@
+//! Banking channel spanning process boundaries over shared memory, for deployments running the
+//! block-production scheduler in a separate process. The producer side traces each sent batch
+//! as `TracedSender` does, while the consumer side can be bridged into a plain
+//! `BankingPacketReceiver`, so that the scheduler is given the usual channels.
+//!
+//! The mapped file is laid out as follows, with integers in little endian:
+//!
+//! * `[0, 8)`: `SHM_CHANNEL_MAGIC`
+//! * `[8, 16)`: the byte capacity of the ring
+//! * `[16, 24)`: the read position, i.e. the end of the last frame received
+//! * `[24, 32)`: the write position, i.e. the end of the last frame sent
+//! * `[32, 40)`: non-zero once the sender is dropped
+//! * `[40, 48)`: non-zero once the receiver is dropped
+//! * `[HEADER_SIZE, HEADER_SIZE + capacity)`: the ring
+//!
+//! Unlike `ShmSink`, the ring is lossless: each batch is framed as its byte length as a `u32`
+//! followed by its bincode serialization, and the sender waits for the receiver to make room.
+//! Positions count the bytes since the creation, as in `ShmSink`.
+//!
+//! Like the async channels, the depths of these channels aren't sampled into the trace as
+//! `ChannelDepths`.
+
+use {
+    super::{
+        shm_sink::{atomic_at, write_ring},
+        ActiveTracer, BankingTracer, ChannelLabel, TraceError,
+    },
+    agave_banking_stage_ingress_types::{BankingPacketBatch, BankingPacketReceiver},
+    crossbeam_channel::{unbounded, RecvError, RecvTimeoutError, TryRecvError},
+    memmap2::MmapMut,
+    std::{
+        fs::OpenOptions,
+        io,
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    },
+    thiserror::Error,
+};
+
+pub const SHM_CHANNEL_MAGIC: &[u8; 8] = b"BNKSHC01";
+pub const SHM_CHANNEL_DEFAULT_BYTE_CAPACITY: u64 = 64 * 1024 * 1024;
+const CAPACITY_OFFSET: usize = 8;
+const READ_POS_OFFSET: usize = 16;
+const WRITE_POS_OFFSET: usize = 24;
+const SENDER_DROPPED_OFFSET: usize = 32;
+const RECEIVER_DROPPED_OFFSET: usize = 40;
+// Keeps the ring aligned to a cache line.
+const HEADER_SIZE: usize = 64;
+const BATCH_LEN_SIZE: usize = std::mem::size_of::<u32>();
+const POLL_INTERVAL: Duration = Duration::from_micros(100);
+const BRIDGE_RECV_TIMEOUT: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Error)]
+pub enum ShmSendError {
+    #[error("shared memory channel is disconnected")]
+    Disconnected(BankingPacketBatch),
+
+    #[error("batch is larger than the ring of the shared memory channel (byte capacity: {1})")]
+    TooLarge(BankingPacketBatch, u64),
+}
+
+struct ShmRingWriter {
+    mmap: MmapMut,
+    capacity: u64,
+    frame_buf: Vec<u8>,
+}
+
+/// Sends batches into the ring of the file at `path` (e.g. under `/dev/shm`), which is created
+/// anew with `byte_capacity` bytes of ring.
+pub struct ShmTracedSender {
+    label: ChannelLabel,
+    writer: Mutex<ShmRingWriter>,
+    active_tracer: Option<ActiveTracer>,
+}
+
+impl ShmTracedSender {
+    fn new(
+        label: ChannelLabel,
+        path: &Path,
+        byte_capacity: u64,
+        active_tracer: Option<ActiveTracer>,
+    ) -> Result<Self, TraceError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(HEADER_SIZE as u64 + byte_capacity)?;
+        // SAFETY: the file has just been truncated and sized by us; the receiver only writes to
+        // the header through atomics.
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+        mmap[..SHM_CHANNEL_MAGIC.len()].copy_from_slice(SHM_CHANNEL_MAGIC);
+        mmap[CAPACITY_OFFSET..READ_POS_OFFSET].copy_from_slice(&byte_capacity.to_le_bytes());
+        Ok(Self {
+            label,
+            writer: Mutex::new(ShmRingWriter {
+                mmap,
+                capacity: byte_capacity,
+                frame_buf: vec![],
+            }),
+            active_tracer,
+        })
+    }
+
+    /// Waits for the receiver to make room for `batch` as long as it's alive, even if it
+    /// hasn't opened the channel yet.
+    pub fn send(&self, batch: BankingPacketBatch) -> Result<(), ShmSendError> {
+        if let Some(active_tracer) = &self.active_tracer {
+            if active_tracer
+                .trace_packet_batch(self.label, &batch)
+                .is_err()
+            {
+                return Err(ShmSendError::Disconnected(batch));
+            }
+        }
+
+        let mut writer = self.writer.lock().unwrap();
+        let ShmRingWriter {
+            mmap,
+            capacity,
+            frame_buf,
+        } = &mut *writer;
+        frame_buf.clear();
+        frame_buf.extend_from_slice(&[0; BATCH_LEN_SIZE]);
+        bincode::serialize_into(&mut *frame_buf, &batch)
+            .expect("serializing packet batches into memory can't fail");
+        let frame_len = frame_buf.len() as u64;
+        let Ok(batch_len) = u32::try_from(frame_len - BATCH_LEN_SIZE as u64) else {
+            return Err(ShmSendError::TooLarge(batch, *capacity));
+        };
+        if frame_len > *capacity {
+            return Err(ShmSendError::TooLarge(batch, *capacity));
+        }
+        frame_buf[..BATCH_LEN_SIZE].copy_from_slice(&batch_len.to_le_bytes());
+
+        let base = mmap.as_ptr();
+        let write_pos = atomic_at(base, WRITE_POS_OFFSET).load(Ordering::Relaxed);
+        loop {
+            if atomic_at(base, RECEIVER_DROPPED_OFFSET).load(Ordering::Acquire) != 0 {
+                return Err(ShmSendError::Disconnected(batch));
+            }
+            let read_pos = atomic_at(base, READ_POS_OFFSET).load(Ordering::Acquire);
+            if write_pos - read_pos + frame_len <= *capacity {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        write_ring(&mut mmap[HEADER_SIZE..], write_pos, frame_buf);
+        atomic_at(base, WRITE_POS_OFFSET).store(write_pos + frame_len, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Drop for ShmTracedSender {
+    fn drop(&mut self) {
+        let writer = self.writer.get_mut().unwrap();
+        atomic_at(writer.mmap.as_ptr(), SENDER_DROPPED_OFFSET).store(1, Ordering::Release);
+    }
+}
+
+/// Receives the batches sent by `ShmTracedSender` through the file at `path`.
+pub struct ShmBankingPacketReceiver {
+    mmap: MmapMut,
+    capacity: u64,
+    frame_buf: Vec<u8>,
+}
+
+impl ShmBankingPacketReceiver {
+    /// Starts receiving from the oldest batch not received yet. There must be at most one
+    /// receiver for a channel at a time.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TraceError> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        // SAFETY: the ring is only read past the read position, which the sender never
+        // overwrites, and the header is only written through atomics.
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+        if mmap.len() < HEADER_SIZE || &mmap[..SHM_CHANNEL_MAGIC.len()] != SHM_CHANNEL_MAGIC {
+            return Err(TraceError::CorruptedFrame(path.to_path_buf(), 0));
+        }
+        let capacity =
+            u64::from_le_bytes(mmap[CAPACITY_OFFSET..READ_POS_OFFSET].try_into().unwrap());
+        Ok(Self {
+            mmap,
+            capacity,
+            frame_buf: vec![],
+        })
+    }
+
+    fn read_ring(&mut self, pos: u64, len: usize) {
+        let ring = &self.mmap[HEADER_SIZE..];
+        let start = (pos % self.capacity) as usize;
+        let first_len = len.min(ring.len() - start);
+        self.frame_buf.clear();
+        self.frame_buf
+            .extend_from_slice(&ring[start..start + first_len]);
+        self.frame_buf.extend_from_slice(&ring[..len - first_len]);
+    }
+
+    pub fn try_recv(&mut self) -> Result<BankingPacketBatch, TryRecvError> {
+        let base = self.mmap.as_ptr();
+        // checked before the write position, so that the last batches aren't missed
+        let is_sender_dropped = atomic_at(base, SENDER_DROPPED_OFFSET).load(Ordering::Acquire) != 0;
+        let write_pos = atomic_at(base, WRITE_POS_OFFSET).load(Ordering::Acquire);
+        let read_pos = atomic_at(base, READ_POS_OFFSET).load(Ordering::Relaxed);
+        if read_pos == write_pos {
+            return Err(if is_sender_dropped {
+                TryRecvError::Disconnected
+            } else {
+                TryRecvError::Empty
+            });
+        }
+
+        self.read_ring(read_pos, BATCH_LEN_SIZE);
+        let batch_len = u32::from_le_bytes(self.frame_buf[..].try_into().unwrap()) as usize;
+        self.read_ring(read_pos + BATCH_LEN_SIZE as u64, batch_len);
+        let batch = bincode::deserialize(&self.frame_buf)
+            .expect("batches are serialized by ShmTracedSender");
+        atomic_at(base, READ_POS_OFFSET).store(
+            read_pos + (BATCH_LEN_SIZE + batch_len) as u64,
+            Ordering::Release,
+        );
+        Ok(batch)
+    }
+
+    pub fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<BankingPacketBatch, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.try_recv() {
+                Ok(batch) => return Ok(batch),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) if Instant::now() >= deadline => {
+                    return Err(RecvTimeoutError::Timeout)
+                }
+                Err(TryRecvError::Empty) => thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+
+    pub fn recv(&mut self) -> Result<BankingPacketBatch, RecvError> {
+        loop {
+            match self.try_recv() {
+                Ok(batch) => return Ok(batch),
+                Err(TryRecvError::Disconnected) => return Err(RecvError),
+                Err(TryRecvError::Empty) => thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+
+    /// Spawns a thread forwarding the batches into the returned receiver, which can be put in
+    /// place of a channel's receiver in `Channels`. The thread exits once `exit` is set, the
+    /// sender is dropped, or the returned receiver is dropped.
+    pub fn spawn_bridge(
+        mut self,
+        exit: Arc<AtomicBool>,
+    ) -> Result<(BankingPacketReceiver, JoinHandle<()>), io::Error> {
+        let (sender, receiver) = unbounded();
+        let bridge_thread = thread::Builder::new()
+            .name("solBnkShmBridge".into())
+            .spawn(move || {
+                while !exit.load(Ordering::Relaxed) {
+                    match self.recv_timeout(BRIDGE_RECV_TIMEOUT) {
+                        Ok(batch) => {
+                            if sender.send(batch).is_err() {
+                                break;
+                            }
+                        }
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+            })?;
+        Ok((receiver, bridge_thread))
+    }
+}
+
+impl Drop for ShmBankingPacketReceiver {
+    fn drop(&mut self) {
+        atomic_at(self.mmap.as_ptr(), RECEIVER_DROPPED_OFFSET).store(1, Ordering::Release);
+    }
+}
+
+impl BankingTracer {
+    /// Creates the producer side of a channel of `label` through the file at `path`, to be
+    /// received from with `ShmBankingPacketReceiver::open()` by another process.
+    pub fn create_shm_channel(
+        &self,
+        label: ChannelLabel,
+        path: impl AsRef<Path>,
+        byte_capacity: u64,
+    ) -> Result<ShmTracedSender, TraceError> {
+        ShmTracedSender::new(
+            label,
+            path.as_ref(),
+            byte_capacity,
+            Some(self.active_tracer.clone()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+            TimedTracedEvent, TracedEvent,
+        },
+        tempfile::TempDir,
+    };
+
+    #[test]
+    fn test_shm_channel() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-channel.shm");
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = sink.recent_events();
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit.clone()).unwrap();
+
+        let batch = sample_packet_batch();
+        let frame_len =
+            (BATCH_LEN_SIZE + bincode::serialized_size(&batch).unwrap() as usize) as u64;
+        // room for 1.5 frames, so that frames wrap around and the sender waits for room
+        let sender = tracer
+            .create_shm_channel(ChannelLabel::NonVote, &path, frame_len * 3 / 2)
+            .unwrap();
+        let receiver = ShmBankingPacketReceiver::open(&path).unwrap();
+        let (receiver, bridge_thread) = receiver.spawn_bridge(exit).unwrap();
+        let sender_thread = thread::spawn({
+            let batch = batch.clone();
+            move || {
+                for _ in 0..5 {
+                    sender.send(batch.clone()).unwrap();
+                }
+            }
+        });
+        for _ in 0..5 {
+            assert_eq!(receiver.recv().unwrap(), batch);
+        }
+        sender_thread.join().unwrap();
+        bridge_thread.join().unwrap();
+        assert!(receiver.recv().is_err());
+
+        drop(tracer);
+        tracer_thread.unwrap().join().unwrap().unwrap();
+        let batch_count = recent_events
+            .recent_events(&EventFilter::default())
+            .iter()
+            .filter(|TimedTracedEvent(_, event)| matches!(event, TracedEvent::PacketBatch(..)))
+            .count();
+        assert_eq!(batch_count, 5);
+    }
+
+    #[test]
+    fn test_send_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let tracer = BankingTracer::new_disabled();
+        let path = temp_dir.path().join("small.shm");
+        let sender = tracer
+            .create_shm_channel(ChannelLabel::NonVote, &path, 64)
+            .unwrap();
+        assert!(matches!(
+            sender.send(sample_packet_batch()),
+            Err(ShmSendError::TooLarge(_, 64))
+        ));
+
+        let path = temp_dir.path().join("abandoned.shm");
+        let sender = tracer
+            .create_shm_channel(ChannelLabel::NonVote, &path, 1024 * 1024)
+            .unwrap();
+        drop(ShmBankingPacketReceiver::open(&path).unwrap());
+        assert!(matches!(
+            sender.send(sample_packet_batch()),
+            Err(ShmSendError::Disconnected(_))
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+//! `TraceSink` writing events into a ring buffer in shared memory, so that co-located consumers
+//! (e.g. profiling agents) can map it read-only and follow the trace without any disk I/O or
+//! socket overhead on latency-critical hosts.
+//!
+//! The mapped file is laid out as follows, with integers in little endian:
+//!
+//! * `[0, 8)`: `SHM_MAGIC`
+//! * `[8, 16)`: the byte capacity of the ring
+//! * `[16, 24)`: the reserved position, i.e. the end of the frame being written
+//! * `[24, 32)`: the committed position, i.e. the end of the last completely written frame
+//! * `[HEADER_SIZE, HEADER_SIZE + capacity)`: the ring
+//!
+//! Positions count the bytes written since the creation, so that a byte at position `p` is
+//! stored at `p % capacity` of the ring. Frames are laid out as in trace files, wrapping around
+//! the end of the ring. There's a single writer, which never waits for readers: a reader
+//! validates the frame it has just copied by checking that the reserved position hasn't lapped
+//! it, as a seqlock does.
+
+use {
+    super::{serialize_frame, TimedTracedEvent, TraceError, TraceSink, FRAME_LEN_SIZE},
+    memmap2::{Mmap, MmapMut, MmapOptions},
+    std::{
+        fs::{File, OpenOptions},
+        path::Path,
+        ptr,
+        sync::atomic::{fence, AtomicU64, Ordering},
+    },
+};
+
+pub const SHM_MAGIC: &[u8; 8] = b"BNKSHM01";
+pub const SHM_SINK_DEFAULT_BYTE_CAPACITY: u64 = 64 * 1024 * 1024;
+const CAPACITY_OFFSET: usize = 8;
+const RESERVED_POS_OFFSET: usize = 16;
+const COMMITTED_POS_OFFSET: usize = 24;
+// Keeps the ring aligned to a cache line.
+const HEADER_SIZE: usize = 64;
+
+pub(super) fn atomic_at<'a>(base: *const u8, offset: usize) -> &'a AtomicU64 {
+    // SAFETY: the offsets are 8-byte aligned within the header of a page-aligned mapping, which
+    // is kept alive by its owner as long as the returned reference is used.
+    unsafe { &*(base.add(offset) as *const AtomicU64) }
+}
+
+/// Writes events into the ring of the file at `path` (e.g. under `/dev/shm`), which is created
+/// anew with `byte_capacity` bytes of ring. Frames larger than the ring fail to be written.
+pub struct ShmSink {
+    mmap: MmapMut,
+    capacity: u64,
+    frame_buf: Vec<u8>,
+}
+
+impl ShmSink {
+    pub fn new(path: impl AsRef<Path>, byte_capacity: u64) -> Result<Self, TraceError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(HEADER_SIZE as u64 + byte_capacity)?;
+        // SAFETY: the file has just been truncated and sized by us; readers never write to it.
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+        mmap[..SHM_MAGIC.len()].copy_from_slice(SHM_MAGIC);
+        mmap[CAPACITY_OFFSET..RESERVED_POS_OFFSET].copy_from_slice(&byte_capacity.to_le_bytes());
+        Ok(Self {
+            mmap,
+            capacity: byte_capacity,
+            frame_buf: vec![],
+        })
+    }
+}
+
+pub(super) fn write_ring(ring: &mut [u8], pos: u64, bytes: &[u8]) {
+    let start = (pos % ring.len() as u64) as usize;
+    let first_len = bytes.len().min(ring.len() - start);
+    ring[start..start + first_len].copy_from_slice(&bytes[..first_len]);
+    ring[..bytes.len() - first_len].copy_from_slice(&bytes[first_len..]);
+}
+
+impl TraceSink for ShmSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        serialize_frame(&mut self.frame_buf, event)?;
+        if self.frame_buf.len() as u64 > self.capacity {
+            return Err(TraceError::SinkBufferFull(self.capacity as usize));
+        }
+        let base = self.mmap.as_mut_ptr();
+        let pos = atomic_at(base, COMMITTED_POS_OFFSET).load(Ordering::Relaxed);
+        let end_pos = pos + self.frame_buf.len() as u64;
+        atomic_at(base, RESERVED_POS_OFFSET).store(end_pos, Ordering::Relaxed);
+        fence(Ordering::Release);
+        write_ring(&mut self.mmap[HEADER_SIZE..], pos, &self.frame_buf);
+        atomic_at(base, COMMITTED_POS_OFFSET).store(end_pos, Ordering::Release);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+/// Follows the ring written by `ShmSink` from a read-only mapping.
+pub struct ShmReader {
+    mmap: Mmap,
+    capacity: u64,
+    read_pos: u64,
+    lost_byte_count: u64,
+}
+
+impl ShmReader {
+    /// Starts reading from the events written after opening.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TraceError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only and the frames read from it are validated against
+        // concurrent writes.
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        if mmap.len() < HEADER_SIZE || &mmap[..SHM_MAGIC.len()] != SHM_MAGIC {
+            return Err(TraceError::CorruptedFrame(path.to_path_buf(), 0));
+        }
+        let capacity = u64::from_le_bytes(
+            mmap[CAPACITY_OFFSET..RESERVED_POS_OFFSET]
+                .try_into()
+                .unwrap(),
+        );
+        let read_pos = atomic_at(mmap.as_ptr(), COMMITTED_POS_OFFSET).load(Ordering::Acquire);
+        Ok(Self {
+            mmap,
+            capacity,
+            read_pos,
+            lost_byte_count: 0,
+        })
+    }
+
+    /// The number of bytes skipped so far because the writer lapped this reader.
+    pub fn lost_byte_count(&self) -> u64 {
+        self.lost_byte_count
+    }
+
+    fn read_ring(&self, pos: u64, buf: &mut [u8]) {
+        let start = (pos % self.capacity) as usize;
+        let ring = &self.mmap[HEADER_SIZE..];
+        let first_len = buf.len().min(ring.len() - start);
+        // SAFETY: the ranges are within the mapping. The bytes may be concurrently overwritten,
+        // which is detected by the caller, so they're copied without creating references.
+        unsafe {
+            ptr::copy_nonoverlapping(ring.as_ptr().add(start), buf.as_mut_ptr(), first_len);
+            ptr::copy_nonoverlapping(
+                ring.as_ptr(),
+                buf.as_mut_ptr().add(first_len),
+                buf.len() - first_len,
+            );
+        }
+    }
+
+    /// Whether the bytes from `pos` read so far are intact, i.e. the writer hasn't started
+    /// overwriting them.
+    fn is_intact(&self, pos: u64) -> bool {
+        fence(Ordering::Acquire);
+        let reserved_pos =
+            atomic_at(self.mmap.as_ptr(), RESERVED_POS_OFFSET).load(Ordering::Relaxed);
+        reserved_pos.saturating_sub(self.capacity) <= pos
+    }
+
+    /// Returns the next event if any has been committed, skipping the lost ones after lapped.
+    pub fn try_next(&mut self) -> Result<Option<TimedTracedEvent>, TraceError> {
+        loop {
+            let committed_pos =
+                atomic_at(self.mmap.as_ptr(), COMMITTED_POS_OFFSET).load(Ordering::Acquire);
+            if self.read_pos >= committed_pos {
+                return Ok(None);
+            }
+            let mut len_bytes = [0; FRAME_LEN_SIZE];
+            self.read_ring(self.read_pos, &mut len_bytes);
+            let payload_len = u32::from_le_bytes(len_bytes) as u64;
+            let mut payload = vec![0; payload_len.min(self.capacity) as usize];
+            self.read_ring(self.read_pos + FRAME_LEN_SIZE as u64, &mut payload);
+            if !self.is_intact(self.read_pos) {
+                // resume from the last committed frame boundary
+                self.lost_byte_count += committed_pos - self.read_pos;
+                self.read_pos = committed_pos;
+                continue;
+            }
+            self.read_pos += payload_len + 2 * FRAME_LEN_SIZE as u64;
+            return Ok(Some(bincode::deserialize(&payload)?));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::banking_trace::TracedEvent, std::time::SystemTime, tempfile::TempDir};
+
+    fn annotation(text: &str) -> TimedTracedEvent {
+        TimedTracedEvent(SystemTime::now(), TracedEvent::Annotation(text.into()))
+    }
+
+    fn texts(reader: &mut ShmReader) -> Vec<String> {
+        std::iter::from_fn(|| reader.try_next().unwrap())
+            .map(|event| match event {
+                TimedTracedEvent(_, TracedEvent::Annotation(text)) => text,
+                event => panic!("unexpected event: {event:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ring() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace.shm");
+        let frame_len = {
+            let mut frame = vec![];
+            serialize_frame(&mut frame, &annotation("a")).unwrap();
+            frame.len() as u64
+        };
+        // room for 2.5 frames, so that frames wrap around
+        let mut sink = ShmSink::new(&path, frame_len * 5 / 2).unwrap();
+        sink.write_event(&annotation("a")).unwrap();
+
+        let mut reader = ShmReader::open(&path).unwrap();
+        assert_eq!(texts(&mut reader), Vec::<String>::new());
+        for text in ["b", "c", "d"] {
+            sink.write_event(&annotation(text)).unwrap();
+            assert_eq!(texts(&mut reader), [text]);
+        }
+
+        // lapped
+        for text in ["e", "f", "g"] {
+            sink.write_event(&annotation(text)).unwrap();
+        }
+        assert_eq!(texts(&mut reader), Vec::<String>::new());
+        assert_eq!(reader.lost_byte_count(), frame_len * 3);
+        sink.write_event(&annotation("h")).unwrap();
+        assert_eq!(texts(&mut reader), ["h"]);
+
+        assert_matches!(
+            sink.write_event(&annotation(&"i".repeat(frame_len as usize * 3))),
+            Err(TraceError::SinkBufferFull(_))
+        );
+    }
+}
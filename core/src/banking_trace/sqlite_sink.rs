@@ -0,0 +1,185 @@
+//! `TraceSink` writing trace events into a local SQLite database, so that traces can be queried
+//! with ad-hoc SQL instead of one-off scanning scripts.
+
+use {
+    super::{TimedTracedEvent, TraceError, TraceSink},
+    rusqlite::{params, Connection},
+    std::{
+        path::Path,
+        time::{Duration, Instant, UNIX_EPOCH},
+    },
+};
+
+// Bounds how stale the rows seen by other connections can be.
+const COMMIT_INTERVAL: Duration = Duration::from_secs(1);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY,
+        timestamp_us INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        label TEXT,
+        slot INTEGER,
+        payload BLOB
+    );
+    CREATE INDEX IF NOT EXISTS events_timestamp_us ON events (timestamp_us);
+    CREATE INDEX IF NOT EXISTS events_label ON events (label);
+    CREATE INDEX IF NOT EXISTS events_slot ON events (slot);
+";
+
+/// Inserts a row per event into the `events` table, with the same columns as the metadata of
+/// `GrpcSink` events. `payload` holds the bincode serialization of the event if
+/// `store_payloads`, or is `NULL` otherwise to keep the database small.
+///
+/// Rows are inserted in transactions which are committed every `COMMIT_INTERVAL` and on flush,
+/// so they become visible to other connections with that much delay.
+pub struct SqliteSink {
+    connection: Connection,
+    store_payloads: bool,
+    transaction_start_time: Option<Instant>,
+    payload_buf: Vec<u8>,
+}
+
+impl SqliteSink {
+    /// Opens the database at `path`, creating it and the schema if needed. Rows are appended to
+    /// any existing ones.
+    pub fn new(path: impl AsRef<Path>, store_payloads: bool) -> Result<Self, TraceError> {
+        let connection = Connection::open(path)?;
+        // let readers query concurrently with the tracer
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection,
+            store_payloads,
+            transaction_start_time: None,
+            payload_buf: vec![],
+        })
+    }
+}
+
+impl TraceSink for SqliteSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        match self.transaction_start_time {
+            Some(start_time) if start_time.elapsed() < COMMIT_INTERVAL => (),
+            _ => {
+                self.flush()?;
+                self.connection.execute_batch("BEGIN")?;
+                self.transaction_start_time = Some(Instant::now());
+            }
+        }
+        let TimedTracedEvent(time, traced_event) = event;
+        let payload = if self.store_payloads {
+            self.payload_buf.clear();
+            bincode::serialize_into(&mut self.payload_buf, event)?;
+            Some(&self.payload_buf)
+        } else {
+            None
+        };
+        self.connection
+            .prepare_cached(
+                "INSERT INTO events (timestamp_us, kind, label, slot, payload) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?
+            .execute(params![
+                time.duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_micros() as i64,
+                traced_event.kind(),
+                traced_event.label().map(|label| format!("{label:?}")),
+                traced_event.slot().map(|slot| slot as i64),
+                payload,
+            ])?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        if self.transaction_start_time.take().is_some() {
+            self.connection.execute_batch("COMMIT")?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SqliteSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            warn!("failed to commit banking trace to SQLite: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{ChannelLabel, PacketDropReason, TracedEvent},
+        std::time::SystemTime,
+        tempfile::TempDir,
+    };
+
+    fn write_events(path: &Path, store_payloads: bool) {
+        let mut sink = SqliteSink::new(path, store_payloads).unwrap();
+        for event in [
+            TracedEvent::PacketsDropped {
+                label: ChannelLabel::TpuVote,
+                count: 1,
+                reason: PacketDropReason::Dedup,
+            },
+            TracedEvent::BankFrozen {
+                slot: 5,
+                tick_height: 64,
+                transaction_count: 0,
+            },
+        ] {
+            sink.write_event(&TimedTracedEvent(SystemTime::now(), event))
+                .unwrap();
+        }
+        sink.flush().unwrap();
+    }
+
+    #[test]
+    fn test_query_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace.sqlite");
+        write_events(&path, false);
+        // appended to the existing rows
+        write_events(&path, true);
+
+        let connection = Connection::open(&path).unwrap();
+        let rows = connection
+            .prepare("SELECT kind, label, slot, payload FROM events ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, Option<Vec<u8>>>(3)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            rows.iter()
+                .map(|(kind, label, slot, payload)| (
+                    kind.as_str(),
+                    label.as_deref(),
+                    *slot,
+                    payload.is_some()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("PacketsDropped", Some("TpuVote"), None, false),
+                ("BankFrozen", None, Some(5), false),
+                ("PacketsDropped", Some("TpuVote"), None, true),
+                ("BankFrozen", None, Some(5), true),
+            ]
+        );
+        assert_matches!(
+            bincode::deserialize(rows[3].3.as_ref().unwrap()),
+            Ok(TimedTracedEvent(_, TracedEvent::BankFrozen { slot: 5, .. }))
+        );
+    }
+}
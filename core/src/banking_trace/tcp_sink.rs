@@ -0,0 +1,227 @@
+//! `TraceSink` streaming trace events to a remote collector over TCP, so that banking traces of
+//! a fleet can be ingested centrally without scraping trace files.
+
+use {
+    super::{serialize_frame, TimedTracedEvent, TraceError, TraceSink, TRACE_FILE_MAGIC},
+    std::{
+        collections::VecDeque,
+        io::{self, Write},
+        net::{TcpStream, ToSocketAddrs},
+        time::{Duration, Instant},
+    },
+};
+
+pub const TCP_SINK_DEFAULT_BUFFER_BYTE_LIMIT: usize = 64 * 1024 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+// Bounds how long the tracer thread can be stalled by an unresponsive collector.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+const MIN_RECONNECT_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Streams events to `endpoint` (`HOST:PORT`) in the trace file format: each connection starts
+/// with `TRACE_FILE_MAGIC`, followed by the frames. So, collectors can store each connection
+/// as a trace file as-is.
+///
+/// While disconnected, frames are buffered up to `buffer_byte_limit` bytes and reconnection is
+/// retried with exponential backoff. Events which don't fit into the buffer fail to be written,
+/// so that they're traced as a `TraceGap` once the stream catches up.
+pub struct TcpSink {
+    endpoint: String,
+    buffer_byte_limit: usize,
+    stream: Option<TcpStream>,
+    pending_frames: VecDeque<Vec<u8>>,
+    pending_bytes: usize,
+    reconnect_interval: Duration,
+    next_reconnect_time: Instant,
+}
+
+impl TcpSink {
+    /// Creates the sink without connecting yet; the connection is made on the first event.
+    pub fn new(endpoint: impl Into<String>, buffer_byte_limit: usize) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            buffer_byte_limit,
+            stream: None,
+            pending_frames: VecDeque::default(),
+            pending_bytes: 0,
+            reconnect_interval: MIN_RECONNECT_INTERVAL,
+            next_reconnect_time: Instant::now(),
+        }
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no address resolved");
+        for addr in self.endpoint.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(mut stream) => {
+                    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    stream.set_nodelay(true)?;
+                    stream.write_all(TRACE_FILE_MAGIC)?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Returns whether connected, (re)connecting if the backoff allows.
+    fn is_connected(&mut self) -> bool {
+        if self.stream.is_none() && Instant::now() >= self.next_reconnect_time {
+            match self.connect() {
+                Ok(stream) => {
+                    info!("connected banking trace stream to {}", self.endpoint);
+                    self.stream = Some(stream);
+                    self.reconnect_interval = MIN_RECONNECT_INTERVAL;
+                }
+                Err(err) => {
+                    debug!(
+                        "failed to connect banking trace stream to {}: {err:?}",
+                        self.endpoint
+                    );
+                    self.next_reconnect_time = Instant::now() + self.reconnect_interval;
+                    self.reconnect_interval =
+                        (self.reconnect_interval * 2).min(MAX_RECONNECT_INTERVAL);
+                }
+            }
+        }
+        self.stream.is_some()
+    }
+
+    /// Sends as many pending frames as possible, in order.
+    fn send_pending_frames(&mut self) {
+        while !self.pending_frames.is_empty() && self.is_connected() {
+            let frame = self.pending_frames.pop_front().unwrap();
+            if let Err(err) = self.stream.as_mut().unwrap().write_all(&frame) {
+                // The partially written frame is sent again in whole over the next connection,
+                // which starts anew with the magic.
+                warn!(
+                    "disconnected banking trace stream to {}: {err:?}",
+                    self.endpoint
+                );
+                self.pending_frames.push_front(frame);
+                self.stream = None;
+                self.next_reconnect_time = Instant::now() + self.reconnect_interval;
+                return;
+            }
+            self.pending_bytes -= frame.len();
+        }
+    }
+}
+
+impl TraceSink for TcpSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        let mut frame = vec![];
+        serialize_frame(&mut frame, event)?;
+        if self.pending_bytes + frame.len() > self.buffer_byte_limit {
+            self.send_pending_frames();
+            if self.pending_bytes + frame.len() > self.buffer_byte_limit {
+                return Err(TraceError::SinkBufferFull(self.buffer_byte_limit));
+            }
+        }
+        self.pending_bytes += frame.len();
+        self.pending_frames.push_back(frame);
+        self.send_pending_frames();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        self.send_pending_frames();
+        if !self.pending_frames.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                format!(
+                    "{} frames couldn't be sent to {}",
+                    self.pending_frames.len(),
+                    self.endpoint
+                ),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{super::reader::TraceFileReader, *},
+        crate::banking_trace::TracedEvent,
+        std::{io::Read, net::TcpListener, time::SystemTime},
+        tempfile::TempDir,
+    };
+
+    fn annotation(text: &str) -> TimedTracedEvent {
+        TimedTracedEvent(SystemTime::now(), TracedEvent::Annotation(text.into()))
+    }
+
+    fn read_events(stream: &mut TcpStream) -> Vec<TimedTracedEvent> {
+        let mut bytes = vec![];
+        stream.read_to_end(&mut bytes).unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("events");
+        std::fs::write(&path, bytes).unwrap();
+        TraceFileReader::open(path)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn test_stream_as_trace_file() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sink = TcpSink::new(
+            listener.local_addr().unwrap().to_string(),
+            TCP_SINK_DEFAULT_BUFFER_BYTE_LIMIT,
+        );
+        sink.write_event(&annotation("a")).unwrap();
+        sink.write_event(&annotation("b")).unwrap();
+        sink.flush().unwrap();
+        drop(sink);
+
+        let (mut stream, _) = listener.accept().unwrap();
+        assert_matches!(
+            &read_events(&mut stream)[..],
+            [
+                TimedTracedEvent(_, TracedEvent::Annotation(a)),
+                TimedTracedEvent(_, TracedEvent::Annotation(b)),
+            ] if (a.as_str(), b.as_str()) == ("a", "b")
+        );
+    }
+
+    #[test]
+    fn test_buffer_while_disconnected() {
+        // reserve a port without listening on it yet
+        let endpoint = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let frame_len = {
+            let mut frame = vec![];
+            serialize_frame(&mut frame, &annotation("a")).unwrap();
+            frame.len()
+        };
+        let mut sink = TcpSink::new(endpoint.to_string(), frame_len * 2);
+        sink.write_event(&annotation("a")).unwrap();
+        sink.write_event(&annotation("b")).unwrap();
+        assert_matches!(
+            sink.write_event(&annotation("c")),
+            Err(TraceError::SinkBufferFull(_))
+        );
+        assert_matches!(sink.flush(), Err(TraceError::IoError(_)));
+
+        let listener = TcpListener::bind(endpoint).unwrap();
+        sink.next_reconnect_time = Instant::now();
+        sink.flush().unwrap();
+        drop(sink);
+
+        let (mut stream, _) = listener.accept().unwrap();
+        assert_matches!(
+            &read_events(&mut stream)[..],
+            [
+                TimedTracedEvent(_, TracedEvent::Annotation(a)),
+                TimedTracedEvent(_, TracedEvent::Annotation(b)),
+            ] if (a.as_str(), b.as_str()) == ("a", "b")
+        );
+    }
+}
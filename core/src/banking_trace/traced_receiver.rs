@@ -0,0 +1,175 @@
+//! Receiving side of the banking channels, timestamping each batch as it's dequeued so that the
+//! queuing latency between TPU ingest and banking consumption can be measured instead of
+//! inferred from `ChannelDepths`.
+//!
+//! Optionally, each dequeue is traced as `PacketBatchDequeued`, which `queue_latencies()` pairs
+//! with the `PacketBatch` traced by the sender in FIFO order. Note that the pairing assumes a
+//! channel per label; the batches of unified channels are labeled with the label of their
+//! sender, while they're dequeued from a single receiver.
+
+use {
+    super::{ActiveTracer, BankingTracer, ChannelLabel, TimedTracedEvent, TracedEvent},
+    agave_banking_stage_ingress_types::{BankingPacketBatch, BankingPacketReceiver},
+    crossbeam_channel::{RecvError, RecvTimeoutError, TryRecvError},
+    std::{
+        collections::{BTreeMap, VecDeque},
+        time::{Duration, SystemTime},
+    },
+};
+
+pub struct TracedReceiver {
+    label: ChannelLabel,
+    receiver: BankingPacketReceiver,
+    /// Set only if dequeues are to be traced.
+    active_tracer: Option<ActiveTracer>,
+}
+
+impl TracedReceiver {
+    /// Returns the batch along with the time it was dequeued.
+    pub fn recv(&self) -> Result<(BankingPacketBatch, SystemTime), RecvError> {
+        self.receiver.recv().map(|batch| self.dequeued(batch))
+    }
+
+    pub fn try_recv(&self) -> Result<(BankingPacketBatch, SystemTime), TryRecvError> {
+        self.receiver.try_recv().map(|batch| self.dequeued(batch))
+    }
+
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<(BankingPacketBatch, SystemTime), RecvTimeoutError> {
+        self.receiver
+            .recv_timeout(timeout)
+            .map(|batch| self.dequeued(batch))
+    }
+
+    pub fn inner(&self) -> &BankingPacketReceiver {
+        &self.receiver
+    }
+
+    fn dequeued(&self, batch: BankingPacketBatch) -> (BankingPacketBatch, SystemTime) {
+        let now = self
+            .active_tracer
+            .as_ref()
+            .map_or_else(SystemTime::now, ActiveTracer::now);
+        if let Some(active_tracer) = &self.active_tracer {
+            active_tracer.trace_event(|| {
+                TimedTracedEvent(now, TracedEvent::PacketBatchDequeued { label: self.label })
+            });
+        }
+        (batch, now)
+    }
+}
+
+impl BankingTracer {
+    /// Wraps `receiver` of the channel of `label`, tracing each dequeue if `trace_dequeues` and
+    /// tracing is enabled.
+    pub fn traced_receiver(
+        &self,
+        label: ChannelLabel,
+        receiver: BankingPacketReceiver,
+        trace_dequeues: bool,
+    ) -> TracedReceiver {
+        TracedReceiver {
+            label,
+            receiver,
+            active_tracer: trace_dequeues.then(|| self.active_tracer.clone()),
+        }
+    }
+}
+
+/// Pairs each `PacketBatchDequeued` with the oldest unpaired `PacketBatch` of its label,
+/// returning the time each batch spent in the channel. Dequeues of batches traced before `events`
+/// start are paired with later batches, so `events` should start with the trace.
+pub fn queue_latencies(
+    events: impl IntoIterator<Item = TimedTracedEvent>,
+) -> BTreeMap<ChannelLabel, Vec<Duration>> {
+    let mut enqueue_times = BTreeMap::<_, VecDeque<_>>::new();
+    let mut latencies = BTreeMap::<_, Vec<_>>::new();
+    for TimedTracedEvent(time, event) in events {
+        match event {
+            TracedEvent::PacketBatch(label, _) => {
+                enqueue_times.entry(label).or_default().push_back(time);
+            }
+            TracedEvent::PacketBatchDequeued { label } => {
+                if let Some(enqueue_time) = enqueue_times
+                    .get_mut(&label)
+                    .and_then(|times| times.pop_front())
+                {
+                    latencies
+                        .entry(label)
+                        .or_default()
+                        .push(time.duration_since(enqueue_time).unwrap_or_default());
+                }
+            }
+            _ => {}
+        }
+    }
+    latencies
+}
+
+#[cfg(all(test, not(feature = "banking-trace-noop")))]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            for_test::sample_packet_batch,
+            ring_buffer_sink::{EventFilter, RingBufferSink},
+        },
+        std::sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[test]
+    fn test_queue_latencies() {
+        let sink = RingBufferSink::new(Duration::from_secs(60), 100);
+        let recent_events = sink.recent_events();
+        let exit = Arc::<AtomicBool>::default();
+        let (tracer, tracer_thread) = BankingTracer::new_with_sink(sink, exit).unwrap();
+        let (sender, receiver) = tracer.create_channel_non_vote();
+        let receiver = tracer.traced_receiver(ChannelLabel::NonVote, receiver, true);
+
+        sender.send(sample_packet_batch()).unwrap();
+        sender.send(sample_packet_batch()).unwrap();
+        let (_batch, first_dequeue_time) = receiver.recv().unwrap();
+        receiver.try_recv().unwrap();
+        assert_eq!(
+            receiver.recv_timeout(Duration::ZERO),
+            Err(RecvTimeoutError::Timeout)
+        );
+        drop((sender, receiver, tracer));
+        tracer_thread.unwrap().join().unwrap().unwrap();
+
+        let events = recent_events.recent_events(&EventFilter::default());
+        let first_enqueue_time = events
+            .iter()
+            .find_map(|TimedTracedEvent(time, event)| {
+                matches!(event, TracedEvent::PacketBatch(..)).then_some(*time)
+            })
+            .unwrap();
+        let latencies = queue_latencies(events);
+        assert_eq!(
+            latencies.keys().collect::<Vec<_>>(),
+            [&ChannelLabel::NonVote]
+        );
+        assert_eq!(latencies[&ChannelLabel::NonVote].len(), 2);
+        assert_eq!(
+            latencies[&ChannelLabel::NonVote][0],
+            first_dequeue_time
+                .duration_since(first_enqueue_time)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_untraced_dequeues() {
+        let (sender, receiver) = BankingTracer::channel_for_test();
+        let receiver =
+            BankingTracer::new_disabled().traced_receiver(ChannelLabel::Dummy, receiver, true);
+        let before = SystemTime::now();
+        sender.send(sample_packet_batch()).unwrap();
+        let (_batch, dequeue_time) = receiver.recv().unwrap();
+        assert!(dequeue_time >= before);
+        drop(sender);
+        assert_eq!(receiver.recv(), Err(RecvError));
+    }
+}
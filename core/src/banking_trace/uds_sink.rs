@@ -0,0 +1,162 @@
+//! `TraceSink` serving the live trace to local subscribers over a Unix domain socket, so that
+//! sidecar processes don't need any access to the trace directory.
+
+use {
+    super::{serialize_frame, TimedTracedEvent, TraceError, TraceSink, TRACE_FILE_MAGIC},
+    std::{
+        fs::remove_file,
+        io::{self, Write},
+        os::unix::net::{UnixListener, UnixStream},
+        path::PathBuf,
+        time::Duration,
+    },
+};
+
+// Subscribers which can't keep up within this are disconnected instead of stalling the tracer
+// thread.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Listens at `path` and streams the events traced after each subscriber has connected, in the
+/// trace file format: each connection starts with `TRACE_FILE_MAGIC`, followed by the frames.
+///
+/// A subscriber is disconnected once a write to it fails; it can just connect again to resume
+/// with a new stream.
+pub struct UdsSink {
+    path: PathBuf,
+    listener: UnixListener,
+    subscribers: Vec<UnixStream>,
+    frame_buf: Vec<u8>,
+}
+
+impl UdsSink {
+    /// Binds to `path`, replacing the stale socket file left by a previous run, if any.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, TraceError> {
+        let path = path.into();
+        match remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => (),
+        }
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            path,
+            listener,
+            subscribers: vec![],
+            frame_buf: vec![],
+        })
+    }
+
+    fn accept_subscribers(&mut self) {
+        loop {
+            let subscriber = self.listener.accept().and_then(|(mut subscriber, _)| {
+                subscriber.set_nonblocking(false)?;
+                subscriber.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                subscriber.write_all(TRACE_FILE_MAGIC)?;
+                Ok(subscriber)
+            });
+            match subscriber {
+                Ok(subscriber) => {
+                    info!("banking trace subscriber connected to {:?}", self.path);
+                    self.subscribers.push(subscriber);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => warn!("failed to accept banking trace subscriber: {err:?}"),
+            }
+        }
+    }
+}
+
+impl TraceSink for UdsSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        self.accept_subscribers();
+        if self.subscribers.is_empty() {
+            return Ok(());
+        }
+        serialize_frame(&mut self.frame_buf, event)?;
+        self.subscribers.retain_mut(|subscriber| {
+            let result = subscriber.write_all(&self.frame_buf);
+            if let Err(err) = &result {
+                info!("banking trace subscriber disconnected: {err:?}");
+            }
+            result.is_ok()
+        });
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+impl Drop for UdsSink {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{super::reader::TraceFileReader, *},
+        crate::banking_trace::TracedEvent,
+        std::{io::Read, time::SystemTime},
+        tempfile::TempDir,
+    };
+
+    fn annotation(text: &str) -> TimedTracedEvent {
+        TimedTracedEvent(SystemTime::now(), TracedEvent::Annotation(text.into()))
+    }
+
+    fn read_annotations(mut subscriber: UnixStream) -> Vec<String> {
+        let mut bytes = vec![];
+        subscriber.read_to_end(&mut bytes).unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("events");
+        std::fs::write(&path, bytes).unwrap();
+        TraceFileReader::open(path)
+            .unwrap()
+            .map(|event| match event.unwrap() {
+                TimedTracedEvent(_, TracedEvent::Annotation(text)) => text,
+                event => panic!("unexpected event: {event:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_subscribers() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace.sock");
+        // stale socket file
+        drop(UnixListener::bind(&path).unwrap());
+
+        let mut sink = UdsSink::new(&path).unwrap();
+        // no subscriber yet
+        sink.write_event(&annotation("a")).unwrap();
+        let early_subscriber = UnixStream::connect(&path).unwrap();
+        sink.write_event(&annotation("b")).unwrap();
+        let late_subscriber = UnixStream::connect(&path).unwrap();
+        sink.write_event(&annotation("c")).unwrap();
+        sink.flush().unwrap();
+        drop(sink);
+
+        assert!(!path.exists());
+        assert_eq!(read_annotations(early_subscriber), ["b", "c"]);
+        assert_eq!(read_annotations(late_subscriber), ["c"]);
+    }
+
+    #[test]
+    fn test_disconnected_subscriber() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("banking-trace.sock");
+
+        let mut sink = UdsSink::new(&path).unwrap();
+        let subscriber = UnixStream::connect(&path).unwrap();
+        sink.write_event(&annotation("a")).unwrap();
+        assert_eq!(sink.subscribers.len(), 1);
+        drop(subscriber);
+        // writes to the closed subscriber eventually fail
+        while !sink.subscribers.is_empty() {
+            sink.write_event(&annotation("b")).unwrap();
+        }
+    }
+}
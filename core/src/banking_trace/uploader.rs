@@ -0,0 +1,226 @@
+//! Uploader shipping the trace files completed by `RollingFileSink` to an object store (S3 or
+//! GCS), so that validators with small disks can still keep a long trace history.
+
+use {
+    super::{TraceError, BASENAME},
+    crossbeam_channel::{unbounded, Receiver, Sender},
+    object_store::{
+        parse_url_opts, path::Path as ObjectPath, ObjectStore, PutMultipartOptions, TagSet,
+        WriteMultipart,
+    },
+    std::{
+        fs::{self, File},
+        io::{self, Read},
+        path::{Path, PathBuf},
+        thread::{self, JoinHandle},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    url::Url,
+};
+
+// Rotated files are moved here until uploaded, so that further rotations don't rename them
+// under the feet of the uploader thread.
+const UPLOAD_DIR: &str = "upload";
+const UPLOAD_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+const MAX_UPLOAD_ATTEMPTS: u32 = 5;
+const MIN_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UploadConfig {
+    /// `s3://BUCKET[/PREFIX]` or `gs://BUCKET[/PREFIX]`. Credentials and the region are taken
+    /// from the environment variables as the SDKs do (e.g. `AWS_ACCESS_KEY_ID`,
+    /// `GOOGLE_SERVICE_ACCOUNT`).
+    pub url: String,
+    /// Tagged to the objects as `retention-days`, so that lifecycle rules of the bucket can
+    /// expire them. GCS doesn't support object tags, so rely on age-based rules there.
+    pub retention_days: Option<u32>,
+}
+
+/// Uploads rotated trace files in the background, removing them locally once uploaded. Files
+/// which fail to be uploaded are left in the `upload` subdirectory of the trace directory and
+/// retried on the next start.
+pub struct RotatedFileUploader {
+    upload_dir: PathBuf,
+    file_sender: Option<Sender<PathBuf>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RotatedFileUploader {
+    pub fn new(trace_dir: &Path, config: UploadConfig) -> Result<Self, TraceError> {
+        let url =
+            Url::parse(&config.url).map_err(|_| TraceError::UnsupportedSink(config.url.clone()))?;
+        let (store, prefix) = parse_url_opts(&url, std::env::vars())?;
+        let upload_dir = trace_dir.join(UPLOAD_DIR);
+        fs::create_dir_all(&upload_dir)?;
+
+        let (file_sender, file_receiver) = unbounded();
+        // resume the uploads interrupted by the last shutdown, oldest first
+        let mut leftover_files = fs::read_dir(&upload_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        leftover_files.sort();
+        for path in leftover_files {
+            file_sender.send(path).unwrap();
+        }
+
+        let thread = thread::Builder::new()
+            .name("solBnkTrcUpload".into())
+            .spawn(move || Self::upload_loop(store, prefix, config, file_receiver))?;
+        Ok(Self {
+            upload_dir,
+            file_sender: Some(file_sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Hands over the completed trace file at `path` to the uploader thread, moving it out of
+    /// the rotated files.
+    pub fn upload_rotated(&self, path: &Path) -> Result<(), TraceError> {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let upload_path = self
+            .upload_dir
+            .join(format!("{BASENAME}-{}", since_epoch.as_micros()));
+        fs::rename(path, &upload_path)?;
+        if let Some(file_sender) = &self.file_sender {
+            file_sender.send(upload_path).unwrap();
+        }
+        Ok(())
+    }
+
+    fn upload_loop(
+        store: Box<dyn ObjectStore>,
+        prefix: ObjectPath,
+        config: UploadConfig,
+        file_receiver: Receiver<PathBuf>,
+    ) {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(err) => {
+                error!("failed to start banking trace uploader: {err:?}");
+                return;
+            }
+        };
+        let mut tags = TagSet::default();
+        if let Some(retention_days) = config.retention_days {
+            tags.push("retention-days", &retention_days.to_string());
+        }
+
+        for path in file_receiver {
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let location = prefix.child(file_name);
+            let mut retry_interval = MIN_RETRY_INTERVAL;
+            for attempt in 1..=MAX_UPLOAD_ATTEMPTS {
+                match runtime.block_on(Self::upload_file(&*store, &location, &path, &tags)) {
+                    Ok(()) => {
+                        info!("uploaded banking trace file {path:?} to {location}");
+                        if let Err(err) = fs::remove_file(&path) {
+                            warn!("failed to remove uploaded {path:?}: {err:?}");
+                        }
+                        break;
+                    }
+                    Err(err) if attempt < MAX_UPLOAD_ATTEMPTS => {
+                        debug!("failed to upload {path:?} (attempt {attempt}): {err:?}");
+                        thread::sleep(retry_interval);
+                        retry_interval *= 2;
+                    }
+                    Err(err) => {
+                        warn!("gave up uploading {path:?} until restart: {err:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    async fn upload_file(
+        store: &dyn ObjectStore,
+        location: &ObjectPath,
+        path: &Path,
+        tags: &TagSet,
+    ) -> Result<(), TraceError> {
+        let mut file = File::open(path)?;
+        let upload = store
+            .put_multipart_opts(
+                location,
+                PutMultipartOptions {
+                    tags: tags.clone(),
+                    ..PutMultipartOptions::default()
+                },
+            )
+            .await?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, UPLOAD_CHUNK_SIZE);
+        let mut buf = vec![0; UPLOAD_CHUNK_SIZE];
+        loop {
+            let len = match file.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    writer.abort().await?;
+                    return Err(err.into());
+                }
+            };
+            writer.wait_for_capacity(2).await?;
+            writer.write(&buf[..len]);
+        }
+        writer.finish().await?;
+        Ok(())
+    }
+}
+
+impl Drop for RotatedFileUploader {
+    /// Waits for the pending uploads to finish or give up.
+    fn drop(&mut self) {
+        drop(self.file_sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tempfile::TempDir};
+
+    #[test]
+    fn test_upload_rotated() {
+        let trace_dir = TempDir::new().unwrap();
+        let bucket_dir = TempDir::new().unwrap();
+        // a leftover of the last run
+        fs::create_dir_all(trace_dir.path().join(UPLOAD_DIR)).unwrap();
+        fs::write(trace_dir.path().join(UPLOAD_DIR).join("events-1"), b"foo").unwrap();
+
+        let uploader = RotatedFileUploader::new(
+            trace_dir.path(),
+            UploadConfig {
+                url: format!("file://{}", bucket_dir.path().display()),
+                retention_days: None,
+            },
+        )
+        .unwrap();
+        let rotated_path = trace_dir.path().join("events.1");
+        fs::write(&rotated_path, b"bar").unwrap();
+        uploader.upload_rotated(&rotated_path).unwrap();
+        drop(uploader);
+
+        let mut uploaded = fs::read_dir(bucket_dir.path())
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>();
+        uploaded.sort();
+        assert_eq!(uploaded, ["bar", "foo"]);
+        assert!(!rotated_path.exists());
+        assert_eq!(
+            fs::read_dir(trace_dir.path().join(UPLOAD_DIR))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+}
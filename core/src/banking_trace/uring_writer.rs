@@ -0,0 +1,191 @@
+//! io_uring backend of `RollingFileSink`, submitting the filled write buffers asynchronously
+//! instead of blocking on each `write(2)`.
+
+use {
+    super::rotating_appender::AppenderWriter,
+    agave_io_uring::{Completion, Ring, RingOp},
+    io_uring::{opcode, squeue, types, IoUring},
+    std::{
+        fs::File,
+        io::{self, Write},
+        mem,
+        os::fd::{AsRawFd as _, RawFd},
+    },
+};
+
+const SQPOLL_IDLE_TIMEOUT: u32 = 50;
+const MAX_IOWQ_WORKERS: u32 = 2;
+// Filled buffers in flight at once. Once all of them are, writing waits for the oldest one.
+const MAX_BUFFER_COUNT: usize = 4;
+
+/// Writes a file sequentially from `offset`, submitting each filled buffer to io_uring without
+/// waiting for it to be written.
+pub(super) struct RingFileWriter {
+    ring: Ring<RingFileWriterState, WriteOp>,
+    // owns the fd referred to by the ops in flight
+    file: File,
+    offset: u64,
+    buffer: Vec<u8>,
+    buffer_capacity: usize,
+}
+
+struct RingFileWriterState {
+    free_buffers: Vec<Vec<u8>>,
+    buffer_count: usize,
+}
+
+impl AppenderWriter for RingFileWriter {
+    fn new(file: File, offset: u64, buffer_capacity: usize) -> io::Result<Self> {
+        let ring = IoUring::builder()
+            .setup_sqpoll(SQPOLL_IDLE_TIMEOUT)
+            .build(MAX_BUFFER_COUNT as u32)?;
+        ring.submitter()
+            .register_iowq_max_workers(&mut [MAX_IOWQ_WORKERS, 0])?;
+        Ok(Self {
+            ring: Ring::new(
+                ring,
+                RingFileWriterState {
+                    free_buffers: Vec::with_capacity(MAX_BUFFER_COUNT),
+                    buffer_count: 1,
+                },
+            ),
+            file,
+            offset,
+            buffer: Vec::with_capacity(buffer_capacity),
+            buffer_capacity,
+        })
+    }
+}
+
+impl RingFileWriter {
+    /// Submits the current buffer and takes a free one, waiting for one if all are in flight.
+    fn submit_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let next_buffer = loop {
+            self.ring.process_completions()?;
+            let state = self.ring.context_mut();
+            if let Some(free_buffer) = state.free_buffers.pop() {
+                break free_buffer;
+            }
+            if state.buffer_count < MAX_BUFFER_COUNT {
+                state.buffer_count += 1;
+                break Vec::with_capacity(self.buffer_capacity);
+            }
+            self.ring.submit_and_wait(1, None)?;
+        };
+        let buffer = mem::replace(&mut self.buffer, next_buffer);
+        let len = buffer.len() as u64;
+        self.ring.push(WriteOp {
+            fd: self.file.as_raw_fd(),
+            buffer,
+            written_len: 0,
+            offset: self.offset,
+        })?;
+        self.offset += len;
+        // in case the sqpoll thread is asleep
+        self.ring.submit()
+    }
+}
+
+impl Write for RingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= self.buffer_capacity {
+            self.submit_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit_buffer()?;
+        self.ring.drain()
+    }
+}
+
+impl Drop for RingFileWriter {
+    fn drop(&mut self) {
+        // the ops in flight refer to the buffers and the fd owned by self
+        if let Err(err) = self.flush() {
+            warn!("failed to flush the trace file: {err:?}");
+        }
+    }
+}
+
+struct WriteOp {
+    fd: RawFd,
+    buffer: Vec<u8>,
+    /// Non-zero if a previous write was short and this one writes the rest.
+    written_len: usize,
+    offset: u64,
+}
+
+impl RingOp<RingFileWriterState> for WriteOp {
+    fn entry(&mut self) -> squeue::Entry {
+        let rest = &self.buffer[self.written_len..];
+        opcode::Write::new(types::Fd(self.fd), rest.as_ptr(), rest.len() as u32)
+            .offset(self.offset + self.written_len as u64)
+            .build()
+    }
+
+    fn complete(
+        &mut self,
+        completion: &mut Completion<RingFileWriterState, Self>,
+        res: io::Result<i32>,
+    ) -> io::Result<()> {
+        let len = res? as usize;
+        if len == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        let mut buffer = mem::take(&mut self.buffer);
+        let written_len = self.written_len + len;
+        if written_len < buffer.len() {
+            completion.push(WriteOp {
+                fd: self.fd,
+                buffer,
+                written_len,
+                offset: self.offset,
+            });
+        } else {
+            buffer.clear();
+            completion.context_mut().free_buffers.push(buffer);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{
+            rotating_appender::RotatingAppender, RollingConditionGrouped, RotationPolicy,
+        },
+        agave_io_uring::io_uring_supported,
+        std::fs,
+        tempfile::TempDir,
+    };
+
+    #[test]
+    fn test_ring_file_writer() {
+        if !io_uring_supported() {
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("events");
+        fs::write(&path, b"old").unwrap();
+        let condition = RollingConditionGrouped::new(RotationPolicy::default(), 1024);
+        let mut appender =
+            RotatingAppender::<RingFileWriter>::new(&path, condition, 2, 10).unwrap();
+        let data = (0..100u8).collect::<Vec<_>>();
+        for chunk in data.chunks(7) {
+            appender.write_all(chunk).unwrap();
+            appender.condition_mut().reset();
+        }
+        appender.flush().unwrap();
+        // the non-blank file is rotated on the first write
+        assert_eq!(fs::read(path.with_extension("1")).unwrap(), b"old");
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+}
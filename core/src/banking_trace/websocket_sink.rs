@@ -0,0 +1,184 @@
+//! `TraceSink` broadcasting JSON-encoded trace events over WebSocket, so that browser-based
+//! monitoring UIs can visualize the packet flow in real time.
+
+use {
+    super::{TimedTracedEvent, TraceError, TraceSink, TracedEvent},
+    serde_json::json,
+    std::{
+        io,
+        net::{SocketAddr, TcpListener, TcpStream},
+        time::{Duration, UNIX_EPOCH},
+    },
+    tungstenite::{Message, WebSocket},
+};
+
+// Handshakes are done on the tracer thread, so they are bounded by this.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+// Clients which can't keep up within this are disconnected instead of stalling the tracer
+// thread.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Listens at `addr` and broadcasts each event as a JSON text message to all clients connected
+/// by then:
+///
+/// `{"timestamp_us": .., "kind": "PacketBatch", "label": "NonVote", "slot": null, "event": ..}`
+///
+/// With `strip_payloads`, the packets of `PacketBatch` events are replaced by their count, which
+/// is usually all that visualizations need.
+pub struct WebSocketSink {
+    listener: TcpListener,
+    clients: Vec<WebSocket<TcpStream>>,
+    strip_payloads: bool,
+}
+
+impl WebSocketSink {
+    pub fn new(addr: SocketAddr, strip_payloads: bool) -> Result<Self, TraceError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        info!(
+            "broadcasting banking trace over WebSocket at {}",
+            listener.local_addr()?
+        );
+        Ok(Self {
+            listener,
+            clients: vec![],
+            strip_payloads,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    fn accept_clients(&mut self) {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("failed to accept banking trace WebSocket client: {err:?}");
+                    continue;
+                }
+            };
+            let client = stream
+                .set_nonblocking(false)
+                .and_then(|()| stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)))
+                .and_then(|()| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+                .map_err(|err| err.to_string())
+                .and_then(|()| tungstenite::accept(stream).map_err(|err| err.to_string()));
+            match client {
+                Ok(client) => self.clients.push(client),
+                Err(err) => warn!("failed to accept banking trace WebSocket client: {err}"),
+            }
+        }
+    }
+
+    fn event_json(&self, event: &TimedTracedEvent) -> serde_json::Result<String> {
+        let TimedTracedEvent(time, traced_event) = event;
+        let details = match traced_event {
+            TracedEvent::PacketBatch(label, batch) if self.strip_payloads => json!({
+                "PacketBatch": [
+                    label,
+                    { "packet_count": batch.iter().map(|batch| batch.len()).sum::<usize>() },
+                ],
+            }),
+            traced_event => serde_json::to_value(traced_event)?,
+        };
+        serde_json::to_string(&json!({
+            "timestamp_us": time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64,
+            "kind": traced_event.kind(),
+            "label": traced_event.label(),
+            "slot": traced_event.slot(),
+            "event": details,
+        }))
+    }
+}
+
+impl TraceSink for WebSocketSink {
+    fn write_event(&mut self, event: &TimedTracedEvent) -> Result<(), TraceError> {
+        self.accept_clients();
+        if self.clients.is_empty() {
+            return Ok(());
+        }
+        let text = self.event_json(event).map_err(io::Error::from)?;
+        self.clients.retain_mut(|client| {
+            let result = client.send(Message::Text(text.clone()));
+            if let Err(err) = &result {
+                info!("banking trace WebSocket client disconnected: {err:?}");
+            }
+            result.is_ok()
+        });
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), TraceError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::banking_trace::{for_test, ChannelLabel},
+        serde_json::Value,
+        std::{
+            thread::{self, sleep},
+            time::SystemTime,
+        },
+    };
+
+    fn receive_events(strip_payloads: bool) -> Vec<Value> {
+        let mut sink = WebSocketSink::new("127.0.0.1:0".parse().unwrap(), strip_payloads).unwrap();
+        let url = format!("ws://{}", sink.local_addr().unwrap());
+        let client_thread = thread::spawn(move || {
+            let (mut client, _) = tungstenite::connect(url).unwrap();
+            let mut events = vec![];
+            while let Ok(Message::Text(text)) = client.read() {
+                events.push(serde_json::from_str::<Value>(&text).unwrap());
+            }
+            events
+        });
+
+        let heartbeat = TimedTracedEvent(SystemTime::now(), TracedEvent::Heartbeat);
+        while sink.clients.is_empty() {
+            sink.write_event(&heartbeat).unwrap();
+            sleep(Duration::from_millis(10));
+        }
+        sink.write_event(&TimedTracedEvent(
+            SystemTime::now(),
+            TracedEvent::PacketBatch(ChannelLabel::NonVote, for_test::sample_packet_batch()),
+        ))
+        .unwrap();
+        drop(sink);
+
+        client_thread.join().unwrap()
+    }
+
+    #[test]
+    fn test_broadcast_json() {
+        let events = receive_events(false);
+        let [heartbeat, packet_batch] = &events[..] else {
+            panic!("unexpected events: {events:?}");
+        };
+        assert_eq!(heartbeat["kind"], "Heartbeat");
+        assert_eq!(heartbeat["label"], Value::Null);
+        assert_eq!(packet_batch["kind"], "PacketBatch");
+        assert_eq!(packet_batch["label"], "NonVote");
+        assert_eq!(packet_batch["event"]["PacketBatch"][0], "NonVote");
+        assert!(packet_batch["event"]["PacketBatch"][1].is_array());
+    }
+
+    #[test]
+    fn test_strip_payloads() {
+        let events = receive_events(true);
+        let [_heartbeat, packet_batch] = &events[..] else {
+            panic!("unexpected events: {events:?}");
+        };
+        assert_eq!(packet_batch["kind"], "PacketBatch");
+        assert_eq!(
+            packet_batch["event"],
+            json!({"PacketBatch": ["NonVote", {"packet_count": 4}]})
+        );
+    }
+}
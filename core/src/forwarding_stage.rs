@@ -2,7 +2,10 @@
 //! packets to a node that is or will be leader soon.
 
 use {
-    crate::next_leader::next_leaders,
+    crate::{
+        banking_trace::{BankingTracer, ChannelLabel},
+        next_leader::next_leaders,
+    },
     agave_banking_stage_ingress_types::BankingPacketBatch,
     agave_transaction_view::transaction_view::SanitizedTransactionView,
     async_trait::async_trait,
@@ -128,6 +131,7 @@ pub(crate) fn spawn_forwarding_stage(
     root_bank_cache: RootBankCache,
     forward_address_getter: ForwardAddressGetter,
     data_budget: DataBudget,
+    banking_tracer: Arc<BankingTracer>,
 ) -> SpawnForwardingStageResult {
     let vote_client = VoteClient::new(vote_client_udp_socket, forward_address_getter.clone());
     match client {
@@ -140,6 +144,7 @@ pub(crate) fn spawn_forwarding_stage(
                 non_vote_client.clone(),
                 root_bank_cache,
                 data_budget,
+                banking_tracer,
             );
             SpawnForwardingStageResult {
                 join_handle: Builder::new()
@@ -168,6 +173,7 @@ pub(crate) fn spawn_forwarding_stage(
                 non_vote_client.clone(),
                 root_bank_cache,
                 data_budget,
+                banking_tracer,
             );
             SpawnForwardingStageResult {
                 join_handle: Builder::new()
@@ -188,6 +194,7 @@ struct ForwardingStage<VoteClient: ForwardingClient, NonVoteClient: ForwardingCl
     non_vote_client: NonVoteClient,
     data_budget: DataBudget,
     metrics: ForwardingStageMetrics,
+    banking_tracer: Arc<BankingTracer>,
 }
 
 impl<VoteClient: ForwardingClient, NonVoteClient: ForwardingClient>
@@ -199,6 +206,7 @@ impl<VoteClient: ForwardingClient, NonVoteClient: ForwardingClient>
         non_vote_client: NonVoteClient,
         root_bank_cache: RootBankCache,
         data_budget: DataBudget,
+        banking_tracer: Arc<BankingTracer>,
     ) -> Self {
         Self {
             receiver,
@@ -208,6 +216,7 @@ impl<VoteClient: ForwardingClient, NonVoteClient: ForwardingClient>
             vote_client,
             data_budget,
             metrics: ForwardingStageMetrics::default(),
+            banking_tracer,
         }
     }
 
@@ -364,6 +373,8 @@ impl<VoteClient: ForwardingClient, NonVoteClient: ForwardingClient>
                     &self.vote_client,
                     &mut self.metrics.votes_forwarded,
                     &mut self.metrics.votes_dropped_on_send,
+                    &self.banking_tracer,
+                    ChannelLabel::TpuVote,
                 );
             } else {
                 non_vote_batch.push(packet_data_vec);
@@ -372,32 +383,32 @@ impl<VoteClient: ForwardingClient, NonVoteClient: ForwardingClient>
                     &self.non_vote_client,
                     &mut self.metrics.non_votes_forwarded,
                     &mut self.metrics.non_votes_dropped_on_send,
+                    &self.banking_tracer,
+                    ChannelLabel::NonVote,
                 );
             }
         }
 
         // Send out remaining packets
         if !vote_batch.is_empty() {
-            let num_votes = vote_batch.len();
-            self.metrics.votes_forwarded += num_votes;
-            if self
-                .vote_client
-                .send_transactions_in_batch(vote_batch)
-                .is_err()
-            {
-                self.metrics.votes_dropped_on_send += num_votes;
-            }
+            send_batch(
+                &mut vote_batch,
+                &self.vote_client,
+                &mut self.metrics.votes_forwarded,
+                &mut self.metrics.votes_dropped_on_send,
+                &self.banking_tracer,
+                ChannelLabel::TpuVote,
+            );
         }
         if !non_vote_batch.is_empty() {
-            let num_non_votes = non_vote_batch.len();
-            self.metrics.non_votes_forwarded += num_non_votes;
-            if self
-                .non_vote_client
-                .send_transactions_in_batch(non_vote_batch)
-                .is_err()
-            {
-                self.metrics.non_votes_dropped_on_send += num_non_votes;
-            }
+            send_batch(
+                &mut non_vote_batch,
+                &self.non_vote_client,
+                &mut self.metrics.non_votes_forwarded,
+                &mut self.metrics.non_votes_dropped_on_send,
+                &self.banking_tracer,
+                ChannelLabel::NonVote,
+            );
         }
     }
 
@@ -444,11 +455,11 @@ impl From<TransportError> for ForwardingClientError {
 /// forward transactions to other validators.
 trait ForwardingClient: Send + Sync + 'static {
     /// Sends a batch of serialized transactions to the currently configured
-    /// address.
+    /// address. Returns the address sent to, if it's known to the client.
     fn send_transactions_in_batch(
         &self,
         wire_transactions: Vec<Vec<u8>>,
-    ) -> Result<(), ForwardingClientError>;
+    ) -> Result<Option<SocketAddr>, ForwardingClientError>;
 }
 
 struct VoteClient {
@@ -476,7 +487,7 @@ impl ForwardingClient for VoteClient {
     fn send_transactions_in_batch(
         &self,
         wire_transactions: Vec<Vec<u8>>,
-    ) -> Result<(), ForwardingClientError> {
+    ) -> Result<Option<SocketAddr>, ForwardingClientError> {
         let Some(current_address) = self.get_next_valid_leader() else {
             return Err(ForwardingClientError::LeaderContactMissing);
         };
@@ -484,7 +495,7 @@ impl ForwardingClient for VoteClient {
             .iter()
             .map(|bytes| (bytes, current_address));
         batch_send(&self.bind_socket, batch_with_addresses)?;
-        Ok(())
+        Ok(Some(current_address))
     }
 }
 
@@ -519,13 +530,13 @@ impl ForwardingClient for ConnectionCacheClient {
     fn send_transactions_in_batch(
         &self,
         wire_transactions: Vec<Vec<u8>>,
-    ) -> Result<(), ForwardingClientError> {
+    ) -> Result<Option<SocketAddr>, ForwardingClientError> {
         let Some(current_address) = self.get_next_valid_leader() else {
             return Err(ForwardingClientError::LeaderContactMissing);
         };
         let conn = self.connection_cache.get_connection(&current_address);
         conn.send_data_batch_async(wire_transactions)?;
-        Ok(())
+        Ok(Some(current_address))
     }
 }
 
@@ -606,9 +617,10 @@ impl ForwardingClient for TpuClientNextClient {
     fn send_transactions_in_batch(
         &self,
         wire_transactions: Vec<Vec<u8>>,
-    ) -> Result<(), ForwardingClientError> {
+    ) -> Result<Option<SocketAddr>, ForwardingClientError> {
         self.sender
             .try_send(TransactionBatch::new(wire_transactions))
+            .map(|()| None)
             .map_err(|_e| ForwardingClientError::Failed)
     }
 }
@@ -684,16 +696,36 @@ fn send_batch_if_full(
     client: &impl ForwardingClient,
     forwarded_counter: &mut usize,
     dropped_counter: &mut usize,
+    banking_tracer: &BankingTracer,
+    label: ChannelLabel,
 ) {
     if batch.len() == FORWARD_BATCH_SIZE {
-        *forwarded_counter += batch.len();
+        send_batch(
+            batch,
+            client,
+            forwarded_counter,
+            dropped_counter,
+            banking_tracer,
+            label,
+        );
+    }
+}
 
-        let mut swap_batch = Vec::with_capacity(FORWARD_BATCH_SIZE);
-        std::mem::swap(batch, &mut swap_batch);
+fn send_batch(
+    batch: &mut Vec<Vec<u8>>,
+    client: &impl ForwardingClient,
+    forwarded_counter: &mut usize,
+    dropped_counter: &mut usize,
+    banking_tracer: &BankingTracer,
+    label: ChannelLabel,
+) {
+    let num_packets = batch.len();
+    *forwarded_counter += num_packets;
 
-        if client.send_transactions_in_batch(swap_batch).is_err() {
-            *dropped_counter += FORWARD_BATCH_SIZE;
-        }
+    let swap_batch = std::mem::replace(batch, Vec::with_capacity(FORWARD_BATCH_SIZE));
+    match client.send_transactions_in_batch(swap_batch) {
+        Ok(target) => banking_tracer.forwarded_event(label, num_packets, target),
+        Err(_) => *dropped_counter += num_packets,
     }
 }
 
@@ -848,9 +880,9 @@ mod tests {
         fn send_transactions_in_batch(
             &self,
             wire_transactions: Vec<Vec<u8>>,
-        ) -> Result<(), ForwardingClientError> {
+        ) -> Result<Option<SocketAddr>, ForwardingClientError> {
             self.packets.lock().unwrap().extend(wire_transactions);
-            Ok(())
+            Ok(None)
         }
     }
 
@@ -907,6 +939,7 @@ mod tests {
             non_vote_mock_client.clone(),
             root_bank_cache,
             DataBudget::default(),
+            BankingTracer::new_disabled(),
         );
 
         // Send packet batches.
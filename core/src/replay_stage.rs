@@ -733,6 +733,7 @@ impl ReplayStage {
                     &blockstore,
                     &bank_forks,
                     &my_pubkey,
+                    &banking_tracer,
                     &vote_account,
                     &mut progress,
                     transaction_status_sender.as_ref(),
@@ -3066,6 +3067,7 @@ impl ReplayStage {
         replay_result_vec: &[ReplaySlotFromBlockstore],
         purge_repair_slot_counter: &mut PurgeRepairSlotCounter,
         my_pubkey: &Pubkey,
+        banking_tracer: &BankingTracer,
     ) -> bool {
         // TODO: See if processing of blockstore replay results and bank completion can be made thread safe.
         let mut did_complete_bank = false;
@@ -3200,6 +3202,13 @@ impl ReplayStage {
                     ("slot", bank_slot, i64),
                     ("hash", bank.hash().to_string(), String),
                 );
+                if is_leader_block {
+                    banking_tracer.bank_frozen_event(
+                        bank_slot,
+                        bank.tick_height(),
+                        bank.executed_transaction_count(),
+                    );
+                }
 
                 let r_replay_stats = replay_stats.read().unwrap();
                 let replay_progress = bank_progress.replay_progress.clone();
@@ -3347,6 +3356,7 @@ impl ReplayStage {
         blockstore: &Blockstore,
         bank_forks: &RwLock<BankForks>,
         my_pubkey: &Pubkey,
+        banking_tracer: &BankingTracer,
         vote_account: &Pubkey,
         progress: &mut ProgressMap,
         transaction_status_sender: Option<&TransactionStatusSender>,
@@ -3447,6 +3457,7 @@ impl ReplayStage {
             &replay_result_vec,
             purge_repair_slot_counter,
             my_pubkey,
+            banking_tracer,
         )
     }
 
@@ -9,12 +9,13 @@ pub use solana_perf::sigverify::{
 };
 use {
     crate::{
-        banking_trace::BankingPacketSender,
+        banking_trace::{BankingPacketSender, PacketDropReason},
         sigverify_stage::{SigVerifier, SigVerifyServiceError},
     },
     agave_banking_stage_ingress_types::BankingPacketBatch,
     crossbeam_channel::Sender,
     solana_perf::{cuda_runtime::PinnedVec, packet::PacketBatch, recycler::Recycler, sigverify},
+    std::time::Duration,
 };
 
 pub struct TransactionSigVerifier {
@@ -69,6 +70,16 @@ impl SigVerifier for TransactionSigVerifier {
         Ok(())
     }
 
+    fn trace_packets_dropped(&self, count: usize, reason: PacketDropReason) {
+        self.banking_stage_sender
+            .trace_packets_dropped(count, reason);
+    }
+
+    fn trace_stats(&self, verified: usize, failed: usize, dedup: usize, interval: Duration) {
+        self.banking_stage_sender
+            .trace_sigverify_stats(verified, failed, dedup, interval);
+    }
+
     fn verify_batches(
         &self,
         mut batches: Vec<PacketBatch>,
@@ -6,7 +6,7 @@
 //! if perf-libs are available
 
 use {
-    crate::sigverify,
+    crate::{banking_trace::PacketDropReason, sigverify},
     core::time::Duration,
     crossbeam_channel::{Receiver, RecvTimeoutError, SendError},
     itertools::Itertools,
@@ -58,6 +58,10 @@ pub trait SigVerifier {
     type SendType: std::fmt::Debug;
     fn verify_batches(&self, batches: Vec<PacketBatch>, valid_packets: usize) -> Vec<PacketBatch>;
     fn send_packets(&mut self, packet_batches: Vec<PacketBatch>) -> Result<(), Self::SendType>;
+    /// Traces packets which have been dropped instead of being sent, if supported.
+    fn trace_packets_dropped(&self, _count: usize, _reason: PacketDropReason) {}
+    /// Traces the stats of the last interval, if supported.
+    fn trace_stats(&self, _verified: usize, _failed: usize, _dedup: usize, _interval: Duration) {}
 }
 
 #[derive(Default, Clone)]
@@ -77,6 +81,7 @@ struct SigVerifierStats {
     total_dedup: usize,
     total_excess_fail: usize,
     total_valid_packets: usize,
+    total_sigverify_fail: usize,
     total_shrinks: usize,
     total_discard_random: usize,
     total_dedup_time_us: usize,
@@ -199,6 +204,7 @@ impl SigVerifierStats {
             ("total_dedup", self.total_dedup, i64),
             ("total_excess_fail", self.total_excess_fail, i64),
             ("total_valid_packets", self.total_valid_packets, i64),
+            ("total_sigverify_fail", self.total_sigverify_fail, i64),
             ("total_discard_random", self.total_discard_random, i64),
             ("total_shrinks", self.total_shrinks, i64),
             ("total_dedup_time_us", self.total_dedup_time_us, i64),
@@ -337,6 +343,13 @@ impl SigVerifyStage {
         // Post-shrink packet batches if many packets are discarded from sigverify
         let (post_shrink_time_us, post_shrink_total, batches) = Self::maybe_shrink_batches(batches);
 
+        verifier.trace_packets_dropped(discard_or_dedup_fail, PacketDropReason::Dedup);
+        verifier.trace_packets_dropped(
+            num_discarded_randomly + excess_fail,
+            PacketDropReason::BufferOverflow,
+        );
+        let sigverify_fail = num_packets_to_verify.saturating_sub(num_valid_packets);
+        verifier.trace_packets_dropped(sigverify_fail, PacketDropReason::SigverifyFailure);
         verifier.send_packets(batches)?;
 
         debug!(
@@ -370,6 +383,7 @@ impl SigVerifyStage {
         stats.total_packets += num_packets;
         stats.total_dedup += discard_or_dedup_fail;
         stats.total_valid_packets += num_valid_packets;
+        stats.total_sigverify_fail += sigverify_fail;
         stats.total_discard_random_time_us += discard_random_time.as_us() as usize;
         stats.total_discard_random += num_discarded_randomly;
         stats.total_excess_fail += excess_fail;
@@ -419,6 +433,14 @@ impl SigVerifyStage {
                         }
                     }
                     if last_print.elapsed().as_secs() > 2 {
+                        if stats.total_batches > 0 {
+                            verifier.trace_stats(
+                                stats.total_valid_packets,
+                                stats.total_sigverify_fail,
+                                stats.total_dedup,
+                                last_print.elapsed(),
+                            );
+                        }
                         stats.maybe_report(metrics_name);
                         stats = SigVerifierStats::default();
                         last_print = Instant::now();
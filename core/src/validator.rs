@@ -5,7 +5,11 @@ use {
     crate::{
         accounts_hash_verifier::AccountsHashVerifier,
         admin_rpc_post_init::{AdminRpcRequestMetadataPostInit, KeyUpdaterType, KeyUpdaters},
-        banking_trace::{self, BankingTracer, TraceError},
+        banking_trace::{
+            self, geyser_sink::GeyserSink, reader::TraceCompression, sampling_sink::SamplingSink,
+            BankingTracer, ChannelLabel, EnvOverrides, FileIoBackend, FsyncPolicy, RollingFileSink,
+            RollingFileSinkConfig, RotationPolicy, TraceError, TraceSink, TraceSinkConfig,
+        },
         cluster_info_vote_listener::VoteTracker,
         completed_data_sets_service::CompletedDataSetsService,
         consensus::{
@@ -275,6 +279,37 @@ pub struct ValidatorConfig {
     pub wait_to_vote_slot: Option<Slot>,
     pub runtime_config: RuntimeConfig,
     pub banking_trace_dir_byte_limit: banking_trace::DirByteLimit,
+    pub banking_trace_sinks: Vec<TraceSinkConfig>,
+    /// Only 1 in this many packet batches are written into the trace files, if more than 1.
+    pub banking_trace_sample_rate: u64,
+    pub banking_trace_rotation_policy: RotationPolicy,
+    /// The number of trace files retained, among which the byte limit is divided.
+    pub banking_trace_rotate_count: u64,
+    /// Overrides the name of the trace file being written into.
+    pub banking_trace_basename: Option<String>,
+    /// If given, completed trace files are renamed after this strftime-style template.
+    pub banking_trace_file_name_template: Option<String>,
+    pub banking_trace_fsync_policy: FsyncPolicy,
+    /// The capacity of the write buffer of the trace file being written into.
+    pub banking_trace_buf_writer_capacity: usize,
+    pub banking_trace_io_backend: FileIoBackend,
+    /// If given, completed trace files are compressed in the background.
+    pub banking_trace_compression: Option<TraceCompression>,
+    /// Whether trace files are preallocated up to the rotation threshold once created.
+    pub banking_trace_preallocate: bool,
+    /// The labels whose packet batches aren't traced.
+    pub banking_trace_untraced_labels: Vec<ChannelLabel>,
+    /// Packet batches aren't traced while the free space of the trace filesystem is below this,
+    /// unless zero.
+    pub banking_trace_min_available_bytes: u64,
+    /// How long the tracer thread sleeps once it has written the events received so far.
+    pub banking_trace_write_interval: Duration,
+    /// Whether the packet batches are serialized by the sending threads instead of the tracer
+    /// thread.
+    pub banking_trace_serialize_in_sender: bool,
+    /// Whether the traced events are timestamped by a millisecond-precise clock cached by the
+    /// tracer thread.
+    pub banking_trace_coarse_clock: bool,
     pub block_verification_method: BlockVerificationMethod,
     pub block_production_method: BlockProductionMethod,
     pub transaction_struct: TransactionStructure,
@@ -350,6 +385,23 @@ impl Default for ValidatorConfig {
             wait_to_vote_slot: None,
             runtime_config: RuntimeConfig::default(),
             banking_trace_dir_byte_limit: 0,
+            banking_trace_sinks: Vec::new(),
+            banking_trace_sample_rate: 1,
+            banking_trace_rotation_policy: RotationPolicy::default(),
+            banking_trace_rotate_count: banking_trace::TRACE_FILE_DEFAULT_ROTATE_COUNT,
+            banking_trace_basename: None,
+            banking_trace_file_name_template: None,
+            banking_trace_fsync_policy: FsyncPolicy::default(),
+            banking_trace_buf_writer_capacity:
+                banking_trace::TRACE_FILE_DEFAULT_BUF_WRITER_CAPACITY,
+            banking_trace_io_backend: FileIoBackend::default(),
+            banking_trace_compression: None,
+            banking_trace_preallocate: false,
+            banking_trace_untraced_labels: Vec::new(),
+            banking_trace_min_available_bytes: 0,
+            banking_trace_write_interval: banking_trace::TRACE_FILE_DEFAULT_WRITE_INTERVAL,
+            banking_trace_serialize_in_sender: false,
+            banking_trace_coarse_clock: false,
             block_verification_method: BlockVerificationMethod::default(),
             block_production_method: BlockProductionMethod::default(),
             transaction_struct: TransactionStructure::default(),
@@ -748,6 +800,7 @@ impl Validator {
             accounts_update_notifier,
             transaction_notifier,
             entry_notifier,
+            banking_trace_notifier,
             block_metadata_notifier,
             slot_status_notifier,
         ) = if let Some(service) = &geyser_plugin_service {
@@ -755,19 +808,21 @@ impl Validator {
                 service.get_accounts_update_notifier(),
                 service.get_transaction_notifier(),
                 service.get_entry_notifier(),
+                service.get_banking_trace_notifier(),
                 service.get_block_metadata_notifier(),
                 service.get_slot_status_notifier(),
             )
         } else {
-            (None, None, None, None, None)
+            (None, None, None, None, None, None)
         };
 
         info!(
             "Geyser plugin: accounts_update_notifier: {}, transaction_notifier: {}, \
-             entry_notifier: {}",
+             entry_notifier: {}, banking_trace_notifier: {}",
             accounts_update_notifier.is_some(),
             transaction_notifier.is_some(),
-            entry_notifier.is_some()
+            entry_notifier.is_some(),
+            banking_trace_notifier.is_some()
         );
 
         let system_monitor_service = Some(SystemMonitorService::new(
@@ -958,17 +1013,106 @@ impl Validator {
             TransactionRecorder::new(record_sender, poh_recorder.is_exited.clone());
         let poh_recorder = Arc::new(RwLock::new(poh_recorder));
 
-        let (banking_tracer, tracer_thread) =
-            BankingTracer::new((config.banking_trace_dir_byte_limit > 0).then_some((
-                &blockstore.banking_trace_path(),
-                exit.clone(),
-                config.banking_trace_dir_byte_limit,
-            )))?;
+        let geyser_sink = banking_trace_notifier
+            .map(|notifier| Box::new(GeyserSink::new(notifier)) as Box<dyn TraceSink>);
+        let banking_trace_env_overrides = if config.banking_trace_dir_byte_limit > 0 {
+            EnvOverrides::from_env()?
+        } else {
+            EnvOverrides::default()
+        };
+        let mut banking_trace_path = blockstore.banking_trace_path();
+        let mut banking_trace_dir_byte_limit = config.banking_trace_dir_byte_limit;
+        let default_file_sink_config = RollingFileSinkConfig::default();
+        let mut file_sink_config = RollingFileSinkConfig {
+            rotate_count: config.banking_trace_rotate_count,
+            rotation_policy: config.banking_trace_rotation_policy,
+            basename: config
+                .banking_trace_basename
+                .clone()
+                .unwrap_or_else(|| default_file_sink_config.basename.clone()),
+            file_name_template: config.banking_trace_file_name_template.clone(),
+            fsync_policy: config.banking_trace_fsync_policy,
+            buf_writer_capacity: config.banking_trace_buf_writer_capacity,
+            io_backend: config.banking_trace_io_backend,
+            compression: config.banking_trace_compression,
+            compression_thread_count: default_file_sink_config.compression_thread_count,
+            preallocate: config.banking_trace_preallocate,
+        };
+        // the environment overrides the trace files whichever sinks they're written along with
+        banking_trace_env_overrides.apply(
+            &mut banking_trace_path,
+            &mut banking_trace_dir_byte_limit,
+            &mut file_sink_config,
+        );
+        let (banking_tracer, tracer_thread) = if config.banking_trace_sinks.is_empty()
+            && geyser_sink.is_none()
+            && config.banking_trace_sample_rate <= 1
+        {
+            BankingTracer::new_with_file_sink_config(
+                (banking_trace_dir_byte_limit > 0).then_some((
+                    &banking_trace_path,
+                    exit.clone(),
+                    banking_trace_dir_byte_limit,
+                )),
+                file_sink_config,
+            )?
+        } else if banking_trace_dir_byte_limit > 0 {
+            let file_sink = RollingFileSink::new_with_config(
+                &banking_trace_path,
+                banking_trace_dir_byte_limit,
+                file_sink_config,
+            )?;
+            let file_sink: Box<dyn TraceSink> = if config.banking_trace_sample_rate > 1 {
+                Box::new(SamplingSink::new(
+                    file_sink,
+                    config.banking_trace_sample_rate,
+                ))
+            } else {
+                Box::new(file_sink)
+            };
+            let sinks = std::iter::once(Ok(file_sink))
+                .chain(geyser_sink.map(Ok))
+                .chain(
+                    config
+                        .banking_trace_sinks
+                        .iter()
+                        .map(TraceSinkConfig::build),
+                )
+                .collect::<Result<Vec<_>, _>>()?;
+            BankingTracer::new_with_sinks(sinks, exit.clone())?
+        } else {
+            if !config.banking_trace_sinks.is_empty() {
+                warn!("--banking-trace-sink is ignored because banking trace is disabled");
+            }
+            BankingTracer::new(None)?
+        };
         if banking_tracer.is_enabled() {
             info!(
-                "Enabled banking trace (dir_byte_limit: {})",
-                config.banking_trace_dir_byte_limit
+                "Enabled banking trace (dir_byte_limit: {}, rotation_policy: {}, rotate_count: \
+                 {}, sinks: {:?})",
+                banking_trace_dir_byte_limit,
+                config.banking_trace_rotation_policy,
+                config.banking_trace_rotate_count,
+                config.banking_trace_sinks,
             );
+            banking_tracer.install_flush_on_panic_hook(banking_trace::PANIC_FLUSH_TIMEOUT);
+            for label in config
+                .banking_trace_untraced_labels
+                .iter()
+                .chain(banking_trace_env_overrides.untraced_labels())
+            {
+                banking_tracer.set_label_traced(*label, false);
+            }
+            banking_tracer.set_min_available_bytes(config.banking_trace_min_available_bytes);
+            banking_tracer.set_write_interval(config.banking_trace_write_interval);
+            banking_tracer.set_serialize_in_sender(config.banking_trace_serialize_in_sender);
+            banking_tracer.set_coarse_clock(config.banking_trace_coarse_clock);
+            poh_recorder.write().unwrap().set_tick_observer(Box::new({
+                let banking_tracer = banking_tracer.clone();
+                move |slot, tick_height, last_tick_in_slot| {
+                    banking_tracer.poh_tick_event(slot, tick_height, last_tick_in_slot)
+                }
+            }));
         } else {
             info!("Disabled banking trace");
         }
@@ -1615,7 +1759,7 @@ impl Validator {
             entry_notification_sender,
             blockstore.clone(),
             &config.broadcast_stage_type,
-            exit,
+            exit.clone(),
             node.info.shred_version(),
             vote_tracker,
             bank_forks.clone(),
@@ -1632,6 +1776,7 @@ impl Validator {
             config.runtime_config.log_messages_bytes_limit,
             &staked_nodes,
             config.staked_nodes_overrides.clone(),
+            banking_tracer.clone(),
             banking_tracer_channels,
             tracer_thread,
             tpu_enable_udp,
@@ -1678,6 +1823,9 @@ impl Validator {
             outstanding_repair_requests,
             cluster_slots,
             gossip_socket: Some(node.sockets.gossip.clone()),
+            banking_tracer: banking_tracer.clone(),
+            banking_trace_path: blockstore.banking_trace_path(),
+            banking_trace_exit: exit.clone(),
         });
 
         Ok(Self {
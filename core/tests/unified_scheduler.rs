@@ -233,10 +233,8 @@ fn test_scheduler_producing_blocks() {
             Some(leader_schedule_cache),
         );
     let pool = DefaultSchedulerPool::new(None, None, None, None, ignored_prioritization_fee_cache);
-    let channels = {
-        let banking_tracer = BankingTracer::new_disabled();
-        banking_tracer.create_channels(true)
-    };
+    let banking_tracer = BankingTracer::new_disabled();
+    let channels = banking_tracer.create_channels(true);
     let cluster_info = {
         let keypair = Arc::new(Keypair::new());
         let node = Node::new_localhost_with_pubkey(&keypair.pubkey());
@@ -254,6 +252,7 @@ fn test_scheduler_producing_blocks() {
         &poh_recorder,
         transaction_recorder,
         BankingStage::num_threads(),
+        &banking_tracer,
     );
     bank_forks.write().unwrap().install_scheduler_pool(pool);
 
@@ -235,6 +235,29 @@ pub enum ReplicaEntryInfoVersions<'a> {
     V0_0_2(&'a ReplicaEntryInfoV2<'a>),
 }
 
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct ReplicaBankingTraceEventInfo<'a> {
+    /// The time of the event in microseconds since the Unix epoch
+    pub timestamp_us: u64,
+    /// The kind of the event, e.g. "PacketBatch"
+    pub kind: &'a str,
+    /// The label of the banking stage channel which the event is about, if any
+    pub label: Option<&'a str>,
+    /// The slot which the event is about, if any
+    pub slot: Option<Slot>,
+    /// The bincode-serialized event, as in the frames of banking trace files
+    pub payload: &'a [u8],
+}
+
+/// A wrapper to future-proof ReplicaBankingTraceEventInfo handling. To make a change to the
+/// structure of ReplicaBankingTraceEventInfo, add an new enum variant wrapping a newer version,
+/// which will force plugin implementations to handle the change.
+#[repr(u32)]
+pub enum ReplicaBankingTraceEventInfoVersions<'a> {
+    V0_0_1(&'a ReplicaBankingTraceEventInfo<'a>),
+}
+
 #[derive(Clone, Debug)]
 #[repr(C)]
 pub struct ReplicaBlockInfo<'a> {
@@ -473,6 +496,15 @@ pub trait GeyserPlugin: Any + Send + Sync + std::fmt::Debug {
         Ok(())
     }
 
+    /// Called when an event is traced by the banking tracer, if enabled.
+    #[allow(unused_variables)]
+    fn notify_banking_trace_event(
+        &self,
+        event: ReplicaBankingTraceEventInfoVersions,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     /// Check if the plugin is interested in account data
     /// Default is true -- if the plugin is not interested in
     /// account data, please return false.
@@ -501,4 +533,11 @@ pub trait GeyserPlugin: Any + Send + Sync + std::fmt::Debug {
     fn entry_notifications_enabled(&self) -> bool {
         false
     }
+
+    /// Check if the plugin is interested in banking trace events
+    /// Default is false -- if the plugin is interested in
+    /// banking trace events, return true.
+    fn banking_trace_notifications_enabled(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,81 @@
+/// Module responsible for notifying plugins about banking trace events
+use {
+    crate::{
+        banking_trace_notifier_interface::BankingTraceNotifier,
+        geyser_plugin_manager::GeyserPluginManager,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::{
+        ReplicaBankingTraceEventInfo, ReplicaBankingTraceEventInfoVersions,
+    },
+    log::*,
+    solana_clock::Slot,
+    solana_measure::measure::Measure,
+    solana_metrics::*,
+    std::sync::{Arc, RwLock},
+};
+
+pub(crate) struct BankingTraceNotifierImpl {
+    plugin_manager: Arc<RwLock<GeyserPluginManager>>,
+}
+
+impl BankingTraceNotifier for BankingTraceNotifierImpl {
+    fn notify_banking_trace_event(
+        &self,
+        timestamp_us: u64,
+        kind: &str,
+        label: Option<&str>,
+        slot: Option<Slot>,
+        payload: &[u8],
+    ) {
+        let mut measure = Measure::start("geyser-plugin-notify_plugins_of_banking_trace_event");
+
+        let plugin_manager = self.plugin_manager.read().unwrap();
+        if plugin_manager.plugins.is_empty() {
+            return;
+        }
+
+        let event_info = ReplicaBankingTraceEventInfo {
+            timestamp_us,
+            kind,
+            label,
+            slot,
+            payload,
+        };
+
+        for plugin in plugin_manager.plugins.iter() {
+            if !plugin.banking_trace_notifications_enabled() {
+                continue;
+            }
+            match plugin.notify_banking_trace_event(ReplicaBankingTraceEventInfoVersions::V0_0_1(
+                &event_info,
+            )) {
+                Err(err) => {
+                    error!(
+                        "Failed to notify banking trace event, error: ({}) to plugin {}",
+                        err,
+                        plugin.name()
+                    )
+                }
+                Ok(_) => {
+                    trace!(
+                        "Successfully notified banking trace event to plugin {}",
+                        plugin.name()
+                    );
+                }
+            }
+        }
+        measure.stop();
+        inc_new_counter_debug!(
+            "geyser-plugin-notify_plugins_of_banking_trace_event-us",
+            measure.as_us() as usize,
+            10000,
+            10000
+        );
+    }
+}
+
+impl BankingTraceNotifierImpl {
+    pub fn new(plugin_manager: Arc<RwLock<GeyserPluginManager>>) -> Self {
+        Self { plugin_manager }
+    }
+}
@@ -0,0 +1,16 @@
+use {solana_clock::Slot, std::sync::Arc};
+
+/// Interface for notifying banking trace events
+pub trait BankingTraceNotifier {
+    /// Notify the banking trace event, whose bincode serialization is `payload`
+    fn notify_banking_trace_event(
+        &self,
+        timestamp_us: u64,
+        kind: &str,
+        label: Option<&str>,
+        slot: Option<Slot>,
+        payload: &[u8],
+    );
+}
+
+pub type BankingTraceNotifierArc = Arc<dyn BankingTraceNotifier + Sync + Send>;
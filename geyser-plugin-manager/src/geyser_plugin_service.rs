@@ -1,6 +1,8 @@
 use {
     crate::{
         accounts_update_notifier::AccountsUpdateNotifierImpl,
+        banking_trace_notifier::BankingTraceNotifierImpl,
+        banking_trace_notifier_interface::BankingTraceNotifierArc,
         block_metadata_notifier::BlockMetadataNotifierImpl,
         block_metadata_notifier_interface::BlockMetadataNotifierArc,
         entry_notifier::EntryNotifierImpl,
@@ -37,6 +39,7 @@ pub struct GeyserPluginService {
     accounts_update_notifier: Option<AccountsUpdateNotifier>,
     transaction_notifier: Option<TransactionNotifierArc>,
     entry_notifier: Option<EntryNotifierArc>,
+    banking_trace_notifier: Option<BankingTraceNotifierArc>,
     block_metadata_notifier: Option<BlockMetadataNotifierArc>,
     slot_status_notifier: Option<SlotStatusNotifier>,
 }
@@ -96,6 +99,8 @@ impl GeyserPluginService {
             plugin_manager.transaction_notifications_enabled() || geyser_plugin_always_enabled;
         let entry_notifications_enabled =
             plugin_manager.entry_notifications_enabled() || geyser_plugin_always_enabled;
+        let banking_trace_notifications_enabled =
+            plugin_manager.banking_trace_notifications_enabled() || geyser_plugin_always_enabled;
         let plugin_manager = Arc::new(RwLock::new(plugin_manager));
 
         let accounts_update_notifier: Option<AccountsUpdateNotifier> =
@@ -124,6 +129,14 @@ impl GeyserPluginService {
             None
         };
 
+        let banking_trace_notifier: Option<BankingTraceNotifierArc> =
+            if banking_trace_notifications_enabled {
+                let banking_trace_notifier = BankingTraceNotifierImpl::new(plugin_manager.clone());
+                Some(Arc::new(banking_trace_notifier))
+            } else {
+                None
+            };
+
         let (slot_status_observer, block_metadata_notifier, slot_status_notifier): (
             Option<SlotStatusObserver>,
             Option<BlockMetadataNotifierArc>,
@@ -161,6 +174,7 @@ impl GeyserPluginService {
             accounts_update_notifier,
             transaction_notifier,
             entry_notifier,
+            banking_trace_notifier,
             block_metadata_notifier,
             slot_status_notifier,
         })
@@ -188,6 +202,10 @@ impl GeyserPluginService {
         self.entry_notifier.clone()
     }
 
+    pub fn get_banking_trace_notifier(&self) -> Option<BankingTraceNotifierArc> {
+        self.banking_trace_notifier.clone()
+    }
+
     pub fn get_block_metadata_notifier(&self) -> Option<BlockMetadataNotifierArc> {
         self.block_metadata_notifier.clone()
     }
@@ -1,4 +1,6 @@
 pub mod accounts_update_notifier;
+pub mod banking_trace_notifier;
+pub mod banking_trace_notifier_interface;
 pub mod block_metadata_notifier;
 pub mod block_metadata_notifier_interface;
 pub mod entry_notifier;
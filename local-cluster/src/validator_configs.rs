@@ -62,6 +62,22 @@ pub fn safe_clone_config(config: &ValidatorConfig) -> ValidatorConfig {
         wait_to_vote_slot: config.wait_to_vote_slot,
         runtime_config: config.runtime_config.clone(),
         banking_trace_dir_byte_limit: config.banking_trace_dir_byte_limit,
+        banking_trace_sinks: config.banking_trace_sinks.clone(),
+        banking_trace_sample_rate: config.banking_trace_sample_rate,
+        banking_trace_rotation_policy: config.banking_trace_rotation_policy,
+        banking_trace_rotate_count: config.banking_trace_rotate_count,
+        banking_trace_basename: config.banking_trace_basename.clone(),
+        banking_trace_file_name_template: config.banking_trace_file_name_template.clone(),
+        banking_trace_fsync_policy: config.banking_trace_fsync_policy,
+        banking_trace_buf_writer_capacity: config.banking_trace_buf_writer_capacity,
+        banking_trace_io_backend: config.banking_trace_io_backend,
+        banking_trace_compression: config.banking_trace_compression,
+        banking_trace_preallocate: config.banking_trace_preallocate,
+        banking_trace_untraced_labels: config.banking_trace_untraced_labels.clone(),
+        banking_trace_min_available_bytes: config.banking_trace_min_available_bytes,
+        banking_trace_write_interval: config.banking_trace_write_interval,
+        banking_trace_serialize_in_sender: config.banking_trace_serialize_in_sender,
+        banking_trace_coarse_clock: config.banking_trace_coarse_clock,
         block_verification_method: config.block_verification_method.clone(),
         block_production_method: config.block_production_method.clone(),
         transaction_struct: config.transaction_struct.clone(),
@@ -58,6 +58,10 @@ pub(crate) type Result<T> = std::result::Result<T, PohRecorderError>;
 
 pub type WorkingBankEntry = (Arc<Bank>, (Entry, u64));
 
+/// Called on each tick with the slot of the tick, the new tick height and whether it's the last
+/// tick of the slot.
+pub type TickObserver = Box<dyn Fn(Slot, u64, bool) + Send + Sync>;
+
 #[derive(Debug, Clone)]
 pub struct BankStart {
     pub working_bank: Arc<Bank>,
@@ -195,6 +199,7 @@ pub struct PohRecorder {
     // Allocation to hold PohEntrys recorded into PoHStream.
     entries: Vec<PohEntry>,
     track_transaction_indexes: bool,
+    tick_observer: Option<TickObserver>,
 }
 
 impl PohRecorder {
@@ -281,6 +286,7 @@ impl PohRecorder {
                 is_exited,
                 entries: Vec::with_capacity(64),
                 track_transaction_indexes: false,
+                tick_observer: None,
             },
             working_bank_receiver,
         )
@@ -290,6 +296,10 @@ impl PohRecorder {
         self.track_transaction_indexes = true;
     }
 
+    pub fn set_tick_observer(&mut self, tick_observer: TickObserver) {
+        self.tick_observer = Some(tick_observer);
+    }
+
     // synchronize PoH with a bank
     pub fn reset(&mut self, reset_bank: Arc<Bank>, next_leader_slot: Option<(Slot, Slot)>) {
         self.clear_bank();
@@ -402,6 +412,13 @@ impl PohRecorder {
         if let Some(poh_entry) = poh_entry {
             self.tick_height += 1;
             trace!("tick_height {}", self.tick_height);
+            if let Some(tick_observer) = &self.tick_observer {
+                tick_observer(
+                    self.slot_for_tick_height(self.tick_height),
+                    self.tick_height,
+                    self.tick_height % self.ticks_per_slot == 0,
+                );
+            }
 
             if self.leader_first_tick_height.is_none() {
                 return;
@@ -1014,6 +1031,45 @@ mod tests {
         assert_eq!(poh_recorder.tick_height, 1);
     }
 
+    #[test]
+    fn test_poh_recorder_tick_observer() {
+        let prev_hash = Hash::default();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Blockstore::open(ledger_path.path())
+            .expect("Expected to be able to open database ledger");
+
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(2);
+        let bank = Arc::new(Bank::new_for_tests(&genesis_config));
+        let (mut poh_recorder, _entry_receiver) = PohRecorder::new(
+            0,
+            prev_hash,
+            bank,
+            None,
+            2,
+            Arc::new(blockstore),
+            &Arc::new(LeaderScheduleCache::default()),
+            &PohConfig::default(),
+            Arc::new(AtomicBool::default()),
+        );
+        let observed_ticks = Arc::<Mutex<Vec<_>>>::default();
+        poh_recorder.set_tick_observer(Box::new({
+            let observed_ticks = observed_ticks.clone();
+            move |slot, tick_height, last_tick_in_slot| {
+                observed_ticks
+                    .lock()
+                    .unwrap()
+                    .push((slot, tick_height, last_tick_in_slot))
+            }
+        }));
+        for _ in 0..3 {
+            poh_recorder.tick();
+        }
+        assert_eq!(
+            *observed_ticks.lock().unwrap(),
+            vec![(0, 1, false), (0, 2, true), (1, 3, false)]
+        );
+    }
+
     #[test]
     fn test_poh_recorder_tick_height_is_last_tick() {
         let prev_hash = Hash::default();
@@ -231,6 +231,12 @@ impl HandlerContext {
     fn banking_stage_helper(&self) -> &BankingStageHelper {
         self.banking_stage_helper.as_ref().unwrap()
     }
+
+    fn banking_task_observer(&self) -> Option<&dyn BankingTaskObserver> {
+        self.banking_stage_helper
+            .as_ref()
+            .and_then(|helper| helper.task_observer.as_deref())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -279,6 +285,8 @@ struct BankingStageHandlerContext {
     banking_packet_handler: Box<dyn BankingPacketHandler>,
     transaction_recorder: TransactionRecorder,
     banking_stage_monitor: Box<dyn BankingStageMonitor>,
+    #[debug("{}", banking_task_observer.is_some())]
+    banking_task_observer: Option<Arc<dyn BankingTaskObserver>>,
 }
 
 trait_set! {
@@ -288,6 +296,21 @@ trait_set! {
 // Make this `Clone`-able so that it can easily propagated to all the handler threads.
 clone_trait_object!(BankingPacketHandler);
 
+/// A stage in the lifecycle of a block-production task, reported to [`BankingTaskObserver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskLifecycle {
+    /// Created from a transaction, including re-creation of a retried task under a new index.
+    Created,
+    /// Buffered by the scheduler thread because some of its accounts are locked by other tasks.
+    Blocked,
+    /// Executed and descheduled, whether committed or not.
+    Completed { committed: bool },
+}
+
+trait_set! {
+    pub trait BankingTaskObserver = Fn(&Task, TaskLifecycle) + Send + Sync + 'static;
+}
+
 /// A helper struct for the banking stage integration, primarily used for task creation.
 ///
 /// This block-production struct is expected to be shared across the scheduler thread and its
@@ -296,7 +319,7 @@ clone_trait_object!(BankingPacketHandler);
 /// Particularly, usage_queue_loader is desired to be shared across hanlders so that task creation
 /// can be processed in the multi-threaded way. For more details, see
 /// solana_core::banking_stage::unified_scheduler module doc.
-#[derive(Debug)]
+#[derive(derive_more::Debug)]
 pub struct BankingStageHelper {
     usage_queue_loader: UsageQueueLoaderInner,
     // Supplemental identification for tasks of identical priority, alloted according to FIFO of
@@ -304,6 +327,8 @@ pub struct BankingStageHelper {
     // collectively.
     next_task_id: AtomicUsize,
     new_task_sender: Sender<NewTaskPayload>,
+    #[debug("{}", task_observer.is_some())]
+    task_observer: Option<Arc<dyn BankingTaskObserver>>,
 }
 
 // AtomicUsize's fetch_add entails the wrapping semantics. So, address such an overflowing, under
@@ -321,11 +346,15 @@ pub struct BankingStageHelper {
 const BANKING_STAGE_MAX_TASK_ID: usize = usize::MAX / 2;
 
 impl BankingStageHelper {
-    fn new(new_task_sender: Sender<NewTaskPayload>) -> Self {
+    fn new(
+        new_task_sender: Sender<NewTaskPayload>,
+        task_observer: Option<Arc<dyn BankingTaskObserver>>,
+    ) -> Self {
         Self {
             usage_queue_loader: UsageQueueLoaderInner::default(),
             next_task_id: AtomicUsize::default(),
             new_task_sender,
+            task_observer,
         }
     }
 
@@ -354,9 +383,17 @@ impl BankingStageHelper {
         transaction: RuntimeTransaction<SanitizedTransaction>,
         index: usize,
     ) -> Task {
-        SchedulingStateMachine::create_task(transaction, index, &mut |pubkey| {
+        let task = SchedulingStateMachine::create_task(transaction, index, &mut |pubkey| {
             self.usage_queue_loader.load(pubkey)
-        })
+        });
+        self.observe_task(&task, TaskLifecycle::Created);
+        task
+    }
+
+    fn observe_task(&self, task: &Task, lifecycle: TaskLifecycle) {
+        if let Some(task_observer) = &self.task_observer {
+            task_observer(task, lifecycle);
+        }
     }
 
     fn recreate_task(&self, executed_task: Box<ExecutedTask>) -> Task {
@@ -736,6 +773,7 @@ where
         banking_packet_handler: Box<dyn BankingPacketHandler>,
         transaction_recorder: TransactionRecorder,
         banking_stage_monitor: Box<dyn BankingStageMonitor>,
+        banking_task_observer: Option<Arc<dyn BankingTaskObserver>>,
     ) {
         *self.banking_stage_handler_context.lock().unwrap() = Some(BankingStageHandlerContext {
             banking_thread_count,
@@ -743,6 +781,7 @@ where
             banking_packet_handler,
             transaction_recorder,
             banking_stage_monitor,
+            banking_task_observer,
         });
         // Immediately start a block production scheduler, so that the scheduler can start
         // buffering tasks, which are preprocessed as much as possible.
@@ -795,7 +834,10 @@ where
                     handler_context.banking_thread_count,
                     handler_context.banking_packet_receiver.clone(),
                     handler_context.banking_packet_handler.clone(),
-                    Some(Arc::new(BankingStageHelper::new(new_task_sender.clone()))),
+                    Some(Arc::new(BankingStageHelper::new(
+                        new_task_sender.clone(),
+                        handler_context.banking_task_observer.clone(),
+                    ))),
                     Some(handler_context.transaction_recorder.clone()),
                 )
             }
@@ -1645,6 +1687,10 @@ impl<S: SpawnableScheduler<TH>, TH: TaskHandler> ThreadManager<S, TH> {
                 }
             },
             BlockProduction => {
+                if let Some(task_observer) = handler_context.banking_task_observer() {
+                    let committed = executed_task.result_with_timings.0.is_ok();
+                    task_observer(&executed_task.task, TaskLifecycle::Completed { committed });
+                }
                 match executed_task.result_with_timings.0 {
                     Ok(()) => {
                         // The most normal case
@@ -1956,10 +2002,21 @@ impl<S: SpawnableScheduler<TH>, TH: TaskHandler> ThreadManager<S, TH> {
                                         let task_index = task.task_index();
                                         sleepless_testing::at(CheckPoint::NewTask(task_index));
 
+                                        // A buffered task is blocked on account locks unless it's queued as unblocked.
+                                        let observed_task = handler_context
+                                            .banking_task_observer()
+                                            .map(|_| (task.clone(), state_machine.unblocked_task_queue_count()));
                                         if let Some(task) = state_machine.schedule_or_buffer_task(task, session_ending) {
                                             runnable_task_sender.send_aux_payload(task).unwrap();
                                         } else {
                                             sleepless_testing::at(CheckPoint::BufferedTask(task_index));
+                                            if let Some((task, unblocked_task_count)) = observed_task {
+                                                if state_machine.unblocked_task_queue_count() == unblocked_task_count {
+                                                    handler_context
+                                                        .banking_stage_helper()
+                                                        .observe_task(&task, TaskLifecycle::Blocked);
+                                                }
+                                            }
                                         }
                                     }
                                     Ok(NewTaskPayload::CloseSubchannel) => {
@@ -3748,6 +3805,7 @@ mod tests {
                 Box::new(|_, _| unreachable!()),
                 transaction_recorder,
                 Box::new(DummyBankingMinitor),
+                None,
             );
         }
 
@@ -3876,6 +3934,7 @@ mod tests {
             Box::new(|_, _| unreachable!()),
             transaction_recorder,
             Box::new(DummyBankingMinitor),
+            None,
         );
 
         let bank = Arc::new(Bank::new_from_parent(
@@ -4494,6 +4553,7 @@ mod tests {
             Box::new(|_, _| unreachable!()),
             transaction_recorder,
             Box::new(DummyBankingMinitor),
+            None,
         );
 
         assert_eq!(bank.transaction_count(), 0);
@@ -4515,6 +4575,74 @@ mod tests {
         poh_service.join().unwrap();
     }
 
+    #[test]
+    fn test_block_production_scheduler_task_observer() {
+        solana_logger::setup();
+
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config_for_block_production(10_000);
+
+        let bank = Bank::new_for_tests(&genesis_config);
+        let (bank, _bank_forks) = setup_dummy_fork_graph(bank);
+
+        let ignored_prioritization_fee_cache = Arc::new(PrioritizationFeeCache::new(0u64));
+        let pool =
+            DefaultSchedulerPool::new(None, None, None, None, ignored_prioritization_fee_cache);
+
+        let (_banking_packet_sender, banking_packet_receiver) = crossbeam_channel::unbounded();
+        let (ledger_path, _blockhash) = create_new_tmp_ledger_auto_delete!(&genesis_config);
+        let blockstore = Arc::new(Blockstore::open(ledger_path.path()).unwrap());
+        let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&bank));
+        let (exit, _poh_recorder, transaction_recorder, poh_service, _signal_receiver) =
+            create_test_recorder_with_index_tracking(
+                bank.clone(),
+                blockstore.clone(),
+                None,
+                Some(leader_schedule_cache),
+            );
+        let observed_tasks = Arc::<Mutex<Vec<_>>>::default();
+        pool.register_banking_stage(
+            None,
+            banking_packet_receiver,
+            // we don't use the banking packet channel in this test. so, pass panicking handler.
+            Box::new(|_, _| unreachable!()),
+            transaction_recorder,
+            Box::new(DummyBankingMinitor),
+            Some(Arc::new({
+                let observed_tasks = observed_tasks.clone();
+                move |task: &Task, lifecycle| {
+                    observed_tasks
+                        .lock()
+                        .unwrap()
+                        .push((task.task_index(), lifecycle))
+                }
+            })),
+        );
+
+        let context = SchedulingContext::for_production(bank.clone());
+        let scheduler = pool.take_scheduler(context);
+        scheduler.unpause_after_taken();
+        let tx0 = RuntimeTransaction::from_transaction_for_tests(system_transaction::transfer(
+            &mint_keypair,
+            &solana_pubkey::new_rand(),
+            2,
+            genesis_config.hash(),
+        ));
+        scheduler.schedule_execution(tx0, 3).unwrap();
+        let bank = BankWithScheduler::new(bank, Some(scheduler));
+        assert_matches!(bank.wait_for_completed_scheduler(), Some((Ok(()), _)));
+        assert_eq!(
+            *observed_tasks.lock().unwrap(),
+            vec![(3, TaskLifecycle::Completed { committed: true })]
+        );
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
     #[test]
     fn test_block_production_scheduler_buffering_on_spawn() {
         solana_logger::setup();
@@ -4571,6 +4699,7 @@ mod tests {
             fixed_banking_packet_handler,
             transaction_recorder,
             Box::new(DummyBankingMinitor),
+            None,
         );
 
         // Confirm the banking packet channel is cleared, even before taking scheduler
@@ -4640,6 +4769,7 @@ mod tests {
             fixed_banking_packet_handler,
             transaction_recorder,
             Box::new(DummyBankingMinitor),
+            None,
         );
 
         // Quickly take and return the scheduler so that this test can test the behavior while
@@ -4716,6 +4846,7 @@ mod tests {
             Box::new(|_, _| unreachable!()),
             transaction_recorder,
             Box::new(DummyBankingMinitor),
+            None,
         );
 
         let context = SchedulingContext::for_production(bank.clone());
@@ -4777,6 +4908,7 @@ mod tests {
             Box::new(|_, _| unreachable!()),
             transaction_recorder,
             Box::new(DummyBankingMinitor),
+            None,
         );
 
         let context = SchedulingContext::for_production(bank);
@@ -4863,6 +4995,7 @@ mod tests {
             Box::new(|_, _| unreachable!()),
             transaction_recorder,
             Box::new(InactiveBankingMinitor),
+            None,
         );
 
         // Quickly take and return scheduler just to remember id
@@ -4918,6 +5051,7 @@ mod tests {
             Box::new(|_, _| unreachable!()),
             transaction_recorder,
             Box::new(DummyBankingMinitor),
+            None,
         );
 
         // Make sure the assertion in BlockProductionSchedulerInner::can_put() doesn't cause false
@@ -5012,6 +5146,7 @@ mod tests {
             fixed_banking_packet_handler,
             transaction_recorder,
             Box::new(SimpleBankingMinitor),
+            None,
         );
 
         // By now, there shuold be a bufferd transaction. Let's discard it.
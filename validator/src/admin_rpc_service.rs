@@ -11,6 +11,10 @@ use {
     solana_accounts_db::accounts_index::AccountIndex,
     solana_core::{
         admin_rpc_post_init::AdminRpcRequestMetadataPostInit,
+        banking_trace::{
+            debug_sink, BankingTracerStatus, ChannelLabel, TraceError,
+            BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT,
+        },
         consensus::{tower_storage::TowerStorage, Tower},
         repair::repair_service,
         validator::ValidatorStartProgress,
@@ -30,6 +34,7 @@ use {
         fmt::{self, Display},
         net::{IpAddr, SocketAddr},
         path::{Path, PathBuf},
+        str::FromStr,
         sync::{
             atomic::{AtomicBool, Ordering},
             Arc, RwLock,
@@ -40,6 +45,12 @@ use {
     tokio::runtime::Runtime,
 };
 
+/// The most events a single `tailBankingTrace` request can ask for, as a channel of that
+/// capacity is allocated upfront.
+pub const MAX_TAIL_BANKING_TRACE_COUNT: usize = 10_000;
+/// The longest a single `tailBankingTrace` request can wait for the events.
+pub const MAX_TAIL_BANKING_TRACE_TIMEOUT_MS: u64 = 60_000;
+
 #[derive(Clone)]
 pub struct AdminRpcRequestMetadata {
     pub rpc_addr: Option<SocketAddr>,
@@ -260,6 +271,49 @@ pub trait AdminRpc {
         meta: Self::Metadata,
         public_tpu_forwards_addr: SocketAddr,
     ) -> Result<()>;
+
+    #[rpc(meta, name = "bankingTraceStatus")]
+    fn banking_trace_status(&self, meta: Self::Metadata) -> Result<BankingTracerStatus>;
+
+    #[rpc(meta, name = "enableBankingTrace")]
+    fn enable_banking_trace(&self, meta: Self::Metadata, dir_byte_limit: Option<u64>)
+        -> Result<()>;
+
+    #[rpc(meta, name = "disableBankingTrace")]
+    fn disable_banking_trace(&self, meta: Self::Metadata) -> Result<()>;
+
+    #[rpc(meta, name = "rotateBankingTrace")]
+    fn rotate_banking_trace(&self, meta: Self::Metadata) -> Result<()>;
+
+    #[rpc(meta, name = "setBankingTraceDirByteLimit")]
+    fn set_banking_trace_dir_byte_limit(
+        &self,
+        meta: Self::Metadata,
+        dir_byte_limit: u64,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "setBankingTraceMinAvailableBytes")]
+    fn set_banking_trace_min_available_bytes(
+        &self,
+        meta: Self::Metadata,
+        min_available_bytes: u64,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "setBankingTraceLabelTraced")]
+    fn set_banking_trace_label_traced(
+        &self,
+        meta: Self::Metadata,
+        label: String,
+        is_traced: bool,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "tailBankingTrace")]
+    fn tail_banking_trace(
+        &self,
+        meta: Self::Metadata,
+        max_count: usize,
+        timeout_ms: u64,
+    ) -> Result<Vec<String>>;
 }
 
 pub struct AdminRpcImpl;
@@ -754,6 +808,144 @@ impl AdminRpc for AdminRpcImpl {
             Ok(())
         })
     }
+
+    fn banking_trace_status(&self, meta: Self::Metadata) -> Result<BankingTracerStatus> {
+        debug!("banking_trace_status request received");
+
+        meta.with_post_init(|post_init| Ok(post_init.banking_tracer.status()))
+    }
+
+    fn enable_banking_trace(
+        &self,
+        meta: Self::Metadata,
+        dir_byte_limit: Option<u64>,
+    ) -> Result<()> {
+        debug!("enable_banking_trace request received: {dir_byte_limit:?}");
+
+        let dir_byte_limit = dir_byte_limit.unwrap_or(BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT);
+        if dir_byte_limit == 0 {
+            return Err(jsonrpc_core::error::Error::invalid_params(
+                "dir_byte_limit must be positive",
+            ));
+        }
+        meta.with_post_init(|post_init| {
+            post_init
+                .banking_tracer
+                .enable((
+                    &post_init.banking_trace_path,
+                    post_init.banking_trace_exit.clone(),
+                    dir_byte_limit,
+                ))
+                .map_err(banking_trace_error)?;
+            info!(
+                "Enabled banking trace into {} (dir_byte_limit: {dir_byte_limit})",
+                post_init.banking_trace_path.display(),
+            );
+            Ok(())
+        })
+    }
+
+    fn disable_banking_trace(&self, meta: Self::Metadata) -> Result<()> {
+        debug!("disable_banking_trace request received");
+
+        meta.with_post_init(|post_init| {
+            post_init
+                .banking_tracer
+                .disable()
+                .map_err(banking_trace_error)?;
+            info!("Disabled banking trace");
+            Ok(())
+        })
+    }
+
+    fn rotate_banking_trace(&self, meta: Self::Metadata) -> Result<()> {
+        debug!("rotate_banking_trace request received");
+
+        meta.with_post_init(|post_init| {
+            post_init
+                .banking_tracer
+                .rotate_now()
+                .map_err(banking_trace_error)
+        })
+    }
+
+    fn set_banking_trace_dir_byte_limit(
+        &self,
+        meta: Self::Metadata,
+        dir_byte_limit: u64,
+    ) -> Result<()> {
+        debug!("set_banking_trace_dir_byte_limit request received: {dir_byte_limit}");
+
+        meta.with_post_init(|post_init| {
+            post_init
+                .banking_tracer
+                .set_dir_byte_limit(dir_byte_limit)
+                .map_err(banking_trace_error)
+        })
+    }
+
+    fn set_banking_trace_min_available_bytes(
+        &self,
+        meta: Self::Metadata,
+        min_available_bytes: u64,
+    ) -> Result<()> {
+        debug!("set_banking_trace_min_available_bytes request received: {min_available_bytes}");
+
+        meta.with_post_init(|post_init| {
+            post_init
+                .banking_tracer
+                .set_min_available_bytes(min_available_bytes);
+            Ok(())
+        })
+    }
+
+    fn set_banking_trace_label_traced(
+        &self,
+        meta: Self::Metadata,
+        label: String,
+        is_traced: bool,
+    ) -> Result<()> {
+        debug!("set_banking_trace_label_traced request received: {label} {is_traced}");
+
+        let label = ChannelLabel::from_str(&label).map_err(|err| {
+            jsonrpc_core::error::Error::invalid_params(format!("Invalid label {label}: {err}"))
+        })?;
+        meta.with_post_init(|post_init| {
+            post_init.banking_tracer.set_label_traced(label, is_traced);
+            Ok(())
+        })
+    }
+
+    fn tail_banking_trace(
+        &self,
+        meta: Self::Metadata,
+        max_count: usize,
+        timeout_ms: u64,
+    ) -> Result<Vec<String>> {
+        debug!("tail_banking_trace request received: {max_count} {timeout_ms}");
+
+        if max_count > MAX_TAIL_BANKING_TRACE_COUNT {
+            return Err(jsonrpc_core::error::Error::invalid_params(format!(
+                "max_count must be at most {MAX_TAIL_BANKING_TRACE_COUNT}"
+            )));
+        }
+        if timeout_ms > MAX_TAIL_BANKING_TRACE_TIMEOUT_MS {
+            return Err(jsonrpc_core::error::Error::invalid_params(format!(
+                "timeout_ms must be at most {MAX_TAIL_BANKING_TRACE_TIMEOUT_MS}"
+            )));
+        }
+        let banking_tracer =
+            meta.with_post_init(|post_init| Ok(post_init.banking_tracer.clone()))?;
+        Ok(banking_tracer
+            .tail(max_count, Duration::from_millis(timeout_ms))
+            .iter()
+            .map(debug_sink::summary_line)
+            .collect())
+    }
+}
+
+fn banking_trace_error(err: TraceError) -> jsonrpc_core::error::Error {
+    jsonrpc_core::error::Error::invalid_params(format!("Banking trace error: {err}"))
 }
 
 impl AdminRpcImpl {
@@ -951,6 +1143,7 @@ mod tests {
         },
         solana_core::{
             admin_rpc_post_init::{KeyUpdaterType, KeyUpdaters},
+            banking_trace::BankingTracer,
             consensus::tower_storage::NullTowerStorage,
             validator::{Validator, ValidatorConfig, ValidatorTpuConfig},
         },
@@ -1006,7 +1199,7 @@ mod tests {
                 SocketAddrSpace::Unspecified,
             ));
             let exit = Arc::new(AtomicBool::new(false));
-            let validator_exit = create_validator_exit(exit);
+            let validator_exit = create_validator_exit(exit.clone());
             let (bank_forks, vote_keypair) = new_bank_forks_with_config(BankTestConfig {
                 accounts_db_config: AccountsDbConfig {
                     account_indexes: Some(config.account_indexes),
@@ -1038,6 +1231,9 @@ mod tests {
                         solana_core::cluster_slots_service::cluster_slots::ClusterSlots::default(),
                     ),
                     gossip_socket: None,
+                    banking_tracer: BankingTracer::new_disabled(),
+                    banking_trace_path: env::temp_dir().join("banking_trace"),
+                    banking_trace_exit: exit,
                 }))),
                 staked_nodes_overrides: Arc::new(RwLock::new(HashMap::new())),
                 rpc_to_plugin_manager_sender: None,
@@ -1070,6 +1266,63 @@ mod tests {
         (BankForks::new_rw_arc(bank), Arc::new(voting_keypair))
     }
 
+    #[test]
+    fn test_banking_trace_status() {
+        let RpcHandler { io, meta, .. } = RpcHandler::start_with_config(TestConfig::default());
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"setBankingTraceLabelTraced","params":["gossip-vote", false]}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(result["result"], Value::Null);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"setBankingTraceLabelTraced","params":["dummy", false]}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert!(result["error"].is_object());
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"bankingTraceStatus"}"#;
+        let res = io.handle_request_sync(req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let status: BankingTracerStatus = serde_json::from_value(result["result"].clone()).unwrap();
+        assert_eq!(
+            status,
+            BankingTracerStatus {
+                is_enabled: false,
+                is_packet_tracing_suspended: false,
+                untraced_labels: vec![ChannelLabel::GossipVote],
+                min_available_bytes: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_tail_banking_trace() {
+        let RpcHandler { io, meta, .. } = RpcHandler::start_with_config(TestConfig::default());
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"tailBankingTrace","params":[10, 0]}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(result["result"], Value::Array(vec![]));
+
+        for params in [
+            format!("[{}, 0]", MAX_TAIL_BANKING_TRACE_COUNT + 1),
+            format!("[10, {}]", MAX_TAIL_BANKING_TRACE_TIMEOUT_MS + 1),
+            format!("[10, {}]", u64::MAX),
+        ] {
+            let req = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"tailBankingTrace","params":{params}}}"#
+            );
+            let res = io.handle_request_sync(&req, meta.clone());
+            let result: Value = serde_json::from_str(&res.expect("actual response"))
+                .expect("actual response deserialization");
+            assert!(result["error"].is_object(), "{params}");
+        }
+    }
+
     #[test]
     fn test_secondary_index_key_sizes() {
         for secondary_index_enabled in [true, false] {
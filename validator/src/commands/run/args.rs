@@ -17,7 +17,10 @@ use {
         keypair::SKIP_SEED_PHRASE_VALIDATION_ARG,
     },
     solana_core::{
-        banking_trace::DirByteLimit,
+        banking_trace::{
+            reader::TraceCompression, ChannelLabel, DirByteLimit, FileIoBackend, FsyncPolicy,
+            RotationPolicy, TraceSinkConfig,
+        },
         validator::{BlockProductionMethod, BlockVerificationMethod, TransactionStructure},
     },
     solana_keypair::Keypair,
@@ -29,7 +32,7 @@ use {
     },
     solana_signer::Signer,
     solana_unified_scheduler_pool::DefaultSchedulerPool,
-    std::{collections::HashSet, net::SocketAddr, str::FromStr},
+    std::{collections::HashSet, net::SocketAddr, num::NonZeroU64, str::FromStr},
 };
 
 const EXCLUDE_KEY: &str = "account-index-exclude-key";
@@ -1580,6 +1583,220 @@ pub fn add_args<'a>(app: App<'a, 'a>, default_args: &'a DefaultArgs) -> App<'a,
             .takes_value(false)
             .help("Disables the banking trace"),
     )
+    .arg(
+        Arg::with_name("banking_trace_sinks")
+            .long("banking-trace-sink")
+            .value_name("SCHEME://DESTINATION")
+            .validator(is_parsable::<TraceSinkConfig>)
+            .takes_value(true)
+            .multiple(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Additionally sends the banking trace to the destination, besides the trace \
+                 files. Supported: tcp://HOST:PORT to stream to a collector if built with the \
+                 banking-trace-tcp feature, uds://PATH to serve local subscribers over a Unix \
+                 domain socket, shm://PATH to write into a ring in shared memory for co-located \
+                 consumers if built with the banking-trace-shm feature, grpc://ADDR to serve \
+                 SubscribeBankingTrace if built with the banking-trace-grpc feature, \
+                 ws://ADDR[?strip-payloads] to broadcast JSON to WebSocket clients if built \
+                 with the banking-trace-websocket feature, prometheus://ADDR to export metrics \
+                 derived from the trace if built with the banking-trace-prometheus feature, \
+                 kafka://BROKERS/TOPIC to publish to a Kafka topic if built with the \
+                 banking-trace-kafka feature, sqlite://PATH[?payloads] to write into a SQLite \
+                 database if built with the banking-trace-sqlite feature, otlp://HOST:PORT[/PATH] \
+                 to export spans over OTLP/HTTP if built with the banking-trace-otel feature, \
+                 postgres://... or clickhouse://HOST:PORT to bulk-insert event summaries if \
+                 built with the banking-trace-bulk-insert feature. May be specified multiple times.",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_sample_rate")
+            .long("banking-trace-sample-rate")
+            .value_name("N")
+            .validator(is_parsable::<u64>)
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Writes only 1 in N packet batches of each channel into the banking trace \
+                 files, while keeping all the other events, to retain a longer but \
+                 lower-resolution trace within the byte limit.",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_rotation_policy")
+            .long("banking-trace-rotation-policy")
+            .value_name("POLICY")
+            .takes_value(true)
+            .possible_values(RotationPolicy::cli_names())
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "When to start a new banking trace file: hourly or daily, besides once the \
+                 file reaches its share of the byte limit, only once it does so (size), or \
+                 never, in which case the byte limit isn't enforced. [default: daily]",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_rotate_count")
+            .long("banking-trace-rotate-count")
+            .value_name("COUNT")
+            .validator(is_parsable::<NonZeroU64>)
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "The number of banking trace files retained, including the one being written, \
+                 among which the byte limit is divided. [default: 14]",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_basename")
+            .long("banking-trace-basename")
+            .value_name("NAME")
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "The name of the banking trace file being written into, after which the \
+                 rotated ones are named, so that multiple tools can share the trace directory. \
+                 [default: events]",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_file_name_template")
+            .long("banking-trace-file-name-template")
+            .value_name("TEMPLATE")
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Renames each completed banking trace file after the template, expanded with \
+                 strftime-style specifiers at the time the file was started and {slot} with \
+                 the first slot traced into it, e.g. events-%Y%m%d-%H",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_fsync_policy")
+            .long("banking-trace-fsync-policy")
+            .value_name("POLICY")
+            .validator(is_parsable::<FsyncPolicy>)
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "When to fsync the banking trace files, so that the last events aren't lost on \
+                 crashes: never, on-rotation, every-flush, or every-<N>s to also flush the \
+                 write buffer every N seconds while tracing. [default: never]",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_buf_writer_capacity")
+            .long("banking-trace-buffer-capacity")
+            .value_name("BYTES")
+            .validator(is_parsable::<usize>)
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "The capacity of the write buffer of the banking trace file being written \
+                 into. Smaller buffers save memory, while larger ones save syscalls. \
+                 [default: 10485760]",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_io_backend")
+            .long("banking-trace-io-backend")
+            .value_name("BACKEND")
+            .possible_values(FileIoBackend::cli_names())
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "How to write the banking trace files. io-uring submits the filled write \
+                 buffers without waiting for them, and is only available on Linux when built \
+                 with the banking-trace-io-uring feature. double-buffered writes out one \
+                 buffer on a helper thread while filling the other. [default: buffered]",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_compression")
+            .long("banking-trace-compression")
+            .value_name("COMPRESSION")
+            .validator(is_parsable::<TraceCompression>)
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Compress each completed banking trace file in the background: zstd or lz4. \
+                 The files are named with the extension appended, e.g. events.1.zst. Only \
+                 available when built with the banking-trace-compression feature",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_preallocate")
+            .long("banking-trace-preallocate")
+            .takes_value(false)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Preallocate each banking trace file up to the rotation threshold once it's \
+                 created, avoiding write stalls on block allocation. Only on Linux when built \
+                 with the banking-trace-fs feature",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_untraced_labels")
+            .long("banking-trace-skip-label")
+            .value_name("LABEL")
+            .validator(is_parsable::<ChannelLabel>)
+            .takes_value(true)
+            .multiple(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Don't trace the packet batches of the given banking channel: non-vote, \
+                 tpu-vote or gossip-vote. May be specified multiple times",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_min_available_bytes")
+            .long("banking-trace-min-available-bytes")
+            .value_name("BYTES")
+            .validator(is_parsable::<u64>)
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Suspend tracing packet batches while the free space of the banking trace \
+                 filesystem is below this, still tracing the other events. 0 disables the \
+                 check. Only effective when built with the banking-trace-fs feature on Unix. \
+                 [default: 0]",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_write_interval_ms")
+            .long("banking-trace-write-interval-ms")
+            .value_name("MILLISECONDS")
+            .validator(is_parsable::<NonZeroU64>)
+            .takes_value(true)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "How long the banking tracer thread sleeps once it has written the events \
+                 received so far. Shorter intervals get the events to the trace sooner at the \
+                 cost of more wakeups. [default: 100]",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_serialize_in_sender")
+            .long("banking-trace-serialize-in-sender")
+            .takes_value(false)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Serialize the traced packet batches on the sending threads instead of the \
+                 single banking tracer thread, which can become a bottleneck at high packet \
+                 rates",
+            ),
+    )
+    .arg(
+        Arg::with_name("banking_trace_coarse_clock")
+            .long("banking-trace-coarse-clock")
+            .takes_value(false)
+            .conflicts_with("disable_banking_trace")
+            .help(
+                "Timestamp the traced events with a clock cached by the banking tracer thread \
+                 as it receives events and every write interval while idle, instead of reading \
+                 the system clock for each of them",
+            ),
+    )
     .arg(
         Arg::with_name("delay_leader_block_for_pending_fork")
             .hidden(hidden_unless_forced())
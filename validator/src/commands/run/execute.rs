@@ -27,7 +27,10 @@ use {
     },
     solana_clock::{Slot, DEFAULT_SLOTS_PER_EPOCH},
     solana_core::{
-        banking_trace::DISABLED_BAKING_TRACE_DIR,
+        banking_trace::{
+            reader::TraceCompression, ChannelLabel, FileIoBackend, FsyncPolicy, RotationPolicy,
+            TraceSinkConfig, DISABLED_BAKING_TRACE_DIR,
+        },
         consensus::tower_storage,
         snapshot_packager_service::SnapshotPackagerService,
         system_monitor_service::SystemMonitorService,
@@ -1389,6 +1392,65 @@ fn configure_banking_trace_dir_byte_limit(
         // explicit user-supplied override value
         value_t_or_exit!(matches, "banking_trace_dir_byte_limit", u64)
     };
+    if matches.is_present("banking_trace_sinks") {
+        validator_config.banking_trace_sinks =
+            values_t_or_exit!(matches, "banking_trace_sinks", TraceSinkConfig);
+    }
+    if matches.is_present("banking_trace_sample_rate") {
+        validator_config.banking_trace_sample_rate =
+            value_t_or_exit!(matches, "banking_trace_sample_rate", u64);
+    }
+    if matches.is_present("banking_trace_rotation_policy") {
+        validator_config.banking_trace_rotation_policy =
+            value_t_or_exit!(matches, "banking_trace_rotation_policy", RotationPolicy);
+    }
+    if matches.is_present("banking_trace_rotate_count") {
+        validator_config.banking_trace_rotate_count =
+            value_t_or_exit!(matches, "banking_trace_rotate_count", u64);
+    }
+    validator_config.banking_trace_basename =
+        matches.value_of("banking_trace_basename").map(String::from);
+    validator_config.banking_trace_file_name_template = matches
+        .value_of("banking_trace_file_name_template")
+        .map(String::from);
+    if matches.is_present("banking_trace_fsync_policy") {
+        validator_config.banking_trace_fsync_policy =
+            value_t_or_exit!(matches, "banking_trace_fsync_policy", FsyncPolicy);
+    }
+    if matches.is_present("banking_trace_buf_writer_capacity") {
+        validator_config.banking_trace_buf_writer_capacity =
+            value_t_or_exit!(matches, "banking_trace_buf_writer_capacity", usize);
+    }
+    if matches.is_present("banking_trace_io_backend") {
+        validator_config.banking_trace_io_backend =
+            value_t_or_exit!(matches, "banking_trace_io_backend", FileIoBackend);
+    }
+    if matches.is_present("banking_trace_compression") {
+        validator_config.banking_trace_compression = Some(value_t_or_exit!(
+            matches,
+            "banking_trace_compression",
+            TraceCompression
+        ));
+    }
+    validator_config.banking_trace_preallocate = matches.is_present("banking_trace_preallocate");
+    if matches.is_present("banking_trace_untraced_labels") {
+        validator_config.banking_trace_untraced_labels =
+            values_t_or_exit!(matches, "banking_trace_untraced_labels", ChannelLabel);
+    }
+    if matches.is_present("banking_trace_min_available_bytes") {
+        validator_config.banking_trace_min_available_bytes =
+            value_t_or_exit!(matches, "banking_trace_min_available_bytes", u64);
+    }
+    if matches.is_present("banking_trace_write_interval_ms") {
+        validator_config.banking_trace_write_interval = Duration::from_millis(value_t_or_exit!(
+            matches,
+            "banking_trace_write_interval_ms",
+            u64
+        ));
+    }
+    validator_config.banking_trace_serialize_in_sender =
+        matches.is_present("banking_trace_serialize_in_sender");
+    validator_config.banking_trace_coarse_clock = matches.is_present("banking_trace_coarse_clock");
 }
 
 fn process_account_indexes(matches: &ArgMatches) -> AccountSecondaryIndexes {